@@ -72,6 +72,25 @@ fn iterate_100k(b: &mut Bencher) {
     })
 }
 
+fn iterate_100k_colocated(b: &mut Bencher) {
+    #[allow(dead_code)]
+    struct Padding([f32; 16]);
+    let mut world = World::new();
+    world.colocate::<Position, Velocity>();
+    for i in 0..100_000 {
+        world.spawn((
+            Position(-(i as f32)),
+            Padding([0.0; 16]),
+            Velocity(i as f32),
+        ));
+    }
+    b.iter(|| {
+        for (_, (pos, vel)) in &mut world.query::<(&mut Position, &Velocity)>() {
+            pos.0 += vel.0;
+        }
+    })
+}
+
 fn build(b: &mut Bencher) {
     let mut world = World::new();
     let mut builder = EntityBuilder::new();
@@ -87,6 +106,7 @@ benchmark_group!(
     spawn_static,
     spawn_batch,
     iterate_100k,
+    iterate_100k_colocated,
     build
 );
 benchmark_main!(benches);
@@ -14,17 +14,25 @@
 
 use crate::alloc::alloc::{alloc, dealloc, Layout};
 use crate::alloc::boxed::Box;
+use crate::alloc::sync::Arc;
 use crate::alloc::{vec, vec::Vec};
-use core::any::{type_name, TypeId};
+use core::any::TypeId;
 use core::cell::UnsafeCell;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
 use core::mem;
 use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 use hashbrown::HashMap;
 
-use crate::borrow::AtomicBorrow;
-use crate::query::Fetch;
-use crate::{Access, Component, Query};
+use crate::arena::PinArena;
+use crate::borrow::{AtomicBorrow, BorrowError};
+use crate::double_buffer::{Shadow, ShadowColumn, ShadowFactory};
+use crate::query::{prefetch, Fetch};
+#[cfg(feature = "stats")]
+use crate::stats::AccessCounts;
+use crate::{Access, ColumnArena, Component, Query};
 
 /// A collection of entities having the same component types
 ///
@@ -34,11 +42,57 @@ pub struct Archetype {
     types: Vec<TypeInfo>,
     state: HashMap<TypeId, TypeState>,
     len: u32,
+    /// Greatest `len` since storage was last shrunk
+    peak: u32,
     entities: Box<[u32]>,
     // UnsafeCell allows unique references into `data` to be constructed while shared references
     // containing the `Archetype` exist
     data: UnsafeCell<NonNull<u8>>,
     data_size: usize,
+    /// Changed whenever entities are added, removed, or reordered, or storage is reallocated
+    ///
+    /// Starts from a value unique to this archetype, so versions of different archetypes are never
+    /// equal in practice.
+    version: u64,
+    /// Whether `data` is external memory that must not be written
+    read_only: bool,
+    /// Arenas of the types stored here that must never be moved in memory, whose columns hold a
+    /// pointer to each component's slot rather than the component itself
+    pins: HashMap<TypeId, Arc<PinArena>>,
+    /// Slots of the pinned components of the entity last moved out by `move_to`, for
+    /// `adopt_pinned` to hand over to its new archetype
+    released: Vec<(TypeId, *mut u8)>,
+    /// Order in which columns are laid out in `data`, if not that of `types`
+    column_order: Option<ColumnOrder>,
+    /// Pairs of types whose columns are laid out in `data` with the second directly after the first
+    colocated: Vec<(TypeId, TypeId)>,
+    /// Arenas from which the columns of particular types are allocated, rather than from `data`
+    arenas: HashMap<TypeId, Arc<ColumnArena>>,
+    /// Write barriers of component types stored here, from `World::add_write_barrier`
+    barriers: HashMap<TypeId, Barrier>,
+    /// Previous values of types registered with `World::double_buffer`
+    shadows: HashMap<TypeId, Box<dyn Shadow>>,
+    #[cfg(feature = "stats")]
+    access: HashMap<TypeId, AccessCounts>,
+}
+
+/// A comparison determining the order of component columns in memory, for
+/// `World::set_column_order`
+pub type ColumnOrder = fn(&TypeInfo, &TypeInfo) -> core::cmp::Ordering;
+
+/// Storage usage of an archetype, from `Archetype::garbage_stats`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct GarbageStats {
+    /// Greatest number of entities the archetype has held since its storage was last shrunk
+    pub peak: u32,
+    /// Number of entities the archetype currently holds
+    pub len: u32,
+    /// Number of entities the archetype can hold without reallocating
+    pub capacity: u32,
+    /// Memory that `World::shrink_to_fit` would free, including change ticks and entity IDs
+    ///
+    /// Zero for archetypes that can't be shrunk, such as those in read-only memory.
+    pub reclaimable_bytes: usize,
 }
 
 impl Archetype {
@@ -48,28 +102,380 @@ impl Archetype {
             "type info unsorted or contains duplicates"
         );
         Self {
+            #[cfg(feature = "stats")]
+            access: types
+                .iter()
+                .map(|x| (x.id, AccessCounts::default()))
+                .collect(),
             types,
             state: HashMap::default(),
             entities: Box::new([]),
             len: 0,
+            peak: 0,
             data: UnsafeCell::new(NonNull::dangling()),
             data_size: 0,
+            version: fresh_version(),
+            read_only: false,
+            pins: HashMap::default(),
+            released: Vec::new(),
+            column_order: None,
+            colocated: Vec::new(),
+            arenas: HashMap::default(),
+            barriers: HashMap::default(),
+            shadows: HashMap::default(),
+        }
+    }
+
+    /// Construct an archetype whose `T` components are stored in caller-owned memory
+    ///
+    /// # Safety
+    /// `data` must be valid for reads of `entities.len()` `T`s for the lifetime of the archetype,
+    /// which must never be structurally modified except by `clear`
+    pub(crate) unsafe fn external<T: Component + Copy>(
+        data: NonNull<T>,
+        entities: Box<[u32]>,
+        tick: u32,
+    ) -> Self {
+        let ty = TypeInfo::of::<T>();
+        let ticks = vec![ComponentTicks::new(tick); entities.len()].into_boxed_slice();
+        let mut state = HashMap::with_capacity(1);
+        state.insert(ty.id, TypeState::new(0, ticks, 0));
+        Self {
+            #[cfg(feature = "stats")]
+            access: core::iter::once((ty.id, AccessCounts::default())).collect(),
+            types: vec![ty],
+            state,
+            len: entities.len() as u32,
+            peak: entities.len() as u32,
+            entities,
+            data: UnsafeCell::new(data.cast()),
+            data_size: 0,
+            version: fresh_version(),
+            read_only: true,
+            pins: HashMap::default(),
+            released: Vec::new(),
+            column_order: None,
+            colocated: Vec::new(),
+            arenas: HashMap::default(),
+            barriers: HashMap::default(),
+            shadows: HashMap::default(),
+        }
+    }
+
+    /// Whether this archetype's components live in external memory, so that they can't be
+    /// modified and its entities can't be moved or despawned individually
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Store components of the types in `pinned` in slots of their arenas, so that they're never
+    /// moved in memory
+    ///
+    /// Must be empty if it stores any of them.
+    pub(crate) fn pin(&mut self, pinned: &HashMap<TypeId, Arc<PinArena>>) {
+        let pins = self
+            .types
+            .iter()
+            .filter_map(|x| Some((x.id, pinned.get(&x.id)?.clone())))
+            .collect::<HashMap<_, _>>();
+        if pins.len() == self.pins.len() {
+            return;
+        }
+        debug_assert!(self.is_empty());
+        // Pinned columns are laid out differently
+        self.free_storage();
+        self.pins = pins;
+    }
+
+    pub(crate) fn with_pins(mut self, pinned: &HashMap<TypeId, Arc<PinArena>>) -> Self {
+        self.pin(pinned);
+        self
+    }
+
+    /// Release all storage, which must hold no entities
+    fn free_storage(&mut self) {
+        debug_assert!(self.is_empty());
+        for ty in &self.types {
+            if let Some(state) = self.state.get(&ty.id) {
+                unsafe {
+                    state.free_column(stored_layout(&self.pins, ty), self.entities.len());
+                }
+            }
+        }
+        if self.data_size != 0 {
+            unsafe {
+                dealloc(
+                    (*self.data.get()).as_ptr().cast(),
+                    Layout::from_size_align_unchecked(self.data_size, self.data_align()),
+                );
+            }
+        }
+        self.state.clear();
+        self.entities = Box::new([]);
+        *self.data.get_mut() = NonNull::dangling();
+        self.data_size = 0;
+        self.version += 1;
+    }
+
+    /// Alignment of `data`, that of the most aligned column
+    fn data_align(&self) -> usize {
+        self.types
+            .iter()
+            .map(|x| stored_layout(&self.pins, x).align())
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Lay out columns according to `order` whenever storage is next allocated
+    pub(crate) fn set_column_order(&mut self, order: Option<ColumnOrder>) {
+        self.column_order = order;
+    }
+
+    pub(crate) fn with_column_order(mut self, order: Option<ColumnOrder>) -> Self {
+        self.set_column_order(order);
+        self
+    }
+
+    /// Lay out the columns of each pair in `colocated` that's stored here next to each other
+    /// whenever storage is next allocated
+    pub(crate) fn set_colocated(&mut self, colocated: &[(TypeId, TypeId)]) {
+        self.colocated = colocated
+            .iter()
+            .copied()
+            .filter(|&(a, b)| self.has_dynamic(a) && self.has_dynamic(b))
+            .collect();
+    }
+
+    pub(crate) fn with_colocated(mut self, colocated: &[(TypeId, TypeId)]) -> Self {
+        self.set_colocated(colocated);
+        self
+    }
+
+    /// Allocate the columns of types in `arenas` from their arena whenever storage is next
+    /// allocated
+    pub(crate) fn set_arenas(&mut self, arenas: &HashMap<TypeId, Arc<ColumnArena>>) {
+        self.arenas = self
+            .types
+            .iter()
+            .filter_map(|x| Some((x.id, arenas.get(&x.id)?.clone())))
+            .collect();
+    }
+
+    pub(crate) fn with_arenas(mut self, arenas: &HashMap<TypeId, Arc<ColumnArena>>) -> Self {
+        self.set_arenas(arenas);
+        self
+    }
+
+    /// Report writes to types in `barriers` through unique borrows of their columns
+    pub(crate) fn set_barriers(&mut self, barriers: &HashMap<TypeId, Arc<WriteBarrier>>) {
+        self.barriers = self
+            .types
+            .iter()
+            .filter_map(|x| {
+                let f = barriers.get(&x.id)?.clone();
+                Some((
+                    x.id,
+                    Barrier {
+                        f,
+                        tick: AtomicU64::new(NO_WRITES),
+                    },
+                ))
+            })
+            .collect();
+    }
+
+    pub(crate) fn with_barriers(mut self, barriers: &HashMap<TypeId, Arc<WriteBarrier>>) -> Self {
+        self.set_barriers(barriers);
+        self
+    }
+
+    /// Keep previous values of the types in `shadows` stored here, starting from their current
+    /// values
+    pub(crate) fn set_shadows(&mut self, shadows: &HashMap<TypeId, ShadowFactory>) {
+        for ty in &self.types {
+            let factory = match shadows.get(&ty.id) {
+                Some(x) if !self.shadows.contains_key(&ty.id) => x,
+                _ => continue,
+            };
+            let mut shadow = factory();
+            unsafe {
+                self.capture_shadow(ty, &mut *shadow);
+            }
+            self.shadows.insert(ty.id, shadow);
         }
     }
 
-    pub(crate) fn clear(&mut self) {
+    pub(crate) fn with_shadows(mut self, shadows: &HashMap<TypeId, ShadowFactory>) -> Self {
+        self.set_shadows(shadows);
+        self
+    }
+
+    /// Previous values of the `T` components here, if `T` is double-buffered
+    pub(crate) fn shadow<T: Component>(&self) -> Option<&[T]> {
+        let x = self.shadows.get(&TypeId::of::<T>())?;
+        x.as_any()
+            .downcast_ref::<ShadowColumn<T>>()
+            .map(|x| &x.0[..])
+    }
+
+    /// Make every current value of a double-buffered type its previous value
+    pub(crate) fn capture_shadows(&mut self) {
+        let mut shadows = mem::take(&mut self.shadows);
         for ty in &self.types {
+            if let Some(shadow) = shadows.get_mut(&ty.id) {
+                unsafe {
+                    self.capture_shadow(ty, &mut **shadow);
+                }
+            }
+        }
+        self.shadows = shadows;
+    }
+
+    /// Make every current `ty` component the previous value in `shadow`
+    unsafe fn capture_shadow(&self, ty: &TypeInfo, shadow: &mut dyn Shadow) {
+        let state = match self.state.get(&ty.id) {
+            Some(x) => x,
+            None => return,
+        };
+        if !self.pins.contains_key(&ty.id) {
+            shadow.capture(state.base(*self.data.get()), self.len);
+            return;
+        }
+        shadow.clear();
+        for index in 0..self.len {
+            let current = self.get_dynamic(ty.id, 0, index).unwrap();
+            shadow.fill(index, current.as_ptr());
+        }
+    }
+
+    /// Carry over the previous values of the entity at `index` in `source` to the entity just
+    /// allocated here, ahead of moving it with `move_to`
+    pub(crate) fn inherit_shadows(&mut self, source: &Archetype, index: u32) {
+        for (ty, shadow) in &mut self.shadows {
+            if let Some(x) = source.shadows.get(ty) {
+                shadow.inherit(&**x, index);
+            }
+        }
+    }
+
+    /// Record that `T` components are being written at change tick `tick` under a unique borrow,
+    /// to be reported to its write barrier when the borrow is released
+    pub(crate) fn note_writes<T: Component>(&self, tick: u32) {
+        if self.barriers.is_empty() {
+            return;
+        }
+        if let Some(x) = self.barriers.get(&TypeId::of::<T>()) {
+            x.tick.store(u64::from(tick), Ordering::Relaxed);
+        }
+    }
+
+    /// Pass the ID of each entity whose `ty` component was written under the current unique borrow
+    /// to the type's write barrier, if any, or only that of the entity at `index` if specified
+    fn report_writes(&self, ty: TypeId, index: Option<u32>) {
+        if self.barriers.is_empty() {
+            return;
+        }
+        let barrier = match self.barriers.get(&ty) {
+            Some(x) => x,
+            None => return,
+        };
+        let tick = barrier.tick.swap(NO_WRITES, Ordering::Relaxed);
+        if let Some(index) = index {
+            (barrier.f)(self.entities[index as usize]);
+            return;
+        }
+        if tick == NO_WRITES {
+            return;
+        }
+        // The column is still uniquely borrowed
+        let ticks = unsafe { &*self.state.get(&ty).unwrap().ticks.get() };
+        for (&id, x) in self.entities.iter().zip(&ticks[..self.len as usize]) {
+            if u64::from(x.changed) == tick {
+                (barrier.f)(id);
+            }
+        }
+    }
+
+    /// Name of a component type in this archetype registered with `World::pin_component`, if any
+    pub(crate) fn pinned(&self) -> Option<&'static str> {
+        if self.pins.is_empty() {
+            return None;
+        }
+        self.types
+            .iter()
+            .find_map(|x| Some(self.pins.get(&x.id)?.name()))
+    }
+
+    /// Give the pinned components of the entity just moved to `index` from `source` by `move_to`
+    /// back their original slots, so that they stay put
+    ///
+    /// Must be called before anything else is written to the entity's components.
+    pub(crate) fn adopt_pinned(&mut self, source: &mut Archetype, index: u32) {
+        for (ty, slot) in source.released.drain(..) {
+            let arena = &source.pins[&ty];
+            unsafe {
+                let slot = NonNull::new_unchecked(slot);
+                let state = match self.state.get(&ty) {
+                    Some(x) if self.pins.contains_key(&ty) => x,
+                    // Removed from the entity
+                    _ => {
+                        arena.free(slot);
+                        continue;
+                    }
+                };
+                let entry = &mut *state
+                    .base(*self.data.get())
+                    .cast::<*mut u8>()
+                    .add(index as usize);
+                // The slot's contents were copied, not dropped, so it's free to take them back
+                if let Some(copy) = NonNull::new(mem::replace(entry, slot.as_ptr())) {
+                    arena.free(copy);
+                }
+            }
+        }
+    }
+
+    /// Free the slots of pinned components moved out by `move_to` that were never adopted
+    fn free_released(&mut self) {
+        for (ty, slot) in self.released.drain(..) {
+            unsafe {
+                self.pins[&ty].free(NonNull::new_unchecked(slot));
+            }
+        }
+    }
+
+    /// Remove every entity, passing each entity ID and component to `discard` to be disposed of
+    ///
+    /// Components that needn't be dropped are only passed to `discard` if `observed` returns true
+    /// for their type.
+    pub(crate) fn clear(
+        &mut self,
+        observed: impl Fn(TypeId) -> bool,
+        mut discard: impl FnMut(u32, &TypeInfo, *mut u8),
+    ) {
+        for ty in &self.types {
+            let arena = self.pins.get(&ty.id);
+            let discarded = ty.needs_drop() || observed(ty.id);
+            if !discarded && arena.is_none() {
+                continue;
+            }
             for index in 0..self.len {
                 unsafe {
-                    let removed = self
-                        .get_dynamic(ty.id, ty.layout.size(), index)
-                        .unwrap()
-                        .as_ptr();
-                    (ty.drop)(removed);
+                    let removed = self.get_dynamic(ty.id, ty.layout.size(), index).unwrap();
+                    if discarded {
+                        discard(self.entities[index as usize], ty, removed.as_ptr());
+                    }
+                    if let Some(arena) = arena {
+                        arena.free(removed);
+                    }
                 }
             }
         }
+        for x in self.shadows.values_mut() {
+            x.clear();
+        }
         self.len = 0;
+        self.version += 1;
     }
 
     pub(crate) fn has<T: Component>(&self) -> bool {
@@ -80,33 +486,90 @@ impl Archetype {
         self.state.contains_key(&id)
     }
 
-    pub(crate) fn get<T: Component>(&self) -> Option<NonNull<T>> {
+    /// The first `T` component, if present
+    pub(crate) fn column<T: Component>(&self) -> Option<ColumnPtr<T>> {
         let state = self.state.get(&TypeId::of::<T>())?;
-        Some(unsafe {
-            NonNull::new_unchecked(
-                (*self.data.get()).as_ptr().add(state.offset).cast::<T>() as *mut T
-            )
-        })
+        unsafe {
+            let base = state.base(*self.data.get());
+            Some(if self.pins.contains_key(&TypeId::of::<T>()) {
+                ColumnPtr::Indirect(NonNull::new_unchecked(base.cast()))
+            } else {
+                ColumnPtr::Direct(NonNull::new_unchecked(base.cast()))
+            })
+        }
+    }
+
+    /// The `T` component of the entity at `index`, if present
+    ///
+    /// # Safety
+    /// `index` must be in-bounds
+    pub(crate) unsafe fn get_at<T: Component>(&self, index: u32) -> Option<NonNull<T>> {
+        let x = self.column::<T>()?.add(index as usize).get();
+        Some(NonNull::new_unchecked(x))
+    }
+
+    /// Invoke `f` on the dynamically borrowed `T` components of this archetype, if present
+    ///
+    /// The borrow is released even if `f` panics.
+    pub(crate) fn with_column<T: Component, R>(&self, f: impl FnOnce(&[T]) -> R) -> Option<R> {
+        let base = match self.column::<T>()? {
+            ColumnPtr::Direct(x) => x,
+            ColumnPtr::Indirect(_) => panic!(
+                "pinned component {} isn't stored contiguously",
+                core::any::type_name::<T>()
+            ),
+        };
+        self.borrow::<T>();
+        let _guard = ColumnGuard::<T>(self, PhantomData);
+        Some(f(unsafe {
+            core::slice::from_raw_parts(base.as_ptr(), self.len as usize)
+        }))
     }
 
     pub(crate) fn borrow<T: Component>(&self) {
+        if let Err(e) = self.try_borrow::<T>() {
+            panic!("{}", e);
+        }
+    }
+
+    pub(crate) fn try_borrow<T: Component>(&self) -> Result<(), BorrowError> {
         if self
             .state
             .get(&TypeId::of::<T>())
-            .map_or(false, |x| !x.borrow.borrow())
+            .is_some_and(|x| !x.borrow.borrow())
         {
-            panic!("{} already borrowed uniquely", type_name::<T>());
+            return Err(BorrowError::new::<T>(false));
         }
+        #[cfg(feature = "stats")]
+        self.record_access(TypeId::of::<T>(), false);
+        Ok(())
     }
 
     pub(crate) fn borrow_mut<T: Component>(&self) {
+        if let Err(e) = self.try_borrow_mut::<T>() {
+            panic!("{}", e);
+        }
+    }
+
+    pub(crate) fn try_borrow_mut<T: Component>(&self) -> Result<(), BorrowError> {
         if self
             .state
             .get(&TypeId::of::<T>())
-            .map_or(false, |x| !x.borrow.borrow_mut())
+            .is_some_and(|x| !x.borrow.borrow_mut())
         {
-            panic!("{} already borrowed", type_name::<T>());
+            return Err(BorrowError::new::<T>(true));
+        }
+        if self.read_only && self.has::<T>() {
+            self.release_mut::<T>();
+            return Err(BorrowError::read_only::<T>());
         }
+        self.touch(TypeId::of::<T>());
+        if let Some(x) = self.barriers.get(&TypeId::of::<T>()) {
+            x.tick.store(NO_WRITES, Ordering::Relaxed);
+        }
+        #[cfg(feature = "stats")]
+        self.record_access(TypeId::of::<T>(), true);
+        Ok(())
     }
 
     pub(crate) fn release<T: Component>(&self) {
@@ -117,14 +580,85 @@ impl Archetype {
 
     pub(crate) fn release_mut<T: Component>(&self) {
         if let Some(x) = self.state.get(&TypeId::of::<T>()) {
+            self.report_writes(TypeId::of::<T>(), None);
+            x.borrow.release_mut();
+        }
+    }
+
+    /// Release a unique borrow of the `T` component of the entity at `index` alone
+    pub(crate) fn release_mut_one<T: Component>(&self, index: u32) {
+        if let Some(x) = self.state.get(&TypeId::of::<T>()) {
+            self.report_writes(TypeId::of::<T>(), Some(index));
             x.borrow.release_mut();
         }
     }
 
-    pub(crate) fn len(&self) -> u32 {
+    /// Number of shared borrows of the `ty` column, and whether it's uniquely borrowed
+    pub(crate) fn borrow_state(&self, ty: TypeId) -> Option<(usize, bool)> {
+        Some(self.state.get(&ty)?.borrow.get())
+    }
+
+    /// Convert a unique borrow of the `T` component of the entity at `index` alone into a shared
+    /// borrow
+    pub(crate) fn downgrade_one<T: Component>(&self, index: u32) {
+        if let Some(x) = self.state.get(&TypeId::of::<T>()) {
+            self.report_writes(TypeId::of::<T>(), Some(index));
+            x.borrow.downgrade();
+        }
+    }
+
+    /// Note that the `ty` components may have been modified
+    pub(crate) fn touch(&self, ty: TypeId) {
+        if let Some(x) = self.state.get(&ty) {
+            x.version.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    fn record_access(&self, ty: TypeId, unique: bool) {
+        if let Some(x) = self.access.get(&ty) {
+            x.record(unique);
+        }
+    }
+
+    /// Numbers of shared and unique borrows of each column since the last reset
+    #[cfg(feature = "stats")]
+    pub(crate) fn access_counts(&self) -> impl Iterator<Item = (&TypeInfo, (u32, u32))> {
+        self.types
+            .iter()
+            .map(move |x| (x, self.access[&x.id].get()))
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn reset_access_counts(&mut self) {
+        for x in self.access.values_mut() {
+            x.reset();
+        }
+    }
+
+    /// Changes whenever entities are added, removed, or reordered, or storage is reallocated
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Changes whenever the `ty` components may have been modified
+    ///
+    /// Conservative: obtaining unique access to a column counts as modifying it, whether or not it
+    /// was actually written.
+    pub(crate) fn column_version(&self, ty: TypeId) -> Option<u32> {
+        Some(self.state.get(&ty)?.version.load(Ordering::Relaxed))
+    }
+
+    /// Number of entities in this archetype
+    pub fn len(&self) -> u32 {
         self.len
     }
 
+    /// Whether this archetype contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub(crate) fn entities(&self) -> NonNull<u32> {
         unsafe { NonNull::new_unchecked(self.entities.as_ptr() as *mut _) }
     }
@@ -145,12 +679,38 @@ impl Archetype {
         index: u32,
     ) -> Option<NonNull<u8>> {
         debug_assert!(index < self.len);
-        Some(NonNull::new_unchecked(
-            (*self.data.get())
-                .as_ptr()
-                .add(self.state.get(&ty)?.offset + size * index as usize)
-                .cast::<u8>(),
-        ))
+        let base = self.state.get(&ty)?.base(*self.data.get());
+        if self.pins.contains_key(&ty) {
+            return NonNull::new(*base.cast::<*mut u8>().add(index as usize));
+        }
+        Some(NonNull::new_unchecked(base.add(size * index as usize)))
+    }
+
+    /// Address of the `ty` column's entry for the entity at `index`, which for pinned types holds a
+    /// pointer to the component
+    unsafe fn entry(&self, ty: &TypeInfo, index: u32) -> *mut u8 {
+        let size = stored_layout(&self.pins, ty).size();
+        self.state
+            .get(&ty.id)
+            .unwrap()
+            .base(*self.data.get())
+            .add(size * index as usize)
+    }
+
+    /// Where to write the `ty` component of the entity at `index`, allocating a slot for it if `ty`
+    /// is pinned and it has none
+    unsafe fn slot(&mut self, ty: TypeId, size: usize, index: u32) -> *mut u8 {
+        let base = self.state.get(&ty).unwrap().base(*self.data.get());
+        match self.pins.get(&ty) {
+            Some(arena) => {
+                let entry = &mut *base.cast::<*mut u8>().add(index as usize);
+                if entry.is_null() {
+                    *entry = arena.alloc().as_ptr();
+                }
+                *entry
+            }
+            None => base.add(size * index as usize),
+        }
     }
 
     /// Every type must be written immediately after this call
@@ -160,13 +720,86 @@ impl Archetype {
         }
 
         self.entities[self.len as usize] = id;
+        for ty in &self.types {
+            if self.pins.contains_key(&ty.id) {
+                // No slot yet
+                *self.entry(ty, self.len).cast::<*mut u8>() = ptr::null_mut();
+            }
+        }
         self.len += 1;
+        self.peak = self.peak.max(self.len);
+        self.version += 1;
         self.len - 1
     }
 
+    /// Copy this archetype and its entities into ordinary storage, preserving change ticks
+    ///
+    /// `clone` is passed each column's type, its first component, the destination, and the number
+    /// of components. If it panics, the partial copy is leaked.
+    ///
+    /// # Safety
+    /// `clone` must initialize every destination component from the corresponding source
+    ///
+    /// Pinned components are cloned into slots of the arenas in `pinned`, which must include one
+    /// for each type pinned here.
+    pub(crate) unsafe fn duplicate(
+        &mut self,
+        pinned: &HashMap<TypeId, Arc<PinArena>>,
+        mut clone: impl FnMut(&TypeInfo, *const u8, *mut u8, usize),
+    ) -> Self {
+        let mut copy = mem::ManuallyDrop::new(
+            Archetype::new(self.types.clone())
+                .with_column_order(self.column_order)
+                .with_pins(pinned),
+        );
+        copy.colocated = self.colocated.clone();
+        copy.arenas = self.arenas.clone();
+        copy.shadows = self
+            .shadows
+            .iter()
+            .map(|(&ty, x)| (ty, x.boxed_clone()))
+            .collect();
+        if self.len == 0 {
+            return mem::ManuallyDrop::into_inner(copy);
+        }
+        copy.grow(self.capacity());
+        let len = self.len as usize;
+        copy.entities[..len].copy_from_slice(&self.entities[..len]);
+        copy.len = self.len;
+        copy.peak = self.peak;
+        let (source_data, target_data) = (*self.data.get(), *copy.data.get());
+        for ty in &self.types {
+            let source = self.state.get_mut(&ty.id).unwrap();
+            let target = copy.state.get_mut(&ty.id).unwrap();
+            target.ticks.get_mut()[..len].copy_from_slice(&source.ticks.get_mut()[..len]);
+            let (source, target) = (source.base(source_data), target.base(target_data));
+            match copy.pins.get(&ty.id) {
+                Some(arena) => {
+                    let source = source.cast::<*mut u8>();
+                    let target = target.cast::<*mut u8>();
+                    for index in 0..len {
+                        // Written first, so that a panic leaks the slot rather than freeing it twice
+                        let slot = arena.alloc().as_ptr();
+                        *target.add(index) = slot;
+                        clone(ty, *source.add(index), slot, 1);
+                    }
+                }
+                None => clone(ty, source, target, len),
+            }
+        }
+        mem::ManuallyDrop::into_inner(copy)
+    }
+
+    /// Record that the entity at `index` has the ID `id`
+    pub(crate) fn set_entity_id(&mut self, index: u32, id: u32) {
+        self.entities[index as usize] = id;
+    }
+
+    /// Ensure `additional` more entities fit without reallocating
     pub(crate) fn reserve(&mut self, additional: u32) {
         if additional > (self.capacity() - self.len()) {
-            self.grow(additional - (self.capacity() - self.len()));
+            // `grow` sizes storage relative to `len`, not the current capacity
+            self.grow(additional);
         }
     }
 
@@ -174,43 +807,129 @@ impl Archetype {
         self.entities.len() as u32
     }
 
+    /// Memory used by this archetype's storage versus what it currently needs
+    ///
+    /// Useful after many entities have been despawned, to decide whether `World::shrink_to_fit`
+    /// is worthwhile.
+    pub fn garbage_stats(&self) -> GarbageStats {
+        let row_bytes = mem::size_of::<u32>()
+            + self
+                .types
+                .iter()
+                .map(|x| stored_layout(&self.pins, x).size() + mem::size_of::<ComponentTicks>())
+                .sum::<usize>();
+        GarbageStats {
+            peak: self.peak,
+            len: self.len,
+            capacity: self.capacity(),
+            reclaimable_bytes: if self.can_shrink() {
+                (self.capacity() - self.len) as usize * row_bytes
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Whether `shrink_to_fit` is permitted to reallocate storage
+    fn can_shrink(&self) -> bool {
+        !self.read_only
+    }
+
+    /// Reallocate storage to fit exactly the current entities, if possible, and reset the peak
+    ///
+    /// Returns whether storage was reallocated.
+    pub(crate) fn shrink_to_fit(&mut self) -> bool {
+        if !self.can_shrink() {
+            return false;
+        }
+        self.peak = self.len;
+        if self.capacity() == self.len {
+            return false;
+        }
+        self.grow(0);
+        true
+    }
+
+    /// Reallocate storage at the same capacity, applying any changes to the layout
+    pub(crate) fn relayout(&mut self) {
+        if self.read_only || self.entities.is_empty() {
+            return;
+        }
+        self.grow(self.capacity() - self.len);
+    }
+
     fn grow(&mut self, increment: u32) {
+        self.version += 1;
         unsafe {
             let old_count = self.len as usize;
+            let old_capacity = self.entities.len();
             let count = old_count + increment as usize;
             let mut new_entities = vec![!0; count].into_boxed_slice();
             new_entities[0..old_count].copy_from_slice(&self.entities[0..old_count]);
             self.entities = new_entities;
 
             let old_data_size = mem::replace(&mut self.data_size, 0);
+            let mut columns = self.types.iter().collect::<Vec<_>>();
+            if let Some(order) = self.column_order {
+                // Stable, so ties keep their canonical order
+                columns.sort_by(|x, y| order(x, y));
+            }
+            for &(leader, follower) in &self.colocated {
+                let follower =
+                    columns.remove(columns.iter().position(|x| x.id == follower).unwrap());
+                let leader = columns.iter().position(|x| x.id == leader).unwrap();
+                columns.insert(leader + 1, follower);
+            }
             let mut state = HashMap::with_capacity(self.types.len());
-            for ty in &self.types {
-                self.data_size = align(self.data_size, ty.layout.align());
-                state.insert(ty.id, TypeState::new(self.data_size));
-                self.data_size += ty.layout.size() * count;
+            for ty in columns {
+                let mut ticks = vec![ComponentTicks::default(); count].into_boxed_slice();
+                let mut version = 0;
+                if let Some(old) = self.state.get_mut(&ty.id) {
+                    ticks[0..old_count].copy_from_slice(&old.ticks.get_mut()[0..old_count]);
+                    version = *old.version.get_mut();
+                }
+                let mut new = TypeState::new(0, ticks, version);
+                let layout = stored_layout(&self.pins, ty);
+                match self.arenas.get(&ty.id) {
+                    Some(arena) => {
+                        let column = arena.alloc(layout.size() * count, layout.align());
+                        new.column = Some((column, arena.clone()));
+                    }
+                    None => {
+                        self.data_size = align(self.data_size, layout.align());
+                        new.offset = self.data_size;
+                        self.data_size += layout.size() * count;
+                    }
+                }
+                state.insert(ty.id, new);
             }
             let new_data = if self.data_size == 0 {
                 NonNull::dangling()
             } else {
                 NonNull::new(alloc(
-                    Layout::from_size_align(
-                        self.data_size,
-                        self.types.first().map_or(1, |x| x.layout.align()),
-                    )
-                    .unwrap(),
+                    Layout::from_size_align(self.data_size, self.data_align()).unwrap(),
                 ))
                 .unwrap()
             };
+            let old_data = *self.data.get();
+            for ty in &self.types {
+                let old = match self.state.get(&ty.id) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let layout = stored_layout(&self.pins, ty);
+                ptr::copy_nonoverlapping(
+                    old.base(old_data),
+                    state.get(&ty.id).unwrap().base(new_data),
+                    layout.size() * old_count,
+                );
+                old.free_column(layout, old_capacity);
+            }
             if old_data_size != 0 {
-                for ty in &self.types {
-                    let old_off = self.state.get(&ty.id).unwrap().offset;
-                    let new_off = state.get(&ty.id).unwrap().offset;
-                    ptr::copy_nonoverlapping(
-                        (*self.data.get()).as_ptr().add(old_off),
-                        new_data.as_ptr().add(new_off),
-                        ty.layout.size() * old_count,
-                    );
-                }
+                dealloc(
+                    old_data.as_ptr(),
+                    Layout::from_size_align_unchecked(old_data_size, self.data_align()),
+                );
             }
 
             self.data = UnsafeCell::new(new_data);
@@ -218,26 +937,36 @@ impl Archetype {
         }
     }
 
+    /// Remove the entity at `index`, passing each component to `discard` to be disposed of
+    ///
     /// Returns the ID of the entity moved into `index`, if any
-    pub(crate) unsafe fn remove(&mut self, index: u32) -> Option<u32> {
+    pub(crate) unsafe fn remove(
+        &mut self,
+        index: u32,
+        mut discard: impl FnMut(&TypeInfo, *mut u8),
+    ) -> Option<u32> {
         let last = self.len - 1;
         for ty in &self.types {
             let removed = self
                 .get_dynamic(ty.id, ty.layout.size(), index)
                 .unwrap()
                 .as_ptr();
-            (ty.drop)(removed);
+            discard(ty, removed);
+            if let Some(arena) = self.pins.get(&ty.id) {
+                arena.free(NonNull::new_unchecked(removed));
+            }
             if index != last {
-                ptr::copy_nonoverlapping(
-                    self.get_dynamic(ty.id, ty.layout.size(), last)
-                        .unwrap()
-                        .as_ptr(),
-                    removed,
-                    ty.layout.size(),
-                );
+                let size = stored_layout(&self.pins, ty).size();
+                ptr::copy_nonoverlapping(self.entry(ty, last), self.entry(ty, index), size);
+                let ticks = self.state.get_mut(&ty.id).unwrap().ticks.get_mut();
+                ticks[index as usize] = ticks[last as usize];
             }
         }
+        for x in self.shadows.values_mut() {
+            x.swap_remove(index);
+        }
         self.len = last;
+        self.version += 1;
         if index != last {
             self.entities[index as usize] = self.entities[last as usize];
             Some(self.entities[last as usize])
@@ -250,26 +979,31 @@ impl Archetype {
     pub(crate) unsafe fn move_to(
         &mut self,
         index: u32,
-        mut f: impl FnMut(*mut u8, TypeId, usize),
+        mut f: impl FnMut(*mut u8, TypeId, usize, ComponentTicks),
     ) -> Option<u32> {
+        self.free_released();
         let last = self.len - 1;
         for ty in &self.types {
             let moved = self
                 .get_dynamic(ty.id, ty.layout.size(), index)
                 .unwrap()
                 .as_ptr();
-            f(moved, ty.id(), ty.layout().size());
+            let ticks = self.state.get_mut(&ty.id).unwrap().ticks.get_mut();
+            f(moved, ty.id(), ty.layout().size(), ticks[index as usize]);
+            if self.pins.contains_key(&ty.id) {
+                self.released.push((ty.id, moved));
+            }
             if index != last {
-                ptr::copy_nonoverlapping(
-                    self.get_dynamic(ty.id, ty.layout.size(), last)
-                        .unwrap()
-                        .as_ptr(),
-                    moved,
-                    ty.layout.size(),
-                );
+                ticks[index as usize] = ticks[last as usize];
+                let size = stored_layout(&self.pins, ty).size();
+                ptr::copy_nonoverlapping(self.entry(ty, last), self.entry(ty, index), size);
             }
         }
+        for x in self.shadows.values_mut() {
+            x.swap_remove(index);
+        }
         self.len -= 1;
+        self.version += 1;
         if index != last {
             self.entities[index as usize] = self.entities[last as usize];
             Some(self.entities[last as usize])
@@ -284,48 +1018,272 @@ impl Archetype {
         ty: TypeId,
         size: usize,
         index: u32,
+        ticks: ComponentTicks,
     ) {
-        let ptr = self
-            .get_dynamic(ty, size, index)
-            .unwrap()
-            .as_ptr()
-            .cast::<u8>();
+        let ptr = self.slot(ty, size, index);
         ptr::copy_nonoverlapping(component, ptr, size);
+        let state = self.state.get_mut(&ty).unwrap();
+        state.ticks.get_mut()[index as usize] = ticks;
+        *state.version.get_mut() = state.version.get_mut().wrapping_add(1);
+        if let Some(x) = self.shadows.get_mut(&ty) {
+            x.fill(index, ptr);
+        }
+    }
+
+    /// Move components from `data` into the `ty` column of the first `ticks.len()` entities
+    ///
+    /// # Safety
+    /// `data` must point to `ticks.len()` contiguous `ty` components, and those entities must have
+    /// been allocated but not yet had their `ty` component written.
+    pub(crate) unsafe fn put_column(
+        &mut self,
+        ty: TypeId,
+        size: usize,
+        data: *const u8,
+        ticks: &[ComponentTicks],
+    ) {
+        debug_assert!(ticks.len() <= self.len as usize);
+        if self.pins.contains_key(&ty) {
+            for (index, &ticks) in ticks.iter().enumerate() {
+                let component = data.add(size * index) as *mut u8;
+                self.put_dynamic(component, ty, size, index as u32, ticks);
+            }
+            return;
+        }
+        let state = self.state.get_mut(&ty).unwrap();
+        let base = state.base(*self.data.get());
+        ptr::copy_nonoverlapping(data, base, size * ticks.len());
+        state.ticks.get_mut()[..ticks.len()].copy_from_slice(ticks);
+        *state.version.get_mut() = state.version.get_mut().wrapping_add(1);
+        if let Some(x) = self.shadows.get_mut(&ty) {
+            for index in 0..ticks.len() {
+                x.fill(index as u32, base.add(size * index));
+            }
+        }
+    }
+
+    /// Change ticks of the `ty` component of the entity at `index`
+    pub(crate) fn component_ticks(&self, ty: TypeId, index: u32) -> Option<ComponentTicks> {
+        debug_assert!(index < self.len);
+        let state = self.state.get(&ty)?;
+        Some(unsafe { (*state.ticks.get())[index as usize] })
+    }
+
+    /// Pointer to the change ticks of the first `T` component, if present
+    ///
+    /// Like component data, ticks may only be written while `T` is uniquely borrowed.
+    pub(crate) fn ticks<T: Component>(&self) -> Option<NonNull<ComponentTicks>> {
+        let state = self.state.get(&TypeId::of::<T>())?;
+        Some(unsafe { NonNull::new_unchecked((*state.ticks.get()).as_mut_ptr()) })
+    }
+
+    /// Ensure no component's ticks are more than `max_age` older than `now`
+    pub(crate) fn clamp_ticks(&mut self, now: u32, max_age: u32) {
+        let len = self.len as usize;
+        for state in self.state.values_mut() {
+            for ticks in &mut state.ticks.get_mut()[..len] {
+                ticks.clamp(now, max_age);
+            }
+            *state.version.get_mut() = state.version.get_mut().wrapping_add(1);
+        }
     }
 
     /// How, if at all, `Q` will access entities in this archetype
     pub fn access<Q: Query>(&self) -> Option<Access> {
         Q::Fetch::access(self)
     }
+
+    /// Feed every `T` component in this archetype, in storage order, into `state`
+    ///
+    /// Returns `false`, leaving `state` untouched, if this archetype has no `T` column. Storage order
+    /// is deterministic given an identical sequence of world operations, so peers in a lockstep
+    /// simulation can compare checksums of individual columns to locate the component type and
+    /// archetype where they first diverged.
+    ///
+    /// Panics if `T` is uniquely borrowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::collections::hash_map::DefaultHasher;
+    /// # use std::hash::Hasher;
+    /// let mut world = World::new();
+    /// world.spawn((1u32, true));
+    /// world.spawn((2u32, true));
+    /// let archetype = world.archetypes().find(|x| x.len() == 2).unwrap();
+    /// let mut hasher = DefaultHasher::new();
+    /// assert!(archetype.column_checksum::<u32>(&mut hasher));
+    /// assert!(!archetype.column_checksum::<i64>(&mut hasher));
+    /// let checksum = hasher.finish();
+    /// ```
+    pub fn column_checksum<T: Component + Hash>(&self, state: &mut impl Hasher) -> bool {
+        self.with_column::<T, _>(|column| column.hash(state))
+            .is_some()
+    }
+}
+
+/// Releases a shared borrow of the `T` column of an archetype when dropped
+struct ColumnGuard<'a, T: Component>(&'a Archetype, PhantomData<fn(T)>);
+
+impl<T: Component> Drop for ColumnGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.release::<T>();
+    }
 }
 
 impl Drop for Archetype {
     fn drop(&mut self) {
-        self.clear();
-        if self.data_size != 0 {
-            unsafe {
-                dealloc(
-                    (*self.data.get()).as_ptr().cast(),
-                    Layout::from_size_align_unchecked(
-                        self.data_size,
-                        self.types.first().map_or(1, |x| x.layout.align()),
-                    ),
-                );
+        self.clear(|_| false, |_, ty, ptr| unsafe { ty.drop(ptr) });
+        self.free_released();
+        self.free_storage();
+    }
+}
+
+/// Layout of a `ty` column's entries given the pinned types of its archetype
+fn stored_layout(pins: &HashMap<TypeId, Arc<PinArena>>, ty: &TypeInfo) -> Layout {
+    if pins.contains_key(&ty.id) {
+        Layout::new::<*mut u8>()
+    } else {
+        ty.layout
+    }
+}
+
+/// Cursor over a column of `T`s, following the pointer to each component for pinned types
+pub(crate) enum ColumnPtr<T> {
+    Direct(NonNull<T>),
+    Indirect(NonNull<*mut T>),
+}
+
+impl<T> Clone for ColumnPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ColumnPtr<T> {}
+
+impl<T> ColumnPtr<T> {
+    /// # Safety
+    /// The result must be within the column, or one past its end
+    pub(crate) unsafe fn add(self, count: usize) -> Self {
+        match self {
+            ColumnPtr::Direct(x) => {
+                ColumnPtr::Direct(NonNull::new_unchecked(x.as_ptr().add(count)))
+            }
+            ColumnPtr::Indirect(x) => {
+                ColumnPtr::Indirect(NonNull::new_unchecked(x.as_ptr().add(count)))
             }
         }
     }
+
+    /// Reinterpret as a column of `U`s
+    #[cfg(feature = "plugin")]
+    pub(crate) fn cast<U>(self) -> ColumnPtr<U> {
+        match self {
+            ColumnPtr::Direct(x) => ColumnPtr::Direct(x.cast()),
+            ColumnPtr::Indirect(x) => ColumnPtr::Indirect(x.cast()),
+        }
+    }
+
+    /// Address of the current component
+    ///
+    /// # Safety
+    /// The cursor must be within the column
+    pub(crate) unsafe fn get(self) -> *mut T {
+        match self {
+            ColumnPtr::Direct(x) => x.as_ptr(),
+            ColumnPtr::Indirect(x) => *x.as_ptr(),
+        }
+    }
+
+    /// Start loading the component `distance` entries ahead into cache
+    pub(crate) fn prefetch(self, distance: usize) {
+        match self {
+            ColumnPtr::Direct(x) => prefetch(x.as_ptr().wrapping_add(distance)),
+            // Only the entry; the component's address isn't known until it's loaded
+            ColumnPtr::Indirect(x) => prefetch(x.as_ptr().wrapping_add(distance)),
+        }
+    }
 }
 
+/// Callback passed the IDs of entities whose components were written under a unique borrow, as it's
+/// released
+pub(crate) type WriteBarrier = dyn Fn(u32) + Send + Sync;
+
+struct Barrier {
+    f: Arc<WriteBarrier>,
+    /// Change tick of writes made under the current unique borrow, if any
+    tick: AtomicU64,
+}
+
+/// `Barrier::tick` when no writes have been made
+const NO_WRITES: u64 = u64::MAX;
+
 struct TypeState {
     offset: usize,
     borrow: AtomicBorrow,
+    ticks: UnsafeCell<Box<[ComponentTicks]>>,
+    /// Incremented whenever the column may have been modified
+    version: AtomicU32,
+    /// The column's own storage, if allocated from an arena rather than at `offset` in the
+    /// archetype's data
+    column: Option<(NonNull<u8>, Arc<ColumnArena>)>,
 }
 
 impl TypeState {
-    fn new(offset: usize) -> Self {
+    fn new(offset: usize, ticks: Box<[ComponentTicks]>, version: u32) -> Self {
         Self {
             offset,
             borrow: AtomicBorrow::new(),
+            ticks: UnsafeCell::new(ticks),
+            version: AtomicU32::new(version),
+            column: None,
+        }
+    }
+
+    /// Pointer to the first component, given the archetype's data
+    unsafe fn base(&self, data: NonNull<u8>) -> *mut u8 {
+        match self.column {
+            Some((column, _)) => column.as_ptr(),
+            None => data.as_ptr().add(self.offset),
+        }
+    }
+
+    /// Release the column's own storage, if any, which has room for `capacity` entries of
+    /// `layout`
+    unsafe fn free_column(&self, layout: Layout, capacity: usize) {
+        if let Some((column, ref arena)) = self.column {
+            arena.dealloc(column, layout.size() * capacity, layout.align());
+        }
+    }
+}
+
+/// Greatest age, in ticks, that change detection can represent
+///
+/// Older ticks are clamped to this age, so that arbitrarily old changes are never mistaken for
+/// recent ones after the tick counter wraps around.
+pub(crate) const MAX_CHANGE_AGE: u32 = u32::MAX / 2;
+
+/// The world change ticks at which a component was added and last modified
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub(crate) struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    fn clamp(&mut self, now: u32, max_age: u32) {
+        for tick in [&mut self.added, &mut self.changed] {
+            if now.wrapping_sub(*tick) > max_age {
+                *tick = now.wrapping_sub(max_age);
+            }
         }
     }
 }
@@ -335,7 +1293,10 @@ impl TypeState {
 pub struct TypeInfo {
     id: TypeId,
     layout: Layout,
-    drop: unsafe fn(*mut u8),
+    /// `None` if dropping is a no-op, allowing values to be discarded without visiting each one
+    drop: Option<unsafe fn(*mut u8)>,
+    #[cfg(feature = "stats")]
+    name: &'static str,
 }
 
 impl TypeInfo {
@@ -348,20 +1309,40 @@ impl TypeInfo {
         Self {
             id: TypeId::of::<T>(),
             layout: Layout::new::<T>(),
-            drop: drop_ptr::<T>,
+            drop: if mem::needs_drop::<T>() {
+                Some(drop_ptr::<T>)
+            } else {
+                None
+            },
+            #[cfg(feature = "stats")]
+            name: core::any::type_name::<T>(),
         }
     }
 
-    pub(crate) fn id(&self) -> TypeId {
+    /// Identifies the type
+    pub fn id(&self) -> TypeId {
         self.id
     }
 
-    pub(crate) fn layout(&self) -> Layout {
+    /// Size and alignment of the type
+    pub fn layout(&self) -> Layout {
         self.layout
     }
 
+    #[cfg(feature = "stats")]
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether `drop` does anything
+    pub(crate) fn needs_drop(&self) -> bool {
+        self.drop.is_some()
+    }
+
     pub(crate) unsafe fn drop(&self, data: *mut u8) {
-        (self.drop)(data)
+        if let Some(drop) = self.drop {
+            drop(data)
+        }
     }
 }
 
@@ -390,6 +1371,13 @@ impl PartialEq for TypeInfo {
 
 impl Eq for TypeInfo {}
 
+/// Initial version of a new archetype, leaving room for 2^32 changes before it could coincide with
+/// another archetype's
+fn fresh_version() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1 << 32, Ordering::Relaxed)
+}
+
 fn align(x: usize, alignment: usize) -> usize {
     debug_assert!(alignment.is_power_of_two());
     (x + alignment - 1) & (!alignment + 1)
@@ -0,0 +1,128 @@
+use core::marker::PhantomData;
+
+use crate::query::{Fetch, QueryTicks};
+use crate::{Access, Archetype, Entity, Query, World};
+
+/// Borrowed, typed access to the components of a single archetype
+///
+/// Lets code built on top of hecs, like custom parallel iterators or mirrors of component data in
+/// GPU memory, work an archetype at a time without raw pointers. Construction checks that `Q`
+/// matches the archetype and acquires the same borrows as executing `Q` would, which are released
+/// when the view is dropped. Rows are numbered from 0 in storage order, which changes whenever
+/// entities are added to or removed from the archetype.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1, 2.0f32));
+/// world.spawn((3, 4.0f32));
+/// world.spawn(("abc",));
+/// for archetype in world.archetypes() {
+///     let mut view = match TypedArchetypeView::<(&mut i32, &f32)>::new(&world, archetype) {
+///         Some(x) => x,
+///         None => continue,
+///     };
+///     let (ints, floats) = view.columns();
+///     for (x, y) in ints.iter_mut().zip(floats) {
+///         *x += *y as i32;
+///     }
+///     let row = view.row(a).unwrap();
+///     assert_eq!(view.entity(row), Some(a));
+///     assert_eq!(*view.get(row).unwrap().0, 3);
+/// }
+/// ```
+pub struct TypedArchetypeView<'w, Q: Query> {
+    world: &'w World,
+    archetype: &'w Archetype,
+    /// Index of `archetype` in `world`
+    index: u32,
+    ticks: QueryTicks,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<'w, Q: Query> TypedArchetypeView<'w, Q> {
+    /// Borrow the components of `archetype`, one of `world`'s archetypes, that `Q` accesses
+    ///
+    /// Returns `None` if `Q` doesn't match `archetype`. Panics if `archetype` belongs to another
+    /// world, or if the borrows conflict with existing ones.
+    pub fn new(world: &'w World, archetype: &'w Archetype) -> Option<Self> {
+        let index = world
+            .archetypes_inner()
+            .iter()
+            .position(|x| core::ptr::eq(x, archetype))
+            .expect("archetype belongs to a different world") as u32;
+        let access = Q::Fetch::access(archetype)?;
+        let ticks = QueryTicks::new(world.change_tick());
+        // Ensures `columns` and `get` can always construct a fetch
+        unsafe { Q::Fetch::get(archetype, 0, ticks)? };
+        if access >= Access::Read {
+            Q::Fetch::borrow(archetype);
+        }
+        Some(Self {
+            world,
+            archetype,
+            index,
+            ticks,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> u32 {
+        self.archetype.len()
+    }
+
+    /// Whether there are no rows
+    pub fn is_empty(&self) -> bool {
+        self.archetype.is_empty()
+    }
+
+    /// The entity in `row`, if in bounds
+    pub fn entity(&self, row: u32) -> Option<Entity> {
+        if row >= self.archetype.len() {
+            return None;
+        }
+        let id = self.archetype.entity_id(row);
+        Some(self.world.entities_meta()[id as usize].entity(id))
+    }
+
+    /// The row of `entity`, if it's live and in this archetype
+    pub fn row(&self, entity: Entity) -> Option<u32> {
+        let loc = self.world.entities_inner().get(entity).ok()?;
+        if loc.archetype != self.index || loc.index >= self.archetype.len() {
+            return None;
+        }
+        Some(loc.index)
+    }
+
+    /// Slices of every row's components, e.g. `(&mut [A], &[B])` for `Q = (&mut A, &B)`
+    pub fn columns(&mut self) -> <Q::Fetch as Fetch<'_>>::Slice {
+        unsafe {
+            let mut fetch = Q::Fetch::get(self.archetype, 0, self.ticks).unwrap();
+            fetch.slice(self.archetype.len() as usize)
+        }
+    }
+
+    /// The components of `row`, if in bounds
+    pub fn get(&mut self, row: u32) -> Option<<Q::Fetch as Fetch<'_>>::Item> {
+        if row >= self.archetype.len() {
+            return None;
+        }
+        unsafe {
+            let mut fetch = Q::Fetch::get(self.archetype, row as usize, self.ticks).unwrap();
+            Some(fetch.next())
+        }
+    }
+}
+
+impl<Q: Query> Drop for TypedArchetypeView<'_, Q> {
+    fn drop(&mut self) {
+        if Q::Fetch::access(self.archetype) >= Some(Access::Read) {
+            Q::Fetch::release(self.archetype);
+        }
+    }
+}
+
+unsafe impl<Q: Query> Send for TypedArchetypeView<'_, Q> {}
+unsafe impl<Q: Query> Sync for TypedArchetypeView<'_, Q> {}
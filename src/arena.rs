@@ -0,0 +1,194 @@
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::alloc::alloc::{alloc, dealloc};
+use crate::alloc::vec::Vec;
+
+/// Memory dedicated to the columns of particular component types
+///
+/// By default, all of an archetype's columns share a single allocation. Columns of types assigned
+/// to an arena with `World::use_arena` are instead allocated individually, aligned to a cache line,
+/// and optionally backed by transparent huge pages, reducing TLB misses in hot loops over large
+/// numbers of components. An arena may be shared by several types, and its usage inspected with
+/// `allocated`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// # use std::sync::Arc;
+/// struct Position([f32; 3]);
+/// let arena = Arc::new(ColumnArena::new().with_hugepages());
+/// let mut world = World::new();
+/// world.use_arena::<Position>(arena.clone());
+/// world.spawn_batch((0..1000).map(|_| (Position([0.0; 3]), true)));
+/// assert!(arena.allocated() >= 1000 * std::mem::size_of::<Position>());
+/// ```
+#[derive(Debug, Default)]
+pub struct ColumnArena {
+    hugepages: bool,
+    allocated: AtomicUsize,
+}
+
+impl ColumnArena {
+    /// Create an arena that allocates from the global allocator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advise the operating system to back columns of at least one huge page with huge pages
+    ///
+    /// Such columns are aligned and padded to the huge page size. Only has an effect on Linux
+    /// with the `hugepages` feature enabled.
+    pub fn with_hugepages(mut self) -> Self {
+        self.hugepages = true;
+        self
+    }
+
+    /// Number of bytes currently allocated for columns
+    pub fn allocated(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed)
+    }
+
+    /// Layout of a column of `size` bytes whose elements have alignment `align`
+    fn layout(&self, size: usize, align: usize) -> Layout {
+        let mut align = align.max(CACHE_LINE);
+        let mut size = size;
+        if self.hugepages
+            && cfg!(all(feature = "hugepages", target_os = "linux"))
+            && size >= HUGE_PAGE
+        {
+            align = align.max(HUGE_PAGE);
+            size = (size + HUGE_PAGE - 1) & !(HUGE_PAGE - 1);
+        }
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    /// Allocate a column of `size` bytes whose elements have alignment `align`
+    pub(crate) fn alloc(&self, size: usize, align: usize) -> NonNull<u8> {
+        let layout = self.layout(size, align);
+        if layout.size() == 0 {
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+        let ptr = NonNull::new(unsafe { alloc(layout) }).expect("column allocation failed");
+        #[cfg(all(feature = "hugepages", target_os = "linux"))]
+        if layout.align() >= HUGE_PAGE {
+            // Purely advisory, so failure is harmless
+            unsafe {
+                libc::madvise(ptr.as_ptr().cast(), layout.size(), libc::MADV_HUGEPAGE);
+            }
+        }
+        self.allocated.fetch_add(layout.size(), Ordering::Relaxed);
+        ptr
+    }
+
+    /// Free a column allocated by `alloc` with the same `size` and `align`
+    pub(crate) unsafe fn dealloc(&self, ptr: NonNull<u8>, size: usize, align: usize) {
+        let layout = self.layout(size, align);
+        if layout.size() == 0 {
+            return;
+        }
+        dealloc(ptr.as_ptr(), layout);
+        self.allocated.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+/// Storage for the components of a type registered with `World::pin_component`, each in a slot of
+/// its own
+///
+/// Slots are carved out of chunks that are never reallocated, so a component stays put for as long
+/// as it's stored, while the archetypes holding it keep only a pointer to its slot. Shared by every
+/// archetype of a world that stores the type, since entities take their slots with them as they
+/// move between archetypes.
+pub(crate) struct PinArena {
+    name: &'static str,
+    layout: Layout,
+    slots: UnsafeCell<Slots>,
+}
+
+#[derive(Default)]
+struct Slots {
+    chunks: Vec<NonNull<u8>>,
+    free: Vec<NonNull<u8>>,
+}
+
+// Slots are only allocated and freed by archetypes, through unique references, and an arena is
+// never shared between worlds, so access through `&PinArena` is never concurrent.
+unsafe impl Send for PinArena {}
+unsafe impl Sync for PinArena {}
+
+impl PinArena {
+    pub(crate) fn new(name: &'static str, layout: Layout) -> Self {
+        Self {
+            name,
+            layout: layout.pad_to_align(),
+            slots: UnsafeCell::new(Slots::default()),
+        }
+    }
+
+    /// An empty arena for the same type
+    pub(crate) fn fresh(&self) -> Self {
+        Self::new(self.name, self.layout)
+    }
+
+    /// Name of the pinned type
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn chunk_layout(&self) -> Layout {
+        Layout::from_size_align(self.layout.size() * PIN_CHUNK, self.layout.align()).unwrap()
+    }
+
+    /// Allocate an uninitialized slot
+    ///
+    /// # Safety
+    /// Must not be called concurrently with any other use of the arena
+    pub(crate) unsafe fn alloc(&self) -> NonNull<u8> {
+        if self.layout.size() == 0 {
+            return NonNull::new_unchecked(self.layout.align() as *mut u8);
+        }
+        let slots = &mut *self.slots.get();
+        if let Some(x) = slots.free.pop() {
+            return x;
+        }
+        let chunk = NonNull::new(alloc(self.chunk_layout())).expect("slot allocation failed");
+        slots.chunks.push(chunk);
+        // Hand out the first slot, keeping the rest in address order
+        slots.free.extend(
+            (1..PIN_CHUNK)
+                .rev()
+                .map(|i| NonNull::new_unchecked(chunk.as_ptr().add(i * self.layout.size()))),
+        );
+        chunk
+    }
+
+    /// Make a slot from `alloc` available for reuse
+    ///
+    /// # Safety
+    /// Must not be called concurrently with any other use of the arena, and `slot` must not be
+    /// used afterwards
+    pub(crate) unsafe fn free(&self, slot: NonNull<u8>) {
+        if self.layout.size() != 0 {
+            (*self.slots.get()).free.push(slot);
+        }
+    }
+}
+
+impl Drop for PinArena {
+    fn drop(&mut self) {
+        let layout = self.chunk_layout();
+        for &chunk in &self.slots.get_mut().chunks {
+            unsafe {
+                dealloc(chunk.as_ptr(), layout);
+            }
+        }
+    }
+}
+
+/// Number of slots allocated at once by a `PinArena`
+const PIN_CHUNK: usize = 64;
+
+const CACHE_LINE: usize = 64;
+const HUGE_PAGE: usize = 2 * 1024 * 1024;
@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::any::type_name;
+use core::fmt;
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::archetype::Archetype;
-use crate::{Component, MissingComponent};
+use crate::archetype::{Archetype, TypeInfo};
+use crate::{Component, MissingComponent, WorldError};
 
 pub struct AtomicBorrow(AtomicUsize);
 
@@ -52,13 +54,131 @@ impl AtomicBorrow {
         debug_assert!(value & UNIQUE_BIT == 0, "shared release of unique borrow");
     }
 
+    /// Convert a unique borrow into a shared borrow, without any intervening unborrowed state
+    pub fn downgrade(&self) {
+        // Clear the unique bit and add a shared borrow in one step
+        let value = self
+            .0
+            .fetch_add(1usize.wrapping_sub(UNIQUE_BIT), Ordering::AcqRel);
+        debug_assert_ne!(value & UNIQUE_BIT, 0, "downgrade of shared borrow");
+    }
+
     pub fn release_mut(&self) {
         let value = self.0.fetch_and(!UNIQUE_BIT, Ordering::Release);
         debug_assert_ne!(value & UNIQUE_BIT, 0, "unique release of shared borrow");
     }
+
+    /// Number of shared borrows, and whether there's a unique borrow
+    pub fn get(&self) -> (usize, bool) {
+        let value = self.0.load(Ordering::Acquire);
+        (value & !UNIQUE_BIT, value & UNIQUE_BIT != 0)
+    }
+}
+
+const UNIQUE_BIT: usize = !(usize::MAX >> 1);
+
+/// Error indicating that a component couldn't be borrowed due to a conflicting borrow, or
+/// couldn't be borrowed uniquely because it's stored in read-only memory
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BorrowError {
+    type_name: &'static str,
+    unique: bool,
+    read_only: bool,
 }
 
-const UNIQUE_BIT: usize = !(usize::max_value() >> 1);
+impl BorrowError {
+    pub(crate) fn new<T: Component>(unique: bool) -> Self {
+        Self {
+            type_name: type_name::<T>(),
+            unique,
+            read_only: false,
+        }
+    }
+
+    pub(crate) fn read_only<T: Component>() -> Self {
+        Self {
+            type_name: type_name::<T>(),
+            unique: true,
+            read_only: true,
+        }
+    }
+
+    /// Name of the component type that couldn't be borrowed
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Whether the failed borrow was unique
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Whether the borrow failed because the component is stored in read-only memory by
+    /// `World::spawn_external`, rather than due to a conflicting borrow
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.read_only {
+            write!(f, "{} is stored in read-only memory", self.type_name)
+        } else if self.unique {
+            write!(f, "{} already borrowed", self.type_name)
+        } else {
+            write!(f, "{} already borrowed uniquely", self.type_name)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BorrowError {}
+
+/// Outstanding borrows of a component type across a world's archetypes, from
+/// `World::borrow_state`
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BorrowState {
+    /// Number of shared borrows of the type's columns
+    pub shared: u32,
+    /// Number of the type's columns that are uniquely borrowed
+    pub unique: u32,
+}
+
+impl BorrowState {
+    /// Whether the type was borrowed at all
+    pub fn is_borrowed(&self) -> bool {
+        self.shared != 0 || self.unique != 0
+    }
+
+    /// Whether shared borrows of the type would have succeeded in every archetype
+    pub fn can_borrow(&self) -> bool {
+        self.unique == 0
+    }
+
+    /// Whether unique borrows of the type would have succeeded in every archetype
+    pub fn can_borrow_mut(&self) -> bool {
+        !self.is_borrowed()
+    }
+}
+
+/// How queries respond to borrowing a component that's already borrowed incompatibly
+///
+/// Set for a world with `World::set_borrow_policy`, or for a single query with
+/// `QueryBorrow::borrow_policy`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum BorrowPolicy {
+    /// Panic, as is appropriate while developing
+    #[default]
+    Panic,
+    /// Make `QueryBorrow::try_iter` return an error, releasing any borrows already made
+    ///
+    /// Methods that can't return an error, like `QueryBorrow::iter`, panic instead.
+    Error,
+    /// Skip the archetypes whose components couldn't be borrowed, recording the errors to be
+    /// retrieved with `QueryBorrow::skipped`
+    Skip,
+}
 
 /// Shared borrow of an entity's component
 #[derive(Clone)]
@@ -72,16 +192,26 @@ impl<'a, T: Component> Ref<'a, T> {
         archetype: &'a Archetype,
         index: u32,
     ) -> Result<Self, MissingComponent> {
-        let target = NonNull::new_unchecked(
-            archetype
-                .get::<T>()
-                .ok_or_else(MissingComponent::new::<T>)?
-                .as_ptr()
-                .add(index as usize),
-        );
-        archetype.borrow::<T>();
+        let target = archetype
+            .get_at::<T>(index)
+            .ok_or_else(MissingComponent::new::<T>)?;
+        Ok(Self::from_raw(archetype, target))
+    }
+
+    /// Like `new`, but fails rather than panicking if the component is uniquely borrowed
+    pub(crate) unsafe fn try_new(archetype: &'a Archetype, index: u32) -> Result<Self, WorldError> {
+        let target = archetype
+            .get_at::<T>(index)
+            .ok_or_else(MissingComponent::new::<T>)?;
+        archetype.try_borrow::<T>()?;
         Ok(Self { archetype, target })
     }
+
+    /// Borrow the component at `target`, which must be stored in `archetype`
+    pub(crate) unsafe fn from_raw(archetype: &'a Archetype, target: NonNull<T>) -> Self {
+        archetype.borrow::<T>();
+        Self { archetype, target }
+    }
 }
 
 unsafe impl<T: Component> Send for Ref<'_, T> {}
@@ -103,23 +233,83 @@ impl<'a, T: Component> Deref for Ref<'a, T> {
 /// Unique borrow of an entity's component
 pub struct RefMut<'a, T: Component> {
     archetype: &'a Archetype,
+    index: u32,
     target: NonNull<T>,
 }
 
 impl<'a, T: Component> RefMut<'a, T> {
+    /// Uniquely borrow a component, marking it as changed at world change tick `tick`
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
         index: u32,
+        tick: u32,
     ) -> Result<Self, MissingComponent> {
-        let target = NonNull::new_unchecked(
-            archetype
-                .get::<T>()
-                .ok_or_else(MissingComponent::new::<T>)?
-                .as_ptr()
-                .add(index as usize),
-        );
+        let target = archetype
+            .get_at::<T>(index)
+            .ok_or_else(MissingComponent::new::<T>)?;
+        Ok(Self::from_raw(archetype, index, target, tick))
+    }
+
+    /// Like `new`, but fails rather than panicking if the component is borrowed or read-only
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        index: u32,
+        tick: u32,
+    ) -> Result<Self, WorldError> {
+        let target = archetype
+            .get_at::<T>(index)
+            .ok_or_else(MissingComponent::new::<T>)?;
+        archetype.try_borrow_mut::<T>()?;
+        (*archetype.ticks::<T>().unwrap().as_ptr().add(index as usize)).changed = tick;
+        Ok(Self {
+            archetype,
+            index,
+            target,
+        })
+    }
+
+    /// Uniquely borrow the component at `target`, which must be at `index` in `archetype`
+    pub(crate) unsafe fn from_raw(
+        archetype: &'a Archetype,
+        index: u32,
+        target: NonNull<T>,
+        tick: u32,
+    ) -> Self {
         archetype.borrow_mut::<T>();
-        Ok(Self { archetype, target })
+        (*archetype.ticks::<T>().unwrap().as_ptr().add(index as usize)).changed = tick;
+        Self {
+            archetype,
+            index,
+            target,
+        }
+    }
+}
+
+impl<'a, T: Component> RefMut<'a, T> {
+    /// Convert into a shared borrow of the same component
+    ///
+    /// Unlike dropping `this` and borrowing again, there's no moment at which the component is
+    /// unborrowed, so a unique borrow by another thread can't intervene.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let mut x = world.get_mut::<i32>(a).unwrap();
+    /// *x = 456;
+    /// let x = RefMut::downgrade(x);
+    /// let y = world.get::<i32>(a).unwrap();
+    /// assert_eq!(*x, *y);
+    /// assert!(world.query::<&mut i32>().borrow_policy(BorrowPolicy::Error).try_iter().is_err());
+    /// ```
+    pub fn downgrade(this: Self) -> Ref<'a, T> {
+        let this = core::mem::ManuallyDrop::new(this);
+        this.archetype.downgrade_one::<T>(this.index);
+        Ref {
+            archetype: this.archetype,
+            target: this.target,
+        }
     }
 }
 
@@ -128,7 +318,7 @@ unsafe impl<T: Component> Sync for RefMut<'_, T> {}
 
 impl<'a, T: Component> Drop for RefMut<'a, T> {
     fn drop(&mut self) {
-        self.archetype.release_mut::<T>();
+        self.archetype.release_mut_one::<T>(self.index);
     }
 }
 
@@ -150,6 +340,7 @@ impl<'a, T: Component> DerefMut for RefMut<'a, T> {
 pub struct EntityRef<'a> {
     archetype: Option<&'a Archetype>,
     index: u32,
+    tick: u32,
 }
 
 impl<'a> EntityRef<'a> {
@@ -158,13 +349,15 @@ impl<'a> EntityRef<'a> {
         Self {
             archetype: None,
             index: 0,
+            tick: 0,
         }
     }
 
-    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32) -> Self {
+    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32, tick: u32) -> Self {
         Self {
             archetype: Some(archetype),
             index,
+            tick,
         }
     }
 
@@ -180,7 +373,56 @@ impl<'a> EntityRef<'a> {
     ///
     /// Panics if the component is already borrowed from another entity with the same components.
     pub fn get_mut<T: Component>(&self) -> Option<RefMut<'a, T>> {
-        Some(unsafe { RefMut::new(self.archetype?, self.index).ok()? })
+        Some(unsafe { RefMut::new(self.archetype?, self.index, self.tick).ok()? })
+    }
+
+    /// Whether the entity has a `T` component
+    ///
+    /// Unlike `get`, never borrows the component, so it can't panic.
+    pub fn has<T: Component>(&self) -> bool {
+        self.archetype.is_some_and(|x| x.has::<T>())
+    }
+
+    /// The types of the entity's components, in no particular order
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let entity = world.entity(a).unwrap();
+    /// assert!(entity.has::<bool>() && !entity.has::<&str>());
+    /// assert!(entity.component_types().any(|ty| ty == TypeInfo::of::<i32>()));
+    /// assert_eq!(entity.component_types().len(), 2);
+    /// ```
+    pub fn component_types(&self) -> impl ExactSizeIterator<Item = TypeInfo> + 'a {
+        self.archetype
+            .map_or(&[][..], |x| x.types())
+            .iter()
+            .copied()
+    }
+
+    /// Number of components the entity has
+    pub fn component_count(&self) -> usize {
+        self.archetype.map_or(0, |x| x.types().len())
+    }
+
+    /// Total size in bytes of the entity's components
+    ///
+    /// Excludes per-entity and per-component bookkeeping, such as change ticks.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123u32, [0u8; 3]));
+    /// let entity = world.entity(a).unwrap();
+    /// assert_eq!(entity.component_count(), 2);
+    /// assert_eq!(entity.bytes(), 7);
+    /// ```
+    pub fn bytes(&self) -> usize {
+        self.archetype
+            .map_or(0, |x| x.types().iter().map(|ty| ty.layout().size()).sum())
     }
 }
 
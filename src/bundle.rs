@@ -144,3 +144,79 @@ macro_rules! count {
 }
 
 smaller_tuples_too!(tuple_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+
+/// A collection of components produced by fallible constructors
+///
+/// Implemented for tuples of closures like `|| -> Result<T, E> { ... }`, which all share an error
+/// type. See `World::try_spawn_with`.
+pub trait TryBundle {
+    /// The components produced on success
+    type Bundle: Bundle;
+    /// The error reported by constructors
+    type Error;
+
+    /// Invoke each constructor, passing the components they produce to `f` to be moved out of
+    ///
+    /// Stops at the first failure. Components already passed to `f` are then its responsibility.
+    #[doc(hidden)]
+    unsafe fn try_put(self, f: impl FnMut(*mut u8, TypeId, usize)) -> Result<(), Self::Error>;
+}
+
+/// A fallible constructor for a component of a `TryBundle`
+pub trait TryComponent {
+    /// The component produced on success
+    type Output: Component;
+    /// The error reported on failure
+    type Error;
+
+    /// Produce the component
+    fn construct(self) -> Result<Self::Output, Self::Error>;
+}
+
+impl<T: Component, E, F: FnOnce() -> Result<T, E>> TryComponent for F {
+    type Output = T;
+    type Error = E;
+
+    fn construct(self) -> Result<T, E> {
+        self()
+    }
+}
+
+/// Pass the component produced by `constructor`, if any, to `f` to be moved out of
+unsafe fn put_constructed<C: TryComponent>(
+    constructor: C,
+    f: &mut impl FnMut(*mut u8, TypeId, usize),
+) -> Result<(), C::Error> {
+    let mut x = mem::ManuallyDrop::new(constructor.construct()?);
+    f(
+        (&mut *x as *mut C::Output).cast::<u8>(),
+        TypeId::of::<C::Output>(),
+        mem::size_of::<C::Output>(),
+    );
+    Ok(())
+}
+
+macro_rules! try_tuple_impl {
+    () => {};
+    ($first: ident $(, $name: ident)*) => {
+        impl<$first: TryComponent, $($name: TryComponent<Error = $first::Error>),*> TryBundle
+            for ($first, $($name,)*)
+        {
+            type Bundle = ($first::Output, $($name::Output,)*);
+            type Error = $first::Error;
+
+            unsafe fn try_put(
+                self,
+                mut f: impl FnMut(*mut u8, TypeId, usize),
+            ) -> Result<(), Self::Error> {
+                #[allow(non_snake_case)]
+                let ($first, $($name,)*) = self;
+                put_constructed($first, &mut f)?;
+                $(put_constructed($name, &mut f)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+smaller_tuples_too!(try_tuple_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
@@ -0,0 +1,91 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::query::Fetch;
+use crate::{Query, QueryAccess, QueryIter, World};
+
+/// The result of a reduction over a query's results, recomputed only when they may have changed
+///
+/// Suited to expensive summaries that are read more often than the underlying components change,
+/// e.g. the bounding box of every `Position`. The result is considered stale if the world has
+/// gained a matching archetype, if entities have been added to, removed from, or moved within a
+/// matching archetype, or if any component read by `Q` in a matching archetype has been uniquely
+/// borrowed, as by a `&mut` query or `World::get_mut`, whether or not it was modified.
+///
+/// A `CachedQuery` must only be used with a single `World`, and `Q` must not borrow anything
+/// uniquely.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Position(f32);
+/// let mut world = World::new();
+/// world.spawn((Position(1.0),));
+/// let a = world.spawn((Position(-2.0), true));
+/// let mut extent = CachedQuery::<&Position, f32>::new(|iter| {
+///     iter.map(|(_, p)| p.0.abs()).fold(0.0, f32::max)
+/// });
+/// assert_eq!(*extent.get(&world), 2.0);
+/// world.get_mut::<Position>(a).unwrap().0 = 3.0;
+/// assert_eq!(*extent.get(&world), 3.0);
+/// ```
+pub struct CachedQuery<Q: Query, K> {
+    reduce: Box<Reduce<Q, K>>,
+    /// Component types read by `Q`
+    types: Vec<TypeId>,
+    /// Versions of the matching archetypes and their read columns when `value` was computed
+    stamp: Vec<u64>,
+    value: Option<K>,
+}
+
+type Reduce<Q, K> = dyn for<'q, 'w> FnMut(QueryIter<'q, 'w, Q>) -> K + Send + Sync;
+
+impl<Q: Query, K> CachedQuery<Q, K> {
+    /// Create a cache of `reduce` applied to the results of `Q`
+    ///
+    /// Panics if `Q` borrows any component uniquely.
+    pub fn new(
+        reduce: impl for<'q, 'w> FnMut(QueryIter<'q, 'w, Q>) -> K + Send + Sync + 'static,
+    ) -> Self {
+        assert!(
+            QueryAccess::of::<Q>().is_read_only(),
+            "cached queries must not borrow components uniquely"
+        );
+        let mut types = Vec::new();
+        Q::Fetch::for_each_borrow(&mut |id, _, _| types.push(id));
+        Self {
+            reduce: Box::new(reduce),
+            types,
+            stamp: Vec::new(),
+            value: None,
+        }
+    }
+
+    /// The result of the reduction over `world`, recomputing it if stale
+    pub fn get(&mut self, world: &World) -> &K {
+        let mut stamp = Vec::with_capacity(self.stamp.len());
+        for archetype in world.archetypes() {
+            if Q::Fetch::access(archetype).is_none() {
+                continue;
+            }
+            stamp.push(archetype.version());
+            stamp.extend(
+                self.types
+                    .iter()
+                    .filter_map(|&ty| archetype.column_version(ty))
+                    .map(u64::from),
+            );
+        }
+        if self.value.is_none() || stamp != self.stamp {
+            self.stamp = stamp;
+            self.value = Some((self.reduce)(world.query::<Q>().iter()));
+        }
+        self.value.as_ref().unwrap()
+    }
+
+    /// Force the next `get` to recompute the result
+    pub fn invalidate(&mut self) {
+        self.value = None;
+    }
+}
@@ -0,0 +1,79 @@
+use core::any::TypeId;
+use core::ptr;
+
+use hashbrown::HashMap;
+
+use crate::{Component, TypeInfo};
+
+/// Functions for cloning each component type, used by `World::clone_with`
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut registry = CloneRegistry::new();
+/// registry.register::<String>().register_copy::<i32>();
+/// let mut world = World::new();
+/// let a = world.spawn((String::from("abc"), 123));
+/// let copy = world.clone_with(&registry);
+/// assert_eq!(*copy.get::<String>(a).unwrap(), "abc");
+/// assert_eq!(*copy.get::<i32>(a).unwrap(), 123);
+/// ```
+#[derive(Default)]
+pub struct CloneRegistry {
+    cloners: HashMap<TypeId, CloneFn>,
+}
+
+impl CloneRegistry {
+    /// Create a registry that supports no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Support cloning worlds containing `T` components
+    pub fn register<T: Component + Clone>(&mut self) -> &mut Self {
+        self.cloners.insert(TypeId::of::<T>(), clone_slice::<T>);
+        self
+    }
+
+    /// Like `register`, but copies `T` components a column at a time rather than cloning each one
+    pub fn register_copy<T: Component + Copy>(&mut self) -> &mut Self {
+        self.cloners.insert(TypeId::of::<T>(), copy_slice::<T>);
+        self
+    }
+
+    /// Whether `T` has been registered
+    pub fn contains<T: Component>(&self) -> bool {
+        self.cloners.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Clone `len` components of type `ty` from `source` into `target`
+    ///
+    /// Panics if `ty` is unregistered.
+    pub(crate) unsafe fn clone(
+        &self,
+        ty: &TypeInfo,
+        source: *const u8,
+        target: *mut u8,
+        len: usize,
+    ) {
+        let clone = self
+            .cloners
+            .get(&ty.id())
+            .expect("cloned a world containing an unregistered component type");
+        clone(source, target, len);
+    }
+}
+
+/// Initializes `len` components at the second pointer from those at the first
+type CloneFn = unsafe fn(*const u8, *mut u8, usize);
+
+unsafe fn clone_slice<T: Clone>(source: *const u8, target: *mut u8, len: usize) {
+    let (source, target) = (source.cast::<T>(), target.cast::<T>());
+    for i in 0..len {
+        ptr::write(target.add(i), (*source.add(i)).clone());
+    }
+}
+
+unsafe fn copy_slice<T: Copy>(source: *const u8, target: *mut u8, len: usize) {
+    ptr::copy_nonoverlapping(source.cast::<T>(), target.cast::<T>(), len);
+}
@@ -0,0 +1,397 @@
+use crate::alloc::string::{String, ToString};
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+use core::str::FromStr;
+
+use hashbrown::HashMap;
+
+use crate::{Component, Entity, EntityBuilder, World};
+
+/// Construct a `Vec<Command>` from a list of operations on labeled entities
+///
+/// Each operation is one of `spawn label { name: value, ... }`, `insert label { name: value, ... }`,
+/// `remove label [name, ...]`, or `despawn label`, separated by semicolons. Values are converted
+/// to text with `ToString`, to be parsed by a `CommandRegistry` when applied.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let commands = hecs::commands![
+///     spawn player { health: 100, name: "bob" };
+///     insert player { armor: 5 };
+///     remove player [name];
+/// ];
+/// assert_eq!(commands[2].to_string(), "remove player name");
+/// ```
+#[macro_export]
+macro_rules! commands {
+    (@ $out:ident) => {};
+    (@ $out:ident spawn $label:ident { $($name:ident : $value:expr),* $(,)? } $(; $($rest:tt)*)?) => {
+        $out.push($crate::Command::Spawn {
+            label: stringify!($label).into(),
+            components: $crate::__component_values(&[$((stringify!($name), &$value)),*]),
+        });
+        $($crate::commands!(@ $out $($rest)*);)?
+    };
+    (@ $out:ident insert $label:ident { $($name:ident : $value:expr),* $(,)? } $(; $($rest:tt)*)?) => {
+        $out.push($crate::Command::Insert {
+            label: stringify!($label).into(),
+            components: $crate::__component_values(&[$((stringify!($name), &$value)),*]),
+        });
+        $($crate::commands!(@ $out $($rest)*);)?
+    };
+    (@ $out:ident remove $label:ident [ $($name:ident),* $(,)? ] $(; $($rest:tt)*)?) => {
+        $out.push($crate::Command::Remove {
+            label: stringify!($label).into(),
+            components: $crate::__component_names(&[$(stringify!($name)),*]),
+        });
+        $($crate::commands!(@ $out $($rest)*);)?
+    };
+    (@ $out:ident despawn $label:ident $(; $($rest:tt)*)?) => {
+        $out.push($crate::Command::Despawn {
+            label: stringify!($label).into(),
+        });
+        $($crate::commands!(@ $out $($rest)*);)?
+    };
+    ($($commands:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut out = $crate::__commands();
+        $crate::commands!(@ out $($commands)*);
+        out
+    }};
+}
+
+#[doc(hidden)]
+pub fn __commands() -> Vec<Command> {
+    Vec::new()
+}
+
+#[doc(hidden)]
+pub fn __component_values(values: &[(&str, &dyn ToString)]) -> Vec<(String, String)> {
+    values
+        .iter()
+        .map(|&(name, value)| (name.into(), value.to_string()))
+        .collect()
+}
+
+#[doc(hidden)]
+pub fn __component_names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|&x| x.into()).collect()
+}
+
+/// A structural change to a `World` in a data-driven form, e.g. from a test fixture or a script
+///
+/// Entities are referred to by labels bound when they're spawned, and components by names
+/// registered with a `CommandRegistry`, with values in text form. Commands display as, and can be
+/// parsed from, a line like `spawn player health=100 name=bob`, so values can't contain
+/// whitespace. See `commands!` for constructing them in code.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Command {
+    /// Spawn an entity with `components`, to be referred to as `label`
+    Spawn {
+        /// Label of the new entity
+        label: String,
+        /// Names and values of the components
+        components: Vec<(String, String)>,
+    },
+    /// Add `components` to an entity, replacing any of the same types
+    Insert {
+        /// Label of the entity
+        label: String,
+        /// Names and values of the components
+        components: Vec<(String, String)>,
+    },
+    /// Remove the named components from an entity, ignoring any it doesn't have
+    Remove {
+        /// Label of the entity
+        label: String,
+        /// Names of the components
+        components: Vec<String>,
+    },
+    /// Despawn an entity
+    Despawn {
+        /// Label of the entity
+        label: String,
+    },
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Command::Spawn {
+                ref label,
+                ref components,
+            }
+            | Command::Insert {
+                ref label,
+                ref components,
+            } => {
+                let op = match *self {
+                    Command::Spawn { .. } => "spawn",
+                    _ => "insert",
+                };
+                write!(f, "{} {}", op, label)?;
+                for (name, value) in components {
+                    write!(f, " {}={}", name, value)?;
+                }
+                Ok(())
+            }
+            Command::Remove {
+                ref label,
+                ref components,
+            } => {
+                write!(f, "remove {}", label)?;
+                for name in components {
+                    write!(f, " {}", name)?;
+                }
+                Ok(())
+            }
+            Command::Despawn { ref label } => write!(f, "despawn {}", label),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, CommandError> {
+        let syntax = || CommandError::Syntax(s.into());
+        let mut words = s.split_whitespace();
+        let op = words.next().ok_or_else(syntax)?;
+        let label = String::from(words.next().ok_or_else(syntax)?);
+        let values = |words: core::str::SplitWhitespace<'_>| {
+            words
+                .map(|x| {
+                    let (name, value) = x.split_once('=').ok_or_else(syntax)?;
+                    Ok((name.into(), value.into()))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match op {
+            "spawn" => Command::Spawn {
+                label,
+                components: values(words)?,
+            },
+            "insert" => Command::Insert {
+                label,
+                components: values(words)?,
+            },
+            "remove" => Command::Remove {
+                label,
+                components: words.map(String::from).collect(),
+            },
+            "despawn" if words.next().is_none() => Command::Despawn { label },
+            _ => return Err(syntax()),
+        })
+    }
+}
+
+/// Applies `Command`s to worlds, parsing components of registered types by name
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// # use std::collections::HashMap;
+/// struct Health(u32);
+/// impl std::str::FromStr for Health {
+///     type Err = std::num::ParseIntError;
+///     fn from_str(s: &str) -> Result<Self, Self::Err> {
+///         s.parse().map(Health)
+///     }
+/// }
+///
+/// let mut registry = CommandRegistry::new();
+/// registry.register::<Health>("health").register::<bool>("hostile");
+/// let script = "spawn goblin health=30 hostile=true\ninsert goblin health=25";
+/// let commands = script
+///     .lines()
+///     .map(|x| x.parse())
+///     .collect::<Result<Vec<Command>, _>>()
+///     .unwrap();
+///
+/// let mut world = World::new();
+/// let labels = registry.apply(&mut world, &commands).unwrap();
+/// assert_eq!(world.get::<Health>(labels["goblin"]).unwrap().0, 25);
+/// ```
+#[derive(Default)]
+pub struct CommandRegistry {
+    types: HashMap<String, CommandType>,
+    /// Reused for assembling components
+    builder: EntityBuilder,
+}
+
+struct CommandType {
+    id: TypeId,
+    /// Parse a component and add it to the builder, returning whether it could be parsed
+    add: fn(&str, &mut EntityBuilder) -> bool,
+}
+
+impl CommandRegistry {
+    /// Create a registry that knows no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refer to `T` components as `name`, parsing them with `FromStr`
+    pub fn register<T: Component + FromStr>(&mut self, name: &str) -> &mut Self {
+        self.types.insert(
+            name.into(),
+            CommandType {
+                id: TypeId::of::<T>(),
+                add: |value, builder| match value.parse::<T>() {
+                    Ok(x) => {
+                        builder.add(x);
+                        true
+                    }
+                    Err(_) => false,
+                },
+            },
+        );
+        self
+    }
+
+    /// Apply `commands` to `world` in order, returning the entities spawned by label
+    ///
+    /// Stops at the first command that fails, leaving the world with the changes made by those
+    /// before it.
+    pub fn apply(
+        &mut self,
+        world: &mut World,
+        commands: &[Command],
+    ) -> Result<HashMap<String, Entity>, CommandError> {
+        let mut labels = HashMap::new();
+        self.apply_with(world, commands, &mut labels)?;
+        Ok(labels)
+    }
+
+    /// Like `apply`, but with entities already bound to labels in `labels`, e.g. to script changes
+    /// to existing entities, and binding spawned entities there
+    pub fn apply_with(
+        &mut self,
+        world: &mut World,
+        commands: &[Command],
+        labels: &mut HashMap<String, Entity>,
+    ) -> Result<(), CommandError> {
+        for command in commands {
+            match *command {
+                Command::Spawn {
+                    ref label,
+                    ref components,
+                } => {
+                    if labels.contains_key(label) {
+                        return Err(CommandError::DuplicateLabel(label.clone()));
+                    }
+                    self.build(components)?;
+                    labels.insert(label.clone(), world.spawn(self.builder.build()));
+                }
+                Command::Insert {
+                    ref label,
+                    ref components,
+                } => {
+                    let entity = lookup(labels, label)?;
+                    self.build(components)?;
+                    world
+                        .insert(entity, self.builder.build())
+                        .map_err(|_| CommandError::NoSuchEntity(label.clone()))?;
+                }
+                Command::Remove {
+                    ref label,
+                    ref components,
+                } => {
+                    let entity = lookup(labels, label)?;
+                    let types = components
+                        .iter()
+                        .map(|x| Ok(self.get(x)?.id))
+                        .collect::<Result<Vec<_>, CommandError>>()?;
+                    world
+                        .insert_and_remove(entity, (), &types)
+                        .map_err(|_| CommandError::NoSuchEntity(label.clone()))?;
+                }
+                Command::Despawn { ref label } => {
+                    let entity = lookup(labels, label)?;
+                    world
+                        .despawn(entity)
+                        .map_err(|_| CommandError::NoSuchEntity(label.clone()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse `components` into `builder`, leaving it empty on failure
+    fn build(&mut self, components: &[(String, String)]) -> Result<(), CommandError> {
+        for (name, value) in components {
+            let ty = match self.types.get(name) {
+                Some(x) => x,
+                None => {
+                    self.builder.clear();
+                    return Err(CommandError::UnknownComponent(name.clone()));
+                }
+            };
+            if !(ty.add)(value, &mut self.builder) {
+                self.builder.clear();
+                return Err(CommandError::InvalidValue {
+                    component: name.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<&CommandType, CommandError> {
+        self.types
+            .get(name)
+            .ok_or_else(|| CommandError::UnknownComponent(name.into()))
+    }
+}
+
+fn lookup(labels: &HashMap<String, Entity>, label: &str) -> Result<Entity, CommandError> {
+    labels
+        .get(label)
+        .copied()
+        .ok_or_else(|| CommandError::UnknownLabel(label.into()))
+}
+
+/// Error indicating that a `Command` couldn't be parsed or applied
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CommandError {
+    /// Text that isn't a valid command
+    Syntax(String),
+    /// A component name that wasn't registered
+    UnknownComponent(String),
+    /// A component value that couldn't be parsed
+    InvalidValue {
+        /// Name of the component
+        component: String,
+        /// The text that failed to parse
+        value: String,
+    },
+    /// A label that isn't bound to an entity
+    UnknownLabel(String),
+    /// A spawn reusing a label that's already bound
+    DuplicateLabel(String),
+    /// A label bound to an entity that no longer exists
+    NoSuchEntity(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CommandError::*;
+        match *self {
+            Syntax(ref x) => write!(f, "invalid command: {}", x),
+            UnknownComponent(ref x) => write!(f, "unknown component {}", x),
+            InvalidValue {
+                ref component,
+                ref value,
+            } => write!(f, "invalid value {} for component {}", value, component),
+            UnknownLabel(ref x) => write!(f, "unknown label {}", x),
+            DuplicateLabel(ref x) => write!(f, "label {} is already bound", x),
+            NoSuchEntity(ref x) => write!(f, "entity {} no longer exists", x),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CommandError {}
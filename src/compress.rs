@@ -0,0 +1,338 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::sync::Arc;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::convert::TryInto;
+use core::fmt;
+use core::mem;
+
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hashbrown::HashMap;
+
+use crate::{Archetype, Component, Entity, EntityBuilder, Snapshot, SpawnAtError, World};
+
+/// Per-component-type routines for compactly encoding whole columns of components
+///
+/// Intended as a building block for snapshot and delta generation, e.g. for networking. Each
+/// registered compressor sees every `T` in an archetype at once, allowing it to exploit
+/// column-wise redundancy (quantization, delta coding, etc.) before the data reaches a
+/// general-purpose byte stream.
+///
+/// Each type is registered under a caller-chosen ID, which must be the same on the encoding and
+/// decoding side. The encoded form of an archetype is a frame made up of, in little-endian order:
+///
+/// - the number of columns, as a `u32`
+/// - the number of entities, as a `u32`
+/// - each entity's `Entity::to_wide_bits`
+/// - for each column, the ID of its type and the length of its compressed data as `u32`s,
+///   followed by the compressed data itself
+///
+/// Frames are simply concatenated, so the output of several calls may be decoded at once.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// #[derive(Debug, PartialEq)]
+/// struct Position(f32);
+/// let mut world = World::new();
+/// let a = world.spawn((Position(1.0),));
+/// let b = world.spawn((Position(2.5), true));
+///
+/// let mut compressors = ColumnCompressors::new();
+/// // Quantize positions to 8.8 fixed point
+/// compressors.register::<Position>(
+///     0,
+///     |column, out| {
+///         for x in column {
+///             out.extend_from_slice(&((x.0 * 256.0) as i16).to_le_bytes());
+///         }
+///     },
+///     |data, out| {
+///         for x in data.chunks_exact(2) {
+///             out.push(Position(f32::from(i16::from_le_bytes([x[0], x[1]])) / 256.0));
+///         }
+///         Ok(())
+///     },
+/// );
+/// let mut out = Vec::new();
+/// compressors.compress_world(&world, &mut out);
+///
+/// let mut replica = World::new();
+/// compressors.decompress_world(&out, &mut replica).unwrap();
+/// assert_eq!(*replica.get::<Position>(a).unwrap(), Position(1.0));
+/// assert_eq!(*replica.get::<Position>(b).unwrap(), Position(2.5));
+/// assert!(replica.get::<bool>(b).is_err());
+/// ```
+#[derive(Default)]
+pub struct ColumnCompressors {
+    compressors: HashMap<TypeId, Compressor>,
+    ids: HashMap<u32, TypeId>,
+}
+
+impl ColumnCompressors {
+    /// Create a registry with no compressors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `compress` to encode columns of `T` and `decompress` to decode them, identifying `T`
+    /// by `id` in encoded frames
+    ///
+    /// Replaces any previously registered compressor for `T`. `decompress` receives exactly the
+    /// bytes written by one call to `compress`, and must append one `T` to its output for each
+    /// component that was compressed.
+    ///
+    /// Panics if `id` is already in use by another type.
+    pub fn register<T: Component>(
+        &mut self,
+        id: u32,
+        compress: impl Fn(&[T], &mut Vec<u8>) + Send + Sync + 'static,
+        decompress: impl Fn(&[u8], &mut Vec<T>) -> Result<(), DecompressError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        let ty = TypeId::of::<T>();
+        assert!(
+            !matches!(self.ids.get(&id), Some(&x) if x != ty),
+            "compressor ID {} is already in use by another type",
+            id
+        );
+        if let Some(old) = self.compressors.get(&ty) {
+            self.ids.remove(&old.id);
+        }
+        self.ids.insert(id, ty);
+        let compress = Arc::new(compress);
+        let snapshot = compress.clone();
+        self.compressors.insert(
+            ty,
+            Compressor {
+                id,
+                archetype: Box::new(move |archetype, out| {
+                    archetype.with_column::<T, _>(|column| compress(column, out));
+                }),
+                snapshot: Box::new(move |column, out| {
+                    snapshot(column.downcast_ref::<Vec<T>>().unwrap(), out)
+                }),
+                decompress: Box::new(move |data, len| {
+                    let mut out = Vec::with_capacity(len as usize);
+                    decompress(data, &mut out)?;
+                    if out.len() != len as usize {
+                        return Err(DecompressError::Malformed);
+                    }
+                    Ok(Box::new(out.into_iter()))
+                }),
+                add: add_component::<T>,
+            },
+        );
+        self
+    }
+
+    /// Whether a compressor has been registered for `T`
+    pub fn contains<T: Component>(&self) -> bool {
+        self.compressors.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Append a frame holding the compressed form of every registered column in `archetype` to
+    /// `out`
+    ///
+    /// Columns are written in the archetype's internal type order, which is stable for a given
+    /// build of the program. Component types without a registered compressor are skipped, and
+    /// must be saved by other means. Nothing is written for archetypes that are empty or have no
+    /// registered columns.
+    ///
+    /// Panics if a registered column is already uniquely borrowed.
+    pub fn compress_archetype(&self, world: &World, archetype: &Archetype, out: &mut Vec<u8>) {
+        let meta = world.entities_meta();
+        self.write_frame(
+            (0..archetype.len()).map(|i| {
+                let id = archetype.entity_id(i);
+                meta[id as usize].entity(id)
+            }),
+            archetype.types().iter().map(|x| x.id()),
+            |_, compressor, out| (compressor.archetype)(archetype, out),
+            out,
+        );
+    }
+
+    /// Append a frame for each archetype in `world` to `out`
+    ///
+    /// Archetypes are visited in the order given by `World::archetypes`.
+    pub fn compress_world(&self, world: &World, out: &mut Vec<u8>) {
+        for archetype in world.archetypes() {
+            self.compress_archetype(world, archetype, out);
+        }
+    }
+
+    /// Append a frame for each archetype captured in `snapshot` to `out`
+    ///
+    /// Produces the same encoding as `compress_world` would have for the world at the time of the
+    /// snapshot, without needing access to the world.
+    pub fn compress_snapshot(&self, snapshot: &Snapshot, out: &mut Vec<u8>) {
+        let meta = &snapshot.entities.meta;
+        for archetype in &snapshot.archetypes {
+            self.write_frame(
+                archetype
+                    .entities
+                    .iter()
+                    .map(|&id| meta[id as usize].entity(id)),
+                archetype.columns.iter().map(|x| x.ty),
+                |ty, compressor, out| {
+                    let column = archetype.columns.iter().find(|x| x.ty == ty).unwrap();
+                    (compressor.snapshot)(&*column.data, out)
+                },
+                out,
+            );
+        }
+    }
+
+    fn write_frame(
+        &self,
+        entities: impl ExactSizeIterator<Item = Entity>,
+        types: impl Iterator<Item = TypeId>,
+        compress: impl Fn(TypeId, &Compressor, &mut Vec<u8>),
+        out: &mut Vec<u8>,
+    ) {
+        let columns = types
+            .filter_map(|ty| Some((ty, self.compressors.get(&ty)?)))
+            .collect::<Vec<_>>();
+        if entities.len() == 0 || columns.is_empty() {
+            return;
+        }
+        out.extend_from_slice(&(columns.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(entities.len() as u32).to_le_bytes());
+        for entity in entities {
+            out.extend_from_slice(&entity.to_wide_bits().to_le_bytes());
+        }
+        for (ty, compressor) in columns {
+            out.extend_from_slice(&compressor.id.to_le_bytes());
+            let start = out.len();
+            out.extend_from_slice(&[0; 4]);
+            compress(ty, compressor, out);
+            let len = (out.len() - start - 4) as u32;
+            out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+        }
+    }
+
+    /// Spawn the entities encoded in `data` into `world`
+    ///
+    /// Each entity is spawned with `World::spawn_at` under its original handle, so a live entity
+    /// with the same handle is replaced. Every frame is decoded before anything is spawned, so
+    /// malformed input leaves `world` untouched, though entities spawned before a
+    /// `DecompressError::Spawn` remain.
+    pub fn decompress_world(&self, data: &[u8], world: &mut World) -> Result<(), DecompressError> {
+        let mut reader = Reader(data);
+        let mut frames = Vec::new();
+        while !reader.0.is_empty() {
+            let column_count = reader.u32()?;
+            let len = reader.u32()?;
+            let entities = (0..len)
+                .map(|_| {
+                    let bits = reader.take(mem::size_of::<u128>())?;
+                    Entity::try_from_wide_bits(u128::from_le_bytes(bits.try_into().unwrap()))
+                        .ok_or(DecompressError::Malformed)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let columns = (0..column_count)
+                .map(|_| {
+                    let id = reader.u32()?;
+                    let size = reader.u32()?;
+                    let data = reader.take(size as usize)?;
+                    let compressor = self
+                        .ids
+                        .get(&id)
+                        .map(|ty| &self.compressors[ty])
+                        .ok_or(DecompressError::UnknownType(id))?;
+                    Ok::<_, DecompressError>((compressor, (compressor.decompress)(data, len)?))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            frames.push((entities, columns));
+        }
+
+        let mut builder = EntityBuilder::new();
+        for (entities, mut columns) in frames {
+            for entity in entities {
+                for (compressor, column) in &mut columns {
+                    (compressor.add)(&mut **column, &mut builder);
+                }
+                world.spawn_at(entity, builder.build())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Compressor {
+    id: u32,
+    /// Encode the column of an archetype
+    archetype: Box<CompressFn>,
+    /// Encode a snapshotted column, a `Vec<T>`
+    snapshot: Box<SnapshotFn>,
+    /// Decode a column of the given length into a `vec::IntoIter<T>`
+    decompress: Box<DecompressFn>,
+    /// Move the next component out of a decoded column into a builder
+    add: fn(&mut dyn Any, &mut EntityBuilder),
+}
+
+type CompressFn = dyn Fn(&Archetype, &mut Vec<u8>) + Send + Sync;
+type SnapshotFn = dyn Fn(&dyn Any, &mut Vec<u8>) + Send + Sync;
+type DecompressFn = dyn Fn(&[u8], u32) -> Result<Box<dyn Any>, DecompressError> + Send + Sync;
+
+fn add_component<T: Component>(column: &mut dyn Any, builder: &mut EntityBuilder) {
+    let column = column
+        .downcast_mut::<crate::alloc::vec::IntoIter<T>>()
+        .unwrap();
+    builder.add(column.next().unwrap());
+}
+
+/// Reads little-endian values from the front of a byte slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecompressError> {
+        if self.0.len() < n {
+            return Err(DecompressError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecompressError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+/// Errors that arise when decoding data produced by `ColumnCompressors`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum DecompressError {
+    /// The data ended partway through a frame
+    Truncated,
+    /// A frame contained a column whose type ID was never registered
+    UnknownType(u32),
+    /// A frame or column was inconsistent with itself, e.g. a decompressor produced the wrong
+    /// number of components
+    Malformed,
+    /// A decoded entity couldn't be spawned
+    Spawn(SpawnAtError),
+}
+
+#[cfg(feature = "std")]
+impl Error for DecompressError {}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecompressError::*;
+        match *self {
+            Truncated => f.write_str("compressed data truncated"),
+            UnknownType(id) => write!(f, "unknown compressor ID {}", id),
+            Malformed => f.write_str("malformed compressed data"),
+            Spawn(ref x) => x.fmt(f),
+        }
+    }
+}
+
+impl From<SpawnAtError> for DecompressError {
+    fn from(x: SpawnAtError) -> Self {
+        DecompressError::Spawn(x)
+    }
+}
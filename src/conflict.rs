@@ -0,0 +1,196 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::query::Fetch;
+use crate::{Access, Archetype, Query, World};
+
+/// Determine whether queries `Q1` and `Q2` could not be executed concurrently
+///
+/// Returns the first component type that one query borrows uniquely and the other borrows at all,
+/// if any. Executing non-conflicting queries at the same time never causes a borrow panic, so this
+/// is suitable for scheduling systems or asserting that they can be run in parallel.
+///
+/// Conservative: queries that can never match the same archetype, e.g. due to `Without`, are still
+/// reported as conflicting if they borrow the same component. See `access_conflicts_in` for a
+/// precise answer for a particular world.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// assert!(access_conflicts::<(&i32, &bool), &i32>().is_none());
+/// let conflict = access_conflicts::<(&mut i32, &bool), Option<&i32>>().unwrap();
+/// assert_eq!(conflict.type_name, "i32");
+/// ```
+pub fn access_conflicts<Q1: Query, Q2: Query>() -> Option<ConflictInfo> {
+    QueryAccess::of::<Q1>().conflicts(&QueryAccess::of::<Q2>())
+}
+
+/// Determine whether queries `Q1` and `Q2` could not be executed concurrently on `world`
+///
+/// Like `access_conflicts`, but only reports conflicts within an archetype of `world` that both
+/// queries match, since components are borrowed per archetype. For example, two systems writing
+/// the same component type can run in parallel if `With` or `Without` filters keep them in
+/// disjoint archetypes. The result only holds until `world` gains a new archetype, which can be
+/// detected with `World::archetypes_generation`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Player;
+/// struct Enemy;
+/// let mut world = World::new();
+/// world.spawn((Player, 0.0f32));
+/// world.spawn((Enemy, 0.0f32));
+/// type Players<'a> = With<Player, &'a mut f32>;
+/// type Enemies<'a> = With<Enemy, &'a mut f32>;
+/// assert!(access_conflicts::<Players, Enemies>().is_some());
+/// assert!(access_conflicts_in::<Players, Enemies>(&world).is_none());
+/// world.spawn((Player, Enemy, 0.0f32));
+/// assert!(access_conflicts_in::<Players, Enemies>(&world).is_some());
+/// ```
+pub fn access_conflicts_in<Q1: Query, Q2: Query>(world: &World) -> Option<ConflictInfo> {
+    QueryAccess::of::<Q1>().conflicts_in(&QueryAccess::of::<Q2>(), world)
+}
+
+/// A component type borrowed incompatibly by two queries, found by `access_conflicts`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ConflictInfo {
+    /// The conflicting component type
+    pub type_id: TypeId,
+    /// Name of the conflicting component type, for diagnostics
+    pub type_name: &'static str,
+}
+
+/// The set of component types borrowed by some queries, and how
+///
+/// Runtime counterpart to `access_conflicts`, useful for describing systems that execute multiple
+/// queries, or that are only known at runtime.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut physics = QueryAccess::of::<(&mut [f32; 3], &f32)>();
+/// physics.extend(&QueryAccess::of::<&bool>());
+/// let render = QueryAccess::of::<(&[f32; 3], &bool)>();
+/// assert!(physics.conflicts(&render).is_some());
+/// assert!(physics.conflicts(&QueryAccess::of::<&bool>()).is_none());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct QueryAccess {
+    borrows: Vec<Borrow>,
+    /// The borrows of each individual query, for checking conflicts per archetype
+    queries: Vec<Shape>,
+}
+
+#[derive(Debug, Clone)]
+struct Shape {
+    /// Whether the query borrows from an archetype
+    matches: fn(&Archetype) -> bool,
+    borrows: Vec<Borrow>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Borrow {
+    id: TypeId,
+    name: &'static str,
+    unique: bool,
+}
+
+impl QueryAccess {
+    /// Describe a set of queries that borrows nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describe the borrows made by `Q`
+    pub fn of<Q: Query>() -> Self {
+        let mut result = Self::new();
+        Q::Fetch::for_each_borrow(&mut |id, name, unique| result.add(id, name, unique));
+        result.queries.push(Shape {
+            matches: |archetype| Q::Fetch::access(archetype) >= Some(Access::Read),
+            borrows: result.borrows.clone(),
+        });
+        result
+    }
+
+    /// Include the borrows described by `other`
+    pub fn extend(&mut self, other: &QueryAccess) {
+        for x in &other.borrows {
+            self.add(x.id, x.name, x.unique);
+        }
+        self.queries.extend(other.queries.iter().cloned());
+    }
+
+    /// Determine whether the queries described by `self` and `other` could not be executed
+    /// concurrently
+    ///
+    /// See `access_conflicts` for details.
+    pub fn conflicts(&self, other: &QueryAccess) -> Option<ConflictInfo> {
+        for x in &self.borrows {
+            for y in &other.borrows {
+                if x.id == y.id && (x.unique || y.unique) {
+                    return Some(ConflictInfo {
+                        type_id: x.id,
+                        type_name: x.name,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Determine whether the queries described by `self` and `other` could not be executed
+    /// concurrently on `world`
+    ///
+    /// See `access_conflicts_in` for details.
+    pub fn conflicts_in(&self, other: &QueryAccess, world: &World) -> Option<ConflictInfo> {
+        for archetype in world.archetypes() {
+            for x in self.queries.iter().filter(|x| (x.matches)(archetype)) {
+                for y in other.queries.iter().filter(|y| (y.matches)(archetype)) {
+                    let conflict = x.borrows.iter().find(|a| {
+                        archetype.has_dynamic(a.id)
+                            && y.borrows
+                                .iter()
+                                .any(|b| a.id == b.id && (a.unique || b.unique))
+                    });
+                    if let Some(a) = conflict {
+                        return Some(ConflictInfo {
+                            type_id: a.id,
+                            type_name: a.name,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether every borrow is shared
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.borrows.iter().all(|x| !x.unique)
+    }
+
+    /// Find a borrow in `other` that isn't also made by `self`, at least as strongly
+    pub(crate) fn excess(&self, other: &QueryAccess) -> Option<ConflictInfo> {
+        other
+            .borrows
+            .iter()
+            .find(|x| {
+                !self
+                    .borrows
+                    .iter()
+                    .any(|y| x.id == y.id && (y.unique || !x.unique))
+            })
+            .map(|x| ConflictInfo {
+                type_id: x.id,
+                type_name: x.name,
+            })
+    }
+
+    fn add(&mut self, id: TypeId, name: &'static str, unique: bool) {
+        match self.borrows.iter_mut().find(|x| x.id == id) {
+            Some(x) => x.unique |= unique,
+            None => self.borrows.push(Borrow { id, name, unique }),
+        }
+    }
+}
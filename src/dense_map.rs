@@ -0,0 +1,158 @@
+use crate::alloc::vec::Vec;
+
+use crate::{Entity, World};
+
+/// A map from entities to values of type `T`, stored in a table indexed by entity ID
+///
+/// Faster than a `HashMap<Entity, T>` for data owned by an external system, e.g. physics bodies or
+/// render proxies, at the cost of memory proportional to the greatest ID stored. Lookups check the
+/// entity's generation, so a handle to a despawned entity never finds the value of a newer entity
+/// that reused its ID. Values of despawned entities are removed by `prune`.
+///
+/// Only meaningful for the entities of a single world.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// let mut bodies = DenseMap::new();
+/// bodies.insert(a, "body a");
+/// bodies.insert(b, "body b");
+/// world.despawn(a).unwrap();
+/// let mut removed = Vec::new();
+/// bodies.prune(&world, |entity, body| removed.push((entity, body)));
+/// assert_eq!(removed, [(a, "body a")]);
+/// assert_eq!(bodies.get(b), Some(&"body b"));
+/// ```
+pub struct DenseMap<T> {
+    slots: Vec<Option<(Entity, T)>>,
+    len: usize,
+    /// The world's despawn epoch as of the last `prune`, if nothing's been inserted since
+    epoch: Option<u64>,
+}
+
+impl<T> DenseMap<T> {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            len: 0,
+            epoch: None,
+        }
+    }
+
+    /// Number of entities with values
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no entities have values
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Associate `value` with `entity`, returning the value it replaces, if any
+    ///
+    /// Any value left for a despawned entity with the same ID is dropped; use `prune` beforehand
+    /// to observe it.
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        let index = entity.id() as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        self.epoch = None;
+        match self.slots[index].replace((entity, value)) {
+            Some((old, value)) if old == entity => Some(value),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Remove and return the value associated with `entity`, if any
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.id() as usize)?;
+        match *slot {
+            Some((x, _)) if x == entity => {
+                self.len -= 1;
+                slot.take().map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `entity` has a value
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    /// The value associated with `entity`, if any
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match *self.slots.get(entity.id() as usize)? {
+            Some((x, ref value)) if x == entity => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Uniquely borrow the value associated with `entity`, if any
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match *self.slots.get_mut(entity.id() as usize)? {
+            Some((x, ref mut value)) if x == entity => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Iterate over every entity and its value, in order of entity ID
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        self.slots
+            .iter()
+            .filter_map(|x| x.as_ref().map(|&(entity, ref value)| (entity, value)))
+    }
+
+    /// Iterate over every entity and a unique borrow of its value, in order of entity ID
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> + '_ {
+        self.slots.iter_mut().filter_map(|x| {
+            x.as_mut()
+                .map(|&mut (entity, ref mut value)| (entity, value))
+        })
+    }
+
+    /// Remove every value
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+        self.epoch = None;
+    }
+
+    /// Remove the values of entities that no longer exist in `world`, passing each to `f`
+    ///
+    /// Nearly free if nothing has been despawned from `world` or inserted into this map since the
+    /// last call.
+    pub fn prune(&mut self, world: &World, mut f: impl FnMut(Entity, T)) {
+        let entities = world.entities_inner();
+        let epoch = entities.epoch();
+        if self.epoch == Some(epoch) {
+            return;
+        }
+        for slot in &mut self.slots {
+            if let Some((entity, _)) = *slot {
+                if !entities.contains(entity) {
+                    let (entity, value) = slot.take().unwrap();
+                    self.len -= 1;
+                    f(entity, value);
+                }
+            }
+        }
+        self.epoch = Some(epoch);
+    }
+}
+
+impl<T> Default for DenseMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,164 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::archetype::Archetype;
+use crate::query::{prefetch, Fetch, QueryTicks};
+use crate::{Access, BorrowError, CloneQuery, Component, Query};
+
+/// Query yielding each entity's `T` component as of the last `World::flush`, for components
+/// registered with `World::double_buffer`
+///
+/// Entities lacking a `T` are skipped. An entity that gained its `T` since the last flush yields
+/// the value it gained. Previous values aren't borrowed, since only `flush` and structural changes
+/// modify them, so this combines freely with `&mut T`: a system can write the current value of
+/// each entity while reading the previous values of every entity, e.g. for flocking or cellular
+/// automata, without its results depending on the order in which entities are visited.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.double_buffer::<i32>();
+/// let a = world.spawn((1,));
+/// world.flush();
+/// for (_, (current, &previous)) in world.query::<(&mut i32, Previous<i32>)>().iter() {
+///     *current = previous * 10;
+/// }
+/// assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+/// assert_eq!(*world.previous::<i32>(a).unwrap(), 1);
+/// world.flush();
+/// assert_eq!(*world.previous::<i32>(a).unwrap(), 10);
+/// ```
+pub struct Previous<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Previous<T> {
+    type Fetch = FetchPrevious<T>;
+}
+
+impl<T: Component + Clone> CloneQuery for Previous<T> {
+    type Owned = T;
+
+    fn clone_item(item: &T) -> T {
+        item.clone()
+    }
+}
+
+#[doc(hidden)]
+pub struct FetchPrevious<T>(NonNull<T>);
+
+impl<'a, T: Component> Fetch<'a> for FetchPrevious<T> {
+    type Item = &'a T;
+    type Slice = &'a [T];
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        archetype.shadow::<T>().map(|_| Access::Read)
+    }
+
+    fn try_borrow(_: &Archetype) -> Result<(), BorrowError> {
+        Ok(())
+    }
+    fn for_each_borrow(_: &mut dyn FnMut(TypeId, &'static str, bool)) {}
+    unsafe fn get(archetype: &'a Archetype, offset: usize, _: QueryTicks) -> Option<Self> {
+        archetype
+            .shadow::<T>()
+            .map(|x| Self(NonNull::new_unchecked(x.as_ptr().add(offset) as *mut T)))
+    }
+    fn release(_: &Archetype) {}
+
+    unsafe fn next(&mut self) -> &'a T {
+        let x = self.0.as_ptr();
+        self.0 = NonNull::new_unchecked(x.add(1));
+        &*x
+    }
+
+    unsafe fn slice(&mut self, len: usize) -> &'a [T] {
+        let x = self.0.as_ptr();
+        self.0 = NonNull::new_unchecked(x.add(len));
+        slice::from_raw_parts(x, len)
+    }
+
+    fn prefetch(&self, distance: usize) {
+        prefetch(self.0.as_ptr().wrapping_add(distance));
+    }
+}
+
+/// Constructs the shadow of a double-buffered component type for a new archetype
+pub(crate) type ShadowFactory = fn() -> Box<dyn Shadow>;
+
+/// Type-erased copies of the components of one double-buffered type in an archetype, as of the
+/// last flush, in the same order as the archetype's entities
+pub(crate) trait Shadow: Send + Sync {
+    /// Copy the component at `current` as the previous value of the entity at `index`, unless it
+    /// already has one
+    ///
+    /// # Safety
+    /// `current` must point to a valid component of the shadowed type, and `index` must not
+    /// exceed the number of previous values
+    unsafe fn fill(&mut self, index: u32, current: *const u8);
+    /// Overwrite the previous values of the first `len` entities with the components at `current`
+    ///
+    /// # Safety
+    /// `current` must point to `len` contiguous valid components of the shadowed type
+    unsafe fn capture(&mut self, current: *const u8, len: u32);
+    /// Copy the previous value of the entity at `index` in `source` as the next previous value
+    /// here, if `source` shadows the same type
+    fn inherit(&mut self, source: &dyn Shadow, index: u32);
+    /// Drop the previous value of the entity at `index`, replacing it with the last
+    fn swap_remove(&mut self, index: u32);
+    fn clear(&mut self);
+    fn boxed_clone(&self) -> Box<dyn Shadow>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+pub(crate) struct ShadowColumn<T>(pub(crate) Vec<T>);
+
+impl<T: Component + Clone> ShadowColumn<T> {
+    pub(crate) fn boxed() -> Box<dyn Shadow> {
+        Box::new(Self(Vec::new()))
+    }
+}
+
+impl<T: Component + Clone> Shadow for ShadowColumn<T> {
+    unsafe fn fill(&mut self, index: u32, current: *const u8) {
+        debug_assert!(index as usize <= self.0.len());
+        if index as usize == self.0.len() {
+            self.0.push((*current.cast::<T>()).clone());
+        }
+    }
+
+    unsafe fn capture(&mut self, current: *const u8, len: u32) {
+        let current = slice::from_raw_parts(current.cast::<T>(), len as usize);
+        self.0.truncate(current.len());
+        let filled = self.0.len();
+        self.0.clone_from_slice(&current[..filled]);
+        self.0.extend_from_slice(&current[filled..]);
+    }
+
+    fn inherit(&mut self, source: &dyn Shadow, index: u32) {
+        if let Some(x) = source.as_any().downcast_ref::<Self>() {
+            self.0.push(x.0[index as usize].clone());
+        }
+    }
+
+    fn swap_remove(&mut self, index: u32) {
+        if (index as usize) < self.0.len() {
+            self.0.swap_remove(index as usize);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Shadow> {
+        Box::new(Self(self.0.clone()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -1,6 +1,13 @@
 use alloc::boxed::Box;
-use alloc::vec::Vec;
+use alloc::sync::Arc;
+use alloc::{vec, vec::Vec};
 use core::convert::TryFrom;
+use core::hash::{Hash, Hasher};
+#[cfg(not(feature = "wide-entities"))]
+use core::num::NonZeroU32 as NonZeroGeneration;
+use core::num::NonZeroU64;
+#[cfg(feature = "wide-entities")]
+use core::num::NonZeroU64 as NonZeroGeneration;
 use core::sync::atomic::{AtomicU32, Ordering};
 use core::{fmt, mem};
 #[cfg(feature = "std")]
@@ -9,30 +16,149 @@ use std::error::Error;
 /// Lightweight unique ID of an entity
 ///
 /// Obtained from `World::spawn`. Can be stored to refer to an entity in the future.
-#[derive(Clone, Copy, Hash, Eq, Ord, PartialEq, PartialOrd)]
+///
+/// Consists of a 32-bit ID, which is reused after an entity is despawned, and a `Generation`
+/// distinguishing its uses. An ID whose generations run out is retired instead of wrapping around,
+/// so a handle never refers to an entity spawned after the one it was obtained for. Generations
+/// are never zero, so `Option<Entity>` is no larger than `Entity`.
+///
+/// Generations are 32 bits wide by default, so an ID that is reused very often, e.g. by a server
+/// that runs for months, may eventually be retired. The `wide-entities` feature widens them to 64
+/// bits, which no world will ever exhaust, at the cost of doubling the size of `Entity`. It doesn't
+/// change any signatures: generations are always reported as `u64`s, and `to_wide_bits` encodes
+/// entities losslessly either way.
+///
+/// A handle is only meaningful to the world it came from, and worlds created by `World::clone_with`
+/// or populated with `World::spawn_at`. The `checked-entities` feature additionally records the
+/// `World::uid` of the world that allocated each handle, and panics when a handle is passed to a
+/// world in which it would refer to an entity allocated by a different world. The record doesn't
+/// participate in comparisons, and isn't preserved by `to_bits`.
+#[derive(Clone, Copy)]
 pub struct Entity {
-    pub(crate) generation: u32,
+    pub(crate) generation: NonZeroGeneration,
     pub(crate) id: u32,
+    /// `World::uid` of the world that allocated this handle, or 0 if unknown
+    #[cfg(feature = "checked-entities")]
+    pub(crate) world: u32,
+}
+
+impl PartialEq for Entity {
+    fn eq(&self, other: &Self) -> bool {
+        self.generation == other.generation && self.id == other.id
+    }
+}
+
+impl Eq for Entity {}
+
+impl PartialOrd for Entity {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entity {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (self.generation, self.id).cmp(&(other.generation, other.id))
+    }
 }
 
+impl Hash for Entity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.generation.hash(state);
+        self.id.hash(state);
+    }
+}
+
+/// Integer type of entity generations
+///
+/// Wide enough for generations with or without the `wide-entities` feature, which only affects
+/// how many generations an ID has before it's retired.
+pub type Generation = u64;
+
 impl Entity {
-    /// Convert to a form convenient for passing outside of rust
+    /// Convert to a form convenient for passing outside of rust, e.g. to scripts, network
+    /// packets, or save files
     ///
-    /// Only useful for identifying entities within the same instance of an application. Do not use
-    /// for serialization between runs.
+    /// The `id` occupies the low 32 bits and the generation minus one the bits above, a layout
+    /// that won't change. Entities' first generation is thus encoded as zero, as it was when
+    /// generations started from zero, so bits stored by earlier versions still decode to the same
+    /// entities. The result only refers to the same entity in a world whose entities were allocated
+    /// identically, e.g. one restored from a snapshot, or populated with `World::spawn_at`.
     ///
-    /// No particular structure is guaranteed for the returned bits.
+    /// Panics if the generation doesn't fit in 32 bits, which is only possible with the
+    /// `wide-entities` feature; see `to_wide_bits`.
     pub fn to_bits(self) -> u64 {
-        u64::from(self.generation) << 32 | u64::from(self.id)
+        let generation = u32::try_from(self.generation())
+            .expect("entity generation too large for to_bits; use to_wide_bits");
+        u64::from(generation - 1) << 32 | u64::from(self.id)
     }
 
     /// Reconstruct an `Entity` previously destructured with `to_bits`
     ///
-    /// Only useful when applied to results from `to_bits` in the same instance of an application.
+    /// Never fails. Bits that never came from `to_bits` produce an entity that `World::contains`
+    /// is unlikely to report as live, and generation bits that are all ones, which `to_bits` never
+    /// produces, produce an entity that is never live; see `try_from_bits` to reject them instead.
     pub fn from_bits(bits: u64) -> Self {
+        Self::try_from_bits(bits).unwrap_or_else(|| Self::new(bits as u32, RETIRED, 0))
+    }
+
+    /// Like `from_bits`, but returns `None` if the generation bits are all ones
+    pub fn try_from_bits(bits: u64) -> Option<Self> {
+        if bits >> 32 == u64::from(u32::MAX) {
+            return None;
+        }
+        Self::try_from_wide_bits(u128::from(bits))
+    }
+
+    /// Like `to_bits`, but able to represent every generation
+    ///
+    /// Uses the same layout as `to_bits`, with the generation in the upper 96 bits, so entities
+    /// whose generations fit in 32 bits have the same encoding either way.
+    pub fn to_wide_bits(self) -> u128 {
+        u128::from(self.generation.get() - 1) << 32 | u128::from(self.id)
+    }
+
+    /// Reconstruct an `Entity` previously destructured with `to_wide_bits` or `to_bits`
+    ///
+    /// Never fails. Generations too large to be represented, which `to_wide_bits` never
+    /// produces, produce an entity that is never live; see `try_from_wide_bits` to reject them
+    /// instead.
+    pub fn from_wide_bits(bits: u128) -> Self {
+        Self::try_from_wide_bits(bits).unwrap_or_else(|| Self::new(bits as u32, RETIRED, 0))
+    }
+
+    /// Like `from_wide_bits`, but returns `None` if the generation is too large to be represented,
+    /// e.g. because the entity was encoded with the `wide-entities` feature and decoded without
+    pub fn try_from_wide_bits(bits: u128) -> Option<Self> {
+        let generation = u64::try_from(bits >> 32).ok()?.checked_add(1)?;
+        let generation = NonZeroGeneration::try_from(NonZeroU64::new(generation)?).ok()?;
+        Some(Self::new(bits as u32, generation, 0))
+    }
+
+    /// `World::uid` of the world that allocated this handle, or 0 if unknown or unrecorded
+    #[inline]
+    pub(crate) fn world(self) -> u32 {
+        #[cfg(feature = "checked-entities")]
+        return self.world;
+        #[cfg(not(feature = "checked-entities"))]
+        0
+    }
+
+    /// The same handle, exempt from checks against the world that allocated it
+    #[cfg(feature = "testing")]
+    pub(crate) fn untagged(self) -> Self {
+        Self::new(self.id, self.generation, 0)
+    }
+
+    /// Assemble a handle allocated by the world with `World::uid` `world`, or 0 if unknown
+    #[inline]
+    #[allow(unused_variables)]
+    pub(crate) fn new(id: u32, generation: NonZeroGeneration, world: u32) -> Self {
         Self {
-            generation: (bits >> 32) as u32,
-            id: bits as u32,
+            generation,
+            id,
+            #[cfg(feature = "checked-entities")]
+            world,
         }
     }
 
@@ -44,6 +170,14 @@ impl Entity {
     pub fn id(self) -> u32 {
         self.id
     }
+
+    /// Extract the number of entities that have previously had the same ID, plus one
+    ///
+    /// A handle is stale when its generation differs from that of the ID's slot, as reported by
+    /// `World::entity_slot`.
+    pub fn generation(self) -> Generation {
+        Generation::from(self.generation.get())
+    }
 }
 
 impl fmt::Debug for Entity {
@@ -52,10 +186,101 @@ impl fmt::Debug for Entity {
     }
 }
 
+/// The state of an entity ID in a `World`, from `World::entity_slot`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EntitySlot {
+    /// Occupied by a live or reserved entity
+    Live(Entity),
+    /// Unoccupied, with the handle the next entity to occupy it will receive
+    Free(Entity),
+    /// Permanently unoccupied, its generations having been exhausted
+    Retired(u32),
+}
+
+impl EntitySlot {
+    /// The entity ID this slot is for
+    pub fn id(&self) -> u32 {
+        match *self {
+            EntitySlot::Live(x) | EntitySlot::Free(x) => x.id,
+            EntitySlot::Retired(id) => id,
+        }
+    }
+}
+
+/// Iterator over entities reserved by `World::reserve_entities`
+pub struct ReserveEntitiesIter<'a> {
+    meta: &'a [EntityMeta],
+    /// IDs taken from the freelist
+    reused: core::iter::Rev<core::slice::Iter<'a, u32>>,
+    /// IDs to be allocated on the next flush
+    fresh: core::ops::Range<u32>,
+    world: u32,
+}
+
+impl Iterator for ReserveEntitiesIter<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        if let Some(&id) = self.reused.next() {
+            return Some(Entity::new(
+                id,
+                self.meta[id as usize].generation,
+                self.world,
+            ));
+        }
+        // Pending entities have implicit generation 1
+        let world = self.world;
+        self.fresh
+            .next()
+            .map(|id| Entity::new(id, NonZeroGeneration::MIN, world))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for ReserveEntitiesIter<'_> {
+    fn len(&self) -> usize {
+        self.reused.len() + self.fresh.len()
+    }
+}
+
+/// Chooses the IDs of new entities in place of a world's own free list, for
+/// `World::with_allocator`
+///
+/// Useful for e.g. giving each server shard its own range of IDs, or matching IDs with those of an
+/// external database. Generations are still managed by the world. IDs must be less than
+/// `ALLOCATOR_ID_LIMIT`.
+pub trait EntityAllocator: Send + Sync + 'static {
+    /// Choose the ID of a new entity
+    ///
+    /// Must be an ID that has never been allocated, or that was passed to `free` since it was
+    /// last allocated or claimed. The world panics otherwise.
+    fn allocate(&self) -> u32;
+
+    /// Note that `id` is no longer in use and may be allocated again
+    ///
+    /// Not called for IDs that have been reused so many times that the world has retired them.
+    fn free(&self, id: u32);
+
+    /// Note that `id` was put in use without being allocated, e.g. by `World::spawn_at` or by
+    /// restoring a snapshot
+    fn claim(&self, id: u32);
+}
+
+/// Exclusive upper bound on the IDs an `EntityAllocator` may choose, beyond which the world panics
+///
+/// The world stores per-entity data in a table indexed by ID, so its memory use grows with the
+/// greatest ID allocated, by a dozen or more bytes per ID. Allocators drawing on larger ID spaces,
+/// e.g. database keys, should map them to a dense range.
+pub const ALLOCATOR_ID_LIMIT: u32 = 1 << 24;
+
 #[derive(Default)]
 pub(crate) struct Entities {
     pub meta: Box<[EntityMeta]>,
-    // Reserved entities outside the range of `meta`, having implicit generation 0, archetype 0, and
+    // Reserved entities outside the range of `meta`, having implicit generation 1, archetype 0, and
     // undefined index. Calling `flush` converts these to real entities, which can have a fully
     // defined location.
     pending: AtomicU32,
@@ -66,6 +291,14 @@ pub(crate) struct Entities {
     // consumed and used to initialize locations to produce real entities after calling `flush`.
     reserved: Box<[AtomicU32]>,
     reserved_cursor: AtomicU32,
+    // Incremented whenever a handle to a live entity may have been invalidated
+    epoch: u64,
+    // Chooses IDs in place of `free`, if set
+    allocator: Option<Arc<dyn EntityAllocator>>,
+    // Whether freed IDs are kept off `free`, so that IDs are allocated in ascending order
+    no_reuse: bool,
+    // `World::uid` of the owning world, recorded in the handles it allocates
+    world: u32,
 }
 
 impl Entities {
@@ -75,6 +308,10 @@ impl Entities {
     /// can be determined by the return value of `flush` and by iterating through the `reserved`
     /// accessors, and should all be written immediately after flushing.
     pub fn reserve_entity(&self) -> Entity {
+        assert!(
+            self.allocator.is_none(),
+            "entities can't be reserved in a world with a custom allocator"
+        );
         loop {
             let index = self.free_cursor.load(Ordering::Relaxed);
             match index.checked_sub(1) {
@@ -82,13 +319,11 @@ impl Entities {
                 // predictable ID to be allocated on the next `flush` call
                 None => {
                     let n = self.pending.fetch_add(1, Ordering::Relaxed);
-                    return Entity {
-                        generation: 0,
-                        id: u32::try_from(self.meta.len())
-                            .ok()
-                            .and_then(|x| x.checked_add(n))
-                            .expect("too many entities"),
-                    };
+                    let id = u32::try_from(self.meta.len())
+                        .ok()
+                        .and_then(|x| x.checked_add(n))
+                        .expect("too many entities");
+                    return Entity::new(id, NonZeroGeneration::MIN, self.world);
                 }
                 // The freelist has entities in it, so move the last entry to the reserved list, to
                 // be consumed by the caller as part of a higher-level flush.
@@ -105,15 +340,54 @@ impl Entities {
                     let id = self.free[next as usize];
                     let reservation = self.reserved_cursor.fetch_add(1, Ordering::Relaxed);
                     self.reserved[reservation as usize].store(id, Ordering::Relaxed);
-                    return Entity {
-                        generation: self.meta[id as usize].generation,
-                        id,
-                    };
+                    return Entity::new(id, self.meta[id as usize].generation, self.world);
                 }
             }
         }
     }
 
+    /// Reserve `count` entity IDs concurrently, as if by calling `reserve_entity` repeatedly
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIter<'_> {
+        assert!(
+            self.allocator.is_none(),
+            "entities can't be reserved in a world with a custom allocator"
+        );
+        // Take as many IDs as possible from the end of the freelist in one step
+        let mut index = self.free_cursor.load(Ordering::Relaxed);
+        let taken = loop {
+            let taken = index.min(count);
+            match self.free_cursor.compare_exchange_weak(
+                index,
+                index - taken,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break taken,
+                // Another thread changed the freelist, start over.
+                Err(x) => index = x,
+            }
+        };
+        let reused = &self.free[(index - taken) as usize..index as usize];
+        let reservation = self.reserved_cursor.fetch_add(taken, Ordering::Relaxed);
+        for (slot, &id) in self.reserved[reservation as usize..].iter().zip(reused) {
+            slot.store(id, Ordering::Relaxed);
+        }
+        // Arrange for the rest to be allocated with predictable IDs on the next `flush` call
+        let fresh = count - taken;
+        let pending = self.pending.fetch_add(fresh, Ordering::Relaxed);
+        let first = u32::try_from(self.meta.len())
+            .ok()
+            .and_then(|x| x.checked_add(pending))
+            .filter(|x| x.checked_add(fresh).is_some())
+            .expect("too many entities");
+        ReserveEntitiesIter {
+            meta: &self.meta,
+            reused: reused.iter().rev(),
+            fresh: first..first + fresh,
+            world: self.world,
+        }
+    }
+
     /// Allocate an entity ID directly
     ///
     /// Location should be written immediately.
@@ -123,55 +397,179 @@ impl Entities {
             0,
             "allocator must be flushed before potentially growing"
         );
+        if let Some(id) = self.allocator.as_ref().map(|x| x.allocate()) {
+            return self.alloc_custom(id);
+        }
         let index = self.free_cursor.load(Ordering::Relaxed);
         match index.checked_sub(1) {
             None => {
                 self.grow(0);
                 let cursor = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
                 let id = self.free[(cursor - 1) as usize];
-                Entity {
-                    generation: self.meta[id as usize].generation,
-                    id,
-                }
+                self.claim(id)
             }
             Some(next) => {
                 // Not racey due to &mut self
                 self.free_cursor.store(next, Ordering::Relaxed);
                 let id = self.free[next as usize];
-                Entity {
-                    generation: self.meta[id as usize].generation,
-                    id,
-                }
+                self.claim(id)
             }
         }
     }
 
+    /// The handle to the newly allocated `id`, recording this world as its allocator
+    fn claim(&mut self, id: u32) -> Entity {
+        let meta = &mut self.meta[id as usize];
+        meta.set_world(self.world);
+        meta.entity(id)
+    }
+
+    /// Use `id`, chosen by a custom allocator
+    fn alloc_custom(&mut self, id: u32) -> Entity {
+        assert!(
+            id < ALLOCATOR_ID_LIMIT,
+            "allocator chose entity ID {}, which isn't below ALLOCATOR_ID_LIMIT",
+            id
+        );
+        if id as usize >= self.meta.len() {
+            self.grow(id + 1 - self.meta.len() as u32);
+        }
+        let meta = &self.meta[id as usize];
+        assert!(
+            meta.location.index == u32::MAX && meta.generation != RETIRED,
+            "allocator chose entity ID {}, which is in use",
+            id
+        );
+        self.claim(id)
+    }
+
+    /// Use a custom allocator instead of the free list
+    ///
+    /// Must be called before any entities are allocated.
+    pub fn set_allocator(&mut self, allocator: Arc<dyn EntityAllocator>) {
+        debug_assert!(self.meta.is_empty());
+        self.allocator = Some(allocator);
+    }
+
+    /// Stop using a custom allocator, returning it
+    pub fn take_allocator(&mut self) -> Option<Arc<dyn EntityAllocator>> {
+        self.allocator.take()
+    }
+
+    /// The live entity with ID `id`, if any
+    ///
+    /// Must not be called while there are unflushed reservations.
+    pub fn occupant(&self, id: u32) -> Option<Entity> {
+        let meta = self.meta.get(id as usize)?;
+        if meta.location.index == u32::MAX {
+            return None;
+        }
+        Some(meta.entity(id))
+    }
+
+    /// Allocate the specific handle `entity`, whose ID must not be occupied
+    ///
+    /// Location should be written immediately.
+    pub fn alloc_at(&mut self, entity: Entity) {
+        assert!(
+            entity.generation != RETIRED,
+            "entity {:?} has an invalid generation",
+            entity
+        );
+        debug_assert_eq!(
+            self.pending.load(Ordering::Relaxed),
+            0,
+            "allocator must be flushed before potentially growing"
+        );
+        if entity.id as usize >= self.meta.len() {
+            self.grow(entity.id + 1 - self.meta.len() as u32);
+        }
+        let cursor = self.free_cursor.load(Ordering::Relaxed); // Not racey due to &mut self
+        if let Some(ref allocator) = self.allocator {
+            allocator.claim(entity.id);
+            self.adopt(entity);
+            return;
+        }
+        match self.free[..cursor as usize]
+            .iter()
+            .position(|&x| x == entity.id)
+        {
+            Some(index) => {
+                self.free.swap(index, cursor as usize - 1);
+                self.free_cursor.store(cursor - 1, Ordering::Relaxed);
+            }
+            // Retired IDs can be brought back by an explicitly chosen generation
+            None => assert_eq!(
+                self.meta[entity.id as usize].generation, RETIRED,
+                "entity ID is not free"
+            ),
+        }
+        self.adopt(entity);
+    }
+
+    /// Make `entity` the occupant of its ID, recording the world that allocated it, if known
+    fn adopt(&mut self, entity: Entity) {
+        let meta = &mut self.meta[entity.id as usize];
+        meta.generation = entity.generation;
+        // Handles of unknown origin, e.g. from `from_bits`, make the slot unchecked
+        meta.set_world(entity.world());
+    }
+
     /// Destroy an entity, allowing it to be reused
     ///
     /// Must not be called on reserved entities prior to `flush`.
     pub fn free(&mut self, entity: Entity) -> Result<Location, NoSuchEntity> {
-        let meta = &mut self.meta[entity.id as usize];
+        let meta = self.meta.get_mut(entity.id as usize).ok_or(NoSuchEntity)?;
         if meta.generation != entity.generation {
             return Err(NoSuchEntity);
         }
-        meta.generation += 1;
+        meta.check_world(entity);
+        meta.generation = next_generation(meta.generation);
+        let retired = meta.generation == RETIRED;
+        self.epoch += 1;
         let loc = mem::replace(
             &mut meta.location,
             Location {
                 archetype: 0,
                 // Guard against bugs in reservation handling
-                index: u32::max_value(),
+                index: u32::MAX,
             },
         );
-        let index = self.free_cursor.fetch_add(1, Ordering::Relaxed); // Not racey due to &mut self
-        self.free[index as usize] = entity.id;
+        if !retired {
+            if let Some(ref allocator) = self.allocator {
+                allocator.free(entity.id);
+            } else if !self.no_reuse {
+                let index = self.free_cursor.fetch_add(1, Ordering::Relaxed); // Not racey due to &mut self
+                self.free[index as usize] = entity.id;
+            }
+        }
         debug_assert!(
-            loc.index != u32::max_value(),
+            loc.index != u32::MAX,
             "free called on reserved entity without flush"
         );
         Ok(loc)
     }
 
+    /// Invalidate `entity` in favor of a new handle to the same ID and location
+    ///
+    /// Must not be called on reserved entities prior to `flush`. Panics if the ID's generations are
+    /// exhausted.
+    pub fn regenerate(&mut self, entity: Entity) -> Result<Entity, NoSuchEntity> {
+        let meta = self.meta.get_mut(entity.id as usize).ok_or(NoSuchEntity)?;
+        if meta.generation != entity.generation {
+            return Err(NoSuchEntity);
+        }
+        meta.check_world(entity);
+        assert!(
+            next_generation(meta.generation) != RETIRED,
+            "entity {:?} has exhausted its generations",
+            entity
+        );
+        meta.generation = next_generation(meta.generation);
+        self.epoch += 1;
+        Ok(meta.entity(entity.id))
+    }
+
     /// Ensure `n` at least allocations can succeed without reallocating
     pub fn reserve(&mut self, additional: u32) {
         debug_assert_eq!(
@@ -179,58 +577,239 @@ impl Entities {
             0,
             "allocator must be flushed before potentially growing"
         );
+        if self.allocator.is_some() {
+            // Storage is allocated as the allocator chooses IDs
+            return;
+        }
         let free = self.free_cursor.load(Ordering::Relaxed);
         if additional > free {
             self.grow(additional - free);
         }
     }
 
+    /// Record `world` as the allocator of handles allocated from now on
+    pub fn set_world(&mut self, world: u32) {
+        self.world = world;
+    }
+
+    /// Changes whenever a handle to a live entity may have been invalidated
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Whether `entity` is live or reserved
     pub fn contains(&self, entity: Entity) -> bool {
-        if entity.id >= self.meta.len() as u32 {
-            return true;
+        self.is_live(entity)
+    }
+
+    /// Set bit `i % 64` of word `i / 64` of `out` for each live `entities[i]`
+    pub fn validate(&self, entities: &[Entity], out: &mut [u64]) {
+        debug_assert!(out.len() * 64 >= entities.len());
+        for (word, chunk) in out.iter_mut().zip(entities.chunks(64)) {
+            let mut bits = 0;
+            for (i, &entity) in chunk.iter().enumerate() {
+                bits |= u64::from(self.is_live(entity)) << i;
+            }
+            *word = bits;
+        }
+    }
+
+    #[inline]
+    fn is_live(&self, entity: Entity) -> bool {
+        match self.meta.get(entity.id as usize) {
+            Some(x) => {
+                if x.generation != entity.generation {
+                    return false;
+                }
+                x.check_world(entity);
+                x.location.index != u32::MAX || self.is_reserved(entity.id)
+            }
+            // Pending entities have implicit generation 1
+            None => {
+                entity.generation == NonZeroGeneration::MIN
+                    && u64::from(entity.id)
+                        < self.meta.len() as u64 + u64::from(self.pending.load(Ordering::Relaxed))
+            }
         }
-        self.meta[entity.id as usize].generation == entity.generation
     }
 
+    /// The state of `id`, if storage has been allocated for it or it's been reserved
+    pub fn slot(&self, id: u32) -> Option<EntitySlot> {
+        let meta = match self.meta.get(id as usize) {
+            Some(x) => x,
+            None if id < self.slots() => {
+                // Pending entities have implicit generation 1
+                return Some(EntitySlot::Live(Entity::new(
+                    id,
+                    NonZeroGeneration::MIN,
+                    self.world,
+                )));
+            }
+            None => return None,
+        };
+        let entity = meta.entity(id);
+        Some(if meta.generation == RETIRED {
+            EntitySlot::Retired(id)
+        } else if meta.location.index != u32::MAX || self.is_reserved(id) {
+            EntitySlot::Live(entity)
+        } else {
+            EntitySlot::Free(entity)
+        })
+    }
+
+    /// Number of IDs that storage has been allocated for or that have been reserved
+    pub fn slots(&self) -> u32 {
+        self.meta.len() as u32 + self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Whether `id`, which is not that of a flushed entity, was reserved from the freelist
+    #[cold]
+    fn is_reserved(&self, id: u32) -> bool {
+        let len = self.reserved_cursor.load(Ordering::Relaxed) as usize;
+        self.reserved[..len]
+            .iter()
+            .any(|x| x.load(Ordering::Relaxed) == id)
+    }
+
+    /// Free every entity
+    ///
+    /// Must not be called while there are unflushed reservations.
     pub fn clear(&mut self) {
-        // Not racey due to &mut self
-        self.free_cursor
-            .store(self.meta.len() as u32, Ordering::Relaxed);
-        for (i, x) in self.free.iter_mut().enumerate() {
-            *x = i as u32;
+        self.epoch += 1;
+        for (id, meta) in self.meta.iter_mut().enumerate() {
+            if meta.location.index != u32::MAX {
+                meta.generation = next_generation(meta.generation);
+                if let (Some(allocator), false) = (&self.allocator, meta.generation == RETIRED) {
+                    allocator.free(id as u32);
+                }
+                meta.location = Location {
+                    archetype: 0,
+                    index: u32::MAX,
+                };
+            }
         }
         self.pending.store(0, Ordering::Relaxed);
         self.reserved_cursor.store(0, Ordering::Relaxed);
+        self.rebuild_free();
     }
 
-    /// Access the location storage of an entity
+    /// Whether freed IDs may be allocated again
     ///
-    /// Must not be called on pending entities.
-    pub fn get_mut(&mut self, entity: Entity) -> Result<&mut Location, NoSuchEntity> {
-        let meta = &mut self.meta[entity.id as usize];
-        if meta.generation == entity.generation {
-            Ok(&mut meta.location)
+    /// Must not be called while there are unflushed reservations.
+    pub fn set_reuse(&mut self, reuse: bool) {
+        self.no_reuse = !reuse;
+        self.rebuild_free();
+    }
+
+    /// Fill the freelist with every unoccupied ID that may be allocated
+    fn rebuild_free(&mut self) {
+        if self.allocator.is_some() {
+            return;
+        }
+        if self.free.len() < self.meta.len() {
+            // Not kept by a custom allocator that's since been removed
+            self.free = vec![0; self.meta.len()].into();
+        }
+        let mut free = 0;
+        for id in 0..self.meta.len() as u32 {
+            let meta = &self.meta[id as usize];
+            let used = meta.generation != NonZeroGeneration::MIN;
+            if meta.location.index == u32::MAX
+                && meta.generation != RETIRED
+                && !(self.no_reuse && used)
+            {
+                self.free[free] = id;
+                free += 1;
+            }
+        }
+        if self.no_reuse {
+            // Allocate in ascending order, as `grow` arranges for fresh IDs
+            self.free[..free].reverse();
+        }
+        // Not racey due to &mut self
+        self.free_cursor.store(free as u32, Ordering::Relaxed);
+    }
+
+    /// Produce `snapshot`, an earlier state of `self`, in which handles to entities freed or
+    /// allocated since are invalid
+    ///
+    /// Neither `self` nor `snapshot` may have unflushed reservations.
+    pub fn rollback(&self, snapshot: &Entities) -> Entities {
+        let mut result = snapshot.clone();
+        result.epoch = self.epoch + 1;
+        result.allocator = self.allocator.clone();
+        if result.meta.len() < self.meta.len() {
+            result.grow((self.meta.len() - result.meta.len()) as u32);
+        }
+        for (id, (meta, current)) in result.meta.iter_mut().zip(self.meta.iter()).enumerate() {
+            let live = current.location.index != u32::MAX;
+            if meta.location.index != u32::MAX {
+                // Live in the snapshot
+                if let (Some(allocator), false) = (&self.allocator, live) {
+                    allocator.claim(id as u32);
+                }
+                continue;
+            }
+            let generation = if live {
+                next_generation(current.generation)
+            } else {
+                current.generation
+            };
+            meta.generation = meta.generation.max(generation);
+            if let (Some(allocator), true, false) =
+                (&self.allocator, live, meta.generation == RETIRED)
+            {
+                allocator.free(id as u32);
+            }
+        }
+        result.no_reuse = self.no_reuse;
+        if result.no_reuse {
+            result.rebuild_free();
         } else {
-            Err(NoSuchEntity)
+            result.drop_retired();
         }
+        result
+    }
+
+    /// Remove retired IDs from the free list
+    fn drop_retired(&mut self) {
+        let cursor = *self.free_cursor.get_mut() as usize;
+        let mut kept = 0;
+        for i in 0..cursor {
+            let id = self.free[i];
+            if self.meta[id as usize].generation != RETIRED {
+                self.free[kept] = id;
+                kept += 1;
+            }
+        }
+        *self.free_cursor.get_mut() = kept as u32;
     }
 
     /// Returns `Ok(Location { archetype: 0, index: undefined })` for pending entities
     pub fn get(&self, entity: Entity) -> Result<Location, NoSuchEntity> {
-        if self.meta.len() <= entity.id as usize {
-            return Ok(Location {
-                archetype: 0,
-                index: u32::max_value(),
-            });
-        }
-        let meta = &self.meta[entity.id as usize];
+        let meta = match self.meta.get(entity.id as usize) {
+            Some(x) => x,
+            // Pending entities have implicit generation 1
+            None if entity.generation == NonZeroGeneration::MIN && entity.id < self.slots() => {
+                return Ok(Location {
+                    archetype: 0,
+                    index: u32::MAX,
+                });
+            }
+            None => return Err(NoSuchEntity),
+        };
         if meta.generation != entity.generation {
             return Err(NoSuchEntity);
         }
+        meta.check_world(entity);
+        if meta.location.index == u32::MAX && !self.is_reserved(entity.id) {
+            // Free slot whose current generation the handle happens to carry
+            return Err(NoSuchEntity);
+        }
         if meta.location.archetype == 0 {
             return Ok(Location {
                 archetype: 0,
-                index: u32::max_value(),
+                index: u32::MAX,
             });
         }
         Ok(meta.location)
@@ -240,6 +819,10 @@ impl Entities {
     #[allow(clippy::reversed_empty_ranges)]
     pub fn flush(&mut self) -> impl Iterator<Item = u32> {
         let pending = self.pending.load(Ordering::Relaxed); // Not racey due to &mut self
+        for i in 0..self.reserved_len() {
+            let id = self.reserved(i);
+            self.meta[id as usize].set_world(self.world);
+        }
 
         if pending != 0 {
             let first = self.meta.len() as u32;
@@ -277,13 +860,20 @@ impl Entities {
         new_meta.resize(
             new_len,
             EntityMeta {
-                generation: 0,
+                generation: NonZeroGeneration::MIN,
                 location: Location {
                     archetype: 0,
-                    index: u32::max_value(), // dummy value, to be filled in
+                    index: u32::MAX, // dummy value, to be filled in
                 },
+                #[cfg(feature = "checked-entities")]
+                world: self.world,
             },
         );
+        if self.allocator.is_some() {
+            // IDs are neither chosen from the freelist nor reserved
+            self.meta = new_meta.into();
+            return;
+        }
 
         let free_cursor = self.free_cursor.load(Ordering::Relaxed); // Not racey due to &mut self
         let mut new_free = Vec::with_capacity(new_len);
@@ -310,10 +900,80 @@ impl Entities {
     }
 }
 
+impl Clone for Entities {
+    fn clone(&self) -> Self {
+        Self {
+            meta: self.meta.clone(),
+            pending: AtomicU32::new(self.pending.load(Ordering::Relaxed)),
+            free: self.free.clone(),
+            free_cursor: AtomicU32::new(self.free_cursor.load(Ordering::Relaxed)),
+            reserved: self
+                .reserved
+                .iter()
+                .map(|x| AtomicU32::new(x.load(Ordering::Relaxed)))
+                .collect(),
+            reserved_cursor: AtomicU32::new(self.reserved_cursor.load(Ordering::Relaxed)),
+            epoch: self.epoch,
+            allocator: self.allocator.clone(),
+            no_reuse: self.no_reuse,
+            world: self.world,
+        }
+    }
+}
+
+/// Generation of an ID that has been used so many times that it's never reused, rather than
+/// wrapping around and making stale handles to it valid again
+///
+/// Never that of a live entity, so that it's distinct from that of any handle ever produced.
+const RETIRED: NonZeroGeneration = NonZeroGeneration::MAX;
+
+/// The generation following that of a live entity
+fn next_generation(generation: NonZeroGeneration) -> NonZeroGeneration {
+    generation
+        .checked_add(1)
+        .expect("live entity has retired generation")
+}
+
 #[derive(Copy, Clone)]
 pub(crate) struct EntityMeta {
-    pub generation: u32,
+    pub generation: NonZeroGeneration,
     pub location: Location,
+    /// `World::uid` of the world that allocated the current generation
+    #[cfg(feature = "checked-entities")]
+    world: u32,
+}
+
+impl EntityMeta {
+    /// The handle to the current generation of `id`
+    #[inline]
+    pub fn entity(&self, id: u32) -> Entity {
+        #[cfg(feature = "checked-entities")]
+        let world = self.world;
+        #[cfg(not(feature = "checked-entities"))]
+        let world = 0;
+        Entity::new(id, self.generation, world)
+    }
+
+    #[inline]
+    #[allow(unused_variables)]
+    fn set_world(&mut self, world: u32) {
+        #[cfg(feature = "checked-entities")]
+        {
+            self.world = world;
+        }
+    }
+
+    /// Panic if `entity`, of the current generation, was allocated by a different world
+    #[inline]
+    #[allow(unused_variables)]
+    fn check_world(&self, entity: Entity) {
+        #[cfg(feature = "checked-entities")]
+        assert!(
+            entity.world == 0 || self.world == 0 || entity.world == self.world,
+            "entity {:?} belongs to a different world",
+            entity
+        );
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -341,10 +1001,19 @@ mod tests {
 
     #[test]
     fn entity_bits_roundtrip() {
-        let e = Entity {
-            generation: 0xDEADBEEF,
-            id: 0xBAADF00D,
-        };
+        let e = Entity::new(0xBAADF00D, NonZeroGeneration::new(0xDEADBEEF).unwrap(), 0);
         assert_eq!(Entity::from_bits(e.to_bits()), e);
+        assert_eq!(Entity::try_from_bits(e.to_bits()), Some(e));
+        assert_eq!(Entity::try_from_bits(0xFFFF_FFFF_BAAD_F00D), None);
+        assert_eq!(Entity::from_wide_bits(e.to_wide_bits()), e);
+        assert_eq!(Entity::try_from_wide_bits(u128::MAX), None);
+    }
+
+    #[test]
+    fn option_entity_size() {
+        assert_eq!(
+            core::mem::size_of::<Option<Entity>>(),
+            core::mem::size_of::<Entity>()
+        );
     }
 }
@@ -80,6 +80,77 @@ impl EntityBuilder {
         self
     }
 
+    /// Add `component` to the entity, replacing any previously `add`ed `T` in place
+    pub(crate) fn replace<T: Component>(&mut self, component: T) -> &mut Self {
+        let id = TypeId::of::<T>();
+        let offset = match self.info.iter().find(|x| x.0.id() == id) {
+            Some(x) => x.1,
+            None => return self.add(component),
+        };
+        unsafe {
+            let ptr = self.storage.as_mut_ptr().add(offset).cast::<T>();
+            let old = ptr.read_unaligned();
+            ptr.write_unaligned(component);
+            drop(old);
+        }
+        self
+    }
+
+    /// Add the `ty` component at `component`, taking ownership of it
+    ///
+    /// # Safety
+    /// `component` must point to a valid `ty`, which must not be used afterwards
+    pub(crate) unsafe fn add_raw(&mut self, ty: TypeInfo, component: *mut u8) {
+        if !self.id_set.insert(ty.id()) {
+            ty.drop(component);
+            return;
+        }
+        let size = ty.layout().size();
+        let end = self.cursor + size;
+        if end > self.storage.len() {
+            self.grow(end);
+        }
+        ptr::copy_nonoverlapping(
+            component,
+            self.storage.as_mut_ptr().add(self.cursor).cast(),
+            size,
+        );
+        self.info.push((ty, self.cursor));
+        self.cursor += size;
+    }
+
+    /// Drop the previously `add`ed component of type `id`, if any, reclaiming its storage
+    pub(crate) fn remove_dynamic(&mut self, id: TypeId) {
+        if !self.id_set.remove(&id) {
+            return;
+        }
+        let i = self.info.iter().position(|x| x.0.id() == id).unwrap();
+        let (ty, offset) = self.info.remove(i);
+        let layout = ty.layout();
+        unsafe {
+            // Suitably aligned storage for drop
+            let tmp = if layout.size() > 0 {
+                alloc(layout).cast()
+            } else {
+                layout.align() as *mut _
+            };
+            ptr::copy_nonoverlapping(self.storage[offset..].as_ptr().cast(), tmp, layout.size());
+            ty.drop(tmp);
+            if layout.size() > 0 {
+                dealloc(tmp, layout);
+            }
+        }
+        // Close the gap, so repeatedly replacing components doesn't grow storage without bound
+        let size = layout.size();
+        self.storage.copy_within(offset + size..self.cursor, offset);
+        self.cursor -= size;
+        for x in &mut self.info {
+            if x.1 > offset {
+                x.1 -= size;
+            }
+        }
+    }
+
     fn grow(&mut self, min_size: usize) {
         let new_len = min_size.next_power_of_two().max(64);
         let mut new_storage = vec![MaybeUninit::uninit(); new_len].into_boxed_slice();
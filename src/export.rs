@@ -0,0 +1,181 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::{mem, slice};
+
+use crate::{Archetype, Component, Entity, World};
+
+/// Converts components into contiguous, column-oriented buffers for offline analysis
+///
+/// Each registered column extracts a plain value, such as a coordinate or a counter, from one
+/// component type. `export` then gathers the values for every entity that has all the columns'
+/// component types, an archetype at a time, into one buffer per column. Buffers are laid out like
+/// fixed-width Arrow arrays without nulls, so `ExportedColumns::bytes` can be handed to an Arrow
+/// or Parquet writer, or written to disk, as-is.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Position([f32; 2]);
+/// struct Health(u32);
+/// let mut world = World::new();
+/// let a = world.spawn((Position([1.0, 2.0]), Health(10)));
+/// world.spawn((Position([3.0, 4.0]),));
+///
+/// let mut exporter = ColumnExporter::new();
+/// exporter
+///     .column("x", |p: &Position| p.0[0])
+///     .column("health", |h: &Health| h.0);
+/// let columns = exporter.export(&world);
+/// assert_eq!(columns.entities(), [a]);
+/// assert_eq!(columns.get::<f32>("x").unwrap(), [1.0]);
+/// assert_eq!(columns.bytes("health").unwrap(), 10u32.to_ne_bytes());
+/// ```
+#[derive(Default)]
+pub struct ColumnExporter {
+    columns: Vec<Column>,
+}
+
+struct Column {
+    name: &'static str,
+    ty: TypeId,
+    new: fn() -> Box<dyn Buffer>,
+    /// Append the values of an archetype's components to a buffer made by `new`
+    fill: Box<FillFn>,
+}
+
+type FillFn = dyn Fn(&Archetype, &mut dyn Buffer) + Send + Sync;
+
+impl ColumnExporter {
+    /// Create an exporter with no columns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a column named `name` containing `f` applied to each entity's `T` component
+    pub fn column<T: Component, V: ColumnValue>(
+        &mut self,
+        name: &'static str,
+        f: impl Fn(&T) -> V + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.columns.push(Column {
+            name,
+            ty: TypeId::of::<T>(),
+            new: || Box::new(Vec::<V>::new()),
+            fill: Box::new(move |archetype, out| {
+                let out = out.as_any_mut().downcast_mut::<Vec<V>>().unwrap();
+                archetype.with_column::<T, _>(|column| out.extend(column.iter().map(&f)));
+            }),
+        });
+        self
+    }
+
+    /// Gather the columns for every entity in `world` having all of their component types
+    ///
+    /// Panics if any of those components are uniquely borrowed.
+    pub fn export(&self, world: &World) -> ExportedColumns {
+        let mut entities = Vec::new();
+        let mut buffers = self.columns.iter().map(|x| (x.new)()).collect::<Vec<_>>();
+        let meta = world.entities_meta();
+        for archetype in world.archetypes() {
+            if archetype.is_empty() || !self.columns.iter().all(|x| archetype.has_dynamic(x.ty)) {
+                continue;
+            }
+            entities.extend((0..archetype.len()).map(|i| {
+                let id = archetype.entity_id(i);
+                meta[id as usize].entity(id)
+            }));
+            for (column, buffer) in self.columns.iter().zip(&mut buffers) {
+                (column.fill)(archetype, &mut **buffer);
+            }
+        }
+        ExportedColumns {
+            entities,
+            names: self.columns.iter().map(|x| x.name).collect(),
+            buffers,
+        }
+    }
+}
+
+/// Column-oriented data produced by `ColumnExporter::export`
+pub struct ExportedColumns {
+    entities: Vec<Entity>,
+    names: Vec<&'static str>,
+    buffers: Vec<Box<dyn Buffer>>,
+}
+
+impl ExportedColumns {
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Whether there are no rows
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// The entity each row was exported from
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Names of the columns, in the order they were added to the exporter
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    /// Values of the column `name`, if it exists and contains `V`s
+    pub fn get<V: ColumnValue>(&self, name: &str) -> Option<&[V]> {
+        self.buffer(name)?
+            .as_any()
+            .downcast_ref::<Vec<V>>()
+            .map(|x| &x[..])
+    }
+
+    /// Raw memory of the column `name`, in native byte order, if it exists
+    pub fn bytes(&self, name: &str) -> Option<&[u8]> {
+        Some(self.buffer(name)?.bytes())
+    }
+
+    fn buffer(&self, name: &str) -> Option<&dyn Buffer> {
+        let index = self.names.iter().position(|&x| x == name)?;
+        Some(&*self.buffers[index])
+    }
+}
+
+/// Plain values that can make up an exported column
+///
+/// # Safety
+/// Must not contain padding, pointers, or any other bytes that aren't meaningful as plain data.
+pub unsafe trait ColumnValue: Copy + Send + Sync + 'static {}
+
+macro_rules! column_value {
+    ($($ty:ty),*) => {
+        $(unsafe impl ColumnValue for $ty {})*
+    };
+}
+
+column_value!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: ColumnValue, const N: usize> ColumnValue for [T; N] {}
+
+trait Buffer: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn bytes(&self) -> &[u8];
+}
+
+impl<V: ColumnValue> Buffer for Vec<V> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.as_ptr().cast(), self.len() * mem::size_of::<V>()) }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use crate::alloc::vec::Vec;
+use core::ptr::{self, NonNull};
+use core::slice;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Scratch memory that lives until the next `World::flush`
+///
+/// Obtained from `World::frame_allocator`. Allocation is lock-free, so scratch space can be
+/// obtained through a shared reference to the world, e.g. from concurrently executing queries.
+/// Everything is reclaimed at once by `World::flush`, which also enlarges the allocator's buffer
+/// to fit everything allocated since the previous flush, so that a workload that's the same from
+/// frame to frame makes no heap allocations at all once warmed up.
+///
+/// Values placed in a `FrameAllocator` are never dropped.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let scratch = world.frame_allocator().alloc_from_iter((0..100).rev());
+/// scratch.sort_unstable();
+/// assert_eq!(scratch[0], 0);
+/// world.flush();
+/// assert_eq!(world.frame_allocator().allocated(), 0);
+/// ```
+pub struct FrameAllocator {
+    data: NonNull<u8>,
+    capacity: usize,
+    /// Bytes of `data` handed out, including padding
+    used: AtomicUsize,
+    /// Total size of the allocations made since `data` was exhausted
+    overflowed: AtomicUsize,
+    /// Allocations made since `data` was exhausted
+    overflow: AtomicPtr<Overflow>,
+}
+
+impl FrameAllocator {
+    pub(crate) fn new() -> Self {
+        Self {
+            data: NonNull::dangling(),
+            capacity: 0,
+            used: AtomicUsize::new(0),
+            overflowed: AtomicUsize::new(0),
+            overflow: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Move `value` into scratch memory
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        unsafe {
+            let ptr = self.alloc_raw(Layout::new::<T>()).cast::<T>().as_ptr();
+            ptr.write(value);
+            &mut *ptr
+        }
+    }
+
+    /// Collect the elements of `iter` into scratch memory
+    ///
+    /// Only allocates from the heap if `iter` doesn't have an accurate upper bound on its length.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_from_iter<T, I: IntoIterator<Item = T>>(&self, iter: I) -> &mut [T] {
+        let mut iter = iter.into_iter();
+        let bound = match iter.size_hint().1 {
+            Some(x) => x,
+            None => return self.alloc_from_vec(iter.collect()),
+        };
+        unsafe {
+            let base = self
+                .alloc_raw(Layout::array::<T>(bound).unwrap())
+                .cast::<T>();
+            let mut len = 0;
+            while len < bound {
+                match iter.next() {
+                    Some(x) => base.as_ptr().add(len).write(x),
+                    None => break,
+                }
+                len += 1;
+            }
+            let written = slice::from_raw_parts_mut(base.as_ptr(), len);
+            match iter.next() {
+                None => written,
+                Some(x) => {
+                    // `iter` lied about its length
+                    let mut all = Vec::with_capacity(len + 1);
+                    all.extend(written.iter().map(|x| ptr::read(x)));
+                    all.push(x);
+                    all.extend(iter);
+                    self.alloc_from_vec(all)
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn alloc_from_vec<T>(&self, mut vec: Vec<T>) -> &mut [T] {
+        unsafe {
+            let base = self
+                .alloc_raw(Layout::array::<T>(vec.len()).unwrap())
+                .cast::<T>()
+                .as_ptr();
+            ptr::copy_nonoverlapping(vec.as_ptr(), base, vec.len());
+            let len = vec.len();
+            vec.set_len(0);
+            slice::from_raw_parts_mut(base, len)
+        }
+    }
+
+    /// Number of bytes allocated since the last `World::flush`
+    pub fn allocated(&self) -> usize {
+        self.used.load(Ordering::Relaxed).min(self.capacity)
+            + self.overflowed.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes that can be allocated without allocating from the heap after a
+    /// `World::flush`
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn alloc_raw(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+        let base = self.data.as_ptr() as usize;
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let start = align(base + used, layout.align()) - base;
+            let end = start + layout.size();
+            if end > self.capacity {
+                break;
+            }
+            match self
+                .used
+                .compare_exchange_weak(used, end, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return unsafe { NonNull::new_unchecked(self.data.as_ptr().add(start)) },
+                Err(x) => used = x,
+            }
+        }
+
+        // Out of space; fall back to the heap until the next reset
+        let (full, offset) = Layout::new::<Overflow>().extend(layout).unwrap();
+        self.overflowed
+            .fetch_add(layout.size() + layout.align(), Ordering::Relaxed);
+        unsafe {
+            let header = alloc(full).cast::<Overflow>();
+            if header.is_null() {
+                handle_alloc_error(full);
+            }
+            let mut next = self.overflow.load(Ordering::Relaxed);
+            loop {
+                header.write(Overflow { next, layout: full });
+                match self.overflow.compare_exchange_weak(
+                    next,
+                    header,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(x) => next = x,
+                }
+            }
+            NonNull::new_unchecked(header.cast::<u8>().add(offset))
+        }
+    }
+
+    /// Free everything, and grow to fit everything allocated since the previous reset
+    pub(crate) fn reset(&mut self) {
+        let overflowed = *self.overflowed.get_mut();
+        let used = (*self.used.get_mut()).min(self.capacity);
+        self.free_overflow();
+        *self.used.get_mut() = 0;
+        *self.overflowed.get_mut() = 0;
+        if overflowed == 0 {
+            return;
+        }
+        self.free_data();
+        self.capacity = (used + overflowed).next_power_of_two();
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(self.capacity, DATA_ALIGN);
+            self.data = NonNull::new(alloc(layout)).unwrap_or_else(|| handle_alloc_error(layout));
+        }
+    }
+
+    fn free_overflow(&mut self) {
+        let mut next = *self.overflow.get_mut();
+        *self.overflow.get_mut() = ptr::null_mut();
+        while !next.is_null() {
+            unsafe {
+                let Overflow {
+                    next: after,
+                    layout,
+                } = next.read();
+                dealloc(next.cast(), layout);
+                next = after;
+            }
+        }
+    }
+
+    fn free_data(&mut self) {
+        if self.capacity != 0 {
+            unsafe {
+                dealloc(
+                    self.data.as_ptr(),
+                    Layout::from_size_align_unchecked(self.capacity, DATA_ALIGN),
+                );
+            }
+        }
+    }
+}
+
+impl Drop for FrameAllocator {
+    fn drop(&mut self) {
+        self.free_overflow();
+        self.free_data();
+    }
+}
+
+unsafe impl Send for FrameAllocator {}
+unsafe impl Sync for FrameAllocator {}
+
+/// Header of an allocation made after a `FrameAllocator`'s buffer was exhausted
+struct Overflow {
+    next: *mut Overflow,
+    /// Layout of the header and the allocation following it
+    layout: Layout,
+}
+
+const DATA_ALIGN: usize = 16;
+
+fn align(x: usize, alignment: usize) -> usize {
+    debug_assert!(alignment.is_power_of_two());
+    (x + alignment - 1) & (!alignment + 1)
+}
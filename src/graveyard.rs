@@ -0,0 +1,175 @@
+use crate::alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::archetype::Archetype;
+use crate::query::Fetch;
+use crate::removal::RemovalSinks;
+use crate::{Component, Entity, EntityRef, Query, QueryBorrow, QueryIter, Ref, World};
+
+/// The components of entities despawned since the last `World::flush`
+///
+/// Enabled with `World::keep_despawned`. Lets systems that handle deaths read an entity's final
+/// state, e.g. its position to spawn a ragdoll at, or its inventory to drop, without copying it out
+/// in advance. Entities are identified by the handles they had before being despawned.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.keep_despawned(true);
+/// let a = world.spawn((123, "abc"));
+/// world.despawn(a).unwrap();
+/// let graveyard = world.graveyard().unwrap();
+/// assert_eq!(*graveyard.get::<i32>(a).unwrap(), 123);
+/// let dead = graveyard.query::<&&str>().iter().map(|(e, &s)| (e, s)).collect::<Vec<_>>();
+/// assert_eq!(dead, [(a, "abc")]);
+///
+/// world.flush();
+/// assert!(world.graveyard().unwrap().is_empty());
+/// ```
+pub struct Graveyard {
+    /// Holds a corpse entity for each despawned entity
+    world: World,
+    /// The archetype of `world` holding corpses from each archetype of the living world, if any
+    archetypes: Vec<Option<u32>>,
+    /// Despawned entity corresponding to each corpse, by corpse ID
+    origins: HashMap<u32, Entity>,
+    /// Corpse of each despawned entity
+    corpses: HashMap<Entity, Entity>,
+}
+
+impl Graveyard {
+    pub(crate) fn new() -> Self {
+        Self {
+            world: World::new(),
+            archetypes: Vec::new(),
+            origins: HashMap::default(),
+            corpses: HashMap::default(),
+        }
+    }
+
+    /// Whether `entity` was despawned since the last `World::flush`
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.corpses.contains_key(&entity)
+    }
+
+    /// Number of entities despawned since the last `World::flush`
+    pub fn len(&self) -> u32 {
+        self.corpses.len() as u32
+    }
+
+    /// Whether no entities have been despawned since the last `World::flush`
+    pub fn is_empty(&self) -> bool {
+        self.corpses.is_empty()
+    }
+
+    /// Borrow the final `T` component of the despawned `entity`
+    ///
+    /// Panics if the component is already uniquely borrowed from another entity with the same
+    /// components.
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<Ref<'_, T>> {
+        self.world.get::<T>(*self.corpses.get(&entity)?).ok()
+    }
+
+    /// Access the final state of the despawned `entity`
+    pub fn entity(&self, entity: Entity) -> Option<EntityRef<'_>> {
+        self.world.entity(*self.corpses.get(&entity)?).ok()
+    }
+
+    /// Query the final state of entities despawned since the last `World::flush`
+    ///
+    /// Like `World::query`, including in its borrowing rules.
+    pub fn query<Q: Query>(&self) -> GraveyardQuery<'_, Q> {
+        GraveyardQuery {
+            borrow: self.world.query(),
+            origins: &self.origins,
+        }
+    }
+
+    /// Take ownership of the components of `entity`, at `index` in the living world's archetype
+    /// `archetype`, which must be removed from it without being dropped
+    pub(crate) unsafe fn bury(
+        &mut self,
+        entity: Entity,
+        archetype_id: u32,
+        archetype: &Archetype,
+        index: u32,
+    ) {
+        let i = archetype_id as usize;
+        if self.archetypes.len() <= i {
+            self.archetypes.resize(i + 1, None);
+        }
+        let target = match self.archetypes[i] {
+            Some(x) => x,
+            None => {
+                let x = self.world.archetype_for_types(archetype.types());
+                self.archetypes[i] = Some(x);
+                x
+            }
+        };
+        let corpse = self.world.adopt(target, archetype, index);
+        self.origins.insert(corpse.id(), entity);
+        self.corpses.insert(entity, corpse);
+    }
+
+    /// Dispose of every corpse's components through `sinks`
+    pub(crate) fn clear(&mut self, sinks: &mut RemovalSinks) {
+        if self.corpses.is_empty() {
+            return;
+        }
+        let origins = &self.origins;
+        let observed = !sinks.is_empty();
+        for archetype in self.world.archetypes_inner_mut() {
+            archetype.clear(
+                |_| observed,
+                |id, ty, ptr| unsafe { sinks.discard(origins[&id], ty, ptr) },
+            );
+        }
+        // Now only forgets the corpse entities
+        self.world.clear();
+        self.origins.clear();
+        self.corpses.clear();
+    }
+}
+
+/// A borrow of a `Graveyard` sufficient to execute the query `Q`
+///
+/// Like `QueryBorrow`, borrows are not released until this object is dropped.
+pub struct GraveyardQuery<'g, Q: Query> {
+    borrow: QueryBorrow<'g, Q>,
+    origins: &'g HashMap<u32, Entity>,
+}
+
+impl<'g, Q: Query> GraveyardQuery<'g, Q> {
+    /// Execute the query
+    ///
+    /// Must be called only once per query.
+    pub fn iter<'q>(&'q mut self) -> GraveyardIter<'q, 'g, Q> {
+        GraveyardIter {
+            inner: self.borrow.iter(),
+            origins: self.origins,
+        }
+    }
+}
+
+/// Iterator over the despawned entities with the components in `Q`
+pub struct GraveyardIter<'q, 'g, Q: Query> {
+    inner: QueryIter<'q, 'g, Q>,
+    origins: &'g HashMap<u32, Entity>,
+}
+
+impl<'q, 'g, Q: Query> Iterator for GraveyardIter<'q, 'g, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (corpse, item) = self.inner.next()?;
+        Some((self.origins[&corpse.id()], item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<Q: Query> ExactSizeIterator for GraveyardIter<'_, '_, Q> {}
@@ -0,0 +1,165 @@
+use crate::alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+
+use crate::archetype::Archetype;
+use crate::entities::Entities;
+use crate::{Component, ComponentError, Entity, MissingComponent, NoSuchEntity};
+
+/// Long-lived reference to a component of a specific entity
+///
+/// Obtained from `World::component_handle`. The world remembers where the component was last
+/// found, so that `World::get_by_handle` only needs to confirm that nothing has moved before
+/// borrowing it, rather than looking it up anew. Remains usable however the entity's components
+/// change, until the entity is despawned, loses the component, or the handle is released with
+/// `World::release_handle`.
+pub struct ComponentHandle<T: Component> {
+    entity: Entity,
+    slot: u32,
+    generation: u32,
+    /// Identifies the world that issued the handle
+    world: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> ComponentHandle<T> {
+    /// The entity whose component this refers to
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl<T: Component> Clone for ComponentHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Component> Copy for ComponentHandle<T> {}
+
+impl<T: Component> PartialEq for ComponentHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.slot == other.slot && self.generation == other.generation && self.world == other.world
+    }
+}
+
+impl<T: Component> Eq for ComponentHandle<T> {}
+
+impl<T: Component> fmt::Debug for ComponentHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentHandle")
+            .field("entity", &self.entity)
+            .field("slot", &self.slot)
+            .finish()
+    }
+}
+
+/// Where each live `ComponentHandle`'s component was last found
+pub(crate) struct Handles {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    /// Distinct from that of any other world's handles
+    world: u32,
+}
+
+impl Handles {
+    pub(crate) fn new(world: u32) -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            world,
+        }
+    }
+
+    fn check<T: Component>(&self, handle: ComponentHandle<T>) {
+        assert!(
+            handle.world == self.world,
+            "component handle used with a different world"
+        );
+    }
+
+    pub(crate) fn alloc<T: Component>(&mut self, entity: Entity) -> ComponentHandle<T> {
+        let slot = match self.free.pop() {
+            Some(x) => x,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    version: AtomicU64::new(0),
+                    index: AtomicU32::new(0),
+                    ptr: AtomicPtr::new(ptr::null_mut()),
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let x = &mut self.slots[slot as usize];
+        // Never matches a real archetype version, since those are multiples of 2^32 at first
+        *x.version.get_mut() = u64::MAX;
+        ComponentHandle {
+            entity,
+            slot,
+            generation: x.generation,
+            world: self.world,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns whether `handle` was live
+    pub(crate) fn free<T: Component>(&mut self, handle: ComponentHandle<T>) -> bool {
+        self.check(handle);
+        match self.slots.get_mut(handle.slot as usize) {
+            Some(x) if x.generation == handle.generation => {
+                x.generation = x.generation.wrapping_add(1);
+                self.free.push(handle.slot);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Locate the component referred to by `handle`, returning its archetype, index therein, and
+    /// address
+    pub(crate) fn resolve<'a, T: Component>(
+        &self,
+        handle: ComponentHandle<T>,
+        entities: &Entities,
+        archetypes: &'a [Archetype],
+    ) -> Result<(&'a Archetype, u32, NonNull<T>), ComponentError> {
+        self.check(handle);
+        let slot = match self.slots.get(handle.slot as usize) {
+            Some(x) if x.generation == handle.generation => x,
+            _ => return Err(NoSuchEntity.into()),
+        };
+        let loc = entities.get(handle.entity)?;
+        let archetype = &archetypes[loc.archetype as usize];
+        // Any writes to `index` and `ptr` made before `version` was set to the current version
+        // describe the current state of the world
+        if slot.version.load(Ordering::Acquire) == archetype.version()
+            && slot.index.load(Ordering::Relaxed) == loc.index
+        {
+            let ptr = slot.ptr.load(Ordering::Relaxed).cast::<T>();
+            return Ok((archetype, loc.index, unsafe { NonNull::new_unchecked(ptr) }));
+        }
+        let ptr = unsafe {
+            archetype
+                .get_at::<T>(loc.index)
+                .ok_or_else(MissingComponent::new::<T>)?
+                .as_ptr()
+        };
+        slot.ptr.store(ptr.cast(), Ordering::Relaxed);
+        slot.index.store(loc.index, Ordering::Relaxed);
+        slot.version.store(archetype.version(), Ordering::Release);
+        Ok((archetype, loc.index, unsafe { NonNull::new_unchecked(ptr) }))
+    }
+}
+
+struct Slot {
+    /// Incremented when the slot is freed, invalidating outstanding handles
+    generation: u32,
+    /// Version of the archetype the component was last found in
+    version: AtomicU64,
+    /// Index of the entity in that archetype
+    index: AtomicU32,
+    ptr: AtomicPtr<u8>,
+}
@@ -0,0 +1,88 @@
+use core::marker::PhantomData;
+
+use crate::query::Fetch;
+use crate::{ArchetypesGeneration, Entity, Query, World};
+
+/// Resumable operation despawning every entity that matches `Q`, a bounded amount at a time
+///
+/// Allows huge despawns to be spread across several frames rather than causing a hitch. `Q` is
+/// only used to select entities, so its components are never borrowed; `DespawnAll<()>` despawns
+/// every entity, like an incremental `World::clear`.
+///
+/// Entities spawned into already-processed archetypes after the operation began are not despawned,
+/// nor are entities spawned with `World::spawn_external`. A `DespawnAll` must only be used with a
+/// single `World`, and panics if used with another.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.spawn_batch((0..10).map(|i| (i,)));
+/// world.spawn(("not despawned",));
+/// let mut op = DespawnAll::<&i32>::new();
+/// assert!(!op.run_for(&mut world, 8));
+/// assert_eq!(world.iter().count(), 3);
+/// assert!(op.run_for(&mut world, 8));
+/// assert_eq!(world.iter().count(), 1);
+/// ```
+pub struct DespawnAll<Q: Query> {
+    /// The world's archetypes generation when the operation began
+    generation: Option<ArchetypesGeneration>,
+    /// Index of the first archetype that may still contain entities to despawn
+    archetype: u32,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<Q: Query> DespawnAll<Q> {
+    /// Prepare to despawn all entities matching `Q`
+    pub fn new() -> Self {
+        Self {
+            generation: None,
+            archetype: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Despawn at most `budget` entities, returning whether the operation is complete
+    pub fn run_for(&mut self, world: &mut World, mut budget: u32) -> bool {
+        world.flush_entities();
+        match self.generation {
+            Some(x) => assert!(
+                world.archetypes_extend(x),
+                "incremental despawn resumed on a different world"
+            ),
+            None => self.generation = Some(world.archetypes_generation()),
+        }
+        while let Some(entity) = self.next(world) {
+            if budget == 0 {
+                return false;
+            }
+            world.despawn(entity).unwrap();
+            budget -= 1;
+        }
+        true
+    }
+
+    /// Find the next entity to despawn, skipping archetypes that contain none
+    fn next(&mut self, world: &World) -> Option<Entity> {
+        loop {
+            let archetype = world.archetypes_inner().get(self.archetype as usize)?;
+            if archetype.is_empty()
+                || archetype.is_read_only()
+                || Q::Fetch::access(archetype).is_none()
+            {
+                self.archetype += 1;
+                continue;
+            }
+            // Taking the last entity avoids moving any others
+            let id = archetype.entity_id(archetype.len() - 1);
+            return Some(world.entities_meta()[id as usize].entity(id));
+        }
+    }
+}
+
+impl<Q: Query> Default for DespawnAll<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
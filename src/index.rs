@@ -0,0 +1,168 @@
+use crate::alloc::boxed::Box;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+use core::mem;
+
+use hashbrown::HashMap;
+
+use crate::{Archetype, Component, Entity};
+
+/// A secondary index over components of type `T`, kept in sync by the `World`
+///
+/// Register with `World::add_component_index`. The world then reports every `T` that is added to,
+/// replaced on, or removed from an entity, so structures like spatial hashes or bounding volume
+/// hierarchies over a position component never need to be rebuilt from scratch.
+///
+/// Changes made in place through `World::get_mut` or a query are not observed automatically; report
+/// them with `World::notify_changed`.
+pub trait ComponentIndex<T: Component>: Send + Sync + 'static {
+    /// `entity` gained `component`
+    fn insert(&mut self, entity: Entity, component: &T);
+    /// `entity` is about to lose `component`
+    fn remove(&mut self, entity: Entity, component: &T);
+    /// `entity`'s existing component was replaced or modified, and now holds `component`
+    fn change(&mut self, entity: Entity, component: &T);
+}
+
+/// Type-erased `ComponentIndex` storage
+#[derive(Default)]
+pub(crate) struct Indices {
+    indices: HashMap<TypeId, Box<dyn ErasedIndex>>,
+}
+
+impl Indices {
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn add<T: Component, I: ComponentIndex<T>>(&mut self, index: I) {
+        self.indices.insert(
+            TypeId::of::<T>(),
+            Box::new(Typed::<T, I>(index, PhantomData)),
+        );
+    }
+
+    pub fn get<T: Component, I: ComponentIndex<T>>(&self) -> Option<&I> {
+        let x = self.indices.get(&TypeId::of::<T>())?;
+        x.as_any().downcast_ref::<Typed<T, I>>().map(|x| &x.0)
+    }
+
+    pub fn get_mut<T: Component, I: ComponentIndex<T>>(&mut self) -> Option<&mut I> {
+        let x = self.indices.get_mut(&TypeId::of::<T>())?;
+        x.as_any_mut()
+            .downcast_mut::<Typed<T, I>>()
+            .map(|x| &mut x.0)
+    }
+
+    pub fn remove<T: Component>(&mut self) -> bool {
+        self.indices.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Report that the `ty` component at `index` in `archetype` was added to `entity`
+    ///
+    /// # Safety
+    /// `index` must be in-bounds for `archetype` and refer to `entity`
+    pub unsafe fn inserted(
+        &mut self,
+        ty: TypeId,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+    ) {
+        if let Some(x) = self.indices.get_mut(&ty) {
+            x.notify(Event::Insert, entity, archetype, index);
+        }
+    }
+
+    /// # Safety
+    /// As for `inserted`
+    pub unsafe fn removed(
+        &mut self,
+        ty: TypeId,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+    ) {
+        if let Some(x) = self.indices.get_mut(&ty) {
+            x.notify(Event::Remove, entity, archetype, index);
+        }
+    }
+
+    /// # Safety
+    /// As for `inserted`
+    pub unsafe fn changed(
+        &mut self,
+        ty: TypeId,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+    ) {
+        if let Some(x) = self.indices.get_mut(&ty) {
+            x.notify(Event::Change, entity, archetype, index);
+        }
+    }
+
+    /// Report that all of `entity`'s components were added
+    ///
+    /// # Safety
+    /// As for `inserted`
+    pub unsafe fn inserted_all(&mut self, entity: Entity, archetype: &Archetype, index: u32) {
+        if self.is_empty() {
+            return;
+        }
+        for ty in archetype.types() {
+            self.inserted(ty.id(), entity, archetype, index);
+        }
+    }
+
+    /// Report that all of `entity`'s components are about to be removed
+    ///
+    /// # Safety
+    /// As for `inserted`
+    pub unsafe fn removed_all(&mut self, entity: Entity, archetype: &Archetype, index: u32) {
+        if self.is_empty() {
+            return;
+        }
+        for ty in archetype.types() {
+            self.removed(ty.id(), entity, archetype, index);
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Event {
+    Insert,
+    Remove,
+    Change,
+}
+
+trait ErasedIndex: Send + Sync {
+    unsafe fn notify(&mut self, event: Event, entity: Entity, archetype: &Archetype, index: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Typed<T, I>(I, PhantomData<fn(T)>);
+
+impl<T: Component, I: ComponentIndex<T>> ErasedIndex for Typed<T, I> {
+    unsafe fn notify(&mut self, event: Event, entity: Entity, archetype: &Archetype, index: u32) {
+        let component = &*archetype
+            .get_dynamic(TypeId::of::<T>(), mem::size_of::<T>(), index)
+            .unwrap()
+            .as_ptr()
+            .cast::<T>();
+        match event {
+            Event::Insert => self.0.insert(entity, component),
+            Event::Remove => self.0.remove(entity, component),
+            Event::Change => self.0.change(entity, component),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,160 @@
+use core::marker::PhantomData;
+
+use crate::archetype::Archetype;
+use crate::entities::EntityMeta;
+use crate::frame::FrameAllocator;
+use crate::query::{Fetch, QueryTicks};
+use crate::{Access, Component, Entity, Query, QueryBorrow, QueryIter};
+
+/// A borrow of a `World` sufficient to execute the query `Q` on entities having a `K`, alongside
+/// the query `R` on the entity each `K` refers to
+///
+/// Constructed with `World::join`. Like `QueryBorrow`, borrows are not released until this object
+/// is dropped.
+pub struct JoinBorrow<'w, K: Component, Q: Query, R: Query, F> {
+    source: QueryBorrow<'w, (&'w K, Q)>,
+    target: Target<'w, R>,
+    key: F,
+}
+
+impl<'w, K, Q, R, F> JoinBorrow<'w, K, Q, R, F>
+where
+    K: Component,
+    Q: Query,
+    R: Query,
+    F: Fn(&K) -> Entity,
+{
+    pub(crate) fn new(
+        meta: &'w [EntityMeta],
+        archetypes: &'w [Archetype],
+        frame: &'w FrameAllocator,
+        tick: u32,
+        key: F,
+    ) -> Self {
+        Self {
+            source: QueryBorrow::new(meta, archetypes, frame, tick),
+            target: Target {
+                meta,
+                archetypes,
+                borrowed: false,
+                ticks: QueryTicks::new(tick),
+                _marker: PhantomData,
+            },
+            key,
+        }
+    }
+
+    /// Execute the query
+    ///
+    /// Must be called only once per join.
+    ///
+    /// Panics if `R` would uniquely borrow any component, since several entities may refer to the
+    /// same target, or if `Q` and `R` borrow the same component and either borrow is unique.
+    pub fn iter<'q>(&'q mut self) -> JoinIter<'q, 'w, K, Q, R, F> {
+        self.target.borrow();
+        JoinIter {
+            source: self.source.iter(),
+            target: &self.target,
+            key: &self.key,
+        }
+    }
+}
+
+/// Shared state for looking up `R` on arbitrary entities
+struct Target<'w, R: Query> {
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    borrowed: bool,
+    ticks: QueryTicks,
+    _marker: PhantomData<R>,
+}
+
+impl<'w, R: Query> Target<'w, R> {
+    fn borrow(&mut self) {
+        if self.borrowed {
+            panic!(
+                "called JoinBorrow::iter twice on the same borrow; construct a new join instead"
+            );
+        }
+        for x in self.archetypes {
+            match R::Fetch::access(x) {
+                Some(Access::Write) => panic!("joined queries must not write components"),
+                Some(Access::Read) => R::Fetch::borrow(x),
+                _ => {}
+            }
+        }
+        self.borrowed = true;
+    }
+
+    fn get<'q>(&'q self, entity: Entity) -> Option<<R::Fetch as Fetch<'q>>::Item> {
+        let meta = self.meta.get(entity.id as usize)?;
+        if meta.generation != entity.generation {
+            return None;
+        }
+        let archetype = &self.archetypes[meta.location.archetype as usize];
+        if meta.location.index >= archetype.len() {
+            // Reserved, but not yet flushed
+            return None;
+        }
+        unsafe {
+            let mut fetch = R::Fetch::get(archetype, meta.location.index as usize, self.ticks)?;
+            Some(fetch.next())
+        }
+    }
+}
+
+impl<R: Query> Drop for Target<'_, R> {
+    fn drop(&mut self) {
+        if self.borrowed {
+            for x in self.archetypes {
+                if R::Fetch::access(x) >= Some(Access::Read) {
+                    R::Fetch::release(x);
+                }
+            }
+        }
+    }
+}
+
+unsafe impl<K: Component, Q: Query, R: Query, F: Send> Send for JoinBorrow<'_, K, Q, R, F> {}
+unsafe impl<K: Component, Q: Query, R: Query, F: Sync> Sync for JoinBorrow<'_, K, Q, R, F> {}
+
+/// Iterator over entities with the components in `Q` and a `K`, and the result of `R` for the
+/// entity that `K` refers to, if it exists and satisfies `R`
+pub struct JoinIter<'q, 'w, K: Component, Q: Query, R: Query, F> {
+    source: QueryIter<'q, 'w, (&'w K, Q)>,
+    target: &'q Target<'w, R>,
+    key: &'q F,
+}
+
+impl<'q, 'w, K, Q, R, F> Iterator for JoinIter<'q, 'w, K, Q, R, F>
+where
+    K: Component,
+    Q: Query,
+    R: Query,
+    F: Fn(&K) -> Entity,
+{
+    type Item = (
+        Entity,
+        <Q::Fetch as Fetch<'q>>::Item,
+        Option<<R::Fetch as Fetch<'q>>::Item>,
+    );
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entity, (key, item)) = self.source.next()?;
+        Some((entity, item, self.target.get((self.key)(key))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<K, Q, R, F> ExactSizeIterator for JoinIter<'_, '_, K, Q, R, F>
+where
+    K: Component,
+    Q: Query,
+    R: Query,
+    F: Fn(&K) -> Entity,
+{
+}
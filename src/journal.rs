@@ -0,0 +1,294 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+
+use hashbrown::HashMap;
+
+use crate::entities::EntityMeta;
+use crate::query::QueryTicks;
+use crate::{Archetype, Component, Entity, EntityBuilder, World};
+
+/// A log of the structural changes made to a `World`, which can be replayed onto another
+///
+/// Start recording with `World::start_journal`. Every spawn, despawn, and insertion or removal of
+/// components is logged in order, along with copies of the inserted components, so replaying the
+/// journal with `World::replay` onto an empty world reproduces the recorded world exactly,
+/// including `Entity` handles. Useful for reproducing bugs from a player's session, or for
+/// deterministic integration tests.
+///
+/// Components modified in place, e.g. through `get_mut` or a query, are only logged for types
+/// registered with `track_writes`, and only when the world is next flushed. Each such component
+/// whose change tick advanced is logged as if it had been inserted again, including those that
+/// were just added. Tags, indices, and other auxiliary state are not logged.
+///
+/// Every component type present in a journaled world must be registered.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut journal = Journal::new();
+/// journal.register::<bool>().track_writes::<i32>();
+/// let mut world = World::new();
+/// world.start_journal(journal);
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456,));
+/// world.despawn(b).unwrap();
+/// *world.get_mut::<i32>(a).unwrap() = 789;
+/// world.flush();
+/// let journal = world.take_journal().unwrap();
+///
+/// let mut replica = World::new();
+/// replica.replay(&journal);
+/// assert_eq!(*replica.get::<i32>(a).unwrap(), 789);
+/// assert!(!replica.contains(b));
+/// ```
+#[derive(Default)]
+pub struct Journal {
+    types: HashMap<TypeId, JournalType>,
+    ops: Vec<Op>,
+    /// Writes made at or after this tick have yet to be logged
+    since: u32,
+}
+
+impl Journal {
+    /// Create an empty journal that supports no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Support journaling worlds containing `T` components
+    pub fn register<T: Component + Clone>(&mut self) -> &mut Self {
+        self.types
+            .entry(TypeId::of::<T>())
+            .or_insert_with(JournalType::of::<T>);
+        self
+    }
+
+    /// Like `register`, but also log `T` components modified in place
+    pub fn track_writes<T: Component + Clone>(&mut self) -> &mut Self {
+        self.register::<T>();
+        self.types.get_mut(&TypeId::of::<T>()).unwrap().track_writes = true;
+        self
+    }
+
+    /// Number of operations logged
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Whether no operations have been logged
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Discard every logged operation, keeping registrations
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// Log that `entity`, stored at `index` in `archetype`, was spawned
+    pub(crate) fn spawned(&mut self, entity: Entity, archetype: &Archetype, index: u32) {
+        let values = self.values(archetype, index, |_| true);
+        self.ops.push(Op::Spawn(entity, values));
+    }
+
+    /// Log that `entity`, now stored at `index` in `archetype`, gained or replaced its `types`
+    /// components
+    pub(crate) fn inserted(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        types: &[TypeId],
+    ) {
+        let values = self.values(archetype, index, |ty| types.contains(&ty));
+        self.ops.push(Op::Insert(entity, values));
+    }
+
+    /// Log that `entity` lost its `types` components
+    pub(crate) fn removed(&mut self, entity: Entity, types: impl IntoIterator<Item = TypeId>) {
+        self.ops
+            .push(Op::Remove(entity, types.into_iter().collect()));
+    }
+
+    /// Log that `entity` was despawned
+    pub(crate) fn despawned(&mut self, entity: Entity) {
+        self.ops.push(Op::Despawn(entity));
+    }
+
+    /// Log every component of a `track_writes` type that changed since the last call, then start
+    /// watching from `now`
+    pub(crate) fn record_writes(
+        &mut self,
+        archetypes: &[Archetype],
+        meta: &[EntityMeta],
+        now: u32,
+    ) {
+        let ticks = QueryTicks {
+            since: self.since,
+            now,
+        };
+        self.since = now;
+        for archetype in archetypes {
+            for ty in archetype.types() {
+                if !self.types.get(&ty.id()).is_some_and(|x| x.track_writes) {
+                    continue;
+                }
+                for index in 0..archetype.len() {
+                    let changed = archetype.component_ticks(ty.id(), index).unwrap().changed;
+                    if !ticks.is_new(changed) {
+                        continue;
+                    }
+                    let id = archetype.entity_id(index);
+                    let entity = meta[id as usize].entity(id);
+                    let values = self.values(archetype, index, |x| x == ty.id());
+                    self.ops.push(Op::Insert(entity, values));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn set_since(&mut self, since: u32) {
+        self.since = since;
+    }
+
+    /// Copy the components at `index` in `archetype` whose types satisfy `filter`
+    fn values(
+        &self,
+        archetype: &Archetype,
+        index: u32,
+        mut filter: impl FnMut(TypeId) -> bool,
+    ) -> Vec<Value> {
+        archetype
+            .types()
+            .iter()
+            .filter(|ty| filter(ty.id()))
+            .map(|ty| {
+                let journal_type = self
+                    .types
+                    .get(&ty.id())
+                    .expect("journaled a world containing an unregistered component type");
+                let data = unsafe {
+                    let ptr = archetype
+                        .get_dynamic(ty.id(), ty.layout().size(), index)
+                        .unwrap();
+                    (journal_type.clone)(ptr.as_ptr())
+                };
+                Value {
+                    add: journal_type.add,
+                    data,
+                }
+            })
+            .collect()
+    }
+}
+
+impl World {
+    /// Log every subsequent structural change to this world in `journal`
+    ///
+    /// Entities that already exist are logged as if they had just been spawned, so the journal can
+    /// always be replayed onto an empty world. Replaces any journal already being recorded.
+    ///
+    /// Panics if the world contains a component type that isn't registered with `journal`.
+    pub fn start_journal(&mut self, journal: Journal) {
+        self.flush_entities();
+        let mut journal = Box::new(journal);
+        journal.set_since(self.increment_change_tick());
+        for archetype in self.archetypes_inner() {
+            for index in 0..archetype.len() {
+                let id = archetype.entity_id(index);
+                let entity = self.entities_meta()[id as usize].entity(id);
+                journal.spawned(entity, archetype, index);
+            }
+        }
+        self.set_journal(Some(journal));
+    }
+
+    /// Stop recording, returning the journal being recorded, if any
+    ///
+    /// Writes to components of types registered with `Journal::track_writes` made since the last
+    /// `flush` are logged first.
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.record_journal_writes();
+        self.set_journal(None).map(|x| *x)
+    }
+
+    /// The journal being recorded, if any
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal_inner()
+    }
+
+    /// Apply every operation logged in `journal` to this world, in order
+    ///
+    /// To reproduce the journaled world, this world should be empty, or in the state the journaled
+    /// world was in when the journal was started with no entities in it.
+    ///
+    /// Panics if an operation can't be applied, as happens when this world is in a different state.
+    pub fn replay(&mut self, journal: &Journal) {
+        let mut builder = EntityBuilder::new();
+        for op in &journal.ops {
+            match *op {
+                Op::Spawn(entity, ref values) => {
+                    for value in values {
+                        (value.add)(&*value.data, &mut builder);
+                    }
+                    self.spawn_at(entity, builder.build())
+                        .expect("replayed spawn onto a world in a different state");
+                }
+                Op::Insert(entity, ref values) => {
+                    for value in values {
+                        (value.add)(&*value.data, &mut builder);
+                    }
+                    self.insert(entity, builder.build())
+                        .expect("replayed insert onto a world in a different state");
+                }
+                Op::Remove(entity, ref types) => {
+                    self.insert_and_remove(entity, (), types)
+                        .expect("replayed remove onto a world in a different state");
+                }
+                Op::Despawn(entity) => {
+                    self.despawn(entity)
+                        .expect("replayed despawn onto a world in a different state");
+                }
+            }
+        }
+    }
+}
+
+enum Op {
+    Spawn(Entity, Vec<Value>),
+    Despawn(Entity),
+    /// Components added or replaced
+    Insert(Entity, Vec<Value>),
+    Remove(Entity, Vec<TypeId>),
+}
+
+/// A copy of a component
+struct Value {
+    add: fn(&dyn Any, &mut EntityBuilder),
+    data: Box<dyn Any + Send + Sync>,
+}
+
+struct JournalType {
+    clone: unsafe fn(*const u8) -> Box<dyn Any + Send + Sync>,
+    add: fn(&dyn Any, &mut EntityBuilder),
+    track_writes: bool,
+}
+
+impl JournalType {
+    fn of<T: Component + Clone>() -> Self {
+        Self {
+            clone: clone_component::<T>,
+            add: add_component::<T>,
+            track_writes: false,
+        }
+    }
+}
+
+unsafe fn clone_component<T: Component + Clone>(ptr: *const u8) -> Box<dyn Any + Send + Sync> {
+    Box::new((*ptr.cast::<T>()).clone())
+}
+
+fn add_component<T: Component + Clone>(value: &dyn Any, builder: &mut EntityBuilder) {
+    builder.add(value.downcast_ref::<T>().unwrap().clone());
+}
@@ -63,31 +63,109 @@ macro_rules! smaller_tuples_too {
 }
 
 mod archetype;
+mod archetype_view;
+mod arena;
 mod borrow;
 mod bundle;
+mod cached_query;
+mod clone;
+mod command;
+mod compress;
+mod conflict;
+mod dense_map;
+mod double_buffer;
 mod entities;
 mod entity_builder;
+mod export;
+mod frame;
+mod graveyard;
+mod handle;
+mod incremental;
+mod index;
+mod join;
+mod journal;
+mod mirror;
+mod modification;
+mod name;
+mod observer;
+#[cfg(feature = "plugin")]
+mod plugin;
+mod prepared_query;
 mod query;
 mod query_one;
+mod registry;
+mod removal;
+mod session;
+mod shared;
+mod snapshot;
+#[cfg(feature = "stats")]
+mod stats;
+mod tag;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod weak;
 mod world;
 
-pub use archetype::Archetype;
-pub use borrow::{EntityRef, Ref, RefMut};
-pub use bundle::{Bundle, DynamicBundle, MissingComponent};
-pub use entities::{Entity, NoSuchEntity};
+pub use archetype::{Archetype, ColumnOrder, GarbageStats, TypeInfo};
+pub use archetype_view::TypedArchetypeView;
+pub use arena::ColumnArena;
+pub use borrow::{BorrowError, BorrowPolicy, BorrowState, EntityRef, Ref, RefMut};
+pub use bundle::{Bundle, DynamicBundle, MissingComponent, TryBundle, TryComponent};
+pub use cached_query::CachedQuery;
+pub use clone::CloneRegistry;
+pub use command::{Command, CommandError, CommandRegistry};
+pub use compress::{ColumnCompressors, DecompressError};
+pub use conflict::{access_conflicts, access_conflicts_in, ConflictInfo, QueryAccess};
+pub use dense_map::DenseMap;
+pub use double_buffer::Previous;
+pub use entities::{
+    Entity, EntityAllocator, EntitySlot, Generation, NoSuchEntity, ReserveEntitiesIter,
+    ALLOCATOR_ID_LIMIT,
+};
 pub use entity_builder::{BuiltEntity, EntityBuilder};
-pub use query::{Access, BatchedIter, Query, QueryBorrow, QueryIter, With, Without};
+pub use export::{ColumnExporter, ColumnValue, ExportedColumns};
+pub use frame::FrameAllocator;
+pub use graveyard::{Graveyard, GraveyardIter, GraveyardQuery};
+pub use handle::ComponentHandle;
+pub use incremental::DespawnAll;
+pub use index::ComponentIndex;
+pub use join::{JoinBorrow, JoinIter};
+pub use journal::Journal;
+pub use mirror::WorldMirror;
+pub use modification::{EntityMut, Modification, SpawnBuilder};
+pub use observer::Observe;
+#[cfg(feature = "plugin")]
+pub use plugin::{PluginComponent, PluginHost, QueryTerm, WorldVTable, PLUGIN_ABI_VERSION};
+pub use prepared_query::{
+    PreparedQuery, PreparedQueryBorrow, PreparedQueryIter, QueryCursor, QueryStats,
+};
+pub use query::{
+    Access, Added, BatchedIter, Changed, CloneQuery, ClonedIter, ColumnIter, GroupedIter, Query,
+    QueryBorrow, QueryIter, QueryReadHalf, QueryReadIter, TickFlags, With, Without,
+};
 pub use query_one::QueryOne;
-pub use world::{ArchetypesGeneration, Component, ComponentError, Iter, SpawnBatchIter, World};
+pub use registry::{GlobalEntity, WorldId, WorldRegistry};
+pub use removal::RemovalSink;
+pub use session::{Session, SystemContext};
+pub use shared::Shared;
+pub use snapshot::{Snapshot, Snapshotter};
+#[cfg(feature = "stats")]
+pub use stats::AccessStats;
+pub use tag::Tag;
+pub use weak::WeakEntity;
+pub use world::{
+    ArchetypesGeneration, Component, ComponentError, EntityMap, Iter, QuotaExceeded, SpawnAtError,
+    SpawnBatchIter, World, WorldError, WorldUid,
+};
 
 // Unstable implementation details needed by the macros
 #[doc(hidden)]
-pub use archetype::TypeInfo;
+pub use command::{__commands, __component_names, __component_values};
 #[cfg(feature = "macros")]
 #[doc(hidden)]
 pub use lazy_static;
 #[doc(hidden)]
-pub use query::Fetch;
+pub use query::{Fetch, QueryTicks};
 
 #[cfg(feature = "macros")]
 pub use hecs_macros::Bundle;
@@ -0,0 +1,245 @@
+use core::any::TypeId;
+
+use crate::alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::archetype::Archetype;
+use crate::query::QueryTicks;
+use crate::{Component, Entity, EntityBuilder, World};
+
+/// Replicates the entities of one `World` into another
+///
+/// Supports the common pattern of separating a simulation world from a presentation world. Each
+/// call to `sync` spawns a counterpart for every new source entity, despawns counterparts of
+/// source entities that no longer exist, and copies every registered component that was added or
+/// changed since the previous `sync`. Unregistered component types are never copied, so the target
+/// world is free to carry its own additional components (e.g. render handles) on mirrored
+/// entities.
+///
+/// Work is driven by the source world's change tracking: archetypes whose entities and registered
+/// columns are untouched since the last `sync` are skipped outright, and only components whose
+/// change ticks are newer than the last `sync` are copied.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut sim = World::new();
+/// let mut render = World::new();
+/// let mut mirror = WorldMirror::new();
+/// mirror.register::<i32>();
+///
+/// let a = sim.spawn((42, "not mirrored"));
+/// mirror.sync(&sim, &mut render);
+/// let a_render = mirror.get(a).unwrap();
+/// assert_eq!(*render.get::<i32>(a_render).unwrap(), 42);
+/// assert!(render.get::<&str>(a_render).is_err());
+///
+/// sim.despawn(a).unwrap();
+/// mirror.sync(&sim, &mut render);
+/// assert!(!render.contains(a_render));
+/// ```
+#[derive(Default)]
+pub struct WorldMirror {
+    map: HashMap<Entity, Entity>,
+    types: Vec<MirrorType>,
+    /// Source change tick as of the last `sync`
+    since: u32,
+    /// Source entity epoch as of the last `sync`, if any
+    epoch: Option<u64>,
+    /// State of each source archetype as of the last `sync`, by archetype index
+    seen: Vec<SeenArchetype>,
+}
+
+impl WorldMirror {
+    /// Create a mirror that copies no components and has not yet mirrored any entities
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy `T` components from the source world on each `sync`
+    ///
+    /// A mirrored entity's `T` is removed from the target world when the source entity loses it.
+    /// Entities mirrored before `T` was registered receive their `T` on the next `sync`.
+    pub fn register<T: Component + Clone>(&mut self) -> &mut Self {
+        let id = TypeId::of::<T>();
+        if self.types.iter().all(|x| x.id != id) {
+            self.types.push(MirrorType::of::<T>());
+            // Revisit every entity so existing counterparts pick up the new type
+            self.seen.clear();
+        }
+        self
+    }
+
+    /// Bring `target` up to date with `source`
+    ///
+    /// Cost is proportional to the number of source entities that were spawned, despawned, or had
+    /// registered components added, removed, or modified since the last `sync`, rather than to the
+    /// size of `source`. New counterparts are spawned with all their registered components at
+    /// once. Counterparts despawned from `target` by other means are respawned the next time their
+    /// source entity changes.
+    ///
+    /// Panics if a registered component is borrowed in either world.
+    pub fn sync(&mut self, source: &World, target: &mut World) {
+        let ticks = QueryTicks {
+            since: self.since,
+            now: source.increment_change_tick(),
+        };
+        self.since = ticks.now;
+
+        let epoch = source.entities_inner().epoch();
+        if self.epoch != Some(epoch) {
+            self.epoch = Some(epoch);
+            self.map.retain(|&src, &mut dst| {
+                if source.contains(src) {
+                    return true;
+                }
+                let _ = target.despawn(dst);
+                false
+            });
+        }
+
+        let archetypes = source.archetypes_inner();
+        let entities = source.entities_inner();
+        self.seen
+            .resize_with(archetypes.len(), SeenArchetype::default);
+        let mut builder = EntityBuilder::new();
+        let mut rows = (0..self.types.len())
+            .map(|_| Vec::new())
+            .collect::<Vec<_>>();
+        let mut dirty = Vec::with_capacity(self.types.len());
+        for (archetype, seen) in archetypes.iter().zip(&mut self.seen) {
+            let structural = seen.version != Some(archetype.version());
+            seen.version = Some(archetype.version());
+            seen.columns.resize(self.types.len(), None);
+            dirty.clear();
+            for (ty, column) in self.types.iter().zip(&mut seen.columns) {
+                let version = archetype.column_version(ty.id);
+                dirty.push(structural || *column != version);
+                *column = version;
+            }
+            if !dirty.contains(&true) {
+                continue;
+            }
+
+            for index in 0..archetype.len() {
+                let id = archetype.entity_id(index);
+                let src = entities.meta[id as usize].entity(id);
+                let dst = match self.map.get(&src) {
+                    Some(&dst) if target.contains(dst) => dst,
+                    // New, or despawned from the target by someone else
+                    _ => {
+                        for ty in &self.types {
+                            if archetype.has_dynamic(ty.id) {
+                                (ty.add)(archetype, index, &mut builder);
+                            }
+                        }
+                        let dst = target.spawn(builder.build());
+                        self.map.insert(src, dst);
+                        continue;
+                    }
+                };
+                for ((ty, rows), &dirty) in self.types.iter().zip(&mut rows).zip(&dirty) {
+                    if !dirty {
+                        continue;
+                    }
+                    match archetype.component_ticks(ty.id, index) {
+                        Some(component)
+                            if ticks.is_new(component.changed)
+                                || (structural && !(ty.present)(target, dst)) =>
+                        {
+                            rows.push((index, dst));
+                        }
+                        Some(_) => {}
+                        // Entities only lose components by moving to another archetype
+                        None if structural => (ty.remove)(target, dst),
+                        None => {}
+                    }
+                }
+            }
+
+            for (ty, rows) in self.types.iter().zip(&mut rows) {
+                if !rows.is_empty() {
+                    (ty.copy)(archetype, rows, target);
+                    rows.clear();
+                }
+            }
+        }
+    }
+
+    /// The counterpart of the source entity `entity` in the target world, if it has been mirrored
+    pub fn get(&self, entity: Entity) -> Option<Entity> {
+        self.map.get(&entity).copied()
+    }
+
+    /// Forget all mirrored entities without despawning them from the target world
+    ///
+    /// The next `sync` will spawn fresh counterparts for every source entity.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.epoch = None;
+        self.seen.clear();
+    }
+}
+
+/// Type-erased operations on a registered component type
+struct MirrorType {
+    id: TypeId,
+    /// Add a clone of the component at an index to a builder
+    add: fn(&Archetype, u32, &mut EntityBuilder),
+    /// Write clones of the components at the given indices to their counterparts
+    copy: fn(&Archetype, &[(u32, Entity)], &mut World),
+    /// Whether a counterpart has the component
+    present: fn(&World, Entity) -> bool,
+    /// Remove the component from a counterpart, if present
+    remove: fn(&mut World, Entity),
+}
+
+impl MirrorType {
+    fn of<T: Component + Clone>() -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            add: add_component::<T>,
+            copy: copy_components::<T>,
+            present: |world, entity| world.entity(entity).is_ok_and(|x| x.has::<T>()),
+            remove: |world, entity| {
+                let _ = world.remove_one::<T>(entity);
+            },
+        }
+    }
+}
+
+fn add_component<T: Component + Clone>(
+    archetype: &Archetype,
+    index: u32,
+    builder: &mut EntityBuilder,
+) {
+    archetype.with_column::<T, _>(|column| {
+        builder.add(column[index as usize].clone());
+    });
+}
+
+fn copy_components<T: Component + Clone>(
+    archetype: &Archetype,
+    rows: &[(u32, Entity)],
+    target: &mut World,
+) {
+    archetype.with_column::<T, _>(|column| {
+        for &(index, dst) in rows {
+            let value = column[index as usize].clone();
+            if let Ok(mut x) = target.get_mut::<T>(dst) {
+                *x = value;
+                continue;
+            }
+            target.insert_one(dst, value).unwrap();
+        }
+    });
+}
+
+/// What a `WorldMirror` last observed of a source archetype
+#[derive(Default)]
+struct SeenArchetype {
+    version: Option<u64>,
+    /// Column version of each registered type, in registration order
+    columns: Vec<Option<u32>>,
+}
@@ -0,0 +1,150 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::{Component, Entity, EntityBuilder, NoSuchEntity, World};
+
+/// A batch of component insertions and removals for one entity, built by `World::modify`
+///
+/// Nothing happens to the entity until `commit` is called. Dropping a `Modification` without
+/// committing it discards the queued changes.
+pub struct Modification<'a> {
+    world: &'a mut World,
+    entity: Entity,
+    inserted: EntityBuilder,
+    removed: Vec<TypeId>,
+}
+
+impl<'a> Modification<'a> {
+    pub(crate) fn new(world: &'a mut World, entity: Entity) -> Self {
+        Self {
+            world,
+            entity,
+            inserted: EntityBuilder::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Add `component` to the entity, replacing any existing `T`
+    ///
+    /// Supersedes any previously queued insertion or removal of `T`.
+    pub fn insert_one<T: Component>(&mut self, component: T) -> &mut Self {
+        let id = TypeId::of::<T>();
+        self.removed.retain(|&x| x != id);
+        self.inserted.replace(component);
+        self
+    }
+
+    /// Remove the entity's `T`, if it has one
+    ///
+    /// Supersedes any previously queued insertion or removal of `T`.
+    pub fn remove_one<T: Component>(&mut self) -> &mut Self {
+        let id = TypeId::of::<T>();
+        self.inserted.remove_dynamic(id);
+        if !self.removed.contains(&id) {
+            self.removed.push(id);
+        }
+        self
+    }
+
+    /// Apply all queued changes, moving the entity to its new archetype at most once
+    ///
+    /// Removed components are dropped. Unlike `World::remove_one`, removing a component the entity
+    /// doesn't have is not an error.
+    pub fn commit(mut self) -> Result<(), NoSuchEntity> {
+        self.apply()
+    }
+
+    fn apply(&mut self) -> Result<(), NoSuchEntity> {
+        let result =
+            self.world
+                .insert_and_remove(self.entity, self.inserted.build(), &self.removed);
+        self.removed.clear();
+        result
+    }
+}
+
+/// A newly spawned entity whose components can be changed cheaply, returned by `World::spawn_mut`
+///
+/// Changes are queued, then applied in a single archetype move when the `EntityMut` is dropped.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let e = world.spawn_mut((123, "abc")).insert(true).remove::<&str>().id();
+/// assert_eq!(*world.get::<i32>(e).unwrap(), 123);
+/// assert!(*world.get::<bool>(e).unwrap());
+/// assert!(world.get::<&str>(e).is_err());
+/// ```
+pub struct EntityMut<'a>(Modification<'a>);
+
+impl<'a> EntityMut<'a> {
+    pub(crate) fn new(world: &'a mut World, entity: Entity) -> Self {
+        Self(Modification::new(world, entity))
+    }
+
+    /// The entity being built
+    pub fn id(&self) -> Entity {
+        self.0.entity
+    }
+
+    /// Add `component` to the entity, replacing any existing `T`
+    pub fn insert<T: Component>(&mut self, component: T) -> &mut Self {
+        self.0.insert_one(component);
+        self
+    }
+
+    /// Remove the entity's `T`, if it has one
+    pub fn remove<T: Component>(&mut self) -> &mut Self {
+        self.0.remove_one::<T>();
+        self
+    }
+}
+
+impl Drop for EntityMut<'_> {
+    fn drop(&mut self) {
+        // The entity can't have been despawned while we held a unique borrow of the world
+        self.0.apply().unwrap();
+    }
+}
+
+/// Components for a new entity, accumulated before it's spawned, returned by `World::build_entity`
+///
+/// The entity is created directly in the archetype for its final set of components, unlike a
+/// `spawn` followed by `insert`s, which moves it through every intermediate archetype. Dropping a
+/// `SpawnBuilder` without calling `spawn` discards the components.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let e = world.build_entity().add(123).add("abc").add(true).spawn();
+/// assert_eq!(*world.get::<i32>(e).unwrap(), 123);
+/// assert_eq!(*world.get::<&str>(e).unwrap(), "abc");
+/// assert!(*world.get::<bool>(e).unwrap());
+/// ```
+pub struct SpawnBuilder<'a> {
+    world: &'a mut World,
+    components: EntityBuilder,
+}
+
+impl<'a> SpawnBuilder<'a> {
+    pub(crate) fn new(world: &'a mut World) -> Self {
+        Self {
+            world,
+            components: EntityBuilder::new(),
+        }
+    }
+
+    /// Add `component` to the entity, replacing any previously added `T`
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<T: Component>(mut self, component: T) -> Self {
+        self.components.replace(component);
+        self
+    }
+
+    /// Create the entity with all added components
+    pub fn spawn(mut self) -> Entity {
+        self.world.spawn(self.components.build())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::alloc::boxed::Box;
+
+use hashbrown::HashMap;
+
+use crate::Entity;
+
+/// Unique names of entities, in both directions
+#[derive(Default, Clone)]
+pub(crate) struct Names {
+    by_name: HashMap<Box<str>, Entity>,
+    by_entity: HashMap<Entity, Box<str>>,
+}
+
+impl Names {
+    /// Give `entity` `name`, replacing its previous name, and returning the entity that had it
+    pub fn insert(&mut self, entity: Entity, name: &str) -> Option<Entity> {
+        let previous = self.by_name.insert(name.into(), entity);
+        if let Some(previous) = previous {
+            self.by_entity.remove(&previous);
+        }
+        if let Some(old) = self.by_entity.insert(entity, name.into()) {
+            if *old != *name {
+                self.by_name.remove(&old);
+            }
+        }
+        previous.filter(|&x| x != entity)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name(&self, entity: Entity) -> Option<&str> {
+        self.by_entity.get(&entity).map(|x| &**x)
+    }
+
+    /// Forget `entity`'s name, returning whether it had one
+    pub fn despawned(&mut self, entity: Entity) -> bool {
+        match self.by_entity.remove(&entity) {
+            Some(name) => {
+                self.by_name.remove(&name);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move `old`'s name to `new`
+    pub fn replaced(&mut self, old: Entity, new: Entity) {
+        if let Some(name) = self.by_entity.remove(&old) {
+            self.by_name.insert(name.clone(), new);
+            self.by_entity.insert(new, name);
+        }
+    }
+
+    /// Iterate over every name and the entity having it
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.by_name.iter().map(|(name, &entity)| (&**name, entity))
+    }
+
+    pub fn clear(&mut self) {
+        self.by_name.clear();
+        self.by_entity.clear();
+    }
+}
@@ -0,0 +1,53 @@
+use crate::alloc::boxed::Box;
+
+use crate::{Added, Changed, Component, Entity, Fetch, Query, World};
+
+/// A kind of component change that can be watched with `World::observe`
+pub trait Observe: Query + 'static {
+    /// The component type whose changes are observed
+    type Target: Component;
+}
+
+impl<T: Component> Observe for Added<T> {
+    type Target = T;
+}
+
+impl<T: Component> Observe for Changed<T> {
+    type Target = T;
+}
+
+/// A callback registered with `World::observe`
+pub(crate) struct Observer {
+    /// Changes made at or after this tick have yet to be reported
+    since: u32,
+    run: Box<RunFn>,
+}
+
+type RunFn = dyn FnMut(&World, u32) + Send + Sync;
+
+impl Observer {
+    pub fn new<O, F>(since: u32, mut f: F) -> Self
+    where
+        O: Observe,
+        for<'a> O::Fetch: Fetch<'a, Item = bool>,
+        F: FnMut(Entity, &O::Target) + Send + Sync + 'static,
+    {
+        Self {
+            since,
+            run: Box::new(move |world: &World, since: u32| {
+                let mut query = world.query::<(&O::Target, O)>().since(since);
+                for (entity, (component, hit)) in query.iter() {
+                    if hit {
+                        f(entity, component);
+                    }
+                }
+            }),
+        }
+    }
+
+    /// Report changes made since the last run, then start watching from `next`
+    pub fn run(&mut self, world: &World, next: u32) {
+        (self.run)(world, self.since);
+        self.since = next;
+    }
+}
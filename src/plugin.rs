@@ -0,0 +1,611 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use hashbrown::HashMap;
+
+use crate::archetype::{ColumnPtr, ComponentTicks};
+use crate::{Archetype, Component, Entity, World};
+
+/// Version of the `WorldVTable` layout and calling conventions
+///
+/// Plugins should refuse to run if `WorldVTable::abi_version` differs.
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+
+/// An `Entity` as passed between host and plugin, in the form of `Entity::to_wide_bits` so that it
+/// doesn't depend on the `wide-entities` feature, split in two since `u128` has no stable C ABI
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct RawEntity {
+    low: u64,
+    high: u64,
+}
+
+fn to_raw(entity: Entity) -> RawEntity {
+    let bits = entity.to_wide_bits();
+    RawEntity {
+        low: bits as u64,
+        high: (bits >> 64) as u64,
+    }
+}
+
+fn raw_bits(raw: RawEntity) -> u128 {
+    u128::from(raw.high) << 64 | u128::from(raw.low)
+}
+
+/// Decode an entity produced by the host, which never produces invalid entities
+fn from_raw(raw: RawEntity) -> Entity {
+    Entity::from_wide_bits(raw_bits(raw))
+}
+
+/// Decode an entity supplied by a plugin, which may be invalid
+fn try_from_raw(raw: RawEntity) -> Option<Entity> {
+    Entity::try_from_wide_bits(raw_bits(raw))
+}
+
+/// The component types that plugins may access, for constructing `WorldVTable`s
+///
+/// `TypeId`s and the layouts of most Rust types can differ between separately compiled binaries,
+/// so a dynamic library, e.g. one reloaded during development, can't safely call `World` methods
+/// on a world owned by the host. Instead, the host registers each component type that plugins may
+/// access under a stable name, then hands plugins a `WorldVTable`, a `#[repr(C)]` table of
+/// `extern "C"` functions operating on its world. Plugins refer to components by the
+/// `PluginComponent` handles they look up by name, rather than by type.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// #[derive(Copy, Clone)]
+/// #[repr(C)]
+/// struct Health(f32);
+///
+/// let mut host = PluginHost::new();
+/// host.register::<Health>("game::Health");
+/// let mut world = World::new();
+/// let a = world.spawn((Health(10.0),));
+///
+/// // Normally called from a dynamic library
+/// fn plugin_update(world: &mut WorldVTable) {
+///     assert_eq!(world.abi_version(), PLUGIN_ABI_VERSION);
+///     let health = world.component_of::<Health>("game::Health").unwrap();
+///     world.query(&[QueryTerm::unique(health)], |_, components| unsafe {
+///         (*components[0].cast::<Health>()).0 -= 1.0;
+///     });
+/// }
+///
+/// plugin_update(&mut host.vtable(&mut world));
+/// assert_eq!(world.get::<Health>(a).unwrap().0, 9.0);
+/// ```
+#[derive(Default)]
+pub struct PluginHost {
+    components: Vec<Registered>,
+    by_name: HashMap<Box<str>, u32>,
+}
+
+impl PluginHost {
+    /// Create a host that exposes no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow plugins to access `T` components by `name`
+    ///
+    /// `T` should have a stable layout, e.g. by being `#[repr(C)]`, so that plugins can interpret
+    /// it. Replaces any type previously registered under the same name.
+    pub fn register<T: Component>(&mut self, name: &str) -> &mut Self {
+        let registered = Registered {
+            ty: TypeId::of::<T>(),
+            size: mem::size_of::<T>(),
+            align: mem::align_of::<T>(),
+            insert: insert::<T>,
+            remove: remove::<T>,
+            column: column::<T>,
+            try_borrow: try_borrow::<T>,
+            release: release::<T>,
+        };
+        match self.by_name.get(name) {
+            Some(&id) => self.components[id as usize] = registered,
+            None => {
+                self.by_name
+                    .insert(name.into(), self.components.len() as u32);
+                self.components.push(registered);
+            }
+        }
+        self
+    }
+
+    /// Construct a table through which plugins can operate on `world`
+    pub fn vtable<'a>(&'a self, world: &'a mut World) -> WorldVTable<'a> {
+        WorldVTable {
+            abi_version: PLUGIN_ABI_VERSION,
+            ctx: Context { host: self, world },
+            component: raw_component,
+            spawn: raw_spawn,
+            despawn: raw_despawn,
+            contains: raw_contains,
+            insert: raw_insert,
+            remove: raw_remove,
+            get: raw_get,
+            query: raw_query,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// A component type registered with a `PluginHost`, as seen by a plugin
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct PluginComponent {
+    /// Host-assigned identifier
+    pub id: u32,
+    /// Size of the component type in bytes
+    pub size: usize,
+    /// Alignment of the component type in bytes
+    pub align: usize,
+}
+
+/// A component accessed by `WorldVTable::query`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(C)]
+pub struct QueryTerm {
+    /// `PluginComponent::id` of the component
+    pub component: u32,
+    /// Whether the component is borrowed uniquely and marked changed
+    pub unique: bool,
+}
+
+impl QueryTerm {
+    /// Borrow `component` immutably
+    pub fn shared(component: PluginComponent) -> Self {
+        Self {
+            component: component.id,
+            unique: false,
+        }
+    }
+
+    /// Borrow `component` uniquely
+    pub fn unique(component: PluginComponent) -> Self {
+        Self {
+            component: component.id,
+            unique: true,
+        }
+    }
+}
+
+/// ABI-stable handle to a `World` owned by a `PluginHost`'s user, for use by dynamic libraries
+///
+/// Constructed with `PluginHost::vtable`. The methods are implemented in terms of the `extern "C"`
+/// functions stored within, so they're safe to call from code compiled separately from the host.
+/// Functions that would panic when called on a `World` directly, e.g. due to borrow conflicts,
+/// instead report failure.
+#[repr(C)]
+pub struct WorldVTable<'a> {
+    abi_version: u32,
+    ctx: Context,
+    component: unsafe extern "C" fn(*const Context, *const u8, usize, *mut PluginComponent) -> bool,
+    spawn: unsafe extern "C" fn(*const Context) -> RawEntity,
+    despawn: unsafe extern "C" fn(*const Context, RawEntity) -> bool,
+    contains: unsafe extern "C" fn(*const Context, RawEntity) -> bool,
+    insert: unsafe extern "C" fn(*const Context, RawEntity, u32, *mut u8) -> bool,
+    remove: unsafe extern "C" fn(*const Context, RawEntity, u32, *mut u8) -> bool,
+    get: unsafe extern "C" fn(*const Context, RawEntity, u32, bool) -> *mut u8,
+    query:
+        unsafe extern "C" fn(*const Context, *const QueryTerm, usize, Visitor, *mut c_void) -> bool,
+    _marker: PhantomData<&'a mut World>,
+}
+
+type Visitor = unsafe extern "C" fn(*mut c_void, RawEntity, *const *mut u8);
+
+impl WorldVTable<'_> {
+    /// The `PLUGIN_ABI_VERSION` of the host
+    pub fn abi_version(&self) -> u32 {
+        self.abi_version
+    }
+
+    /// Look up the component type registered as `name`
+    pub fn component(&self, name: &str) -> Option<PluginComponent> {
+        let mut out = MaybeUninit::uninit();
+        unsafe {
+            if (self.component)(&self.ctx, name.as_ptr(), name.len(), out.as_mut_ptr()) {
+                Some(out.assume_init())
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Look up the component type registered as `name`, if its layout matches `T`
+    ///
+    /// Because layouts may coincide, this doesn't guarantee that the host's type is really `T`.
+    pub fn component_of<T>(&self, name: &str) -> Option<PluginComponent> {
+        self.component(name)
+            .filter(|x| x.size == mem::size_of::<T>() && x.align == mem::align_of::<T>())
+    }
+
+    /// Create an entity with no components
+    pub fn spawn(&mut self) -> Entity {
+        from_raw(unsafe { (self.spawn)(&self.ctx) })
+    }
+
+    /// Destroy an entity and all its components, returning whether it existed
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        unsafe { (self.despawn)(&self.ctx, to_raw(entity)) }
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        unsafe { (self.contains)(&self.ctx, to_raw(entity)) }
+    }
+
+    /// Add `value` to `entity`, replacing any existing component of the same type, and returning
+    /// whether it was added
+    ///
+    /// # Safety
+    /// `T` must be the type `component` was registered with by the host.
+    pub unsafe fn insert<T>(
+        &mut self,
+        entity: Entity,
+        component: PluginComponent,
+        value: T,
+    ) -> bool {
+        let mut value = ManuallyDrop::new(value);
+        let ptr = (&mut *value as *mut T).cast::<u8>();
+        if (self.insert)(&self.ctx, to_raw(entity), component.id, ptr) {
+            true
+        } else {
+            ManuallyDrop::drop(&mut value);
+            false
+        }
+    }
+
+    /// Remove and return `entity`'s `component`, if it has one
+    ///
+    /// # Safety
+    /// `T` must be the type `component` was registered with by the host.
+    pub unsafe fn remove<T>(&mut self, entity: Entity, component: PluginComponent) -> Option<T> {
+        let mut out = MaybeUninit::<T>::uninit();
+        if (self.remove)(
+            &self.ctx,
+            to_raw(entity),
+            component.id,
+            out.as_mut_ptr().cast(),
+        ) {
+            Some(out.assume_init())
+        } else {
+            None
+        }
+    }
+
+    /// Locate `entity`'s `component` for reading, if it has one
+    ///
+    /// # Safety
+    /// The result must not be used after the world is next modified through this table, nor while
+    /// the component is uniquely borrowed, e.g. by a running query.
+    pub unsafe fn get(&self, entity: Entity, component: PluginComponent) -> Option<NonNull<u8>> {
+        NonNull::new((self.get)(&self.ctx, to_raw(entity), component.id, false))
+    }
+
+    /// Locate `entity`'s `component` for writing, if it has one, and mark it changed
+    ///
+    /// # Safety
+    /// As for `get`, and the component must not be borrowed at all.
+    pub unsafe fn get_mut(
+        &mut self,
+        entity: Entity,
+        component: PluginComponent,
+    ) -> Option<NonNull<u8>> {
+        NonNull::new((self.get)(&self.ctx, to_raw(entity), component.id, true))
+    }
+
+    /// Invoke `f` with each entity having all of `terms`, and pointers to the corresponding
+    /// components
+    ///
+    /// Components borrowed uniquely are marked changed. Returns `false` without calling `f` if any
+    /// term refers to an unknown component or couldn't be borrowed.
+    pub fn query<F: FnMut(Entity, &[*mut u8])>(&mut self, terms: &[QueryTerm], mut f: F) -> bool {
+        unsafe extern "C" fn visit<F: FnMut(Entity, &[*mut u8])>(
+            user: *mut c_void,
+            entity: RawEntity,
+            components: *const *mut u8,
+        ) {
+            let (f, len) = &mut *user.cast::<(&mut F, usize)>();
+            f(from_raw(entity), slice::from_raw_parts(components, *len));
+        }
+        let mut user = (&mut f, terms.len());
+        unsafe {
+            (self.query)(
+                &self.ctx,
+                terms.as_ptr(),
+                terms.len(),
+                visit::<F>,
+                (&mut user as *mut (&mut F, usize)).cast(),
+            )
+        }
+    }
+}
+
+#[repr(C)]
+struct Context {
+    host: *const PluginHost,
+    world: *mut World,
+}
+
+/// Type-erased operations on a registered component type, monomorphized by the host
+struct Registered {
+    ty: TypeId,
+    size: usize,
+    align: usize,
+    insert: unsafe fn(&mut World, Entity, *mut u8),
+    remove: fn(&mut World, Entity, *mut u8) -> bool,
+    column: fn(&Archetype) -> Option<Column>,
+    try_borrow: fn(&Archetype, bool) -> bool,
+    release: fn(&Archetype, bool),
+}
+
+unsafe fn insert<T: Component>(world: &mut World, entity: Entity, value: *mut u8) {
+    world
+        .insert(entity, (ptr::read(value.cast::<T>()),))
+        .unwrap();
+}
+
+fn remove<T: Component>(world: &mut World, entity: Entity, out: *mut u8) -> bool {
+    match world.remove_one::<T>(entity) {
+        Ok(x) => {
+            unsafe {
+                ptr::write(out.cast::<T>(), x);
+            }
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Base pointers of an archetype's storage for a component and its change ticks
+type Column = (ColumnPtr<u8>, NonNull<ComponentTicks>);
+
+fn column<T: Component>(archetype: &Archetype) -> Option<Column> {
+    Some((archetype.column::<T>()?.cast(), archetype.ticks::<T>()?))
+}
+
+/// The component at `index` in a column of `size`-byte components
+unsafe fn component(column: ColumnPtr<u8>, size: usize, index: usize) -> *mut u8 {
+    match column {
+        ColumnPtr::Direct(x) => x.as_ptr().add(index * size),
+        ColumnPtr::Indirect(_) => column.add(index).get(),
+    }
+}
+
+fn try_borrow<T: Component>(archetype: &Archetype, unique: bool) -> bool {
+    if unique {
+        archetype.try_borrow_mut::<T>().is_ok()
+    } else {
+        archetype.try_borrow::<T>().is_ok()
+    }
+}
+
+fn release<T: Component>(archetype: &Archetype, unique: bool) {
+    if unique {
+        archetype.release_mut::<T>();
+    } else {
+        archetype.release::<T>();
+    }
+}
+
+/// Find the archetype and index of `entity`, if it exists and has been flushed
+fn locate(world: &World, entity: Entity) -> Option<(&Archetype, u32)> {
+    if !world.contains(entity) {
+        return None;
+    }
+    let loc = world.entities_meta().get(entity.id as usize)?.location;
+    let archetype = &world.archetypes_inner()[loc.archetype as usize];
+    if loc.index >= archetype.len() {
+        return None;
+    }
+    Some((archetype, loc.index))
+}
+
+unsafe extern "C" fn raw_component(
+    ctx: *const Context,
+    name: *const u8,
+    len: usize,
+    out: *mut PluginComponent,
+) -> bool {
+    let host = &*(*ctx).host;
+    let name = match core::str::from_utf8(slice::from_raw_parts(name, len)) {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+    let id = match host.by_name.get(name) {
+        Some(&x) => x,
+        None => return false,
+    };
+    let registered = &host.components[id as usize];
+    out.write(PluginComponent {
+        id,
+        size: registered.size,
+        align: registered.align,
+    });
+    true
+}
+
+unsafe extern "C" fn raw_spawn(ctx: *const Context) -> RawEntity {
+    to_raw((*(*ctx).world).spawn(()))
+}
+
+unsafe extern "C" fn raw_despawn(ctx: *const Context, entity: RawEntity) -> bool {
+    let world = &mut *(*ctx).world;
+    let entity = match try_from_raw(entity) {
+        Some(x) => x,
+        None => return false,
+    };
+    match locate(world, entity) {
+        Some((archetype, _)) if !archetype.is_read_only() => world.despawn(entity).is_ok(),
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn raw_contains(ctx: *const Context, entity: RawEntity) -> bool {
+    try_from_raw(entity).is_some_and(|x| (*(*ctx).world).contains(x))
+}
+
+unsafe extern "C" fn raw_insert(
+    ctx: *const Context,
+    entity: RawEntity,
+    id: u32,
+    value: *mut u8,
+) -> bool {
+    let host = &*(*ctx).host;
+    let world = &mut *(*ctx).world;
+    let entity = match try_from_raw(entity) {
+        Some(x) => x,
+        None => return false,
+    };
+    let registered = match host.components.get(id as usize) {
+        Some(x) => x,
+        None => return false,
+    };
+    world.flush_entities();
+    match locate(world, entity) {
+        Some((archetype, _)) if !archetype.is_read_only() => {
+            (registered.insert)(world, entity, value);
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn raw_remove(
+    ctx: *const Context,
+    entity: RawEntity,
+    id: u32,
+    out: *mut u8,
+) -> bool {
+    let host = &*(*ctx).host;
+    let world = &mut *(*ctx).world;
+    let entity = match try_from_raw(entity) {
+        Some(x) => x,
+        None => return false,
+    };
+    let registered = match host.components.get(id as usize) {
+        Some(x) => x,
+        None => return false,
+    };
+    world.flush_entities();
+    match locate(world, entity) {
+        Some((archetype, _)) if !archetype.is_read_only() => {
+            (registered.remove)(world, entity, out)
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn raw_get(
+    ctx: *const Context,
+    entity: RawEntity,
+    id: u32,
+    unique: bool,
+) -> *mut u8 {
+    let host = &*(*ctx).host;
+    let world = &*(*ctx).world;
+    let registered = match host.components.get(id as usize) {
+        Some(x) => x,
+        None => return ptr::null_mut(),
+    };
+    let (archetype, index) = match try_from_raw(entity).and_then(|x| locate(world, x)) {
+        Some(x) => x,
+        None => return ptr::null_mut(),
+    };
+    let (base, ticks) = match (registered.column)(archetype) {
+        Some(x) => x,
+        None => return ptr::null_mut(),
+    };
+    if unique {
+        if archetype.is_read_only() {
+            return ptr::null_mut();
+        }
+        archetype.touch(registered.ty);
+        (*ticks.as_ptr().add(index as usize)).changed = world.change_tick();
+    }
+    component(base, registered.size, index as usize)
+}
+
+unsafe extern "C" fn raw_query(
+    ctx: *const Context,
+    terms: *const QueryTerm,
+    len: usize,
+    visit: Visitor,
+    user: *mut c_void,
+) -> bool {
+    let host = &*(*ctx).host;
+    let world = &*(*ctx).world;
+    let terms = slice::from_raw_parts(terms, len);
+    let mut registered = Vec::with_capacity(len);
+    for term in terms {
+        match host.components.get(term.component as usize) {
+            Some(x) => registered.push(x),
+            None => return false,
+        }
+    }
+    let archetypes = world.archetypes_inner();
+    let matches = |archetype: &Archetype| registered.iter().all(|x| archetype.has_dynamic(x.ty));
+    let release = |archetype: &Archetype, count: usize| {
+        for (term, x) in terms.iter().zip(&registered).take(count) {
+            (x.release)(archetype, term.unique);
+        }
+    };
+
+    // Acquire every borrow up front, so that nothing is visited if any conflict
+    for (i, archetype) in archetypes.iter().enumerate() {
+        if !matches(archetype) {
+            continue;
+        }
+        let writes = terms.iter().any(|x| x.unique);
+        let borrowed = if writes && archetype.is_read_only() {
+            0
+        } else {
+            terms
+                .iter()
+                .zip(&registered)
+                .take_while(|(term, x)| (x.try_borrow)(archetype, term.unique))
+                .count()
+        };
+        if borrowed < terms.len() {
+            release(archetype, borrowed);
+            for archetype in archetypes[..i].iter().filter(|x| matches(x)) {
+                release(archetype, terms.len());
+            }
+            return false;
+        }
+    }
+
+    let tick = world.change_tick();
+    let meta = world.entities_meta();
+    let mut components = Vec::with_capacity(len);
+    for archetype in archetypes.iter().filter(|x| matches(x)) {
+        let columns = registered
+            .iter()
+            .map(|x| (x.column)(archetype).unwrap())
+            .collect::<Vec<_>>();
+        for index in 0..archetype.len() as usize {
+            components.clear();
+            for ((term, x), &(base, ticks)) in terms.iter().zip(&registered).zip(&columns) {
+                components.push(component(base, x.size, index));
+                if term.unique {
+                    (*ticks.as_ptr().add(index)).changed = tick;
+                }
+            }
+            let id = *archetype.entities().as_ptr().add(index);
+            let entity = meta[id as usize].entity(id);
+            visit(user, to_raw(entity), components.as_ptr());
+        }
+    }
+    for archetype in archetypes.iter().filter(|x| matches(x)) {
+        release(archetype, terms.len());
+    }
+    true
+}
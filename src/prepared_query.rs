@@ -0,0 +1,455 @@
+use crate::alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use hashbrown::HashSet;
+
+use crate::archetype::Archetype;
+use crate::entities::EntityMeta;
+use crate::query::{ChunkIter, Fetch, QueryTicks};
+use crate::{Access, ArchetypesGeneration, Entity, Query, World};
+
+/// A query that remembers which archetypes it matches, and records statistics about its execution
+///
+/// Each execution only needs to inspect archetypes created since the previous one, making this
+/// cheaper than `World::query` when a world has many archetypes. A `PreparedQuery` must only be
+/// used with a single `World`, and panics if used with another.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.spawn((123, true));
+/// world.spawn((456,));
+/// world.spawn(("abc",));
+/// let mut query = PreparedQuery::<&i32>::new();
+/// assert_eq!(query.query(&world).iter().count(), 2);
+/// let stats = query.stats();
+/// assert_eq!(stats.executions, 1);
+/// assert_eq!(stats.archetypes, 2);
+/// assert_eq!(stats.rows, 2);
+/// ```
+pub struct PreparedQuery<Q: Query> {
+    /// The world's archetypes generation as of the last execution
+    generation: Option<ArchetypesGeneration>,
+    /// Number of the world's archetypes that have been checked against `Q`
+    checked: usize,
+    /// Indices of archetypes that `Q` accesses
+    matched: Vec<u32>,
+    stats: QueryStats,
+    timer: Option<fn() -> u64>,
+    tracker: Option<MatchTracker>,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<Q: Query> PreparedQuery<Q> {
+    /// Create a query that has not yet inspected any world
+    pub fn new() -> Self {
+        Self {
+            generation: None,
+            checked: 0,
+            matched: Vec::new(),
+            stats: QueryStats::default(),
+            timer: None,
+            tracker: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Measure execution time with `timer`, which returns a monotonic timestamp in any unit
+    ///
+    /// Each execution is timed from the call to `PreparedQueryBorrow::iter` until the
+    /// `PreparedQueryBorrow` is dropped, and the difference accumulated in `QueryStats::elapsed`.
+    pub fn set_timer(&mut self, timer: fn() -> u64) {
+        self.timer = Some(timer);
+    }
+
+    /// Statistics accumulated over all executions since creation or the last `reset_stats`
+    pub fn stats(&self) -> &QueryStats {
+        &self.stats
+    }
+
+    /// Zero the accumulated statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = QueryStats::default();
+    }
+
+    /// Record which entities start or stop matching `Q` between calls to `query`
+    ///
+    /// Once enabled, each call to `query` updates `newly_matched` and `no_longer_matched`, only
+    /// inspecting archetypes that entities have entered or left since the previous call. Entities
+    /// moving between archetypes that both match `Q` are not reported. Disabling discards the
+    /// recorded match set, so the next call after re-enabling reports every match as new.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let mut query = PreparedQuery::<&i32>::new();
+    /// query.track_matches(true);
+    /// query.query(&world);
+    /// assert_eq!(query.newly_matched(), [a]);
+    ///
+    /// let b = world.spawn((456,));
+    /// world.remove_one::<i32>(a).unwrap();
+    /// query.query(&world);
+    /// assert_eq!(query.newly_matched(), [b]);
+    /// assert_eq!(query.no_longer_matched(), [a]);
+    /// ```
+    pub fn track_matches(&mut self, enabled: bool) {
+        if !enabled {
+            self.tracker = None;
+        } else if self.tracker.is_none() {
+            self.tracker = Some(MatchTracker::default());
+        }
+    }
+
+    /// Entities that began matching `Q` between the last two calls to `query`
+    ///
+    /// Empty unless enabled by `track_matches`.
+    pub fn newly_matched(&self) -> &[Entity] {
+        self.tracker.as_ref().map_or(&[], |x| &x.entered)
+    }
+
+    /// Entities that stopped matching `Q`, including by being despawned, between the last two
+    /// calls to `query`
+    ///
+    /// Empty unless enabled by `track_matches`.
+    pub fn no_longer_matched(&self) -> &[Entity] {
+        self.tracker.as_ref().map_or(&[], |x| &x.left)
+    }
+
+    /// Prepare to execute the query against `world`
+    ///
+    /// Like `World::query`, but borrows only the archetypes that `Q` matches.
+    pub fn query<'q>(&'q mut self, world: &'q World) -> PreparedQueryBorrow<'q, Q> {
+        let archetypes = world.archetypes_inner();
+        let generation = world.archetypes_generation();
+        if self.generation != Some(generation) {
+            if let Some(old) = self.generation {
+                assert!(
+                    world.archetypes_extend(old),
+                    "prepared query used with a different world"
+                );
+            }
+            for (i, archetype) in archetypes.iter().enumerate().skip(self.checked) {
+                if Q::Fetch::access(archetype).is_some() {
+                    self.matched.push(i as u32);
+                }
+            }
+            self.checked = archetypes.len();
+            self.generation = Some(generation);
+        }
+        if let Some(ref mut tracker) = self.tracker {
+            tracker.update(world, &self.matched);
+        }
+        PreparedQueryBorrow {
+            meta: world.entities_meta(),
+            archetypes,
+            matched: &self.matched,
+            stats: &mut self.stats,
+            timer: self.timer,
+            start: None,
+            ticks: QueryTicks::new(world.change_tick()),
+            prefetch: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Q: Query> Default for PreparedQuery<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The entities matched by a `PreparedQuery`, and how they changed in the last update
+#[derive(Default)]
+struct MatchTracker {
+    /// `Archetype::version` and entities of each matched archetype as of the last update, by
+    /// index into `PreparedQuery::matched`
+    archetypes: Vec<(Option<u64>, Vec<Entity>)>,
+    entered: Vec<Entity>,
+    left: Vec<Entity>,
+}
+
+impl MatchTracker {
+    fn update(&mut self, world: &World, matched: &[u32]) {
+        self.entered.clear();
+        self.left.clear();
+        let archetypes = world.archetypes_inner();
+        let meta = world.entities_meta();
+        self.archetypes
+            .resize_with(matched.len(), || (None, Vec::new()));
+        for (&index, (version, entities)) in matched.iter().zip(&mut self.archetypes) {
+            let archetype = &archetypes[index as usize];
+            if *version == Some(archetype.version()) {
+                continue;
+            }
+            *version = Some(archetype.version());
+            let current = (0..archetype.len())
+                .map(|i| {
+                    let id = archetype.entity_id(i);
+                    meta[id as usize].entity(id)
+                })
+                .collect::<Vec<_>>();
+            let old = entities.iter().copied().collect::<HashSet<_>>();
+            let new = current.iter().copied().collect::<HashSet<_>>();
+            self.entered
+                .extend(current.iter().filter(|x| !old.contains(x)));
+            self.left
+                .extend(entities.iter().filter(|x| !new.contains(x)));
+            *entities = current;
+        }
+        if self.entered.is_empty() || self.left.is_empty() {
+            return;
+        }
+        // Entities that moved between matched archetypes are still matched
+        let entered = self.entered.iter().copied().collect::<HashSet<_>>();
+        let left = self.left.iter().copied().collect::<HashSet<_>>();
+        self.entered.retain(|x| !left.contains(x));
+        self.left.retain(|x| !entered.contains(x));
+    }
+}
+
+/// Cumulative execution statistics of a `PreparedQuery`
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct QueryStats {
+    /// Number of times the query was iterated
+    pub executions: u64,
+    /// Total number of non-empty archetypes visited
+    pub archetypes: u64,
+    /// Total number of entities yielded
+    pub rows: u64,
+    /// Total time spent executing, as measured by the timer passed to `PreparedQuery::set_timer`
+    pub elapsed: u64,
+}
+
+/// A borrow of a `World` sufficient to execute a `PreparedQuery`
+///
+/// Borrows are not released, and statistics not recorded, until this object is dropped.
+pub struct PreparedQueryBorrow<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: &'q [Archetype],
+    matched: &'q [u32],
+    stats: &'q mut QueryStats,
+    timer: Option<fn() -> u64>,
+    /// Timestamp at which iteration began, or 0 if untimed; `None` if not yet borrowed
+    start: Option<u64>,
+    ticks: QueryTicks,
+    prefetch: u32,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
+    /// Report changes made at or after world change tick `tick` via `Added` and `Changed`
+    ///
+    /// See `QueryBorrow::since` for details.
+    pub fn since(mut self, tick: u32) -> Self {
+        self.ticks.since = tick;
+        self
+    }
+
+    /// Issue software prefetches for the entity `distance` places ahead during iteration
+    ///
+    /// See `QueryBorrow::prefetch` for details.
+    pub fn prefetch(mut self, distance: u32) -> Self {
+        self.prefetch = distance;
+        self
+    }
+
+    /// Execute the query
+    ///
+    /// Must be called only once per borrow.
+    pub fn iter<'i>(&'i mut self) -> PreparedQueryIter<'i, 'q, Q> {
+        self.iter_from(QueryCursor::default())
+    }
+
+    /// Execute the query, resuming from a position recorded by `PreparedQueryIter::cursor`
+    ///
+    /// Allows a system to spread a pass over many entities across several frames. Entities
+    /// spawned into archetypes the pass has already finished with are not visited until the next
+    /// pass. If entities were added to or removed from the archetype the cursor points into, that
+    /// archetype is visited again from its start, so no entity present throughout the pass is
+    /// skipped, though some may be visited twice. To begin a new pass, use `QueryCursor::default`.
+    ///
+    /// `cursor` must have been obtained from this `PreparedQuery`. Must be called only once per
+    /// borrow.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// let mut query = PreparedQuery::<&i32>::new();
+    /// let mut cursor = QueryCursor::default();
+    /// let mut visited = Vec::new();
+    /// for _frame in 0..4 {
+    ///     let mut borrow = query.query(&world);
+    ///     let mut iter = borrow.iter_from(cursor);
+    ///     visited.extend(iter.by_ref().take(3).map(|(_, &x)| x));
+    ///     cursor = iter.cursor();
+    /// }
+    /// assert_eq!(visited, (0..10).collect::<Vec<_>>());
+    /// ```
+    pub fn iter_from<'i>(&'i mut self, cursor: QueryCursor) -> PreparedQueryIter<'i, 'q, Q> {
+        if self.start.is_some() {
+            panic!(
+                "called PreparedQueryBorrow::iter twice on the same borrow; construct a new query \
+                 instead"
+            );
+        }
+        for &i in self.matched {
+            let archetype = &self.archetypes[i as usize];
+            if Q::Fetch::access(archetype) >= Some(Access::Read) {
+                Q::Fetch::borrow(archetype);
+            }
+        }
+        self.start = Some(self.timer.map_or(0, |f| f()));
+        self.stats.executions += 1;
+        let mut iter = PreparedQueryIter {
+            borrow: self,
+            next_archetype: cursor.archetype,
+            iter: None,
+        };
+        if cursor.row != 0 {
+            iter.resume(cursor);
+        }
+        iter
+    }
+}
+
+/// A position within the entities matched by a `PreparedQuery`, for resuming iteration later
+///
+/// Obtained from `PreparedQueryIter::cursor`. The default cursor is the beginning of a pass.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct QueryCursor {
+    /// Index into `PreparedQuery::matched` of the next archetype to visit
+    archetype: usize,
+    /// Index of the next entity to visit in that archetype
+    row: u32,
+    /// `Archetype::version` at the time the cursor was taken, meaningful only if `row != 0`
+    version: u64,
+}
+
+impl<Q: Query> Drop for PreparedQueryBorrow<'_, Q> {
+    fn drop(&mut self) {
+        let start = match self.start {
+            Some(x) => x,
+            None => return,
+        };
+        for &i in self.matched {
+            let archetype = &self.archetypes[i as usize];
+            if Q::Fetch::access(archetype) >= Some(Access::Read) {
+                Q::Fetch::release(archetype);
+            }
+        }
+        if let Some(timer) = self.timer {
+            self.stats.elapsed += timer().wrapping_sub(start);
+        }
+    }
+}
+
+unsafe impl<Q: Query> Send for PreparedQueryBorrow<'_, Q> {}
+unsafe impl<Q: Query> Sync for PreparedQueryBorrow<'_, Q> {}
+
+/// Iterator over the entities matched by a `PreparedQuery`
+pub struct PreparedQueryIter<'i, 'q, Q: Query> {
+    borrow: &'i mut PreparedQueryBorrow<'q, Q>,
+    next_archetype: usize,
+    iter: Option<ChunkIter<Q>>,
+}
+
+impl<'i, 'q, Q: Query> Iterator for PreparedQueryIter<'i, 'q, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'i>>::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter {
+                None => {
+                    let &index = self.borrow.matched.get(self.next_archetype)?;
+                    self.next_archetype += 1;
+                    let archetype = &self.borrow.archetypes[index as usize];
+                    if archetype.is_empty() {
+                        continue;
+                    }
+                    self.borrow.stats.archetypes += 1;
+                    unsafe {
+                        self.iter =
+                            Q::Fetch::get(archetype, 0, self.borrow.ticks).map(|fetch| ChunkIter {
+                                entities: archetype.entities(),
+                                fetch,
+                                len: archetype.len(),
+                                prefetch: self.borrow.prefetch,
+                            });
+                    }
+                }
+                Some(ref mut iter) => match unsafe { iter.next() } {
+                    None => {
+                        self.iter = None;
+                        continue;
+                    }
+                    Some((id, components)) => {
+                        self.borrow.stats.rows += 1;
+                        return Some((self.borrow.meta[id as usize].entity(id), components));
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<Q: Query> PreparedQueryIter<'_, '_, Q> {
+    /// The position of the next entity to be yielded, for use with
+    /// `PreparedQueryBorrow::iter_from`
+    pub fn cursor(&self) -> QueryCursor {
+        match self.iter {
+            Some(ref iter) if iter.len != 0 => {
+                let index = self.borrow.matched[self.next_archetype - 1];
+                let archetype = &self.borrow.archetypes[index as usize];
+                QueryCursor {
+                    archetype: self.next_archetype - 1,
+                    row: archetype.len() - iter.len,
+                    version: archetype.version(),
+                }
+            }
+            _ => QueryCursor {
+                archetype: self.next_archetype,
+                row: 0,
+                version: 0,
+            },
+        }
+    }
+
+    /// Begin iteration partway through the archetype `cursor` refers to, if it hasn't changed
+    fn resume(&mut self, cursor: QueryCursor) {
+        let &index = match self.borrow.matched.get(cursor.archetype) {
+            Some(x) => x,
+            None => return,
+        };
+        let archetype = &self.borrow.archetypes[index as usize];
+        if archetype.version() != cursor.version || cursor.row >= archetype.len() {
+            return;
+        }
+        self.next_archetype += 1;
+        self.borrow.stats.archetypes += 1;
+        unsafe {
+            self.iter =
+                Q::Fetch::get(archetype, cursor.row as usize, self.borrow.ticks).map(|fetch| {
+                    ChunkIter {
+                        entities: NonNull::new_unchecked(
+                            archetype.entities().as_ptr().add(cursor.row as usize),
+                        ),
+                        fetch,
+                        len: archetype.len() - cursor.row,
+                        prefetch: self.borrow.prefetch,
+                    }
+                });
+        }
+    }
+}
+
+unsafe impl<Q: Query> Send for PreparedQueryIter<'_, '_, Q> {}
+unsafe impl<Q: Query> Sync for PreparedQueryIter<'_, '_, Q> {}
@@ -12,12 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::iter::Take;
 use core::marker::PhantomData;
-use core::ptr::NonNull;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::slice;
 
-use crate::archetype::Archetype;
+use crate::archetype::{Archetype, ColumnPtr, ComponentTicks, MAX_CHANGE_AGE};
 use crate::entities::EntityMeta;
-use crate::{Component, Entity};
+use crate::frame::FrameAllocator;
+use crate::{BorrowError, BorrowPolicy, Component, Entity, QueryAccess};
 
 /// A collection of component types to fetch from a `World`
 pub trait Query {
@@ -25,21 +31,42 @@ pub trait Query {
     type Fetch: for<'a> Fetch<'a>;
 }
 
+/// A read-only `Query` whose items can be cloned into owned values
+///
+/// Implemented for `&T` where `T: Clone`, yielding `T`, and for `Option`, `With`, `Without`,
+/// `Added`, `Changed`, and tuples of such queries. See `QueryBorrow::iter_cloned`.
+pub trait CloneQuery: Query {
+    /// Type of the owned values
+    type Owned;
+
+    /// Clone the values referenced by `item`
+    fn clone_item(item: <Self::Fetch as Fetch<'_>>::Item) -> Self::Owned;
+}
+
 /// Streaming iterators over contiguous homogeneous ranges of components
 pub trait Fetch<'a>: Sized {
     /// Type of value to be fetched
     type Item;
+    /// Type of a run of consecutive values, as fetched by `slice`
+    type Slice;
 
     /// How this query will access `archetype`, if at all
     fn access(archetype: &Archetype) -> Option<Access>;
 
-    /// Acquire dynamic borrows from `archetype`
-    fn borrow(archetype: &Archetype);
+    /// Acquire dynamic borrows from `archetype`, panicking if they conflict with existing borrows
+    fn borrow(archetype: &Archetype) {
+        if let Err(e) = Self::try_borrow(archetype) {
+            panic!("{}", e);
+        }
+    }
+    /// Acquire dynamic borrows from `archetype`, acquiring none if any conflict with existing
+    /// borrows
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError>;
     /// Construct a `Fetch` for `archetype` if it should be traversed
     ///
     /// # Safety
     /// `offset` must be in bounds of `archetype`
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self>;
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self>;
     /// Release dynamic borrows acquired by `borrow`
     fn release(archetype: &Archetype);
 
@@ -51,6 +78,68 @@ pub trait Fetch<'a>: Sized {
     /// - Bounds-checking must be performed externally
     /// - Any resulting borrows must be legal (e.g. no &mut to something another iterator might access)
     unsafe fn next(&mut self) -> Self::Item;
+
+    /// Access the next `len` items in this archetype at once, without bounds checking
+    ///
+    /// # Safety
+    /// As for `next`, with bounds-checking covering all `len` items
+    unsafe fn slice(&mut self, len: usize) -> Self::Slice;
+
+    /// Invoke `f` with the ID and name of each component type this query borrows, and whether the
+    /// borrow is unique
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool));
+
+    /// Hint that the item `distance` places after the next will be accessed soon
+    ///
+    /// Must not affect program behavior, so may be called regardless of bounds.
+    fn prefetch(&self, distance: usize) {
+        let _ = distance;
+    }
+}
+
+/// Hint that memory at `ptr` will be read soon
+///
+/// `ptr` need not be valid.
+#[inline(always)]
+pub(crate) fn prefetch<T>(ptr: *const T) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast());
+    }
+    #[cfg(target_arch = "x86")]
+    unsafe {
+        use core::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast());
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    let _ = ptr;
+}
+
+/// World change ticks against which a query is executed
+#[doc(hidden)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct QueryTicks {
+    /// Changes made at or after this tick are reported
+    pub(crate) since: u32,
+    /// The world's tick when the query was made, recorded for any components it writes
+    pub(crate) now: u32,
+}
+
+impl QueryTicks {
+    pub(crate) fn new(now: u32) -> Self {
+        Self { since: 0, now }
+    }
+
+    /// Whether a component stamped with `tick` was touched in `since..now`
+    ///
+    /// Changes made at `now` are deferred until the next query, so that a query run once per
+    /// tick sees each change exactly once.
+    pub(crate) fn is_new(self, tick: u32) -> bool {
+        let age = self.now.wrapping_sub(tick);
+        let limit = self.now.wrapping_sub(self.since).min(MAX_CHANGE_AGE);
+        age != 0 && age <= limit
+    }
 }
 
 /// Type of access a `Query` may have to an `Archetype`
@@ -64,15 +153,24 @@ pub enum Access {
     Write,
 }
 
-impl<'a, T: Component> Query for &'a T {
+impl<T: Component> Query for &T {
     type Fetch = FetchRead<T>;
 }
 
+impl<T: Component + Clone> CloneQuery for &T {
+    type Owned = T;
+
+    fn clone_item(item: &T) -> T {
+        item.clone()
+    }
+}
+
 #[doc(hidden)]
-pub struct FetchRead<T>(NonNull<T>);
+pub struct FetchRead<T>(ColumnPtr<T>);
 
 impl<'a, T: Component> Fetch<'a> for FetchRead<T> {
     type Item = &'a T;
+    type Slice = &'a [T];
 
     fn access(archetype: &Archetype) -> Option<Access> {
         if archetype.has::<T>() {
@@ -82,34 +180,46 @@ impl<'a, T: Component> Fetch<'a> for FetchRead<T> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        archetype.borrow::<T>();
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
+    }
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        f(TypeId::of::<T>(), type_name::<T>(), false);
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        archetype
-            .get::<T>()
-            .map(|x| Self(NonNull::new_unchecked(x.as_ptr().add(offset))))
+    unsafe fn get(archetype: &'a Archetype, offset: usize, _: QueryTicks) -> Option<Self> {
+        archetype.column::<T>().map(|x| Self(x.add(offset)))
     }
     fn release(archetype: &Archetype) {
         archetype.release::<T>();
     }
 
     unsafe fn next(&mut self) -> &'a T {
-        let x = self.0.as_ptr();
-        self.0 = NonNull::new_unchecked(x.add(1));
+        let x = self.0.get();
+        self.0 = self.0.add(1);
         &*x
     }
+
+    unsafe fn slice(&mut self, len: usize) -> &'a [T] {
+        let x = contiguous(self.0);
+        self.0 = self.0.add(len);
+        slice::from_raw_parts(x, len)
+    }
+
+    fn prefetch(&self, distance: usize) {
+        self.0.prefetch(distance);
+    }
 }
 
-impl<'a, T: Component> Query for &'a mut T {
+impl<T: Component> Query for &mut T {
     type Fetch = FetchWrite<T>;
 }
 
 #[doc(hidden)]
-pub struct FetchWrite<T>(NonNull<T>);
+pub struct FetchWrite<T>(ColumnPtr<T>, NonNull<ComponentTicks>, u32);
 
 impl<'a, T: Component> Fetch<'a> for FetchWrite<T> {
     type Item = &'a mut T;
+    type Slice = &'a mut [T];
 
     fn access(archetype: &Archetype) -> Option<Access> {
         if archetype.has::<T>() {
@@ -119,44 +229,93 @@ impl<'a, T: Component> Fetch<'a> for FetchWrite<T> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        archetype.borrow_mut::<T>();
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow_mut::<T>()
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        archetype
-            .get::<T>()
-            .map(|x| Self(NonNull::new_unchecked(x.as_ptr().add(offset))))
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        f(TypeId::of::<T>(), type_name::<T>(), true);
+    }
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
+        let column = archetype.column::<T>()?;
+        archetype.note_writes::<T>(ticks.now);
+        Some(Self(
+            column.add(offset),
+            NonNull::new_unchecked(archetype.ticks::<T>()?.as_ptr().add(offset)),
+            ticks.now,
+        ))
     }
     fn release(archetype: &Archetype) {
         archetype.release_mut::<T>();
     }
 
     unsafe fn next(&mut self) -> &'a mut T {
-        let x = self.0.as_ptr();
-        self.0 = NonNull::new_unchecked(x.add(1));
+        let x = self.0.get();
+        self.0 = self.0.add(1);
+        let ticks = self.1.as_ptr();
+        (*ticks).changed = self.2;
+        self.1 = NonNull::new_unchecked(ticks.add(1));
         &mut *x
     }
+
+    unsafe fn slice(&mut self, len: usize) -> &'a mut [T] {
+        let x = contiguous(self.0);
+        self.0 = self.0.add(len);
+        let ticks = self.1.as_ptr();
+        for i in 0..len {
+            (*ticks.add(i)).changed = self.2;
+        }
+        self.1 = NonNull::new_unchecked(ticks.add(len));
+        slice::from_raw_parts_mut(x, len)
+    }
+
+    fn prefetch(&self, distance: usize) {
+        self.0.prefetch(distance);
+        prefetch(self.1.as_ptr().wrapping_add(distance));
+    }
+}
+
+/// The components at `column` as a slice, which pinned components can't be
+fn contiguous<T>(column: ColumnPtr<T>) -> *mut T {
+    match column {
+        ColumnPtr::Direct(x) => x.as_ptr(),
+        ColumnPtr::Indirect(_) => panic!(
+            "pinned component {} isn't stored contiguously",
+            type_name::<T>()
+        ),
+    }
 }
 
 impl<T: Query> Query for Option<T> {
     type Fetch = TryFetch<T::Fetch>;
 }
 
+impl<T: CloneQuery> CloneQuery for Option<T> {
+    type Owned = Option<T::Owned>;
+
+    fn clone_item(item: <Self::Fetch as Fetch<'_>>::Item) -> Self::Owned {
+        item.map(T::clone_item)
+    }
+}
+
 #[doc(hidden)]
 pub struct TryFetch<T>(Option<T>);
 
 impl<'a, T: Fetch<'a>> Fetch<'a> for TryFetch<T> {
     type Item = Option<T::Item>;
+    type Slice = Option<T::Slice>;
 
     fn access(archetype: &Archetype) -> Option<Access> {
         Some(T::access(archetype).unwrap_or(Access::Iterate))
     }
 
-    fn borrow(archetype: &Archetype) {
-        T::borrow(archetype)
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        T::try_borrow(archetype)
+    }
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        T::for_each_borrow(f);
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        Some(Self(T::get(archetype, offset)))
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
+        Some(Self(T::get(archetype, offset, ticks)))
     }
     fn release(archetype: &Archetype) {
         T::release(archetype)
@@ -165,6 +324,16 @@ impl<'a, T: Fetch<'a>> Fetch<'a> for TryFetch<T> {
     unsafe fn next(&mut self) -> Option<T::Item> {
         Some(self.0.as_mut()?.next())
     }
+
+    unsafe fn slice(&mut self, len: usize) -> Option<T::Slice> {
+        Some(self.0.as_mut()?.slice(len))
+    }
+
+    fn prefetch(&self, distance: usize) {
+        if let Some(ref x) = self.0 {
+            x.prefetch(distance);
+        }
+    }
 }
 
 /// Query transformer skipping entities that have a `T` component
@@ -190,11 +359,20 @@ impl<T: Component, Q: Query> Query for Without<T, Q> {
     type Fetch = FetchWithout<T, Q::Fetch>;
 }
 
+impl<T: Component, Q: CloneQuery> CloneQuery for Without<T, Q> {
+    type Owned = Q::Owned;
+
+    fn clone_item(item: <Self::Fetch as Fetch<'_>>::Item) -> Self::Owned {
+        Q::clone_item(item)
+    }
+}
+
 #[doc(hidden)]
 pub struct FetchWithout<T, F>(F, PhantomData<fn(T)>);
 
 impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
     type Item = F::Item;
+    type Slice = F::Slice;
 
     fn access(archetype: &Archetype) -> Option<Access> {
         if archetype.has::<T>() {
@@ -204,14 +382,17 @@ impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        F::borrow(archetype)
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        F::try_borrow(archetype)
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        F::for_each_borrow(f);
+    }
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
         if archetype.has::<T>() {
             return None;
         }
-        Some(Self(F::get(archetype, offset)?, PhantomData))
+        Some(Self(F::get(archetype, offset, ticks)?, PhantomData))
     }
     fn release(archetype: &Archetype) {
         F::release(archetype)
@@ -220,6 +401,14 @@ impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
     unsafe fn next(&mut self) -> F::Item {
         self.0.next()
     }
+
+    unsafe fn slice(&mut self, len: usize) -> F::Slice {
+        self.0.slice(len)
+    }
+
+    fn prefetch(&self, distance: usize) {
+        self.0.prefetch(distance);
+    }
 }
 
 /// Query transformer skipping entities that do not have a `T` component
@@ -247,11 +436,20 @@ impl<T: Component, Q: Query> Query for With<T, Q> {
     type Fetch = FetchWith<T, Q::Fetch>;
 }
 
+impl<T: Component, Q: CloneQuery> CloneQuery for With<T, Q> {
+    type Owned = Q::Owned;
+
+    fn clone_item(item: <Self::Fetch as Fetch<'_>>::Item) -> Self::Owned {
+        Q::clone_item(item)
+    }
+}
+
 #[doc(hidden)]
 pub struct FetchWith<T, F>(F, PhantomData<fn(T)>);
 
 impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
     type Item = F::Item;
+    type Slice = F::Slice;
 
     fn access(archetype: &Archetype) -> Option<Access> {
         if archetype.has::<T>() {
@@ -261,14 +459,17 @@ impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        F::borrow(archetype)
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        F::try_borrow(archetype)
+    }
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        F::for_each_borrow(f);
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
         if !archetype.has::<T>() {
             return None;
         }
-        Some(Self(F::get(archetype, offset)?, PhantomData))
+        Some(Self(F::get(archetype, offset, ticks)?, PhantomData))
     }
     fn release(archetype: &Archetype) {
         F::release(archetype)
@@ -277,6 +478,237 @@ impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
     unsafe fn next(&mut self) -> F::Item {
         self.0.next()
     }
+
+    unsafe fn slice(&mut self, len: usize) -> F::Slice {
+        self.0.slice(len)
+    }
+
+    fn prefetch(&self, distance: usize) {
+        self.0.prefetch(distance);
+    }
+}
+
+/// Query yielding whether an entity's `T` component was added since the query's change tick
+///
+/// Entities lacking a `T` are skipped. Reading change ticks borrows `T`, so this can't be combined
+/// with `&mut T` in a single query. See `QueryBorrow::since` for how ticks are compared.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let last_run = world.increment_change_tick();
+/// let b = world.spawn((456,));
+/// world.increment_change_tick();
+/// let added = world.query::<Added<i32>>()
+///     .since(last_run)
+///     .iter()
+///     .filter(|&(_, added)| added)
+///     .map(|(e, _)| e)
+///     .collect::<Vec<_>>();
+/// assert_eq!(added, &[b]);
+/// ```
+pub struct Added<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Added<T> {
+    type Fetch = FetchAdded<T>;
+}
+
+impl<T: Component> CloneQuery for Added<T> {
+    type Owned = bool;
+
+    fn clone_item(item: bool) -> bool {
+        item
+    }
+}
+
+#[doc(hidden)]
+pub struct FetchAdded<T>(NonNull<ComponentTicks>, QueryTicks, PhantomData<fn(T)>);
+
+impl<'a, T: Component> Fetch<'a> for FetchAdded<T> {
+    type Item = bool;
+    type Slice = TickFlags<'a>;
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            Some(Access::Read)
+        } else {
+            None
+        }
+    }
+
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
+    }
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        f(TypeId::of::<T>(), type_name::<T>(), false);
+    }
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
+        archetype.ticks::<T>().map(|x| {
+            Self(
+                NonNull::new_unchecked(x.as_ptr().add(offset)),
+                ticks,
+                PhantomData,
+            )
+        })
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+    }
+
+    unsafe fn next(&mut self) -> bool {
+        let x = self.0.as_ptr();
+        self.0 = NonNull::new_unchecked(x.add(1));
+        self.1.is_new((*x).added)
+    }
+
+    unsafe fn slice(&mut self, len: usize) -> TickFlags<'a> {
+        let x = self.0.as_ptr();
+        self.0 = NonNull::new_unchecked(x.add(len));
+        TickFlags {
+            ticks: slice::from_raw_parts(x, len),
+            query: self.1,
+            added: true,
+        }
+    }
+
+    fn prefetch(&self, distance: usize) {
+        prefetch(self.0.as_ptr().wrapping_add(distance));
+    }
+}
+
+/// Query yielding whether an entity's `T` component was added or modified since the query's change
+/// tick
+///
+/// A component is considered modified whenever it's uniquely borrowed, whether by a `&mut T` query,
+/// `World::get_mut`, or replacement with `World::insert`. Entities lacking a `T` are skipped.
+/// Reading change ticks borrows `T`, so this can't be combined with `&mut T` in a single query.
+/// See `QueryBorrow::since` for how ticks are compared.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// let last_run = world.increment_change_tick();
+/// *world.get_mut::<i32>(a).unwrap() = 42;
+/// world.increment_change_tick();
+/// let changed = world.query::<(&i32, Changed<i32>)>()
+///     .since(last_run)
+///     .iter()
+///     .filter(|&(_, (_, changed))| changed)
+///     .map(|(e, (&x, _))| (e, x))
+///     .collect::<Vec<_>>();
+/// assert_eq!(changed, &[(a, 42)]);
+/// ```
+pub struct Changed<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Changed<T> {
+    type Fetch = FetchChanged<T>;
+}
+
+impl<T: Component> CloneQuery for Changed<T> {
+    type Owned = bool;
+
+    fn clone_item(item: bool) -> bool {
+        item
+    }
+}
+
+#[doc(hidden)]
+pub struct FetchChanged<T>(NonNull<ComponentTicks>, QueryTicks, PhantomData<fn(T)>);
+
+impl<'a, T: Component> Fetch<'a> for FetchChanged<T> {
+    type Item = bool;
+    type Slice = TickFlags<'a>;
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if archetype.has::<T>() {
+            Some(Access::Read)
+        } else {
+            None
+        }
+    }
+
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
+    }
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+        f(TypeId::of::<T>(), type_name::<T>(), false);
+    }
+    unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
+        archetype.ticks::<T>().map(|x| {
+            Self(
+                NonNull::new_unchecked(x.as_ptr().add(offset)),
+                ticks,
+                PhantomData,
+            )
+        })
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+    }
+
+    unsafe fn next(&mut self) -> bool {
+        let x = self.0.as_ptr();
+        self.0 = NonNull::new_unchecked(x.add(1));
+        self.1.is_new((*x).changed)
+    }
+
+    unsafe fn slice(&mut self, len: usize) -> TickFlags<'a> {
+        let x = self.0.as_ptr();
+        self.0 = NonNull::new_unchecked(x.add(len));
+        TickFlags {
+            ticks: slice::from_raw_parts(x, len),
+            query: self.1,
+            added: false,
+        }
+    }
+
+    fn prefetch(&self, distance: usize) {
+        prefetch(self.0.as_ptr().wrapping_add(distance));
+    }
+}
+
+/// Whether each of a run of components was added or changed, as fetched for `Added` or `Changed`
+/// by `QueryBorrow::iter_columns`
+#[derive(Copy, Clone)]
+pub struct TickFlags<'a> {
+    ticks: &'a [ComponentTicks],
+    query: QueryTicks,
+    added: bool,
+}
+
+impl<'a> TickFlags<'a> {
+    /// Number of components covered
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    /// Whether no components are covered
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    /// Whether the `index`th component was added or changed, if in bounds
+    pub fn get(&self, index: usize) -> Option<bool> {
+        let ticks = self.ticks.get(index)?;
+        Some(self.query.is_new(if self.added {
+            ticks.added
+        } else {
+            ticks.changed
+        }))
+    }
+
+    /// Iterate over whether each component was added or changed
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = bool> + 'a {
+        let (query, added) = (self.query, self.added);
+        self.ticks
+            .iter()
+            .map(move |x| query.is_new(if added { x.added } else { x.changed }))
+    }
 }
 
 /// A borrow of a `World` sufficient to execute the query `Q`
@@ -285,25 +717,81 @@ impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
 pub struct QueryBorrow<'w, Q: Query> {
     meta: &'w [EntityMeta],
     archetypes: &'w [Archetype],
+    frame: &'w FrameAllocator,
     borrowed: bool,
+    /// Whether `iter` or similar has been called, which may only happen once
+    iterated: bool,
+    ticks: QueryTicks,
+    prefetch: u32,
+    policy: BorrowPolicy,
+    /// Indices of archetypes that couldn't be borrowed under `BorrowPolicy::Skip`, and why
+    skipped: Vec<(u32, BorrowError)>,
     _marker: PhantomData<Q>,
 }
 
 impl<'w, Q: Query> QueryBorrow<'w, Q> {
-    pub(crate) fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype]) -> Self {
+    pub(crate) fn new(
+        meta: &'w [EntityMeta],
+        archetypes: &'w [Archetype],
+        frame: &'w FrameAllocator,
+        tick: u32,
+    ) -> Self {
         Self {
             meta,
             archetypes,
+            frame,
             borrowed: false,
+            iterated: false,
+            ticks: QueryTicks::new(tick),
+            prefetch: 0,
+            policy: BorrowPolicy::Panic,
+            skipped: Vec::new(),
             _marker: PhantomData,
         }
     }
 
+    /// Determine how to respond to conflicting borrows when the query is executed
+    ///
+    /// Defaults to the policy set by `World::set_borrow_policy`.
+    pub fn borrow_policy(mut self, policy: BorrowPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Report changes made at or after world change tick `tick` via `Added` and `Changed`
+    ///
+    /// Changes made at the world's current tick are excluded, to be reported by queries made after
+    /// the tick is next incremented. To observe every change exactly once, a system should run
+    /// `since` the value returned by `World::increment_change_tick` on its previous run. Ticks
+    /// older than an implementation-defined limit are treated as equal to that limit, so a system
+    /// that runs very rarely may see some old changes reported again.
+    ///
+    /// Defaults to 0, reporting all changes since the world was created.
+    pub fn since(mut self, tick: u32) -> Self {
+        self.ticks.since = tick;
+        self
+    }
+
+    /// Issue software prefetches for the components of the entity `distance` places ahead of the
+    /// one being yielded
+    ///
+    /// Can speed up iteration over large archetypes that doesn't otherwise keep the CPU busy
+    /// enough to hide memory latency. Tune `distance` by benchmarking; larger components generally
+    /// call for smaller distances. Has no effect on platforms without prefetch support.
+    ///
+    /// Defaults to 0, disabling prefetching.
+    pub fn prefetch(mut self, distance: u32) -> Self {
+        self.prefetch = distance;
+        self
+    }
+
     /// Execute the query
     ///
     /// Must be called only once per query.
     pub fn iter<'q>(&'q mut self) -> QueryIter<'q, 'w, Q> {
-        self.borrow();
+        if let Err(e) = self.borrow() {
+            panic!("{}", e);
+        }
         QueryIter {
             borrow: self,
             archetype_index: 0,
@@ -311,11 +799,112 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         }
     }
 
+    /// Like `iter`, but yielding owned clones of the components
+    ///
+    /// Convenient for collecting results that outlive the borrow, e.g. to send them to another
+    /// thread.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((String::from("abc"), 123));
+    /// let b = world.spawn((String::from("def"),));
+    /// let names = world
+    ///     .query::<(&String, Option<&i32>)>()
+    ///     .iter_cloned()
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     names,
+    ///     [(a, (String::from("abc"), Some(123))), (b, (String::from("def"), None))]
+    /// );
+    /// ```
+    pub fn iter_cloned<'q>(&'q mut self) -> ClonedIter<'q, 'w, Q>
+    where
+        Q: CloneQuery,
+    {
+        ClonedIter(self.iter())
+    }
+
+    /// Like `iter`, but returns an error rather than panicking if a component is already borrowed
+    /// under `BorrowPolicy::Error`
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// world.set_borrow_policy(BorrowPolicy::Error);
+    /// let mut a = world.query::<&mut i32>();
+    /// let _a = a.iter();
+    /// let mut b = world.query::<&i32>();
+    /// assert_eq!(b.try_iter().err().unwrap().type_name(), "i32");
+    /// ```
+    pub fn try_iter<'q>(&'q mut self) -> Result<QueryIter<'q, 'w, Q>, BorrowError> {
+        self.borrow()?;
+        Ok(QueryIter {
+            borrow: self,
+            archetype_index: 0,
+            iter: None,
+        })
+    }
+
+    /// Execute the query, yielding at most `limit` entities after skipping the first `offset`
+    ///
+    /// Equivalent to `iter().skip(offset).take(limit)`, but skips entire archetypes at once rather
+    /// than visiting every skipped entity, making it cheap to page through huge result sets, e.g.
+    /// in an editor or debug console. Pages are only stable while the world isn't modified.
+    ///
+    /// Must be called only once per query.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..100).map(|i| (i,)));
+    /// world.spawn_batch((100..200).map(|i| (i, true)));
+    /// let page = world.query::<&i32>()
+    ///     .iter_page(95, 10)
+    ///     .map(|(_, &x)| x)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(page, (95..105).collect::<Vec<_>>());
+    /// ```
+    pub fn iter_page<'q>(&'q mut self, offset: usize, limit: usize) -> Take<QueryIter<'q, 'w, Q>> {
+        let mut iter = self.iter();
+        iter.advance(offset);
+        iter.take(limit)
+    }
+
+    /// Errors encountered while borrowing archetypes that were skipped under `BorrowPolicy::Skip`
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// world.spawn((456,));
+    /// let mut a = world.query::<&mut i32>().with::<bool>();
+    /// let _a = a.iter();
+    /// let mut b = world.query::<&i32>().borrow_policy(BorrowPolicy::Skip);
+    /// assert_eq!(b.iter().map(|(_, &x)| x).collect::<Vec<_>>(), [456]);
+    /// assert_eq!(b.skipped().count(), 1);
+    /// ```
+    pub fn skipped(&self) -> impl ExactSizeIterator<Item = &BorrowError> + '_ {
+        self.skipped.iter().map(|x| &x.1)
+    }
+
+    /// Whether archetype `index` was skipped due to a borrow conflict
+    fn is_skipped(&self, index: u32) -> bool {
+        !self.skipped.is_empty() && self.skipped.iter().any(|x| x.0 == index)
+    }
+
     /// Like `iter`, but returns child iterators of at most `batch_size` elements
     ///
     /// Useful for distributing work over a threadpool.
     pub fn iter_batched<'q>(&'q mut self, batch_size: u32) -> BatchedIter<'q, 'w, Q> {
-        self.borrow();
+        if let Err(e) = self.borrow() {
+            panic!("{}", e);
+        }
         BatchedIter {
             borrow: self,
             archetype_index: 0,
@@ -324,19 +913,145 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         }
     }
 
-    fn borrow(&mut self) {
-        if self.borrowed {
+    /// Execute the query, yielding each archetype's entities alongside slices of their components
+    ///
+    /// `&T` yields `&[T]`, `&mut T` yields `&mut [T]`, `Option<Q>` yields an optional slice, and
+    /// `Added<T>` and `Changed<T>` yield `TickFlags`; all are length-matched with the entity slice,
+    /// so that a loop over indices, e.g. one written for SIMD, can tell which entity each lane
+    /// belongs to. Archetypes without any matching entities are skipped. The entity slices are taken
+    /// from `World::frame_allocator`, and not freed until the next `World::flush`.
+    ///
+    /// Must be called only once per query.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1.0f32, 2.0f32 as f64));
+    /// let b = world.spawn((3.0f32,));
+    /// let mut over = Vec::new();
+    /// for (entities, values) in world.query::<&mut f32>().iter_columns() {
+    ///     for i in 0..values.len() {
+    ///         values[i] *= 2.0;
+    ///         if values[i] > 4.0 {
+    ///             over.push(entities[i]);
+    ///         }
+    ///     }
+    /// }
+    /// assert_eq!(over, [b]);
+    /// ```
+    pub fn iter_columns<'q>(&'q mut self) -> ColumnIter<'q, 'w, Q> {
+        if let Err(e) = self.borrow() {
+            panic!("{}", e);
+        }
+        ColumnIter {
+            borrow: self,
+            archetype_index: 0,
+        }
+    }
+
+    /// Execute the query, yielding runs of entities whose `K` components are equal
+    ///
+    /// Groups are yielded in ascending order of `K`, with each group's entities in the same order as
+    /// `iter` would visit them. Entities lacking a `K` are skipped. Useful for e.g. batching draw
+    /// calls by material.
+    ///
+    /// Collects and sorts the query's entire result set up front, so takes `O(n log n)` time and
+    /// `O(n)` space in the number of matching entities. That space is taken from
+    /// `World::frame_allocator`, and not freed until the next `World::flush`.
+    ///
+    /// Must be called only once per query. Panics if `Q` uniquely borrows `K`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+    /// struct Material(u32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Material(1), "a"));
+    /// let b = world.spawn((Material(0), "b", true));
+    /// let c = world.spawn((Material(1), "c", true));
+    /// let groups = world.query::<&&str>()
+    ///     .iter_grouped_by::<Material>()
+    ///     .map(|(material, group)| (material.0, group.iter().map(|&(_, &s)| s).collect::<Vec<_>>()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(groups, &[(0, vec!["b"]), (1, vec!["a", "c"])]);
+    /// ```
+    pub fn iter_grouped_by<'q, K: Component + Ord + Clone>(&'q mut self) -> GroupedIter<'q, K, Q> {
+        let archetypes = self.archetypes;
+        let meta = self.meta;
+        for x in archetypes {
+            x.borrow::<K>();
+        }
+        let frame = self.frame;
+        let sorted =
+            frame.alloc_from_iter(self.iter().enumerate().filter_map(|(i, (entity, item))| {
+                let loc = meta[entity.id as usize].location;
+                let archetype = &archetypes[loc.archetype as usize];
+                let key = unsafe { &*archetype.get_at::<K>(loc.index)?.as_ptr() };
+                Some((key.clone(), i, entity, item))
+            }));
+        for x in archetypes {
+            x.release::<K>();
+        }
+        // Break ties by visitation order for stability, since a stable sort would allocate
+        sorted.sort_unstable_by(|x, y| x.0.cmp(&y.0).then(x.1.cmp(&y.1)));
+        // Ownership of the items moves to `rows`; keys are moved out of `sorted` as they're yielded
+        let rows = frame.alloc_from_iter(sorted.iter().map(|x| (x.2, unsafe { ptr::read(&x.3) })));
+        GroupedIter {
+            sorted,
+            rows,
+            next: 0,
+        }
+    }
+
+    fn borrow(&mut self) -> Result<(), BorrowError> {
+        if self.iterated {
             panic!(
                 "called QueryBorrow::iter twice on the same borrow; construct a new query instead"
             );
         }
-        for x in self.archetypes {
-            // TODO: Release prior borrows on failure?
-            if Q::Fetch::access(x) >= Some(Access::Read) {
-                Q::Fetch::borrow(x);
+        self.iterated = true;
+        if self.borrowed {
+            // Borrowed in advance by `borrow_now`
+            return Ok(());
+        }
+        for (i, x) in self.archetypes.iter().enumerate() {
+            if Q::Fetch::access(x) < Some(Access::Read) {
+                continue;
+            }
+            match self.policy {
+                // TODO: Release prior borrows on failure?
+                BorrowPolicy::Panic => Q::Fetch::borrow(x),
+                BorrowPolicy::Error => {
+                    if let Err(e) = Q::Fetch::try_borrow(x) {
+                        for x in &self.archetypes[..i] {
+                            if Q::Fetch::access(x) >= Some(Access::Read) {
+                                Q::Fetch::release(x);
+                            }
+                        }
+                        self.iterated = false;
+                        return Err(e);
+                    }
+                }
+                BorrowPolicy::Skip => {
+                    if let Err(e) = Q::Fetch::try_borrow(x) {
+                        self.skipped.push((i as u32, e));
+                    }
+                }
             }
         }
         self.borrowed = true;
+        Ok(())
+    }
+
+    /// Acquire borrows immediately, failing if any conflict regardless of the policy
+    pub(crate) fn borrow_now(mut self) -> Result<Self, BorrowError> {
+        let policy = mem::replace(&mut self.policy, BorrowPolicy::Error);
+        self.borrow()?;
+        self.policy = policy;
+        self.iterated = false;
+        Ok(self)
     }
 
     /// Transform the query into one that requires a certain component without borrowing it
@@ -387,12 +1102,69 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         self.transform()
     }
 
+    /// Divide the query into a half that only reads components, which can be shared, and a half
+    /// that writes them
+    ///
+    /// `R` and `W` must borrow only components that `Q` borrows, and no more strongly. Typically
+    /// `R` consists of `Q`'s shared borrows and `W` of its unique ones, so that the read half can
+    /// be passed to helper functions or other threads while the write half is iterated. Each half
+    /// matches entities independently, and filters in `Q` such as `With` don't apply to either.
+    ///
+    /// Panics if `R` would uniquely borrow any component, if `R` or `W` borrows a component
+    /// incompatibly with `Q` or with each other, or if the query has already been executed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, 10u32));
+    /// let b = world.spawn((2, 20u32));
+    /// let (read, mut write) = world
+    ///     .query::<(&i32, &mut u32)>()
+    ///     .split::<&i32, &mut u32>();
+    /// let total = read.iter().map(|(_, &x)| x).sum::<i32>();
+    /// for (e, x) in write.iter() {
+    ///     *x += (total + *read.get(e).unwrap()) as u32;
+    /// }
+    /// drop((read, write));
+    /// assert_eq!(*world.get::<u32>(a).unwrap(), 14);
+    /// assert_eq!(*world.get::<u32>(b).unwrap(), 25);
+    /// ```
+    pub fn split<R: Query, W: Query>(self) -> (QueryReadHalf<'w, R>, QueryBorrow<'w, W>) {
+        if self.borrowed {
+            panic!("called QueryBorrow::split after iter; construct a new query instead");
+        }
+        let access = QueryAccess::of::<Q>();
+        let read = QueryAccess::of::<R>();
+        let write = QueryAccess::of::<W>();
+        if !read.is_read_only() {
+            panic!("the read half of a split query must not write components");
+        }
+        if let Some(x) = access.excess(&read).or_else(|| access.excess(&write)) {
+            panic!(
+                "split query borrows {} incompatibly with the original",
+                x.type_name
+            );
+        }
+        if let Some(x) = read.conflicts(&write) {
+            panic!("split query halves both borrow {}", x.type_name);
+        }
+        let read = QueryReadHalf::new(self.meta, self.archetypes, self.ticks);
+        (read, self.transform())
+    }
+
     /// Helper to change the type of the query
     fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
         let x = QueryBorrow {
             meta: self.meta,
             archetypes: self.archetypes,
+            frame: self.frame,
             borrowed: self.borrowed,
+            iterated: self.iterated,
+            ticks: self.ticks,
+            prefetch: self.prefetch,
+            policy: self.policy,
+            skipped: mem::take(&mut self.skipped),
             _marker: PhantomData,
         };
         // Ensure `Drop` won't fire redundantly
@@ -407,8 +1179,8 @@ unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> {}
 impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
     fn drop(&mut self) {
         if self.borrowed {
-            for x in self.archetypes {
-                if Q::Fetch::access(x) >= Some(Access::Read) {
+            for (i, x) in self.archetypes.iter().enumerate() {
+                if Q::Fetch::access(x) >= Some(Access::Read) && !self.is_skipped(i as u32) {
                     Q::Fetch::release(x);
                 }
             }
@@ -445,11 +1217,16 @@ impl<'q, 'w, Q: Query> Iterator for QueryIter<'q, 'w, Q> {
                 None => {
                     let archetype = self.borrow.archetypes.get(self.archetype_index as usize)?;
                     self.archetype_index += 1;
+                    if self.borrow.is_skipped(self.archetype_index - 1) {
+                        continue;
+                    }
                     unsafe {
-                        self.iter = Q::Fetch::get(archetype, 0).map(|fetch| ChunkIter {
+                        let ticks = self.borrow.ticks;
+                        self.iter = Q::Fetch::get(archetype, 0, ticks).map(|fetch| ChunkIter {
                             entities: archetype.entities(),
                             fetch,
                             len: archetype.len(),
+                            prefetch: self.borrow.prefetch,
                         });
                     }
                 }
@@ -459,13 +1236,7 @@ impl<'q, 'w, Q: Query> Iterator for QueryIter<'q, 'w, Q> {
                         continue;
                     }
                     Some((id, components)) => {
-                        return Some((
-                            Entity {
-                                id,
-                                generation: self.borrow.meta[id as usize].generation,
-                            },
-                            components,
-                        ));
+                        return Some((self.borrow.meta[id as usize].entity(id), components));
                     }
                 },
             }
@@ -476,38 +1247,287 @@ impl<'q, 'w, Q: Query> Iterator for QueryIter<'q, 'w, Q> {
         let n = self.len();
         (n, Some(n))
     }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.advance(n);
+        self.next()
+    }
+}
+
+impl<'q, 'w, Q: Query> QueryIter<'q, 'w, Q> {
+    /// Skip the next `n` entities without fetching their components
+    fn advance(&mut self, mut n: usize) {
+        let ticks = self.borrow.ticks;
+        let prefetch = self.borrow.prefetch;
+        if let Some(ref iter) = self.iter {
+            if n < iter.len as usize {
+                let archetype = &self.borrow.archetypes[self.archetype_index as usize - 1];
+                let row = archetype.len() - iter.len + n as u32;
+                self.iter = unsafe { ChunkIter::new(archetype, row, ticks, prefetch) };
+                return;
+            }
+            n -= iter.len as usize;
+            self.iter = None;
+        }
+        while let Some(archetype) = self.borrow.archetypes.get(self.archetype_index as usize) {
+            self.archetype_index += 1;
+            if Q::Fetch::access(archetype).is_none()
+                || self.borrow.is_skipped(self.archetype_index - 1)
+            {
+                continue;
+            }
+            if n < archetype.len() as usize {
+                self.iter = unsafe { ChunkIter::new(archetype, n as u32, ticks, prefetch) };
+                return;
+            }
+            n -= archetype.len() as usize;
+        }
+    }
 }
 
 impl<'q, 'w, Q: Query> ExactSizeIterator for QueryIter<'q, 'w, Q> {
     fn len(&self) -> usize {
-        self.borrow
-            .archetypes
-            .iter()
-            .filter(|&x| Q::Fetch::access(x).is_some())
-            .map(|x| x.len() as usize)
-            .sum()
+        let current = self.iter.as_ref().map_or(0, |x| x.len as usize);
+        current
+            + self
+                .borrow
+                .archetypes
+                .iter()
+                .enumerate()
+                .skip(self.archetype_index as usize)
+                .filter(|&(i, x)| {
+                    Q::Fetch::access(x).is_some() && !self.borrow.is_skipped(i as u32)
+                })
+                .map(|(_, x)| x.len() as usize)
+                .sum::<usize>()
     }
 }
 
-struct ChunkIter<Q: Query> {
-    entities: NonNull<u32>,
-    fetch: Q::Fetch,
-    len: u32,
+/// Iterator over owned clones of the components in `Q`, from `QueryBorrow::iter_cloned`
+pub struct ClonedIter<'q, 'w, Q: Query>(QueryIter<'q, 'w, Q>);
+
+impl<Q: CloneQuery> Iterator for ClonedIter<'_, '_, Q> {
+    type Item = (Entity, Q::Owned);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entity, item) = self.0.next()?;
+        Some((entity, Q::clone_item(item)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<Q: CloneQuery> ExactSizeIterator for ClonedIter<'_, '_, Q> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+pub(crate) struct ChunkIter<Q: Query> {
+    pub(crate) entities: NonNull<u32>,
+    pub(crate) fetch: Q::Fetch,
+    pub(crate) len: u32,
+    /// Distance ahead to prefetch, or 0 to disable
+    pub(crate) prefetch: u32,
 }
 
 impl<Q: Query> ChunkIter<Q> {
+    /// Iterate over the entities in `archetype` from `row` onwards
+    ///
+    /// # Safety
+    /// `row` must be in-bounds, and `archetype` borrowed as required by `Q`
+    pub(crate) unsafe fn new(
+        archetype: &Archetype,
+        row: u32,
+        ticks: QueryTicks,
+        prefetch: u32,
+    ) -> Option<Self> {
+        Some(Self {
+            entities: NonNull::new_unchecked(archetype.entities().as_ptr().add(row as usize)),
+            fetch: Q::Fetch::get(archetype, row as usize, ticks)?,
+            len: archetype.len() - row,
+            prefetch,
+        })
+    }
+
     #[inline]
-    unsafe fn next<'a>(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'a>>::Item)> {
+    pub(crate) unsafe fn next<'a>(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'a>>::Item)> {
         if self.len == 0 {
             return None;
         }
         self.len -= 1;
+        if self.prefetch != 0 && self.len >= self.prefetch {
+            self.fetch.prefetch(self.prefetch as usize);
+        }
         let entity = self.entities.as_ptr();
         self.entities = NonNull::new_unchecked(entity.add(1));
         Some((*entity, self.fetch.next()))
     }
 }
 
+/// The read-only half of a query, obtained from `QueryBorrow::split`
+///
+/// Unlike a `QueryBorrow`, components are borrowed immediately, and the query may be executed any
+/// number of times through a shared reference.
+pub struct QueryReadHalf<'w, Q: Query> {
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    ticks: QueryTicks,
+    _marker: PhantomData<Q>,
+}
+
+impl<'w, Q: Query> QueryReadHalf<'w, Q> {
+    /// `Q` must not borrow any component uniquely
+    fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype], ticks: QueryTicks) -> Self {
+        for x in archetypes {
+            if Q::Fetch::access(x) >= Some(Access::Read) {
+                Q::Fetch::borrow(x);
+            }
+        }
+        Self {
+            meta,
+            archetypes,
+            ticks,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query
+    pub fn iter(&self) -> QueryReadIter<'_, 'w, Q> {
+        QueryReadIter {
+            half: self,
+            archetype_index: 0,
+            iter: None,
+        }
+    }
+
+    /// Execute the query on a single entity, if it exists and satisfies `Q`
+    pub fn get(&self, entity: Entity) -> Option<<Q::Fetch as Fetch<'_>>::Item> {
+        let meta = self.meta.get(entity.id as usize)?;
+        if meta.generation != entity.generation {
+            return None;
+        }
+        let archetype = &self.archetypes[meta.location.archetype as usize];
+        if meta.location.index >= archetype.len() {
+            // Reserved, but not yet flushed
+            return None;
+        }
+        unsafe {
+            let mut fetch = Q::Fetch::get(archetype, meta.location.index as usize, self.ticks)?;
+            Some(fetch.next())
+        }
+    }
+}
+
+unsafe impl<Q: Query> Send for QueryReadHalf<'_, Q> {}
+unsafe impl<Q: Query> Sync for QueryReadHalf<'_, Q> {}
+
+impl<Q: Query> Drop for QueryReadHalf<'_, Q> {
+    fn drop(&mut self) {
+        for x in self.archetypes {
+            if Q::Fetch::access(x) >= Some(Access::Read) {
+                Q::Fetch::release(x);
+            }
+        }
+    }
+}
+
+/// Iterator over the set of entities matched by a `QueryReadHalf`
+pub struct QueryReadIter<'q, 'w, Q: Query> {
+    half: &'q QueryReadHalf<'w, Q>,
+    archetype_index: u32,
+    iter: Option<ChunkIter<Q>>,
+}
+
+unsafe impl<Q: Query> Send for QueryReadIter<'_, '_, Q> {}
+unsafe impl<Q: Query> Sync for QueryReadIter<'_, '_, Q> {}
+
+impl<'q, Q: Query> Iterator for QueryReadIter<'q, '_, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter {
+                None => {
+                    let archetype = self.half.archetypes.get(self.archetype_index as usize)?;
+                    self.archetype_index += 1;
+                    unsafe {
+                        self.iter =
+                            Q::Fetch::get(archetype, 0, self.half.ticks).map(|fetch| ChunkIter {
+                                entities: archetype.entities(),
+                                fetch,
+                                len: archetype.len(),
+                                prefetch: 0,
+                            });
+                    }
+                }
+                Some(ref mut iter) => match unsafe { iter.next() } {
+                    None => {
+                        self.iter = None;
+                        continue;
+                    }
+                    Some((id, components)) => {
+                        return Some((self.half.meta[id as usize].entity(id), components));
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Iterator over groups of entities matched by a query, returned by `QueryBorrow::iter_grouped_by`
+pub struct GroupedIter<'q, K, Q: Query> {
+    /// Keys, in ascending order, alongside the rows they were sorted with
+    ///
+    /// Only keys at or after `next` are valid. Items have been moved into `rows`.
+    #[allow(clippy::type_complexity)]
+    sorted: &'q mut [(K, usize, Entity, <Q::Fetch as Fetch<'q>>::Item)],
+    /// The rows of `sorted` that are yet to be yielded
+    #[allow(clippy::type_complexity)]
+    rows: &'q mut [(Entity, <Q::Fetch as Fetch<'q>>::Item)],
+    next: usize,
+}
+
+impl<'q, K: Eq, Q: Query> Iterator for GroupedIter<'q, K, Q> {
+    type Item = (K, &'q mut [(Entity, <Q::Fetch as Fetch<'q>>::Item)]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.next;
+        let key = &self.sorted.get(start)?.0;
+        let len = self.sorted[start..]
+            .iter()
+            .take_while(|x| x.0 == *key)
+            .count();
+        self.next += len;
+        let key = unsafe {
+            for x in &mut self.sorted[start + 1..self.next] {
+                ptr::drop_in_place(&mut x.0);
+            }
+            ptr::read(&self.sorted[start].0)
+        };
+        let (group, rest) = mem::take(&mut self.rows).split_at_mut(len);
+        self.rows = rest;
+        Some((key, group))
+    }
+}
+
+impl<K, Q: Query> Drop for GroupedIter<'_, K, Q> {
+    fn drop(&mut self) {
+        for x in &mut self.sorted[self.next..] {
+            unsafe {
+                ptr::drop_in_place(&mut x.0);
+            }
+        }
+    }
+}
+
+unsafe impl<K: Send, Q: Query> Send for GroupedIter<'_, K, Q> {}
+unsafe impl<K: Sync, Q: Query> Sync for GroupedIter<'_, K, Q> {}
+
 /// Batched version of `QueryIter`
 pub struct BatchedIter<'q, 'w, Q: Query> {
     borrow: &'q mut QueryBorrow<'w, Q>,
@@ -526,12 +1546,13 @@ impl<'q, 'w, Q: Query> Iterator for BatchedIter<'q, 'w, Q> {
         loop {
             let archetype = self.borrow.archetypes.get(self.archetype_index as usize)?;
             let offset = self.batch_size * self.batch;
-            if offset >= archetype.len() {
+            if offset >= archetype.len() || self.borrow.is_skipped(self.archetype_index) {
                 self.archetype_index += 1;
                 self.batch = 0;
                 continue;
             }
-            if let Some(fetch) = unsafe { Q::Fetch::get(archetype, offset as usize) } {
+            let ticks = self.borrow.ticks;
+            if let Some(fetch) = unsafe { Q::Fetch::get(archetype, offset as usize, ticks) } {
                 self.batch += 1;
                 return Some(Batch {
                     _marker: PhantomData,
@@ -544,6 +1565,7 @@ impl<'q, 'w, Q: Query> Iterator for BatchedIter<'q, 'w, Q> {
                         },
                         fetch,
                         len: self.batch_size.min(archetype.len() - offset),
+                        prefetch: self.borrow.prefetch,
                     },
                 });
             } else {
@@ -558,6 +1580,47 @@ impl<'q, 'w, Q: Query> Iterator for BatchedIter<'q, 'w, Q> {
     }
 }
 
+/// Iterator over the archetypes matched by `Q`, yielding their entities and slices of their
+/// components
+///
+/// Obtained from `QueryBorrow::iter_columns`.
+pub struct ColumnIter<'q, 'w, Q: Query> {
+    borrow: &'q mut QueryBorrow<'w, Q>,
+    archetype_index: u32,
+}
+
+unsafe impl<'q, 'w, Q: Query> Send for ColumnIter<'q, 'w, Q> {}
+unsafe impl<'q, 'w, Q: Query> Sync for ColumnIter<'q, 'w, Q> {}
+
+impl<'q, 'w, Q: Query> Iterator for ColumnIter<'q, 'w, Q> {
+    type Item = (&'q [Entity], <Q::Fetch as Fetch<'q>>::Slice);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let archetype = self.borrow.archetypes.get(self.archetype_index as usize)?;
+            self.archetype_index += 1;
+            if archetype.is_empty() || self.borrow.is_skipped(self.archetype_index - 1) {
+                continue;
+            }
+            let ticks = self.borrow.ticks;
+            let mut fetch = match unsafe { Q::Fetch::get(archetype, 0, ticks) } {
+                Some(x) => x,
+                None => continue,
+            };
+            let meta = self.borrow.meta;
+            let entities = self
+                .borrow
+                .frame
+                .alloc_from_iter((0..archetype.len()).map(|i| {
+                    let id = archetype.entity_id(i);
+                    meta[id as usize].entity(id)
+                }));
+            let columns = unsafe { fetch.slice(archetype.len() as usize) };
+            return Some((entities, columns));
+        }
+    }
+}
+
 /// A sequence of entities yielded by `BatchedIter`
 pub struct Batch<'q, 'w, Q: Query> {
     _marker: PhantomData<&'q ()>,
@@ -570,13 +1633,7 @@ impl<'q, 'w, Q: Query> Iterator for Batch<'q, 'w, Q> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let (id, components) = unsafe { self.state.next()? };
-        Some((
-            Entity {
-                id,
-                generation: self.meta[id as usize].generation,
-            },
-            components,
-        ))
+        Some((self.meta[id as usize].entity(id), components))
     }
 }
 
@@ -587,6 +1644,7 @@ macro_rules! tuple_impl {
     ($($name: ident),*) => {
         impl<'a, $($name: Fetch<'a>),*> Fetch<'a> for ($($name,)*) {
             type Item = ($($name::Item,)*);
+            type Slice = ($($name::Slice,)*);
 
             #[allow(unused_variables, unused_mut)]
             fn access(archetype: &Archetype) -> Option<Access> {
@@ -597,29 +1655,77 @@ macro_rules! tuple_impl {
                 Some(access)
             }
 
+            #[allow(unused_variables, unused_mut, unused_assignments, clippy::redundant_closure_call)]
+            fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+                let mut borrowed = 0;
+                let result = (|| {
+                    $(
+                        $name::try_borrow(archetype)?;
+                        borrowed += 1;
+                    )*
+                    Ok(())
+                })();
+                if result.is_err() {
+                    // Release whatever was borrowed before the failure
+                    let mut i = 0;
+                    $(
+                        if i < borrowed {
+                            $name::release(archetype);
+                        }
+                        i += 1;
+                    )*
+                }
+                result
+            }
             #[allow(unused_variables)]
-            fn borrow(archetype: &Archetype) {
-                $($name::borrow(archetype);)*
+            fn for_each_borrow(f: &mut dyn FnMut(TypeId, &'static str, bool)) {
+                $($name::for_each_borrow(f);)*
             }
             #[allow(unused_variables)]
-            unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-                Some(($($name::get(archetype, offset)?,)*))
+            unsafe fn get(archetype: &'a Archetype, offset: usize, ticks: QueryTicks) -> Option<Self> {
+                Some(($($name::get(archetype, offset, ticks)?,)*))
             }
             #[allow(unused_variables)]
             fn release(archetype: &Archetype) {
                 $($name::release(archetype);)*
             }
 
+            #[allow(clippy::unused_unit)]
             unsafe fn next(&mut self) -> Self::Item {
                 #[allow(non_snake_case)]
                 let ($($name,)*) = self;
                 ($($name.next(),)*)
             }
+
+            #[allow(unused_variables, clippy::unused_unit)]
+            unsafe fn slice(&mut self, len: usize) -> Self::Slice {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                ($($name.slice(len),)*)
+            }
+
+            #[allow(unused_variables)]
+            fn prefetch(&self, distance: usize) {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                $($name.prefetch(distance);)*
+            }
         }
 
         impl<$($name: Query),*> Query for ($($name,)*) {
             type Fetch = ($($name::Fetch,)*);
         }
+
+        impl<$($name: CloneQuery),*> CloneQuery for ($($name,)*) {
+            type Owned = ($($name::Owned,)*);
+
+            #[allow(clippy::unused_unit)]
+            fn clone_item(item: <Self::Fetch as Fetch<'_>>::Item) -> Self::Owned {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = item;
+                ($($name::clone_item($name),)*)
+            }
+        }
     };
 }
 
@@ -630,6 +1736,21 @@ smaller_tuples_too!(tuple_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
 mod tests {
     use super::*;
 
+    #[test]
+    fn tick_wraparound() {
+        let ticks = QueryTicks {
+            since: u32::MAX - 1,
+            now: 2,
+        };
+        assert!(!ticks.is_new(u32::MAX - 2));
+        assert!(ticks.is_new(u32::MAX - 1));
+        assert!(ticks.is_new(1));
+        assert!(!ticks.is_new(2));
+        let stale = QueryTicks { since: 3, now: 2 };
+        assert!(stale.is_new(2u32.wrapping_sub(MAX_CHANGE_AGE)));
+        assert!(!stale.is_new(1u32.wrapping_sub(MAX_CHANGE_AGE)));
+    }
+
     #[test]
     fn access_order() {
         assert!(Access::Write > Access::Read);
@@ -1,6 +1,6 @@
 use core::marker::PhantomData;
 
-use crate::query::{Fetch, With, Without};
+use crate::query::{Fetch, QueryTicks, With, Without};
 use crate::{Archetype, Component, Query};
 
 /// A borrow of a `World` sufficient to execute the query `Q` on a single entity
@@ -8,6 +8,7 @@ pub struct QueryOne<'a, Q: Query> {
     archetype: &'a Archetype,
     index: u32,
     borrowed: bool,
+    ticks: QueryTicks,
     _marker: PhantomData<Q>,
 }
 
@@ -17,11 +18,12 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
     /// # Safety
     ///
     /// `index` must be in-bounds for `archetype`
-    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32) -> Self {
+    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32, tick: u32) -> Self {
         Self {
             archetype,
             index,
             borrowed: false,
+            ticks: QueryTicks::new(tick),
             _marker: PhantomData,
         }
     }
@@ -37,13 +39,21 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
             panic!("called QueryOnce::get twice; construct a new query instead");
         }
         unsafe {
-            let mut fetch = Q::Fetch::get(self.archetype, self.index as usize)?;
+            let mut fetch = Q::Fetch::get(self.archetype, self.index as usize, self.ticks)?;
             self.borrowed = true;
             Q::Fetch::borrow(self.archetype);
             Some(fetch.next())
         }
     }
 
+    /// Report changes made at or after world change tick `tick` via `Added` and `Changed`
+    ///
+    /// See `QueryBorrow::since` for details.
+    pub fn since(mut self, tick: u32) -> Self {
+        self.ticks.since = tick;
+        self
+    }
+
     /// Transform the query into one that requires a certain component without borrowing it
     ///
     /// See `QueryBorrow::with` for details.
@@ -64,6 +74,7 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
             archetype: self.archetype,
             index: self.index,
             borrowed: self.borrowed,
+            ticks: self.ticks,
             _marker: PhantomData,
         };
         // Ensure `Drop` won't fire redundantly
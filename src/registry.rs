@@ -0,0 +1,255 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::{
+    Component, ComponentError, DynamicBundle, Entity, EntityBuilder, NoSuchEntity, Ref, RefMut,
+    World,
+};
+
+/// A collection of named worlds, e.g. one per level or dimension
+///
+/// Entities in any of the worlds can be identified by `GlobalEntity` handles, and moved between
+/// worlds with `transfer`. Systems written against `&mut World` can be run on each world in turn
+/// with `iter_mut`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut worlds = WorldRegistry::new();
+/// let overworld = worlds.insert("overworld", World::new());
+/// let nether = worlds.insert("nether", World::new());
+/// let player = worlds.spawn(overworld, ("player", 100));
+///
+/// let player = worlds.transfer(player, nether).unwrap();
+/// assert_eq!(player.world, nether);
+/// assert_eq!(*worlds.get::<i32>(player).unwrap(), 100);
+/// assert_eq!(worlds[overworld].iter().count(), 0);
+/// ```
+#[derive(Default)]
+pub struct WorldRegistry {
+    /// Indexed by `WorldId`. Removed worlds leave a gap, so IDs are never reused.
+    worlds: Vec<Slot>,
+    by_name: HashMap<Box<str>, WorldId>,
+    /// Reused for moving entities between worlds
+    builder: EntityBuilder,
+}
+
+impl WorldRegistry {
+    /// Create a registry containing no worlds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `world` under `name`, returning its ID
+    ///
+    /// Panics if a world named `name` is already present.
+    pub fn insert(&mut self, name: &str, world: World) -> WorldId {
+        assert!(
+            !self.by_name.contains_key(name),
+            "a world named {} already exists",
+            name
+        );
+        let id = WorldId(self.worlds.len() as u32);
+        self.worlds.push(Some((name.into(), world)));
+        self.by_name.insert(name.into(), id);
+        id
+    }
+
+    /// Remove and return the world `id`, if present
+    ///
+    /// Handles to its entities are invalid from then on, even if it's inserted again.
+    pub fn remove(&mut self, id: WorldId) -> Option<World> {
+        let (name, world) = self.worlds.get_mut(id.0 as usize)?.take()?;
+        self.by_name.remove(&name);
+        Some(world)
+    }
+
+    /// The ID of the world named `name`, if present
+    pub fn id(&self, name: &str) -> Option<WorldId> {
+        self.by_name.get(name).copied()
+    }
+
+    /// The name of the world `id`, if present
+    pub fn name(&self, id: WorldId) -> Option<&str> {
+        self.slot(id).map(|x| &*x.0)
+    }
+
+    /// Access the world `id`, if present
+    pub fn world(&self, id: WorldId) -> Option<&World> {
+        self.slot(id).map(|x| &x.1)
+    }
+
+    /// Uniquely access the world `id`, if present
+    pub fn world_mut(&mut self, id: WorldId) -> Option<&mut World> {
+        slot_world(self.worlds.get_mut(id.0 as usize))
+    }
+
+    /// Uniquely access two different worlds at once, e.g. to copy data between them
+    ///
+    /// Panics if `a` and `b` are the same.
+    pub fn world_pair_mut(
+        &mut self,
+        a: WorldId,
+        b: WorldId,
+    ) -> (Option<&mut World>, Option<&mut World>) {
+        pair_mut(&mut self.worlds, a, b)
+    }
+
+    /// Iterate over every world, with its ID and name
+    pub fn iter(&self) -> impl Iterator<Item = (WorldId, &str, &World)> + '_ {
+        self.worlds.iter().enumerate().filter_map(|(i, x)| {
+            let (name, world) = x.as_ref()?;
+            Some((WorldId(i as u32), &**name, world))
+        })
+    }
+
+    /// Iterate over every world uniquely, with its ID and name
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (WorldId, &str, &mut World)> + '_ {
+        self.worlds.iter_mut().enumerate().filter_map(|(i, x)| {
+            let (name, world) = x.as_mut()?;
+            Some((WorldId(i as u32), &**name, world))
+        })
+    }
+
+    /// Number of worlds
+    pub fn len(&self) -> u32 {
+        self.by_name.len() as u32
+    }
+
+    /// Whether there are no worlds
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// Spawn an entity into the world `id`
+    ///
+    /// Panics if the world doesn't exist. See `World::spawn`.
+    pub fn spawn(&mut self, id: WorldId, components: impl DynamicBundle) -> GlobalEntity {
+        let entity = self.expect_mut(id).spawn(components);
+        GlobalEntity { world: id, entity }
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: GlobalEntity) -> bool {
+        self.world(entity.world)
+            .is_some_and(|x| x.contains(entity.entity))
+    }
+
+    /// Borrow the `T` component of `entity`
+    ///
+    /// See `World::get`.
+    pub fn get<T: Component>(&self, entity: GlobalEntity) -> Result<Ref<'_, T>, ComponentError> {
+        self.world(entity.world)
+            .ok_or(NoSuchEntity)?
+            .get::<T>(entity.entity)
+    }
+
+    /// Uniquely borrow the `T` component of `entity`
+    ///
+    /// See `World::get_mut`.
+    pub fn get_mut<T: Component>(
+        &self,
+        entity: GlobalEntity,
+    ) -> Result<RefMut<'_, T>, ComponentError> {
+        self.world(entity.world)
+            .ok_or(NoSuchEntity)?
+            .get_mut::<T>(entity.entity)
+    }
+
+    /// Destroy `entity` and all its components
+    pub fn despawn(&mut self, entity: GlobalEntity) -> Result<(), NoSuchEntity> {
+        self.world_mut(entity.world)
+            .ok_or(NoSuchEntity)?
+            .despawn(entity.entity)
+    }
+
+    /// Move `entity`, with all its components, into the world `to`, returning its new handle
+    ///
+    /// The old handle becomes invalid. If `entity` is already in `to`, it's returned unchanged.
+    ///
+    /// Panics if `to` doesn't exist, or if a limit set by `World::set_entity_limit` or
+    /// `World::set_archetype_limit` would be exceeded.
+    pub fn transfer(
+        &mut self,
+        entity: GlobalEntity,
+        to: WorldId,
+    ) -> Result<GlobalEntity, NoSuchEntity> {
+        if entity.world == to {
+            if !self.expect(to).contains(entity.entity) {
+                return Err(NoSuchEntity);
+            }
+            return Ok(entity);
+        }
+        let (source, target) = pair_mut(&mut self.worlds, entity.world, to);
+        let target = target.expect("no such world");
+        let source = source.ok_or(NoSuchEntity)?;
+        source.take_dynamic(entity.entity, &mut self.builder)?;
+        Ok(GlobalEntity {
+            world: to,
+            entity: target.spawn(self.builder.build()),
+        })
+    }
+
+    fn slot(&self, id: WorldId) -> Option<&(Box<str>, World)> {
+        self.worlds.get(id.0 as usize)?.as_ref()
+    }
+
+    fn expect(&self, id: WorldId) -> &World {
+        self.world(id).expect("no such world")
+    }
+
+    fn expect_mut(&mut self, id: WorldId) -> &mut World {
+        self.world_mut(id).expect("no such world")
+    }
+}
+
+impl core::ops::Index<WorldId> for WorldRegistry {
+    type Output = World;
+
+    /// Panics if the world doesn't exist
+    fn index(&self, id: WorldId) -> &World {
+        self.expect(id)
+    }
+}
+
+impl core::ops::IndexMut<WorldId> for WorldRegistry {
+    /// Panics if the world doesn't exist
+    fn index_mut(&mut self, id: WorldId) -> &mut World {
+        self.expect_mut(id)
+    }
+}
+
+type Slot = Option<(Box<str>, World)>;
+
+fn pair_mut(
+    worlds: &mut [Slot],
+    a: WorldId,
+    b: WorldId,
+) -> (Option<&mut World>, Option<&mut World>) {
+    assert_ne!(a, b, "worlds must be distinct");
+    let (x, y) = (a.0 as usize, b.0 as usize);
+    let split = x.max(y).min(worlds.len());
+    let (low, high) = worlds.split_at_mut(split);
+    let (low, high) = (low.get_mut(x.min(y)), high.first_mut());
+    let (first, second) = if x < y { (low, high) } else { (high, low) };
+    (slot_world(first), slot_world(second))
+}
+
+fn slot_world(slot: Option<&mut Slot>) -> Option<&mut World> {
+    slot.and_then(|x| x.as_mut()).map(|x| &mut x.1)
+}
+
+/// Identifies a world in a `WorldRegistry`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct WorldId(u32);
+
+/// Identifies an entity in some world of a `WorldRegistry`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct GlobalEntity {
+    /// The world containing the entity
+    pub world: WorldId,
+    /// The entity's handle within `world`
+    pub entity: Entity,
+}
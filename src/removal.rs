@@ -0,0 +1,97 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+
+use crate::archetype::TypeInfo;
+use crate::{Component, Entity};
+
+/// A destination for `T` components that the `World` would otherwise drop
+///
+/// Register with `World::add_removal_sink`. The world then hands over every `T` that is discarded
+/// by `despawn`, `clear`, replacement via `insert`, or removal via `Modification`, allowing e.g.
+/// resource managers to reclaim handles stored in components without tracking them separately.
+/// Components returned to the caller, as by `World::remove` or `World::insert_one`, are not
+/// delivered.
+pub trait RemovalSink<T: Component>: Send + Sync + 'static {
+    /// `entity` lost `component`
+    fn removed(&mut self, entity: Entity, component: T);
+}
+
+/// Buffers removed components for later processing
+impl<T: Component> RemovalSink<T> for Vec<(Entity, T)> {
+    fn removed(&mut self, entity: Entity, component: T) {
+        self.push((entity, component));
+    }
+}
+
+/// Type-erased `RemovalSink` storage
+#[derive(Default)]
+pub(crate) struct RemovalSinks {
+    sinks: HashMap<TypeId, Box<dyn ErasedSink>>,
+}
+
+impl RemovalSinks {
+    pub fn add<T: Component, S: RemovalSink<T>>(&mut self, sink: S) {
+        self.sinks.insert(
+            TypeId::of::<T>(),
+            Box::new(Typed::<T, S>(sink, PhantomData)),
+        );
+    }
+
+    pub fn get<T: Component, S: RemovalSink<T>>(&self) -> Option<&S> {
+        let x = self.sinks.get(&TypeId::of::<T>())?;
+        x.as_any().downcast_ref::<Typed<T, S>>().map(|x| &x.0)
+    }
+
+    pub fn get_mut<T: Component, S: RemovalSink<T>>(&mut self) -> Option<&mut S> {
+        let x = self.sinks.get_mut(&TypeId::of::<T>())?;
+        x.as_any_mut()
+            .downcast_mut::<Typed<T, S>>()
+            .map(|x| &mut x.0)
+    }
+
+    pub fn remove<T: Component>(&mut self) -> bool {
+        self.sinks.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Deliver the `ty` component at `ptr`, which belonged to `entity`, to its sink, or drop it
+    /// if there is none
+    ///
+    /// # Safety
+    /// `ptr` must point to a valid `ty`, which must not be used afterwards
+    pub unsafe fn discard(&mut self, entity: Entity, ty: &TypeInfo, ptr: *mut u8) {
+        match self.sinks.get_mut(&ty.id()) {
+            Some(x) => x.take(entity, ptr),
+            None => ty.drop(ptr),
+        }
+    }
+}
+
+trait ErasedSink: Send + Sync {
+    unsafe fn take(&mut self, entity: Entity, ptr: *mut u8);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Typed<T, S>(S, PhantomData<fn(T)>);
+
+impl<T: Component, S: RemovalSink<T>> ErasedSink for Typed<T, S> {
+    unsafe fn take(&mut self, entity: Entity, ptr: *mut u8) {
+        self.0.removed(entity, ptr.cast::<T>().read());
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
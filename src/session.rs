@@ -0,0 +1,221 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::type_name;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::{Bundle, DynamicBundle, Entity, Query, QueryAccess, QueryBorrow, World};
+
+/// A scope in which parts of a `World` are handed out to independent systems
+///
+/// Created by `World::session`. Each `SystemContext` taken from a session may run the query it was
+/// created for, and record structural changes like spawns and despawns to be applied once the
+/// session ends. Contexts are checked against each other for conflicting access when they're
+/// created, so any number of them may be moved to scoped threads and run concurrently without
+/// risking a borrow panic.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1i32, 1.0f32));
+/// let spawned = world.session(|session| {
+///     let mut ints = session.system::<&mut i32>();
+///     let mut floats = session.system::<&f32>();
+///     std::thread::scope(|s| {
+///         s.spawn(move || {
+///             for (_, x) in ints.query().iter() {
+///                 *x += 1;
+///             }
+///             ints.despawn(a);
+///         });
+///         s.spawn(move || floats.spawn((floats.query().iter().count(),))).join().unwrap()
+///     })
+/// });
+/// assert!(!world.contains(a));
+/// assert_eq!(*world.get::<usize>(spawned).unwrap(), 1);
+/// ```
+pub struct Session<'w> {
+    world: &'w World,
+    /// Access granted to every context handed out so far
+    claimed: RefCell<QueryAccess>,
+    commands: CommandQueue,
+}
+
+impl<'w> Session<'w> {
+    fn new(world: &'w World) -> Self {
+        Self {
+            world,
+            claimed: RefCell::new(QueryAccess::new()),
+            commands: CommandQueue::new(),
+        }
+    }
+
+    /// Hand out a context for a system that executes `Q`
+    ///
+    /// Access is granted for the remainder of the session, even after the context is dropped.
+    ///
+    /// Panics if `Q` could conflict with a context previously handed out by this session.
+    pub fn system<Q: Query>(&self) -> SystemContext<'_, Q> {
+        let access = QueryAccess::of::<Q>();
+        let mut claimed = self.claimed.borrow_mut();
+        if let Some(x) = claimed.conflicts_in(&access, self.world) {
+            panic!(
+                "system context for {} conflicts with an existing context over {}",
+                type_name::<Q>(),
+                x.type_name
+            );
+        }
+        claimed.extend(&access);
+        SystemContext {
+            world: self.world,
+            sink: &self.commands,
+            commands: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Access to the `World` for a system executing `Q` within a `Session`
+///
+/// Structural changes recorded through a context are applied in the order they were recorded,
+/// after those of any contexts dropped before it, once the session ends. Changes to entities that
+/// no longer exist by then are ignored.
+pub struct SystemContext<'s, Q: Query> {
+    world: &'s World,
+    sink: &'s CommandQueue,
+    commands: Vec<Command>,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<'s, Q: Query> SystemContext<'s, Q> {
+    /// Prepare to execute `Q`
+    ///
+    /// Like `World::query`. Never panics on borrowing unless another `QueryBorrow` from this
+    /// context is still live.
+    pub fn query(&self) -> QueryBorrow<'s, Q> {
+        self.world.query()
+    }
+
+    /// Reserve an entity to be spawned with `components` when the session ends
+    pub fn spawn(&mut self, components: impl DynamicBundle + Send + 'static) -> Entity {
+        let entity = self.world.reserve_entity();
+        self.defer(move |world| {
+            let _ = world.insert(entity, components);
+        });
+        entity
+    }
+
+    /// Add `components` to `entity` when the session ends
+    pub fn insert(&mut self, entity: Entity, components: impl DynamicBundle + Send + 'static) {
+        self.defer(move |world| {
+            let _ = world.insert(entity, components);
+        });
+    }
+
+    /// Remove and drop the `T` components of `entity` when the session ends
+    pub fn remove<T: Bundle + 'static>(&mut self, entity: Entity) {
+        self.defer(move |world| {
+            let _ = world.remove::<T>(entity);
+        });
+    }
+
+    /// Despawn `entity` when the session ends
+    pub fn despawn(&mut self, entity: Entity) {
+        self.defer(move |world| {
+            let _ = world.despawn(entity);
+        });
+    }
+
+    /// Call `f` on the world when the session ends
+    pub fn defer(&mut self, f: impl FnOnce(&mut World) + Send + 'static) {
+        self.commands.push(Box::new(f));
+    }
+}
+
+impl<Q: Query> Drop for SystemContext<'_, Q> {
+    fn drop(&mut self) {
+        if !self.commands.is_empty() {
+            self.sink.push(core::mem::take(&mut self.commands));
+        }
+    }
+}
+
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Lock-free stack of the commands recorded by each dropped context
+struct CommandQueue {
+    head: AtomicPtr<Batch>,
+}
+
+struct Batch {
+    next: *mut Batch,
+    commands: Vec<Command>,
+}
+
+impl CommandQueue {
+    fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    fn push(&self, commands: Vec<Command>) {
+        let batch = Box::into_raw(Box::new(Batch {
+            next: ptr::null_mut(),
+            commands,
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe {
+                (*batch).next = head;
+            }
+            match self
+                .head
+                .compare_exchange_weak(head, batch, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(x) => head = x,
+            }
+        }
+    }
+
+    /// Remove every batch, in the order they were pushed
+    fn drain(&mut self) -> Vec<Vec<Command>> {
+        let mut out = Vec::new();
+        let mut batch = core::mem::replace(self.head.get_mut(), ptr::null_mut());
+        while !batch.is_null() {
+            let boxed = unsafe { Box::from_raw(batch) };
+            batch = boxed.next;
+            out.push(boxed.commands);
+        }
+        out.reverse();
+        out
+    }
+}
+
+impl Drop for CommandQueue {
+    fn drop(&mut self) {
+        self.drain();
+    }
+}
+
+impl World {
+    /// Run `f` with a `Session` that hands out access-checked contexts for concurrent systems,
+    /// then apply the structural changes they recorded
+    ///
+    /// A middle ground between executing queries directly and a full scheduler. See `Session` for
+    /// details. If `f` panics, recorded changes are discarded.
+    pub fn session<R>(&mut self, f: impl FnOnce(&Session<'_>) -> R) -> R {
+        let mut session = Session::new(self);
+        let result = f(&session);
+        let batches = session.commands.drain();
+        drop(session);
+        for command in batches.into_iter().flatten() {
+            command(self);
+        }
+        result
+    }
+}
@@ -0,0 +1,182 @@
+use crate::alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::marker::PhantomData;
+
+use hashbrown::hash_map::DefaultHashBuilder;
+use hashbrown::HashMap;
+
+use crate::{Component, Entity, RemovalSink};
+
+/// A counted reference to a `T` stored once in a `World`, for use as a component
+///
+/// Allows any number of entities to share a large immutable value, such as a mesh descriptor,
+/// while each stores only a small index. Obtain from `World::share` or `World::intern`, duplicate
+/// with `World::clone_shared`, and read the value with `World::get_shared`.
+///
+/// The value is dropped when the last reference is discarded by the world, as by `despawn`, or
+/// returned with `World::release_shared`. A `Shared` dropped by other means, e.g. after being
+/// removed with `World::remove`, keeps its value alive until the world is dropped.
+///
+/// A `Shared` is only meaningful to the world it was obtained from. Other worlds, including those
+/// created by `World::clone_with`, treat it as referring to nothing.
+pub struct Shared<T> {
+    index: u32,
+    /// Distinct from that of any other world's references
+    world: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Shared<T> {
+    fn new(index: u32, world: u32) -> Self {
+        Self {
+            index,
+            world,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.world == other.world
+    }
+}
+
+impl<T> Eq for Shared<T> {}
+
+impl<T> Hash for Shared<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.world.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Shared").field(&self.index).finish()
+    }
+}
+
+/// Storage for the values referenced by `Shared<T>`s
+///
+/// Lives in the world's removal sink for `Shared<T>`, so that it's informed when references are
+/// discarded.
+pub(crate) struct SharedValues<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    /// Indices of interned values, by hash
+    interned: HashMap<u64, Vec<u32>>,
+    hasher: DefaultHashBuilder,
+    /// Distinct from that of any other world's references
+    world: u32,
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    refs: u32,
+    interned: Option<u64>,
+}
+
+impl<T: Component> SharedValues<T> {
+    pub fn new(world: u32) -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            interned: HashMap::default(),
+            hasher: DefaultHashBuilder::default(),
+            world,
+        }
+    }
+
+    pub fn share(&mut self, value: T) -> Shared<T> {
+        self.alloc(value, None)
+    }
+
+    pub fn intern(&mut self, value: T) -> Shared<T>
+    where
+        T: Hash + Eq,
+    {
+        let hash = self.hasher.hash_one(&value);
+        if let Some(candidates) = self.interned.get(&hash) {
+            for &index in candidates {
+                let slot = &mut self.slots[index as usize];
+                if slot.value.as_ref() == Some(&value) {
+                    slot.refs += 1;
+                    return Shared::new(index, self.world);
+                }
+            }
+        }
+        let shared = self.alloc(value, Some(hash));
+        self.interned.entry(hash).or_default().push(shared.index);
+        shared
+    }
+
+    fn alloc(&mut self, value: T, interned: Option<u64>) -> Shared<T> {
+        let slot = Slot {
+            value: Some(value),
+            refs: 1,
+            interned,
+        };
+        match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize] = slot;
+                Shared::new(index, self.world)
+            }
+            None => {
+                self.slots.push(slot);
+                Shared::new(self.slots.len() as u32 - 1, self.world)
+            }
+        }
+    }
+
+    /// The live slot `shared` refers to, if it came from this world
+    fn slot(&mut self, shared: &Shared<T>) -> Option<&mut Slot<T>> {
+        if shared.world != self.world {
+            return None;
+        }
+        self.slots
+            .get_mut(shared.index as usize)
+            .filter(|x| x.value.is_some())
+    }
+
+    pub fn get(&self, shared: &Shared<T>) -> Option<&T> {
+        if shared.world != self.world {
+            return None;
+        }
+        self.slots.get(shared.index as usize)?.value.as_ref()
+    }
+
+    pub fn clone_shared(&mut self, shared: &Shared<T>) -> Option<Shared<T>> {
+        self.slot(shared)?.refs += 1;
+        Some(Shared::new(shared.index, self.world))
+    }
+
+    pub fn release(&mut self, shared: Shared<T>) -> bool {
+        let slot = match self.slot(&shared) {
+            Some(x) => x,
+            None => return false,
+        };
+        slot.refs -= 1;
+        if slot.refs != 0 {
+            return true;
+        }
+        slot.value = None;
+        if let Some(hash) = slot.interned.take() {
+            let candidates = self.interned.get_mut(&hash).unwrap();
+            candidates.retain(|&x| x != shared.index);
+            if candidates.is_empty() {
+                self.interned.remove(&hash);
+            }
+        }
+        self.free.push(shared.index);
+        true
+    }
+}
+
+impl<T: Component> RemovalSink<Shared<T>> for SharedValues<T> {
+    fn removed(&mut self, _: Entity, component: Shared<T>) {
+        // References from other worlds, e.g. copied by `World::clone_with`, hold nothing here
+        self.release(component);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::alloc::sync::Arc;
+use crate::alloc::vec::Vec;
+use core::any::{Any, TypeId};
+
+use hashbrown::HashMap;
+
+use crate::archetype::ComponentTicks;
+use crate::entities::Entities;
+use crate::{Archetype, ArchetypesGeneration, Component, DynamicBundle, EntityBuilder, World};
+
+/// Captures and restores the complete state of a `World`, e.g. for rollback networking
+///
+/// Snapshots share storage with one another: each column of components is copied only if it may
+/// have been modified since the previous snapshot, and each archetype's entity list only if
+/// entities were added to or removed from it. Taking a snapshot of a world that has barely changed
+/// therefore costs little more than a pass over its archetypes, making it practical to snapshot
+/// every tick.
+///
+/// Modifications are tracked conservatively: uniquely borrowing a column through a query or
+/// `get_mut` causes it to be copied, whether or not it was actually written.
+///
+/// Every component type present in a snapshotted world must be registered. A `Snapshotter` must
+/// only be used with a single `World`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let mut snapshotter = Snapshotter::new();
+/// snapshotter.register::<i32>().register::<bool>();
+/// let a = world.spawn((123, true));
+/// let snapshot = snapshotter.take(&mut world);
+///
+/// *world.get_mut::<i32>(a).unwrap() = 456;
+/// let b = world.spawn((789,));
+/// snapshotter.restore(&snapshot, &mut world);
+/// assert_eq!(*world.get::<i32>(a).unwrap(), 123);
+/// assert!(!world.contains(b));
+/// ```
+#[derive(Default)]
+pub struct Snapshotter {
+    cloners: HashMap<TypeId, Cloner>,
+    /// The most recent snapshot, whose storage may be shared by the next
+    last: Option<Snapshot>,
+}
+
+impl Snapshotter {
+    /// Create a snapshotter that supports no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Support snapshotting worlds containing `T` components
+    pub fn register<T: Component + Clone>(&mut self) -> &mut Self {
+        self.cloners.insert(
+            TypeId::of::<T>(),
+            Cloner {
+                column: clone_column::<T>,
+                add: add_component::<T>,
+                raw: None,
+            },
+        );
+        self
+    }
+
+    /// Like `register`, but restores `T` components with bulk copies rather than by cloning each
+    /// one
+    ///
+    /// Archetypes made up entirely of types registered this way are restored a column at a time.
+    pub fn register_copy<T: Component + Copy>(&mut self) -> &mut Self {
+        self.cloners.insert(
+            TypeId::of::<T>(),
+            Cloner {
+                column: clone_column::<T>,
+                add: add_component::<T>,
+                raw: Some(raw_column::<T>),
+            },
+        );
+        self
+    }
+
+    /// Capture the current state of `world`
+    ///
+    /// Panics if `world` contains an unregistered component type.
+    pub fn take(&mut self, world: &mut World) -> Snapshot {
+        world.flush_entities();
+        let last = self.last.take();
+        let last_archetypes = last.as_ref().map_or(&[][..], |x| &x.archetypes[..]);
+        let mut unchanged = last.is_some();
+        let archetypes = world
+            .archetypes_inner()
+            .iter()
+            .enumerate()
+            .map(|(i, archetype)| {
+                let old = last_archetypes
+                    .get(i)
+                    .filter(|old| old.version == archetype.version());
+                unchanged &= old.is_some();
+                self.take_archetype(archetype, old)
+            })
+            .collect::<Vec<_>>();
+        unchanged &= archetypes.len() == last_archetypes.len();
+        let entities = match last {
+            Some(x) if unchanged => x.entities,
+            _ => Arc::new(world.entities_inner().clone()),
+        };
+        let snapshot = Snapshot {
+            generation: world.archetypes_generation(),
+            entities,
+            archetypes,
+        };
+        self.last = Some(snapshot.clone());
+        snapshot
+    }
+
+    /// Snapshot `archetype`, sharing storage with `old`, a snapshot of the same archetype with the
+    /// same entities, where possible
+    fn take_archetype(
+        &self,
+        archetype: &Archetype,
+        old: Option<&ArchetypeSnapshot>,
+    ) -> ArchetypeSnapshot {
+        let entities = match old {
+            Some(x) => x.entities.clone(),
+            None => (0..archetype.len())
+                .map(|i| archetype.entity_id(i))
+                .collect(),
+        };
+        let columns = if archetype.is_empty() {
+            Vec::new()
+        } else {
+            archetype
+                .types()
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    let version = archetype.column_version(ty.id()).unwrap();
+                    if let Some(x) = old
+                        .and_then(|x| x.columns.get(i))
+                        .filter(|x| x.version == version)
+                    {
+                        return x.clone();
+                    }
+                    let cloner = self
+                        .cloners
+                        .get(&ty.id())
+                        .expect("snapshotted a world containing an unregistered component type");
+                    ColumnSnapshot {
+                        ty: ty.id(),
+                        version,
+                        data: (cloner.column)(archetype),
+                        ticks: (0..archetype.len())
+                            .map(|i| archetype.component_ticks(ty.id(), i).unwrap())
+                            .collect(),
+                    }
+                })
+                .collect()
+        };
+        ArchetypeSnapshot {
+            version: archetype.version(),
+            entities,
+            columns,
+        }
+    }
+
+    /// Replace the contents of `world` with `snapshot`
+    ///
+    /// Entities spawned since the snapshot was taken are despawned, and entities despawned since
+    /// are restored with the same `Entity` IDs. Component indices are notified as if every entity
+    /// had been despawned and respawned. The world's change tick is not rewound.
+    ///
+    /// Handles to entities spawned since the snapshot was taken remain invalid, so entities
+    /// spawned after restoring do not necessarily receive the same IDs as they did before.
+    ///
+    /// Panics if `snapshot` was taken from a different world.
+    pub fn restore(&mut self, snapshot: &Snapshot, world: &mut World) {
+        assert!(
+            world.archetypes_extend(snapshot.generation),
+            "snapshot taken from a different world"
+        );
+        // Restoring reallocates every entity, so there's nothing left to share
+        self.last = None;
+        let mut builder = EntityBuilder::new();
+        unsafe {
+            world.restore(&snapshot.entities, |i, archetype| {
+                let snapshot = match snapshot.archetypes.get(i) {
+                    Some(x) => x,
+                    None => return,
+                };
+                archetype.reserve(snapshot.entities.len() as u32);
+                let raw = snapshot
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        let raw = self.cloners[&column.ty].raw?;
+                        let ty = archetype.types().iter().find(|x| x.id() == column.ty)?;
+                        Some((raw(&*column.data), ty.layout().size()))
+                    })
+                    .collect::<Vec<_>>();
+                if raw.iter().all(|x| x.is_some()) {
+                    // Nothing can panic, so fill whole columns at once
+                    for &id in snapshot.entities.iter() {
+                        archetype.allocate(id);
+                    }
+                    for (column, raw) in snapshot.columns.iter().zip(&raw) {
+                        let (data, size) = raw.unwrap();
+                        archetype.put_column(column.ty, size, data, &column.ticks);
+                    }
+                    return;
+                }
+                for (row, &id) in snapshot.entities.iter().enumerate() {
+                    for (column, raw) in snapshot.columns.iter().zip(&raw) {
+                        if raw.is_none() {
+                            (self.cloners[&column.ty].add)(&*column.data, row, &mut builder);
+                        }
+                    }
+                    let index = archetype.allocate(id);
+                    debug_assert_eq!(index as usize, row);
+                    builder.build().put(|ptr, ty, size| {
+                        let column = snapshot.columns.iter().find(|x| x.ty == ty).unwrap();
+                        archetype.put_dynamic(ptr, ty, size, index, column.ticks[row]);
+                        true
+                    });
+                    for (column, raw) in snapshot.columns.iter().zip(&raw) {
+                        if let Some((data, size)) = *raw {
+                            archetype.put_dynamic(
+                                data.add(size * row) as *mut u8,
+                                column.ty,
+                                size,
+                                index,
+                                column.ticks[row],
+                            );
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// The state of a `World` at a point in time, captured by `Snapshotter::take`
+///
+/// Cheap to clone.
+#[derive(Clone)]
+pub struct Snapshot {
+    /// `World::archetypes_generation` at the time of the snapshot
+    generation: ArchetypesGeneration,
+    pub(crate) entities: Arc<Entities>,
+    pub(crate) archetypes: Vec<ArchetypeSnapshot>,
+}
+
+#[derive(Clone)]
+pub(crate) struct ArchetypeSnapshot {
+    /// `Archetype::version` at the time of the snapshot
+    version: u64,
+    pub(crate) entities: Arc<[u32]>,
+    /// In the archetype's type order; empty if the archetype was empty
+    pub(crate) columns: Vec<ColumnSnapshot>,
+}
+
+#[derive(Clone)]
+pub(crate) struct ColumnSnapshot {
+    pub(crate) ty: TypeId,
+    /// `Archetype::column_version` at the time of the snapshot
+    version: u32,
+    /// A `Vec<T>`
+    pub(crate) data: Arc<dyn Any + Send + Sync>,
+    ticks: Arc<[ComponentTicks]>,
+}
+
+struct Cloner {
+    column: fn(&Archetype) -> Arc<dyn Any + Send + Sync>,
+    add: fn(&dyn Any, usize, &mut EntityBuilder),
+    /// Pointer to the first element of a column, for `Copy` types only
+    raw: Option<fn(&dyn Any) -> *const u8>,
+}
+
+fn clone_column<T: Component + Clone>(archetype: &Archetype) -> Arc<dyn Any + Send + Sync> {
+    Arc::new(archetype.with_column::<T, _>(|x| x.to_vec()).unwrap())
+}
+
+fn add_component<T: Component + Clone>(column: &dyn Any, row: usize, builder: &mut EntityBuilder) {
+    let column = column.downcast_ref::<Vec<T>>().unwrap();
+    builder.add(column[row].clone());
+}
+
+fn raw_column<T: Component + Copy>(column: &dyn Any) -> *const u8 {
+    column.downcast_ref::<Vec<T>>().unwrap().as_ptr().cast()
+}
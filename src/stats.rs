@@ -0,0 +1,59 @@
+use core::alloc::Layout;
+use core::any::TypeId;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// How often a component type was borrowed since the last `World::flush`, from
+/// `World::access_stats`
+///
+/// A borrow is counted each time one is granted for a column of the type in some archetype, e.g.
+/// once per matching archetype for each execution of a query. Access through `&mut World`, such as
+/// by `World::query_mut`, isn't counted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AccessStats {
+    /// The component type
+    pub id: TypeId,
+    /// Name of the component type, for diagnostics
+    pub name: &'static str,
+    /// Size and alignment of the component type
+    pub layout: Layout,
+    /// Number of live entities having the component
+    pub entities: u32,
+    /// Number of shared borrows granted
+    pub reads: u32,
+    /// Number of unique borrows granted
+    pub writes: u32,
+}
+
+impl AccessStats {
+    /// Memory occupied by the components, excluding unused capacity
+    pub fn bytes(&self) -> usize {
+        self.layout.size() * self.entities as usize
+    }
+}
+
+/// Borrows granted for a single column
+#[derive(Default)]
+pub(crate) struct AccessCounts {
+    reads: AtomicU32,
+    writes: AtomicU32,
+}
+
+impl AccessCounts {
+    pub fn record(&self, unique: bool) {
+        let counter = if unique { &self.writes } else { &self.reads };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Numbers of shared and unique borrows
+    pub fn get(&self) -> (u32, u32) {
+        (
+            self.reads.load(Ordering::Relaxed),
+            self.writes.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn reset(&mut self) {
+        *self.reads.get_mut() = 0;
+        *self.writes.get_mut() = 0;
+    }
+}
@@ -0,0 +1,82 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::Entity;
+
+/// A dataless marker identified by name, defined at runtime
+///
+/// Obtain from `World::tag`. Unlike a zero-sized component type, new tags can be created without
+/// recompiling, e.g. by an editor or script. Tags are added to and removed from entities with
+/// `World::add_tag` and `World::remove_tag`, without moving them between archetypes, and all
+/// entities with a tag can be found with `World::tagged`.
+///
+/// A `Tag` must only be used with the `World` that created it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Tag(u32);
+
+/// Tag names, and the entities having each tag
+#[derive(Default, Clone)]
+pub(crate) struct Tags {
+    names: Vec<Box<str>>,
+    by_name: HashMap<Box<str>, Tag>,
+    members: Vec<HashSet<Entity>>,
+}
+
+impl Tags {
+    pub fn get_or_insert(&mut self, name: &str) -> Tag {
+        if let Some(&tag) = self.by_name.get(name) {
+            return tag;
+        }
+        let tag = Tag(self.names.len() as u32);
+        self.names.push(name.into());
+        self.by_name.insert(name.into(), tag);
+        self.members.push(HashSet::new());
+        tag
+    }
+
+    pub fn get(&self, name: &str) -> Option<Tag> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name(&self, tag: Tag) -> &str {
+        &self.names[tag.0 as usize]
+    }
+
+    pub fn members(&self, tag: Tag) -> &HashSet<Entity> {
+        &self.members[tag.0 as usize]
+    }
+
+    pub fn members_mut(&mut self, tag: Tag) -> &mut HashSet<Entity> {
+        &mut self.members[tag.0 as usize]
+    }
+
+    /// Remove `entity` from every tag
+    pub fn despawned(&mut self, entity: Entity) {
+        for x in &mut self.members {
+            x.remove(&entity);
+        }
+    }
+
+    /// Iterate over every tag's name and members
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &HashSet<Entity>)> {
+        self.names.iter().map(|x| &**x).zip(&self.members)
+    }
+
+    /// Move `old`'s tags to `new`
+    pub fn replaced(&mut self, old: Entity, new: Entity) {
+        for x in &mut self.members {
+            if x.remove(&old) {
+                x.insert(new);
+            }
+        }
+    }
+
+    /// Remove every entity from every tag, preserving the tags themselves
+    pub fn clear(&mut self) {
+        for x in &mut self.members {
+            x.clear();
+        }
+    }
+}
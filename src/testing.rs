@@ -0,0 +1,285 @@
+//! Helpers for testing code that operates on `World`s
+//!
+//! An `Inspector` knows how to compare and print a set of component types, which it uses to check
+//! which components entities have and to report every difference between two worlds, e.g. one
+//! produced by the code under test and one built by hand with `test_world!`.
+//!
+//! # Example
+//! ```
+//! # use hecs::*;
+//! use hecs::testing::Inspector;
+//!
+//! let (mut actual, entities) = hecs::test_world![(1, "a"), (2, "b", true)];
+//! let (expected, _) = hecs::test_world![(1, "a"), (3, "b")];
+//! *actual.get_mut::<i32>(entities[1]).unwrap() *= 2;
+//! actual.remove_one::<bool>(entities[1]).unwrap();
+//!
+//! let mut inspector = Inspector::new();
+//! inspector.register::<i32>().register::<&str>().register::<bool>();
+//! inspector.assert_components::<(i32, &str)>(&actual, entities[1]);
+//! assert_eq!(
+//!     inspector.diff(&actual, &expected)[0].to_string(),
+//!     format!("{:?}: i32 is 4 on the left but 3 on the right", entities[1]),
+//! );
+//! ```
+
+use crate::alloc::format;
+use crate::alloc::string::{String, ToString};
+use crate::alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use hashbrown::HashMap;
+
+use crate::{Bundle, Component, Entity, World};
+
+/// Construct a `World` containing an entity for each of a list of component tuples
+///
+/// Evaluates to the world and a `Vec` of the entities, in the order given.
+///
+/// # Example
+/// ```
+/// let (world, entities) = hecs::test_world![(123, true), ("abc",)];
+/// assert_eq!(*world.get::<bool>(entities[0]).unwrap(), true);
+/// assert_eq!(world.query::<&&str>().iter().count(), 1);
+/// ```
+#[macro_export]
+macro_rules! test_world {
+    ($($components:expr),* $(,)?) => {{
+        let mut world = $crate::World::new();
+        #[allow(unused_mut)]
+        let mut entities = $crate::testing::__entities();
+        $(entities.push(world.spawn($components));)*
+        (world, entities)
+    }};
+}
+
+#[doc(hidden)]
+pub fn __entities() -> Vec<Entity> {
+    Vec::new()
+}
+
+/// Compares and prints components of registered types
+#[derive(Default)]
+pub struct Inspector {
+    types: HashMap<TypeId, Entry>,
+}
+
+struct Entry {
+    name: &'static str,
+    /// Whether an entity's component in one world equals that of an entity in another
+    eq: fn(&World, Entity, &World, Entity) -> bool,
+    debug: fn(&World, Entity) -> String,
+}
+
+impl Inspector {
+    /// Create an inspector that knows no types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare and print `T` components
+    ///
+    /// Components of unregistered types are only checked for presence, and are named by their
+    /// `TypeId`.
+    pub fn register<T: Component + PartialEq + fmt::Debug>(&mut self) -> &mut Self {
+        self.types.insert(
+            TypeId::of::<T>(),
+            Entry {
+                name: type_name::<T>(),
+                eq: |a, x, b, y| *a.get::<T>(x).unwrap() == *b.get::<T>(y).unwrap(),
+                debug: |world, x| format!("{:?}", *world.get::<T>(x).unwrap()),
+            },
+        );
+        self
+    }
+
+    /// Describe the components of `entity`, e.g. `{i32: 123, bool: true}`
+    ///
+    /// Returns `None` if `entity` doesn't exist.
+    pub fn describe(&self, world: &World, entity: Entity) -> Option<String> {
+        let mut out = String::from("{");
+        for (i, ty) in component_types(world, entity)?.iter().enumerate() {
+            if i != 0 {
+                out.push_str(", ");
+            }
+            out.push_str(&self.name(*ty));
+            if let Some(entry) = self.types.get(ty) {
+                out.push_str(": ");
+                out.push_str(&(entry.debug)(world, entity));
+            }
+        }
+        out.push('}');
+        Some(out)
+    }
+
+    /// Panic unless `entity` has exactly the components in `T`
+    pub fn assert_components<T: Bundle>(&self, world: &World, entity: Entity) {
+        let actual = match component_types(world, entity) {
+            Some(x) => x,
+            None => panic!("{:?} does not exist", entity),
+        };
+        let mut expected = T::with_static_ids(|ids| ids.to_vec());
+        expected.sort_unstable();
+        if actual != expected {
+            panic!(
+                "{:?} has components {}, expected {}",
+                entity,
+                self.names(&actual),
+                self.names(&expected)
+            );
+        }
+    }
+
+    /// Every difference between `left` and `right`, matching entities by their handles
+    ///
+    /// Returns an empty `Vec` if the worlds contain the same entities, with the same components,
+    /// of equal value where registered.
+    pub fn diff(&self, left: &World, right: &World) -> Vec<Difference> {
+        let mut out = Vec::new();
+        for (entity, _) in left.iter() {
+            // Entities are matched by handle, regardless of which world allocated them
+            let entity = entity.untagged();
+            let right_types = match component_types(right, entity) {
+                Some(x) => x,
+                None => {
+                    out.push(Difference::new(entity, Kind::MissingEntity { left: false }));
+                    continue;
+                }
+            };
+            let left_types = component_types(left, entity).unwrap();
+            for &ty in &left_types {
+                if !right_types.contains(&ty) {
+                    let kind = Kind::MissingComponent {
+                        name: self.name(ty),
+                        left: false,
+                    };
+                    out.push(Difference::new(entity, kind));
+                    continue;
+                }
+                let entry = match self.types.get(&ty) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                if !(entry.eq)(left, entity, right, entity) {
+                    let kind = Kind::Unequal {
+                        name: entry.name,
+                        left: (entry.debug)(left, entity),
+                        right: (entry.debug)(right, entity),
+                    };
+                    out.push(Difference::new(entity, kind));
+                }
+            }
+            for &ty in right_types.iter().filter(|x| !left_types.contains(x)) {
+                let kind = Kind::MissingComponent {
+                    name: self.name(ty),
+                    left: true,
+                };
+                out.push(Difference::new(entity, kind));
+            }
+        }
+        for (entity, _) in right.iter().filter(|&(x, _)| !left.contains(x.untagged())) {
+            out.push(Difference::new(entity, Kind::MissingEntity { left: true }));
+        }
+        out
+    }
+
+    /// Panic, listing every difference, unless `left` and `right` are equal according to `diff`
+    pub fn assert_eq(&self, left: &World, right: &World) {
+        let diff = self.diff(left, right);
+        if diff.is_empty() {
+            return;
+        }
+        let mut message = format!("worlds differ in {} ways:", diff.len());
+        for x in &diff {
+            message.push_str("\n  ");
+            message.push_str(&x.to_string());
+        }
+        panic!("{}", message);
+    }
+
+    fn name(&self, ty: TypeId) -> String {
+        match self.types.get(&ty) {
+            Some(x) => x.name.to_string(),
+            None => format!("{:?}", ty),
+        }
+    }
+
+    fn names(&self, types: &[TypeId]) -> String {
+        let names = types.iter().map(|&x| self.name(x)).collect::<Vec<_>>();
+        format!("[{}]", names.join(", "))
+    }
+}
+
+/// A way in which two worlds differ, as found by `Inspector::diff`
+///
+/// Displays as a human-readable description.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Difference {
+    entity: Entity,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Kind {
+    MissingEntity {
+        /// Whether the entity is missing from the left world, rather than the right
+        left: bool,
+    },
+    MissingComponent {
+        name: String,
+        left: bool,
+    },
+    Unequal {
+        name: &'static str,
+        left: String,
+        right: String,
+    },
+}
+
+impl Difference {
+    fn new(entity: Entity, kind: Kind) -> Self {
+        Self { entity, kind }
+    }
+
+    /// The entity that differs
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |left| if left { "left" } else { "right" };
+        match self.kind {
+            Kind::MissingEntity { left } => {
+                write!(f, "{:?} is missing on the {}", self.entity, side(left))
+            }
+            Kind::MissingComponent { ref name, left } => write!(
+                f,
+                "{:?}: {} is missing on the {}",
+                self.entity,
+                name,
+                side(left)
+            ),
+            Kind::Unequal {
+                name,
+                ref left,
+                ref right,
+            } => write!(
+                f,
+                "{:?}: {} is {} on the left but {} on the right",
+                self.entity, name, left, right
+            ),
+        }
+    }
+}
+
+/// Sorted IDs of the component types of `entity`, if it exists
+fn component_types(world: &World, entity: Entity) -> Option<Vec<TypeId>> {
+    let loc = world.entities_inner().get(entity).ok()?;
+    let archetype = &world.archetypes_inner()[loc.archetype as usize];
+    let mut types = archetype.types().iter().map(|x| x.id()).collect::<Vec<_>>();
+    types.sort_unstable();
+    Some(types)
+}
@@ -0,0 +1,109 @@
+use crate::alloc::vec::Vec;
+
+use crate::world::WorldUid;
+use crate::{Entity, World};
+
+/// A handle to an entity that may since have been despawned
+///
+/// Obtained from `World::downgrade`. Besides the entity, records how far along the world was in
+/// despawning entities, so that checking whether the entity is still live is nearly free as long
+/// as nothing has been despawned since. Useful for references between entities, like targets and
+/// parents, that must tolerate the entity they refer to disappearing.
+///
+/// Only meaningful for the world it was obtained from; upgrading it in another panics.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// let weak = [world.downgrade(a).unwrap(), world.downgrade(b).unwrap()];
+/// assert_eq!(weak[0].upgrade(&world), Some(a));
+/// world.despawn(a).unwrap();
+/// assert_eq!(weak[0].upgrade(&world), None);
+/// assert_eq!(
+///     world.upgrade_all(&weak).collect::<Vec<_>>(),
+///     [None, Some(b)]
+/// );
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct WeakEntity {
+    entity: Entity,
+    world: WorldUid,
+    /// The world's despawn epoch when `entity` was last known to be live
+    epoch: u64,
+}
+
+impl WeakEntity {
+    /// The entity referred to, which may no longer exist
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// The world the entity belongs to
+    pub fn world(&self) -> WorldUid {
+        self.world
+    }
+
+    /// The entity referred to, if it still exists in `world`
+    ///
+    /// Panics if the entity belongs to a different world.
+    pub fn upgrade(&self, world: &World) -> Option<Entity> {
+        self.check(world);
+        let entities = world.entities_inner();
+        if entities.epoch() == self.epoch || entities.contains(self.entity) {
+            Some(self.entity)
+        } else {
+            None
+        }
+    }
+
+    fn check(&self, world: &World) {
+        assert!(
+            self.world == world.uid(),
+            "weak entity {:?} belongs to a different world",
+            self.entity
+        );
+    }
+}
+
+impl World {
+    /// Obtain a `WeakEntity` referring to `entity`, if it exists
+    pub fn downgrade(&self, entity: Entity) -> Option<WeakEntity> {
+        let entities = self.entities_inner();
+        if !entities.contains(entity) {
+            return None;
+        }
+        Some(WeakEntity {
+            entity,
+            world: self.uid(),
+            epoch: entities.epoch(),
+        })
+    }
+
+    /// Upgrade each of `weak`, as if by `WeakEntity::upgrade`
+    pub fn upgrade_all<'a>(
+        &'a self,
+        weak: &'a [WeakEntity],
+    ) -> impl ExactSizeIterator<Item = Option<Entity>> + 'a {
+        weak.iter().map(move |x| x.upgrade(self))
+    }
+
+    /// Remove every element of `weak` referring to an entity that no longer exists, preserving
+    /// the order of the rest
+    ///
+    /// The remaining elements are refreshed, so upgrading them stays cheap until the next despawn.
+    pub fn retain_live(&self, weak: &mut Vec<WeakEntity>) {
+        let entities = self.entities_inner();
+        let epoch = entities.epoch();
+        weak.retain_mut(|x| {
+            x.check(self);
+            if x.epoch != epoch && !entities.contains(x.entity) {
+                return false;
+            }
+            x.epoch = epoch;
+            true
+        });
+    }
+}
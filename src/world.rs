@@ -12,21 +12,49 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::alloc::vec::Vec;
-use core::any::TypeId;
+use crate::alloc::alloc::Layout;
+use crate::alloc::sync::Arc;
+use crate::alloc::{boxed::Box, vec, vec::Vec};
+use core::any::{type_name, TypeId};
 use core::convert::TryFrom;
-use core::{fmt, mem, ptr};
+use core::hash::Hash;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use core::{fmt, mem};
 
 #[cfg(feature = "std")]
 use std::error::Error;
 
 use hashbrown::{HashMap, HashSet};
 
-use crate::archetype::Archetype;
-use crate::entities::{Entities, Location};
+use crate::archetype::{
+    Archetype, ColumnOrder, ColumnPtr, ComponentTicks, TypeInfo, WriteBarrier, MAX_CHANGE_AGE,
+};
+use crate::arena::PinArena;
+use crate::double_buffer::{ShadowColumn, ShadowFactory};
+use crate::entities::{
+    Entities, EntityAllocator, EntityMeta, EntitySlot, Generation, Location, ReserveEntitiesIter,
+};
+use crate::frame::FrameAllocator;
+use crate::graveyard::Graveyard;
+use crate::handle::{ComponentHandle, Handles};
+use crate::index::Indices;
+use crate::journal::Journal;
+use crate::modification::{EntityMut, Modification, SpawnBuilder};
+use crate::name::Names;
+use crate::observer::{Observe, Observer};
+use crate::query::QueryTicks;
+use crate::removal::RemovalSinks;
+use crate::shared::SharedValues;
+#[cfg(feature = "stats")]
+use crate::stats::AccessStats;
+use crate::tag::Tags;
+use crate::ColumnArena;
 use crate::{
-    Bundle, DynamicBundle, Entity, EntityRef, MissingComponent, NoSuchEntity, Query, QueryBorrow,
-    QueryOne, Ref, RefMut,
+    Access, BorrowError, BorrowPolicy, BorrowState, Bundle, CloneRegistry, ComponentIndex,
+    DynamicBundle, Entity, EntityBuilder, EntityRef, Fetch, JoinBorrow, MissingComponent,
+    NoSuchEntity, Query, QueryBorrow, QueryOne, Ref, RefMut, RemovalSink, Shared, Tag, TryBundle,
+    TypedArchetypeView,
 };
 
 /// An unordered collection of entities, each having any number of distinctly typed components
@@ -41,24 +69,122 @@ pub struct World {
     index: HashMap<Vec<TypeId>, u32>,
     archetypes: Vec<Archetype>,
     archetype_generation: u64,
+    /// Distinct from that of every other world, including clones
+    uid: WorldUid,
+    indices: Indices,
+    removal_sinks: RemovalSinks,
+    tags: Tags,
+    names: Names,
+    borrow_policy: BorrowPolicy,
+    frame: FrameAllocator,
+    graveyard: Option<Box<Graveyard>>,
+    handles: Handles,
+    /// Slots in which components of each type registered with `pin_component` are stored
+    pinned: HashMap<TypeId, Arc<PinArena>>,
+    column_order: Option<ColumnOrder>,
+    colocated: Vec<(TypeId, TypeId)>,
+    arenas: HashMap<TypeId, Arc<ColumnArena>>,
+    write_barriers: HashMap<TypeId, Arc<WriteBarrier>>,
+    /// Backs the columns split off by `split_cold`
+    cold: Arc<ColumnArena>,
+    /// Component types whose values as of the last `flush` are kept, from `double_buffer`
+    double_buffered: HashMap<TypeId, ShadowFactory>,
+    entity_limit: Option<u32>,
+    /// Limits on the number of entities in particular archetypes, by index
+    archetype_limits: HashMap<u32, u32>,
+    change_tick: AtomicU32,
+    last_clamp: u32,
+    observers: Vec<Observer>,
+    journal: Option<Box<Journal>>,
 }
 
 impl World {
     /// Create an empty world
     pub fn new() -> Self {
         // `flush` assumes archetype 0 always exists, representing entities with no components.
-        let mut archetypes = Vec::new();
-        archetypes.push(Archetype::new(Vec::new()));
+        let archetypes = vec![Archetype::new(Vec::new())];
         let mut index = HashMap::default();
         index.insert(Vec::new(), 0);
+        let archetype_generation = fresh_generation();
+        let uid = WorldUid((archetype_generation >> 32) as u32);
+        let mut entities = Entities::default();
+        entities.set_world(uid.0);
         Self {
-            entities: Entities::default(),
+            entities,
             index,
             archetypes,
-            archetype_generation: 0,
+            archetype_generation,
+            uid,
+            indices: Indices::default(),
+            removal_sinks: RemovalSinks::default(),
+            tags: Tags::default(),
+            names: Names::default(),
+            borrow_policy: BorrowPolicy::Panic,
+            frame: FrameAllocator::new(),
+            graveyard: None,
+            handles: Handles::new((archetype_generation >> 32) as u32),
+            pinned: HashMap::default(),
+            column_order: None,
+            colocated: Vec::new(),
+            arenas: HashMap::default(),
+            write_barriers: HashMap::default(),
+            cold: Arc::new(ColumnArena::new()),
+            double_buffered: HashMap::default(),
+            entity_limit: None,
+            archetype_limits: HashMap::default(),
+            change_tick: AtomicU32::new(0),
+            last_clamp: 0,
+            observers: Vec::new(),
+            journal: None,
         }
     }
 
+    /// Create an empty world whose entity IDs are chosen by `allocator`
+    ///
+    /// `reserve_entity`, and features that rely on it such as `Session::spawn`, panic in such a
+    /// world, since they must allocate IDs concurrently. So does spawning an entity whose ID
+    /// `allocator` chose to be `ALLOCATOR_ID_LIMIT` or more. Worlds derived from this one, e.g. by
+    /// `clone_with`, share `allocator`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::sync::Mutex;
+    /// /// Allocates IDs from a range reserved for one server shard
+    /// struct Shard {
+    ///     next: Mutex<u32>,
+    ///     free: Mutex<Vec<u32>>,
+    /// }
+    ///
+    /// impl EntityAllocator for Shard {
+    ///     fn allocate(&self) -> u32 {
+    ///         self.free.lock().unwrap().pop().unwrap_or_else(|| {
+    ///             let mut next = self.next.lock().unwrap();
+    ///             *next += 1;
+    ///             *next - 1
+    ///         })
+    ///     }
+    ///     fn free(&self, id: u32) {
+    ///         self.free.lock().unwrap().push(id);
+    ///     }
+    ///     fn claim(&self, id: u32) {
+    ///         self.free.lock().unwrap().retain(|&x| x != id);
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::with_allocator(Shard {
+    ///     next: Mutex::new(5000),
+    ///     free: Mutex::new(Vec::new()),
+    /// });
+    /// assert_eq!(world.spawn((123,)).id(), 5000);
+    /// assert_eq!(world.spawn((456,)).id(), 5001);
+    /// ```
+    pub fn with_allocator(allocator: impl EntityAllocator) -> Self {
+        let mut world = Self::new();
+        world.entities.set_allocator(Arc::new(allocator));
+        world
+    }
+
     /// Create an entity with certain components
     ///
     /// Returns the ID of the newly created entity.
@@ -70,6 +196,8 @@ impl World {
     ///
     /// Any type that satisfies `Send + Sync + 'static` can be used as a component.
     ///
+    /// Panics if a limit set by `set_entity_limit` or `set_archetype_limit` would be exceeded.
+    ///
     /// # Example
     /// ```
     /// # use hecs::*;
@@ -78,39 +206,363 @@ impl World {
     /// let b = world.spawn((456, true));
     /// ```
     pub fn spawn(&mut self, components: impl DynamicBundle) -> Entity {
+        self.try_spawn(components)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `spawn`, but returns an error rather than panicking if a limit set by
+    /// `set_entity_limit` or `set_archetype_limit` would be exceeded
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_entity_limit(Some(1));
+    /// let a = world.try_spawn((123,)).unwrap();
+    /// assert_eq!(world.try_spawn((456,)).unwrap_err().limit(), 1);
+    /// world.despawn(a).unwrap();
+    /// assert!(world.try_spawn((456,)).is_ok());
+    /// ```
+    pub fn try_spawn(&mut self, components: impl DynamicBundle) -> Result<Entity, QuotaExceeded> {
         // Ensure all entity allocations are accounted for so `self.entities` can realloc if
         // necessary
-        self.flush();
+        self.flush_entities();
+
+        let archetype_id = self.bundle_archetype(&components);
+        self.check_quota(archetype_id, 1)?;
+
+        let entity = self.entities.alloc();
+        unsafe {
+            self.place(entity, archetype_id, components);
+        }
+        Ok(entity)
+    }
+
+    /// Create an entity with components produced by fallible constructors
+    ///
+    /// Constructors are invoked in order, each component being written straight into storage. If
+    /// one fails, the components already produced are dropped and no entity is created, so
+    /// components that depend on e.g. loading an asset never leave a half-initialized entity
+    /// behind. The same holds if a constructor panics.
+    ///
+    /// Panics if a limit set by `set_entity_limit` or `set_archetype_limit` would be exceeded.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let load = |name: &'static str| move || name.strip_suffix(".png").ok_or(name);
+    /// let a = world
+    ///     .try_spawn_with((load("grass.png"), || Ok(42)))
+    ///     .unwrap();
+    /// assert_eq!(*world.get::<&str>(a).unwrap(), "grass");
+    /// assert_eq!(
+    ///     world.try_spawn_with((|| Ok(42), load("grass.jpg"))),
+    ///     Err("grass.jpg")
+    /// );
+    /// assert_eq!(world.len(), 1);
+    /// ```
+    pub fn try_spawn_with<B: TryBundle>(&mut self, components: B) -> Result<Entity, B::Error> {
+        self.flush_entities();
+
+        let archetype_id = self.archetype_for::<B::Bundle>();
+        self.check_quota(archetype_id, 1)
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let ticks = ComponentTicks::new(self.change_tick());
+        let archetype = &mut self.archetypes[archetype_id as usize];
+        // Rolls back the partially written row on failure, including by panic
+        let mut row = PartialRow {
+            index: unsafe { archetype.allocate(u32::MAX) },
+            archetype,
+            written: Vec::new(),
+        };
+        let result = unsafe {
+            components.try_put(|ptr, ty, size| {
+                row.archetype.put_dynamic(ptr, ty, size, row.index, ticks);
+                row.written.push(ty);
+            })
+        };
+        if let Err(e) = result {
+            drop(row);
+            return Err(e);
+        }
+        let index = row.finish();
 
         let entity = self.entities.alloc();
-        let archetype_id = components.with_ids(|ids| {
+        let archetype = &mut self.archetypes[archetype_id as usize];
+        archetype.set_entity_id(index, entity.id);
+        self.entities.meta[entity.id as usize].location = Location {
+            archetype: archetype_id,
+            index,
+        };
+        unsafe {
+            self.indices.inserted_all(entity, archetype, index);
+        }
+        if let Some(ref mut journal) = self.journal {
+            journal.spawned(entity, archetype, index);
+        }
+        Ok(entity)
+    }
+
+    /// Create an entity with the specific handle `entity`, e.g. one produced by another `World`
+    ///
+    /// Lets a client mirror the entities replicated from a server without translating handles.
+    /// If `entity` is already live, its components are replaced as if it had been despawned
+    /// first. Fails if another entity with the same ID is live, or if a limit set by
+    /// `set_entity_limit` or `set_archetype_limit` would be exceeded.
+    ///
+    /// Panics if `entity` has the greatest possible generation, which no `World` produces.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut server = World::new();
+    /// let a = server.spawn((123,));
+    /// server.despawn(a).unwrap();
+    /// let b = server.spawn((456,));
+    ///
+    /// let mut client = World::new();
+    /// client.spawn_at(b, (456,)).unwrap();
+    /// assert_eq!(*client.get::<i32>(b).unwrap(), 456);
+    /// assert_eq!(client.spawn_at(a, (123,)), Err(SpawnAtError::Occupied(b)));
+    /// ```
+    pub fn spawn_at(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), SpawnAtError> {
+        self.flush_entities();
+
+        let archetype_id = self.bundle_archetype(&components);
+        match self.entities.occupant(entity.id) {
+            Some(x) if x != entity => return Err(SpawnAtError::Occupied(x)),
+            Some(_) => {
+                let loc = self.entities.get(entity).unwrap();
+                if loc.archetype != archetype_id {
+                    self.check_quota(archetype_id, 1)?;
+                }
+                self.despawn(entity).unwrap();
+            }
+            None => self.check_quota(archetype_id, 1)?,
+        }
+
+        self.entities.alloc_at(entity);
+        unsafe {
+            self.place(entity, archetype_id, components);
+        }
+        Ok(())
+    }
+
+    /// Find or create the archetype for exactly the components of `components`
+    fn bundle_archetype(&mut self, components: &impl DynamicBundle) -> u32 {
+        components.with_ids(|ids| {
             self.index.get(ids).copied().unwrap_or_else(|| {
                 let x = self.archetypes.len() as u32;
-                self.archetypes.push(Archetype::new(components.type_info()));
+                self.archetypes.push(
+                    Archetype::new(components.type_info())
+                        .with_pins(&self.pinned)
+                        .with_column_order(self.column_order)
+                        .with_colocated(&self.colocated)
+                        .with_arenas(&self.arenas)
+                        .with_barriers(&self.write_barriers)
+                        .with_shadows(&self.double_buffered),
+                );
                 self.index.insert(ids.to_vec(), x);
                 self.archetype_generation += 1;
                 x
             })
-        });
+        })
+    }
 
+    /// Move `components` into `archetype`, recording the location of the freshly allocated
+    /// `entity`
+    ///
+    /// # Safety
+    /// `archetype` must be the archetype of `components` and have room for another entity
+    unsafe fn place(&mut self, entity: Entity, archetype_id: u32, components: impl DynamicBundle) {
+        let ticks = ComponentTicks::new(self.change_tick());
         let archetype = &mut self.archetypes[archetype_id as usize];
+        let index = archetype.allocate(entity.id);
+        components.put(|ptr, ty, size| {
+            archetype.put_dynamic(ptr, ty, size, index, ticks);
+            true
+        });
+        self.entities.meta[entity.id as usize].location = Location {
+            archetype: archetype_id,
+            index,
+        };
+        self.indices.inserted_all(entity, archetype, index);
+        if let Some(ref mut journal) = self.journal {
+            journal.spawned(entity, archetype, index);
+        }
+    }
+
+    /// Limit the number of live entities, or remove the limit if `limit` is `None`
+    ///
+    /// Once the limit is reached, `try_spawn` returns an error, while `spawn`, `spawn_batch`, and
+    /// `spawn_external` panic. Useful to enforce e.g. a per-match budget on a server. Entities
+    /// created through `reserve_entity` are exempt. Lowering the limit below the current number of
+    /// entities doesn't despawn any.
+    pub fn set_entity_limit(&mut self, limit: Option<u32>) {
+        self.entity_limit = limit;
+    }
+
+    /// Limit the number of live entities with exactly the components in `T`, or remove the limit
+    /// if `limit` is `None`
+    ///
+    /// Enforced like `set_entity_limit`, only when spawning, so entities can still enter the
+    /// archetype by gaining or losing components.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Projectile;
+    /// let mut world = World::new();
+    /// world.set_archetype_limit::<(Projectile, u32)>(Some(2));
+    /// world.spawn_batch((0..2).map(|i| (Projectile, i as u32)));
+    /// let error = world.try_spawn((Projectile, 2u32)).unwrap_err();
+    /// assert!(error.is_archetype_limit());
+    /// assert!(world.try_spawn((Projectile,)).is_ok());
+    /// ```
+    pub fn set_archetype_limit<T: Bundle>(&mut self, limit: Option<u32>) {
+        let archetype = self.archetype_for::<T>();
+        match limit {
+            Some(x) => self.archetype_limits.insert(archetype, x),
+            None => self.archetype_limits.remove(&archetype),
+        };
+    }
+
+    /// Control whether the IDs of despawned entities are allocated again, as they are by default
+    ///
+    /// Without reuse, IDs are allocated in ascending order, so a stale handle can't share its ID
+    /// with a live entity, and using one reliably fails rather than finding an unrelated entity.
+    /// Useful for deterministic replays and for debugging dangling handles. Memory use then grows
+    /// with the number of entities ever spawned, rather than the number alive at once, and spawning
+    /// panics once every ID has been used. Has no effect on a world created by `with_allocator`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_id_reuse(false);
+    /// let a = world.spawn((123,));
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn((456,));
+    /// assert_eq!(b.id(), a.id() + 1);
+    /// ```
+    pub fn set_id_reuse(&mut self, reuse: bool) {
+        self.flush_entities();
+        self.entities.set_reuse(reuse);
+    }
+
+    /// Ensure `additional` entities can be spawned into archetype `archetype`
+    fn check_quota(&self, archetype: u32, additional: u32) -> Result<(), QuotaExceeded> {
+        let (room, error) = self.room(archetype);
+        if room < additional {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Number of entities that can be spawned into `archetype`, and the error to report once
+    /// they have been
+    fn room(&self, archetype: u32) -> (u32, QuotaExceeded) {
+        let mut room = (u32::MAX, QuotaExceeded::new(u32::MAX, false));
+        if let Some(limit) = self.entity_limit {
+            room = (
+                limit.saturating_sub(self.len()),
+                QuotaExceeded::new(limit, false),
+            );
+        }
+        if let Some(&limit) = self.archetype_limits.get(&archetype) {
+            let available = limit.saturating_sub(self.archetypes[archetype as usize].len());
+            if available <= room.0 {
+                room = (available, QuotaExceeded::new(limit, true));
+            }
+        }
+        room
+    }
+
+    /// Create an entity with certain components, then make further changes before it's finalized
+    ///
+    /// Components inserted into or removed from the returned `EntityMut` are applied together in
+    /// a single archetype move once it's dropped, making this cheaper than the equivalent sequence
+    /// of `spawn`, `insert_one`, and `remove_one` calls.
+    pub fn spawn_mut(&mut self, components: impl DynamicBundle) -> EntityMut<'_> {
+        let entity = self.spawn(components);
+        EntityMut::new(self, entity)
+    }
+
+    /// Accumulate components for a new entity, then spawn it with `SpawnBuilder::spawn`
+    ///
+    /// The entity is written into the archetype for its final set of components exactly once,
+    /// making this cheaper than spawning it and then inserting components one at a time.
+    pub fn build_entity(&mut self) -> SpawnBuilder<'_> {
+        SpawnBuilder::new(self)
+    }
+
+    /// Spawn an entity for each element of `data`, using it in place as component storage
+    ///
+    /// Enables zero-copy loading of immutable data, e.g. from a leaked memory map of a baked level.
+    /// The new entities behave like any others, except that their components can't be borrowed
+    /// uniquely and they can't be individually despawned or have components inserted or removed;
+    /// attempting to do so panics. They're removed only by `clear`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// static POSITIONS: [[f32; 2]; 3] = [[0.0, 1.0], [2.0, 3.0], [4.0, 5.0]];
+    /// let mut world = World::new();
+    /// let entities = world.spawn_external(&POSITIONS);
+    /// assert_eq!(*world.get::<[f32; 2]>(entities[1]).unwrap(), [2.0, 3.0]);
+    /// assert_eq!(world.query::<&[f32; 2]>().iter().count(), 3);
+    /// assert!(world.archetypes().any(|x| x.is_read_only()));
+    /// ```
+    pub fn spawn_external<T: Component + Copy>(&mut self, data: &'static [T]) -> Vec<Entity> {
+        self.flush_entities();
+        let archetype = self.archetypes.len() as u32;
+        if let Err(e) = self.check_quota(archetype, data.len() as u32) {
+            panic!("{}", e);
+        }
+        let entities = (0..data.len() as u32)
+            .map(|index| {
+                let entity = self.entities.alloc();
+                self.entities.meta[entity.id as usize].location = Location { archetype, index };
+                entity
+            })
+            .collect::<Vec<_>>();
+        let ids = entities.iter().map(|x| x.id).collect();
+        let tick = self.change_tick();
         unsafe {
-            let index = archetype.allocate(entity.id);
-            components.put(|ptr, ty, size| {
-                archetype.put_dynamic(ptr, ty, size, index);
-                true
-            });
-            self.entities.meta[entity.id as usize].location = Location {
-                archetype: archetype_id,
-                index,
-            };
+            let data = NonNull::new_unchecked(data.as_ptr() as *mut T);
+            self.archetypes
+                .push(Archetype::external(data, ids, tick).with_shadows(&self.double_buffered));
         }
-        entity
+        self.archetype_generation += 1;
+        if !self.indices.is_empty() {
+            let archetype = self.archetypes.last().unwrap();
+            for (index, &entity) in entities.iter().enumerate() {
+                unsafe {
+                    self.indices.inserted_all(entity, archetype, index as u32);
+                }
+            }
+        }
+        if let Some(ref mut journal) = self.journal {
+            let archetype = self.archetypes.last().unwrap();
+            for (index, &entity) in entities.iter().enumerate() {
+                journal.spawned(entity, archetype, index as u32);
+            }
+        }
+        entities
     }
 
     /// Efficiently spawn a large number of entities with the same components
     ///
-    /// Faster than calling `spawn` repeatedly with the same components.
+    /// Faster than calling `spawn` repeatedly with the same components: the archetype is looked up
+    /// once, and storage for the number of entities given by `iter`'s size hint is allocated up
+    /// front. Entities are spawned as the returned iterator is advanced, and any remaining when
+    /// it's dropped are spawned then.
     ///
     /// # Example
     /// ```
@@ -128,19 +580,25 @@ impl World {
     {
         // Ensure all entity allocations are accounted for so `self.entities` can realloc if
         // necessary
-        self.flush();
+        self.flush_entities();
 
         let iter = iter.into_iter();
         let (lower, upper) = iter.size_hint();
         let archetype_id = self.reserve_inner::<I::Item>(
             u32::try_from(upper.unwrap_or(lower)).expect("iterator too large"),
         );
+        let (room, quota) = self.room(archetype_id);
 
         SpawnBatchIter {
             inner: iter,
+            room,
+            quota,
+            ticks: ComponentTicks::new(self.change_tick.load(Ordering::Relaxed)),
             entities: &mut self.entities,
             archetype_id,
             archetype: &mut self.archetypes[archetype_id as usize],
+            indices: &mut self.indices,
+            journal: self.journal.as_deref_mut(),
         }
     }
 
@@ -154,108 +612,1115 @@ impl World {
     /// explicitly by calling `flush`.
     ///
     /// Useful for reserving an ID that will later have components attached to it with `insert`.
+    ///
+    /// Panics if the world was created by `with_allocator`.
     pub fn reserve_entity(&self) -> Entity {
         self.entities.reserve_entity()
     }
 
+    /// Allocate `count` entity IDs concurrently, as if by calling `reserve_entity` that many times
+    ///
+    /// Cheaper than reserving them individually. IDs are taken from those freed by earlier
+    /// despawns first, and the rest are consecutive.
+    ///
+    /// Panics if the world was created by `with_allocator`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = world.reserve_entities(3).collect::<Vec<_>>();
+    /// for (i, &entity) in entities.iter().enumerate() {
+    ///     world.insert_one(entity, i as u32).unwrap();
+    /// }
+    /// assert_eq!(*world.get::<u32>(entities[2]).unwrap(), 2);
+    /// ```
+    pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIter<'_> {
+        self.entities.reserve_entities(count)
+    }
+
     /// Destroy an entity and all its components
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
-        self.flush();
-        let loc = self.entities.free(entity)?;
-        if let Some(moved) = unsafe { self.archetypes[loc.archetype as usize].remove(loc.index) } {
-            self.entities.meta[moved as usize].location.index = loc.index;
-        }
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        self.assert_writable(loc);
+        self.despawn_unchecked(entity);
         Ok(())
     }
 
-    /// Ensure `additional` entities with exact components `T` can be spawned without reallocating
-    pub fn reserve<T: Bundle>(&mut self, additional: u32) {
-        self.reserve_inner::<T>(additional);
-    }
-
-    fn reserve_inner<T: Bundle>(&mut self, additional: u32) -> u32 {
-        self.flush();
-        self.entities.reserve(additional);
-
-        let archetype_id = T::with_static_ids(|ids| {
-            self.index.get(ids).copied().unwrap_or_else(|| {
-                let x = self.archetypes.len() as u32;
-                self.archetypes.push(Archetype::new(T::static_type_info()));
-                self.index.insert(ids.to_vec(), x);
-                self.archetype_generation += 1;
-                x
-            })
-        });
-
-        self.archetypes[archetype_id as usize].reserve(additional);
-        archetype_id
+    /// Like `despawn`, but returns an error rather than panicking if `entity` is stored in
+    /// read-only memory
+    pub fn try_despawn(&mut self, entity: Entity) -> Result<(), WorldError> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        self.check_writable(loc)?;
+        self.despawn(entity)?;
+        Ok(())
     }
 
-    /// Despawn all entities
+    /// Replace `entity`'s handle with a new one, keeping its components, tags, and name
     ///
-    /// Preserves allocated storage for reuse.
-    pub fn clear(&mut self) {
-        for x in &mut self.archetypes {
-            x.clear();
+    /// Every copy of the old handle, and every `ComponentHandle` made from it, becomes invalid as if
+    /// the entity had been despawned. Useful when transferring ownership of an entity, e.g. to
+    /// another player, to ensure nothing the previous owner held onto can still refer to it.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let b = world.invalidate_handles(a).unwrap();
+    /// assert!(!world.contains(a));
+    /// assert_eq!(*world.get::<i32>(b).unwrap(), 123);
+    /// ```
+    pub fn invalidate_handles(&mut self, entity: Entity) -> Result<Entity, NoSuchEntity> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        let new = self.entities.regenerate(entity)?;
+        self.tags.replaced(entity, new);
+        self.names.replaced(entity, new);
+        if loc.index != u32::MAX {
+            let archetype = &self.archetypes[loc.archetype as usize];
+            unsafe {
+                self.indices.removed_all(entity, archetype, loc.index);
+                self.indices.inserted_all(new, archetype, loc.index);
+            }
+            if let Some(ref mut journal) = self.journal {
+                journal.despawned(entity);
+                journal.spawned(new, archetype, loc.index);
+            }
         }
-        self.entities.clear();
-    }
-
-    /// Whether `entity` still exists
-    pub fn contains(&self, entity: Entity) -> bool {
-        self.entities.contains(entity)
+        Ok(new)
     }
 
-    /// Efficiently iterate over all entities that have certain components
-    ///
-    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
-    /// type. A query type is `&T`, `&mut T`, a tuple of query types, or an `Option` wrapping a
-    /// query type, where `T` is any component type. Components queried with `&mut` must only appear
-    /// once. Entities which do not have a component type referenced outside of an `Option` will be
-    /// skipped.
-    ///
-    /// Entities are yielded in arbitrary order.
+    /// Destroy every entity matching `Q`, returning how many there were
     ///
-    /// The returned `QueryBorrow` can be further transformed with combinator methods; see its
-    /// documentation for details.
+    /// Much faster than calling `despawn` for each entity, since every matching archetype is
+    /// emptied in a single pass without moving any components or looking up entity locations.
+    /// `Q` is only used to select entities, so its components are never borrowed. Entities spawned
+    /// with `spawn_external` are not despawned. To despawn everything in a bounded amount of time
+    /// per call, see `DespawnAll`.
     ///
-    /// Iterating a query will panic if it would violate an existing unique reference or construct
-    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
-    /// the same entity. Simultaneous queries can access the same component type if and only if the
-    /// world contains no entities that have all components required by both queries, assuming no
-    /// other component borrows are outstanding.
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn(("abc",));
+    /// assert_eq!(world.despawn_all::<Without<bool, &i32>>(), 10);
+    /// assert!(world.contains(a) && world.contains(b));
+    /// ```
+    pub fn despawn_all<Q: Query>(&mut self) -> u32 {
+        self.flush_entities();
+        let mut count = 0;
+        for (archetype_id, archetype) in self.archetypes.iter_mut().enumerate() {
+            if archetype.is_empty()
+                || archetype.is_read_only()
+                || Q::Fetch::access(archetype).is_none()
+            {
+                continue;
+            }
+            let meta = &self.entities.meta;
+            let entity = |index| {
+                let id = archetype.entity_id(index);
+                meta[id as usize].entity(id)
+            };
+            for index in 0..archetype.len() {
+                unsafe {
+                    self.indices.removed_all(entity(index), archetype, index);
+                }
+            }
+            let len = archetype.len();
+            match self.graveyard {
+                Some(ref mut graveyard) => {
+                    for index in 0..len {
+                        unsafe {
+                            graveyard.bury(entity(index), archetype_id as u32, archetype, index);
+                        }
+                    }
+                    archetype.clear(|_| false, |_, _, _| {});
+                }
+                None => {
+                    let sinks = &mut self.removal_sinks;
+                    let observed = !sinks.is_empty();
+                    archetype.clear(
+                        |_| observed,
+                        |id, ty, ptr| unsafe {
+                            let entity = meta[id as usize].entity(id);
+                            sinks.discard(entity, ty, ptr)
+                        },
+                    );
+                }
+            }
+            // Clearing leaves the IDs of the former entities in place
+            for index in 0..len {
+                let id = archetype.entity_id(index);
+                let entity = self.entities.meta[id as usize].entity(id);
+                self.entities.free(entity).unwrap();
+                self.tags.despawned(entity);
+                self.names.despawned(entity);
+                if let Some(ref mut journal) = self.journal {
+                    journal.despawned(entity);
+                }
+            }
+            count += len;
+        }
+        count
+    }
+
+    /// Destroy every entity for which `f` returns `false`, returning how many there were
     ///
-    /// Iterating a query yields references with lifetimes bound to the `QueryBorrow` returned
-    /// here. To ensure those are invalidated, the return value of this method must be dropped for
-    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
-    /// references obtained from a query cannot outlive the `QueryBorrow`.
+    /// Visits entities an archetype at a time, without looking up their locations, so it's much
+    /// faster than collecting entities to pass to `despawn`. Despawned entities are handled exactly
+    /// as by `despawn`. Entities spawned with `spawn_external` are not visited.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<(&i32, &bool)>()
-    ///     .iter()
-    ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert_eq!(entities.len(), 2);
-    /// assert!(entities.contains(&(a, 123, true)));
-    /// assert!(entities.contains(&(b, 456, false)));
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// let a = world.spawn(("abc",));
+    /// let removed = world.retain(|_, e| e.get::<i32>().is_none_or(|x| *x < 5));
+    /// assert_eq!(removed, 5);
+    /// assert_eq!(world.len(), 6);
+    /// assert!(world.contains(a));
     /// ```
-    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
-        QueryBorrow::new(&self.entities.meta, &self.archetypes)
+    pub fn retain(&mut self, mut f: impl FnMut(Entity, EntityRef<'_>) -> bool) -> u32 {
+        self.flush_entities();
+        let tick = self.change_tick();
+        let mut doomed = Vec::new();
+        let mut count = 0;
+        for archetype_id in 0..self.archetypes.len() as u32 {
+            let archetype = &self.archetypes[archetype_id as usize];
+            if archetype.is_read_only() {
+                continue;
+            }
+            for index in 0..archetype.len() {
+                let id = archetype.entity_id(index);
+                let entity = self.entities.meta[id as usize].entity(id);
+                if !f(entity, unsafe { EntityRef::new(archetype, index, tick) }) {
+                    doomed.push(entity);
+                }
+            }
+            // Removing from the back first only ever moves entities that are being kept
+            for entity in doomed.drain(..).rev() {
+                self.despawn_unchecked(entity);
+                count += 1;
+            }
+        }
+        count
     }
 
-    /// Prepare a query against a single entity
-    ///
-    /// Call `get` on the resulting `QueryOne` to actually execute the query. The `QueryOne` value
-    /// is responsible for releasing the dynamically-checked borrow made by `get`, so it can't be
-    /// dropped while references returned by `get` are live.
+    /// Destroy every entity having a `T` component for which `f` returns `true`, returning how
+    /// many there were
     ///
-    /// Handy for accessing multiple components simultaneously.
+    /// Like `retain`, but only reads the `T` columns of archetypes that have them, e.g. to despawn
+    /// everything whose position is outside the streaming radius. Despawned entities are handled
+    /// exactly as by `despawn`. Entities spawned with `spawn_external` are not visited.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (Position(i as f32),)));
+    /// let a = world.spawn((Position(100.0), "far"));
+    /// let b = world.spawn(("nowhere",));
+    /// assert_eq!(world.despawn_where::<Position>(|x| x.0 > 4.5), 6);
+    /// assert_eq!(world.len(), 6);
+    /// assert!(!world.contains(a) && world.contains(b));
+    /// ```
+    pub fn despawn_where<T: Component>(&mut self, mut f: impl FnMut(&T) -> bool) -> u32 {
+        self.flush_entities();
+        let mut doomed = Vec::new();
+        let mut count = 0;
+        for archetype_id in 0..self.archetypes.len() {
+            let archetype = &self.archetypes[archetype_id];
+            if archetype.is_read_only() {
+                continue;
+            }
+            let column = match archetype.column::<T>() {
+                Some(x) => x,
+                None => continue,
+            };
+            for index in 0..archetype.len() {
+                if f(unsafe { &*column.add(index as usize).get() }) {
+                    doomed.push(archetype.entity_id(index));
+                }
+            }
+            // Removing from the back first only ever moves entities that are being kept
+            for id in doomed.drain(..).rev() {
+                let entity = self.entities.meta[id as usize].entity(id);
+                self.despawn_unchecked(entity);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Destroy `entity`, moving its `T` components out rather than dropping them
+    ///
+    /// Useful for transferring an entity's data to another world or a save file without cloning
+    /// it. Any other components are dropped, as by `despawn`. Unlike `despawn`, the entity is
+    /// not kept in the `graveyard`. Fails without modifying the world if the entity doesn't have
+    /// every component in `T`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, "abc", true));
+    /// assert_eq!(world.take::<(i32, &str)>(a), Ok((123, "abc")));
+    /// assert!(!world.contains(a));
+    /// ```
+    pub fn take<T: Bundle>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        self.assert_writable(loc);
+        let ids = T::with_static_ids(|ids| ids.to_vec());
+        let archetype = &self.archetypes[loc.archetype as usize];
+        let bundle = unsafe { T::get(|ty, size| archetype.get_dynamic(ty, size, loc.index))? };
+        let mut sinks = mem::take(&mut self.removal_sinks);
+        self.take_unchecked(entity, |ty, ptr| {
+            if !ids.contains(&ty.id()) {
+                unsafe { sinks.discard(entity, ty, ptr) }
+            }
+        });
+        self.removal_sinks = sinks;
+        Ok(bundle)
+    }
+
+    /// Destroy `entity`, moving all of its components into `builder`
+    ///
+    /// Like `take`, for when the components aren't statically known. Spawning `builder.build()`,
+    /// perhaps in another world, recreates the entity.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, "abc"));
+    /// let mut builder = EntityBuilder::new();
+    /// world.take_dynamic(a, &mut builder).unwrap();
+    /// let mut other = World::new();
+    /// let b = other.spawn(builder.build());
+    /// assert_eq!(*other.get::<&str>(b).unwrap(), "abc");
+    /// ```
+    pub fn take_dynamic(
+        &mut self,
+        entity: Entity,
+        builder: &mut EntityBuilder,
+    ) -> Result<(), NoSuchEntity> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        self.assert_writable(loc);
+        self.take_unchecked(entity, |ty, ptr| unsafe { builder.add_raw(*ty, ptr) });
+        Ok(())
+    }
+
+    /// Destroy the live `entity`, which must be writable, passing each component to `f` to be
+    /// disposed of
+    fn take_unchecked(&mut self, entity: Entity, f: impl FnMut(&TypeInfo, *mut u8)) {
+        let loc = self.entities.meta[entity.id as usize].location;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        unsafe {
+            self.indices.removed_all(entity, archetype, loc.index);
+        }
+        self.entities.free(entity).unwrap();
+        unsafe {
+            if let Some(moved) = archetype.remove(loc.index, f) {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
+        }
+        self.tags.despawned(entity);
+        self.names.despawned(entity);
+        if let Some(ref mut journal) = self.journal {
+            journal.despawned(entity);
+        }
+    }
+
+    /// Destroy every live entity in `entities`, returning how many there were
+    ///
+    /// Dead and duplicate handles are ignored. Faster than calling `despawn` for each entity, since
+    /// entities are removed from the back of each archetype first, minimizing the number of other
+    /// entities moved to fill the gaps.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    /// world.despawn(entities[0]).unwrap();
+    /// assert_eq!(world.despawn_many(&entities[..5]), 4);
+    /// assert_eq!(world.query::<()>().iter().count(), 5);
+    /// ```
+    pub fn despawn_many(&mut self, entities: &[Entity]) -> usize {
+        self.flush_entities();
+        let mut doomed = entities
+            .iter()
+            .filter_map(|&entity| Some((self.entities.get(entity).ok()?, entity)))
+            .collect::<Vec<_>>();
+        // Back to front within each archetype, so that entities yet to be despawned are never moved
+        doomed.sort_unstable_by_key(|&(loc, _)| core::cmp::Reverse((loc.archetype, loc.index)));
+        doomed.dedup_by_key(|&mut (_, entity)| entity);
+
+        // Validate everything before modifying anything
+        for &(loc, _) in &doomed {
+            self.assert_writable(loc);
+        }
+
+        for &(_, entity) in &doomed {
+            self.despawn_unchecked(entity);
+        }
+        doomed.len()
+    }
+
+    /// Destroy the live `entity`, which must be writable
+    fn despawn_unchecked(&mut self, entity: Entity) {
+        let loc = self.entities.meta[entity.id as usize].location;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        // Before anything is modified, so that a panicking index leaves the entity intact
+        unsafe {
+            self.indices.removed_all(entity, archetype, loc.index);
+        }
+        self.entities.free(entity).unwrap();
+        let sinks = &mut self.removal_sinks;
+        let moved = match self.graveyard {
+            Some(ref mut graveyard) => unsafe {
+                graveyard.bury(entity, loc.archetype, archetype, loc.index);
+                archetype.remove(loc.index, |_, _| {})
+            },
+            None => unsafe {
+                archetype.remove(loc.index, |ty, ptr| sinks.discard(entity, ty, ptr))
+            },
+        };
+        if let Some(moved) = moved {
+            self.entities.meta[moved as usize].location.index = loc.index;
+        }
+        self.tags.despawned(entity);
+        self.names.despawned(entity);
+        if let Some(ref mut journal) = self.journal {
+            journal.despawned(entity);
+        }
+    }
+
+    /// Panic if entities at `loc` can't be moved or despawned
+    fn assert_writable(&self, loc: Location) {
+        if let Err(e) = self.check_writable(loc) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Fail if entities at `loc` can't be moved or despawned
+    fn check_writable(&self, loc: Location) -> Result<(), WorldError> {
+        if self.archetypes[loc.archetype as usize].is_read_only() {
+            return Err(WorldError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Ensure `additional` entities with exact components `T` can be spawned without reallocating
+    pub fn reserve<T: Bundle>(&mut self, additional: u32) {
+        self.reserve_inner::<T>(additional);
+    }
+
+    fn reserve_inner<T: Bundle>(&mut self, additional: u32) -> u32 {
+        self.flush_entities();
+        self.entities.reserve(additional);
+        let archetype_id = self.archetype_for::<T>();
+        self.archetypes[archetype_id as usize].reserve(additional);
+        archetype_id
+    }
+
+    /// Release unused capacity in every archetype, resetting their peaks
+    ///
+    /// Read-only archetypes are left unchanged. See
+    /// `Archetype::garbage_stats` for how much memory this would free.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = (0..1000).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    /// for &e in &entities[10..] {
+    ///     world.despawn(e).unwrap();
+    /// }
+    /// let garbage = |world: &World| {
+    ///     world
+    ///         .archetypes()
+    ///         .map(|x| x.garbage_stats().reclaimable_bytes)
+    ///         .sum::<usize>()
+    /// };
+    /// assert!(garbage(&world) > 0);
+    /// world.shrink_to_fit();
+    /// assert_eq!(garbage(&world), 0);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.flush_entities();
+        for archetype in &mut self.archetypes {
+            archetype.shrink_to_fit();
+        }
+    }
+
+    /// Create the archetype for entities with exactly the components in `T`, if it doesn't
+    /// already exist
+    ///
+    /// Spares the first `spawn` of such an entity, e.g. in the middle of a frame, the cost of
+    /// creating the archetype. To also preallocate storage for the entities, use `reserve`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.ensure_archetype::<(i32, bool)>();
+    /// let generation = world.archetypes_generation();
+    /// world.spawn((true, 42));
+    /// assert_eq!(world.archetypes_generation(), generation);
+    /// ```
+    pub fn ensure_archetype<T: Bundle>(&mut self) {
+        self.archetype_for::<T>();
+    }
+
+    /// Like `ensure_archetype`, for a set of component types not known at compile time
+    ///
+    /// `types` may be in any order, and duplicates are ignored.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.ensure_archetype_dynamic(&[TypeInfo::of::<i32>(), TypeInfo::of::<bool>()]);
+    /// let generation = world.archetypes_generation();
+    /// world.spawn((true, 42));
+    /// assert_eq!(world.archetypes_generation(), generation);
+    /// ```
+    pub fn ensure_archetype_dynamic(&mut self, types: &[TypeInfo]) {
+        self.archetype_for_types(types);
+    }
+
+    /// Find or create the archetype for entities with exactly the components in `types`
+    pub(crate) fn archetype_for_types(&mut self, types: &[TypeInfo]) -> u32 {
+        let mut info = types.to_vec();
+        info.sort();
+        info.dedup();
+        let ids = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+        if let Some(&x) = self.index.get(&ids) {
+            return x;
+        }
+        let x = self.archetypes.len() as u32;
+        self.archetypes.push(
+            Archetype::new(info)
+                .with_pins(&self.pinned)
+                .with_column_order(self.column_order)
+                .with_colocated(&self.colocated)
+                .with_arenas(&self.arenas)
+                .with_barriers(&self.write_barriers)
+                .with_shadows(&self.double_buffered),
+        );
+        self.index.insert(ids, x);
+        self.archetype_generation += 1;
+        x
+    }
+
+    /// Forbid `T` components from being moved in memory once stored, for types holding pointers
+    /// into themselves
+    ///
+    /// Storage is normally kept dense by moving components: the last entity in an archetype fills
+    /// the gap left by a despawned one, entities move between archetypes as components are
+    /// inserted and removed, and storage is reallocated as it grows. Once `T` is pinned, each `T`
+    /// is instead stored in a slot of its own, which its archetype's column points to, so that
+    /// only the pointer moves. The slot is handed over as the entity moves between archetypes.
+    /// Iterating over `T`s is therefore slower, and they can't be accessed as slices, e.g. with
+    /// `QueryBorrow::iter_columns` or `ArchetypeView::columns`, which panic instead.
+    ///
+    /// Merging worlds moves the merged world's components, pinned or not, and `backfill` panics
+    /// rather than move a `T`.
+    ///
+    /// Pinning can't be undone. Panics if a `T` is already stored.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Node(u32);
+    /// let mut world = World::new();
+    /// world.pin_component::<Node>();
+    /// let a = world.spawn((Node(1),));
+    /// let b = world.spawn((Node(2),));
+    /// let address = |world: &World| &*world.get::<Node>(b).unwrap() as *const Node;
+    /// let before = address(&world);
+    /// world.despawn(a).unwrap();
+    /// world.insert_one(b, true).unwrap();
+    /// assert_eq!(address(&world), before);
+    /// ```
+    pub fn pin_component<T: Component>(&mut self) {
+        assert!(
+            self.archetypes
+                .iter()
+                .all(|x| x.is_empty() || !x.has::<T>()),
+            "can't pin component {} once stored",
+            type_name::<T>()
+        );
+        let arena = PinArena::new(type_name::<T>(), Layout::new::<T>());
+        self.pinned.insert(TypeId::of::<T>(), Arc::new(arena));
+        for archetype in &mut self.archetypes {
+            archetype.pin(&self.pinned);
+        }
+    }
+
+    /// Lay out each archetype's component columns in the order given by `order`, or restore the
+    /// default of descending alignment if `None`
+    ///
+    /// Only affects memory layout, for experimenting with cache behavior on particular workloads,
+    /// e.g. placing the columns of frequently accessed components next to each other. Columns that
+    /// `order` considers equal keep their default relative order. Takes effect for each archetype
+    /// the next time its storage is allocated.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// // Largest components first
+    /// world.set_column_order(Some(|x, y| y.layout().size().cmp(&x.layout().size())));
+    /// let a = world.spawn((1u8, 2u64, [3u16; 7]));
+    /// assert_eq!(*world.get::<[u16; 7]>(a).unwrap(), [3; 7]);
+    /// ```
+    pub fn set_column_order(&mut self, order: Option<ColumnOrder>) {
+        self.column_order = order;
+        for archetype in &mut self.archetypes {
+            archetype.set_column_order(order);
+        }
+    }
+
+    /// Lay out the `B` column of each archetype directly after its `A` column
+    ///
+    /// A hint for components that are almost always accessed together, such as position and
+    /// velocity, so that iterating over both touches adjacent memory. Applies on top of
+    /// `set_column_order`, and has no effect on columns allocated from a `ColumnArena`. Takes
+    /// effect for each archetype the next time its storage is allocated.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position([f32; 3]);
+    /// struct Velocity([f32; 3]);
+    /// let mut world = World::new();
+    /// world.colocate::<Position, Velocity>();
+    /// let a = world.spawn((Position([0.0; 3]), 1u64, Velocity([1.0; 3])));
+    /// assert_eq!(world.get::<Velocity>(a).unwrap().0, [1.0; 3]);
+    /// ```
+    pub fn colocate<A: Component, B: Component>(&mut self) {
+        assert_ne!(
+            TypeId::of::<A>(),
+            TypeId::of::<B>(),
+            "colocated component types must differ"
+        );
+        self.colocated.push((TypeId::of::<A>(), TypeId::of::<B>()));
+        for archetype in &mut self.archetypes {
+            archetype.set_colocated(&self.colocated);
+        }
+    }
+
+    /// Allocate the columns of `T` components from `arena` rather than alongside other columns
+    ///
+    /// Takes effect for each archetype the next time its storage is allocated. See `ColumnArena`.
+    pub fn use_arena<T: Component>(&mut self, arena: Arc<ColumnArena>) {
+        self.arenas.insert(TypeId::of::<T>(), arena);
+        for archetype in &mut self.archetypes {
+            archetype.set_arenas(&self.arenas);
+        }
+    }
+
+    /// Move the `T` column of every archetype out of its storage into a separate allocation
+    ///
+    /// Bulky components that are rarely accessed, e.g. as found with `access_stats`, otherwise
+    /// spread an archetype's frequently accessed columns apart in memory. Split columns are linked
+    /// to their archetype by row, so queries and every other kind of access find them as before.
+    /// Unlike `use_arena`, existing archetypes are reorganized immediately, and it can be undone
+    /// with `merge_cold`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Mesh([u8; 4096]);
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, Mesh([7; 4096])));
+    /// world.split_cold::<Mesh>();
+    /// assert!(world.is_cold::<Mesh>());
+    /// assert_eq!(world.get::<Mesh>(a).unwrap().0[0], 7);
+    /// world.merge_cold::<Mesh>();
+    /// assert_eq!(world.query::<(&i32, &Mesh)>().iter().count(), 1);
+    /// ```
+    pub fn split_cold<T: Component>(&mut self) {
+        self.set_cold(TypeId::of::<T>(), true);
+    }
+
+    /// Store the `T` column of every archetype alongside its other columns again, undoing
+    /// `split_cold`
+    pub fn merge_cold<T: Component>(&mut self) {
+        self.set_cold(TypeId::of::<T>(), false);
+    }
+
+    /// Whether `T` columns have been split off by `split_cold`
+    pub fn is_cold<T: Component>(&self) -> bool {
+        self.is_cold_dynamic(TypeId::of::<T>())
+    }
+
+    fn is_cold_dynamic(&self, ty: TypeId) -> bool {
+        self.arenas
+            .get(&ty)
+            .is_some_and(|x| Arc::ptr_eq(x, &self.cold))
+    }
+
+    fn set_cold(&mut self, ty: TypeId, cold: bool) {
+        if self.is_cold_dynamic(ty) == cold {
+            return;
+        }
+        if cold {
+            self.arenas.insert(ty, self.cold.clone());
+        } else {
+            self.arenas.remove(&ty);
+        }
+        for archetype in &mut self.archetypes {
+            if archetype.has_dynamic(ty) {
+                archetype.set_arenas(&self.arenas);
+                archetype.relayout();
+            }
+        }
+    }
+
+    /// Split off the columns of every component type borrowed at most `max_borrows` times since
+    /// the last `flush`, as if by `split_cold`, returning their `access_stats`
+    ///
+    /// Meant to be called after running a representative workload, e.g. a frame of gameplay.
+    #[cfg(feature = "stats")]
+    pub fn split_cold_columns(&mut self, max_borrows: u32) -> Vec<AccessStats> {
+        let cold = self
+            .access_stats()
+            .into_iter()
+            .filter(|x| x.entities != 0 && x.reads + x.writes <= max_borrows)
+            .collect::<Vec<_>>();
+        for x in &cold {
+            self.set_cold(x.id, true);
+        }
+        cold
+    }
+
+    /// Keep the value every entity's `T` component had at the last `flush`, readable through the
+    /// `Previous<T>` query and `previous`
+    ///
+    /// Each archetype storing `T` keeps a copy of its `T` column, which `flush` overwrites with the
+    /// current values. An entity that gains a `T` starts out with its new value as the previous
+    /// one; otherwise previous values are unaffected by changes to an entity's components between
+    /// flushes. Entities already having a `T` start out with their current values. Registering the
+    /// same type again has no effect.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.double_buffer::<u32>();
+    /// let cells = (0..4u32).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    /// for _ in 0..2 {
+    ///     // Each cell takes the previous value of the one before it
+    ///     for (i, &cell) in cells.iter().enumerate() {
+    ///         let before = *world.previous::<u32>(cells[(i + 3) % 4]).unwrap();
+    ///         *world.get_mut::<u32>(cell).unwrap() = before;
+    ///     }
+    ///     world.flush();
+    /// }
+    /// let values = cells.iter().map(|&e| *world.get::<u32>(e).unwrap()).collect::<Vec<_>>();
+    /// assert_eq!(values, [2, 3, 0, 1]);
+    /// ```
+    pub fn double_buffer<T: Component + Clone>(&mut self) {
+        if self.double_buffered.contains_key(&TypeId::of::<T>()) {
+            return;
+        }
+        self.double_buffered
+            .insert(TypeId::of::<T>(), ShadowColumn::<T>::boxed);
+        for archetype in &mut self.archetypes {
+            archetype.set_shadows(&self.double_buffered);
+        }
+    }
+
+    /// The value of `entity`'s `T` component as of the last `flush`, for `T` registered with
+    /// `double_buffer`
+    ///
+    /// Fails with `MissingComponent` if `entity` has no `T` or `T` isn't double-buffered.
+    pub fn previous<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        self.archetypes[loc.archetype as usize]
+            .shadow::<T>()
+            .and_then(|x| x.get(loc.index as usize))
+            .ok_or_else(|| MissingComponent::new::<T>().into())
+    }
+
+    /// Find or create the archetype for entities with exactly the components in `T`
+    fn archetype_for<T: Bundle>(&mut self) -> u32 {
+        T::with_static_ids(|ids| {
+            self.index.get(ids).copied().unwrap_or_else(|| {
+                let x = self.archetypes.len() as u32;
+                self.archetypes.push(
+                    Archetype::new(T::static_type_info())
+                        .with_pins(&self.pinned)
+                        .with_column_order(self.column_order)
+                        .with_colocated(&self.colocated)
+                        .with_arenas(&self.arenas)
+                        .with_barriers(&self.write_barriers)
+                        .with_shadows(&self.double_buffered),
+                );
+                self.index.insert(ids.to_vec(), x);
+                self.archetype_generation += 1;
+                x
+            })
+        })
+    }
+
+    /// Despawn all entities
+    ///
+    /// Preserves allocated storage for reuse, so refilling the world, e.g. when reloading a level,
+    /// doesn't need to grow every archetype from scratch. Handles to the despawned entities,
+    /// including reserved ones, become invalid as usual.
+    pub fn clear(&mut self) {
+        self.flush_entities();
+        for x in &mut self.archetypes {
+            if !self.indices.is_empty() || self.journal.is_some() {
+                for index in 0..x.len() {
+                    let id = x.entity_id(index);
+                    let entity = self.entities.meta[id as usize].entity(id);
+                    unsafe {
+                        self.indices.removed_all(entity, x, index);
+                    }
+                    if let Some(ref mut journal) = self.journal {
+                        journal.despawned(entity);
+                    }
+                }
+            }
+            let meta = &self.entities.meta;
+            let sinks = &mut self.removal_sinks;
+            let observed = !sinks.is_empty();
+            x.clear(
+                |_| observed,
+                |id, ty, ptr| {
+                    let entity = meta[id as usize].entity(id);
+                    unsafe { sinks.discard(entity, ty, ptr) }
+                },
+            );
+        }
+        self.tags.clear();
+        self.names.clear();
+        self.entities.clear();
+        if let Some(ref mut graveyard) = self.graveyard {
+            graveyard.clear(&mut self.removal_sinks);
+        }
+    }
+
+    /// Number of live entities
+    ///
+    /// Reserved entities aren't counted until they're flushed, matching iteration. Cheap compared
+    /// to counting the results of a query, though not constant-time.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// world.spawn(());
+    /// world.reserve_entity();
+    /// assert_eq!(world.len(), 2);
+    /// world.flush();
+    /// assert_eq!(world.len(), 3);
+    /// ```
+    pub fn len(&self) -> u32 {
+        self.archetypes.iter().map(|x| x.len()).sum()
+    }
+
+    /// Whether there are no live entities
+    pub fn is_empty(&self) -> bool {
+        self.archetypes.iter().all(|x| x.is_empty())
+    }
+
+    /// Whether `entity` still exists
+    ///
+    /// Entities that have been reserved but not yet flushed exist. Handles that were never issued
+    /// by this world, e.g. ones from another world or made up with `Entity::from_bits`, generally
+    /// don't.
+    #[doc(alias = "is_alive")]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// The current generation of `entity`'s ID, whether or not `entity` is stale
+    ///
+    /// `entity` is stale exactly when its own generation differs, so this is useful for telling
+    /// apart handles to an entity that has since been despawned from handles to its ID's current
+    /// occupant. Returns `None` if the world has yet to allocate storage for the ID, or if the ID
+    /// has been retired.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// assert_eq!(world.generation(a), Some(a.generation()));
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn((456,));
+    /// assert_eq!(b.id(), a.id());
+    /// assert_eq!(world.generation(a), Some(b.generation()));
+    /// ```
+    pub fn generation(&self, entity: Entity) -> Option<Generation> {
+        match self.entities.slot(entity.id())? {
+            EntitySlot::Live(x) | EntitySlot::Free(x) => Some(x.generation()),
+            EntitySlot::Retired(_) => None,
+        }
+    }
+
+    /// The state of entity ID `id`, or `None` if the world has yet to allocate storage for it
+    ///
+    /// Useful for diagnosing stale handles: a handle whose generation differs from that of its
+    /// ID's slot refers to an entity that has since been despawned.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// assert_eq!(world.entity_slot(a.id()), Some(EntitySlot::Live(a)));
+    /// world.despawn(a).unwrap();
+    /// match world.entity_slot(a.id()).unwrap() {
+    ///     EntitySlot::Free(next) => assert!(next.generation() > a.generation()),
+    ///     _ => unreachable!(),
+    /// }
+    /// let b = world.spawn((456,));
+    /// assert_eq!(world.entity_slot(a.id()), Some(EntitySlot::Live(b)));
+    /// ```
+    pub fn entity_slot(&self, id: u32) -> Option<EntitySlot> {
+        self.entities.slot(id)
+    }
+
+    /// Iterate over the state of every entity ID the world has allocated storage for, in order of
+    /// ID
+    ///
+    /// Storage is allocated in batches, so this includes free IDs that have never been used.
+    pub fn entity_slots(&self) -> impl ExactSizeIterator<Item = EntitySlot> + '_ {
+        (0..self.entities.slots()).map(move |id| self.entities.slot(id).unwrap())
+    }
+
+    /// Check the liveness of many entities at once
+    ///
+    /// Returns a bitmask in which bit `i % 64` of word `i / 64` is set if and only if
+    /// `entities[i]` still exists, as if by `contains`. Faster than calling `contains` for each
+    /// entity, e.g. when pruning large collections of possibly-stale references.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// let b = world.spawn(());
+    /// world.despawn(a).unwrap();
+    /// let live = world.validate(&[a, b, a]);
+    /// assert_eq!(live, [0b010]);
+    /// ```
+    pub fn validate(&self, entities: &[Entity]) -> Vec<u64> {
+        let mut out = vec![0; entities.len().div_ceil(64)];
+        self.entities.validate(entities, &mut out);
+        out
+    }
+
+    /// Efficiently iterate over all entities that have certain components
+    ///
+    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
+    /// type. A query type is `&T`, `&mut T`, a tuple of query types, or an `Option` wrapping a
+    /// query type, where `T` is any component type. Components queried with `&mut` must only appear
+    /// once. Entities which do not have a component type referenced outside of an `Option` will be
+    /// skipped.
+    ///
+    /// Entities are yielded in arbitrary order.
+    ///
+    /// The returned `QueryBorrow` can be further transformed with combinator methods; see its
+    /// documentation for details.
+    ///
+    /// Iterating a query will panic if it would violate an existing unique reference or construct
+    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
+    /// the same entity. Simultaneous queries can access the same component type if and only if the
+    /// world contains no entities that have all components required by both queries, assuming no
+    /// other component borrows are outstanding.
+    ///
+    /// Iterating a query yields references with lifetimes bound to the `QueryBorrow` returned
+    /// here. To ensure those are invalidated, the return value of this method must be dropped for
+    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
+    /// references obtained from a query cannot outlive the `QueryBorrow`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<(&i32, &bool)>()
+    ///     .iter()
+    ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(entities.len(), 2);
+    /// assert!(entities.contains(&(a, 123, true)));
+    /// assert!(entities.contains(&(b, 456, false)));
+    /// ```
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
+        QueryBorrow::new(
+            &self.entities.meta,
+            &self.archetypes,
+            &self.frame,
+            self.change_tick(),
+        )
+        .borrow_policy(self.borrow_policy)
+    }
+
+    /// Like `query`, but borrows the components immediately, returning an error rather than
+    /// panicking if any is already borrowed incompatibly, or if `Q` would uniquely borrow
+    /// components stored in read-only memory by `spawn_external`
+    ///
+    /// Iterating the returned query can't fail.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// let mut a = world.try_query::<&mut i32>().unwrap();
+    /// match world.try_query::<&i32>() {
+    ///     Err(WorldError::Borrow(e)) => assert_eq!(e.type_name(), "i32"),
+    ///     _ => unreachable!(),
+    /// }
+    /// assert_eq!(a.iter().count(), 1);
+    /// ```
+    pub fn try_query<Q: Query>(&self) -> Result<QueryBorrow<'_, Q>, WorldError> {
+        Ok(self.query().borrow_now()?)
+    }
+
+    /// Determine how queries respond to conflicting borrows by default
+    ///
+    /// A server that must keep running might prefer `BorrowPolicy::Error` or `BorrowPolicy::Skip`,
+    /// while the default `BorrowPolicy::Panic` makes mistakes obvious during development. Only
+    /// affects queries constructed with `query`.
+    pub fn set_borrow_policy(&mut self, policy: BorrowPolicy) {
+        self.borrow_policy = policy;
+    }
+
+    /// Outstanding borrows of `T` components, e.g. by queries or `get`
+    ///
+    /// Borrows are tracked separately for each archetype's column of a type, so that e.g.
+    /// `Query<(&mut T, &A)>` and `Query<(&T, &B)>` conflict only over the entities having `T`, `A`,
+    /// and `B`. The returned state sums the borrows of every column. It's a snapshot that other
+    /// threads may invalidate immediately, so it suits backing off and retrying later, or asserting
+    /// that all borrows have been released at the end of a frame, rather than guaranteeing that a
+    /// subsequent borrow will succeed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// world.spawn((456,));
+    /// let mut query = world.query::<&mut i32>();
+    /// let _ = query.iter();
+    /// let state = world.borrow_state::<i32>();
+    /// assert_eq!(state.unique, 2);
+    /// assert!(!state.can_borrow());
+    /// drop(query);
+    /// assert!(!world.borrow_state::<i32>().is_borrowed());
+    /// ```
+    pub fn borrow_state<T: Component>(&self) -> BorrowState {
+        let mut state = BorrowState::default();
+        for archetype in &self.archetypes {
+            if let Some((shared, unique)) = archetype.borrow_state(TypeId::of::<T>()) {
+                state.shared = state.shared.saturating_add(shared as u32);
+                state.unique += unique as u32;
+            }
+        }
+        state
+    }
+
+    /// Scratch memory that's freed by the next `flush`
+    ///
+    /// Used by convenience APIs such as `QueryBorrow::iter_grouped_by` in place of heap
+    /// allocations. Calling `flush` once per frame keeps memory use bounded.
+    pub fn frame_allocator(&self) -> &FrameAllocator {
+        &self.frame
+    }
+
+    /// Keep the components of despawned entities readable through `graveyard` until the next
+    /// `flush`, rather than dropping them immediately
+    ///
+    /// Kept components reach removal sinks when they're finally dropped. Disabling drops any that
+    /// are currently kept.
+    pub fn keep_despawned(&mut self, enabled: bool) {
+        match (enabled, self.graveyard.take()) {
+            (true, graveyard) => {
+                self.graveyard = Some(graveyard.unwrap_or_else(|| Box::new(Graveyard::new())));
+            }
+            (false, Some(mut graveyard)) => graveyard.clear(&mut self.removal_sinks),
+            (false, None) => {}
+        }
+    }
+
+    /// The components of entities despawned since the last `flush`, if enabled by
+    /// `keep_despawned`
+    pub fn graveyard(&self) -> Option<&Graveyard> {
+        self.graveyard.as_deref()
+    }
+
+    /// Efficiently iterate over all entities that have a `K` and the components in `Q`, together
+    /// with the result of `R` for the entity that `key` extracts from each `K`
+    ///
+    /// Handy for following references between entities, e.g. looking up the position of each
+    /// entity's target. `R` is `None` for entities whose referent doesn't exist or doesn't satisfy
+    /// `R`. Because several entities may refer to the same one, `R` must not access any component
+    /// uniquely.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Target(Entity);
+    /// struct Position(f32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Position(1.0),));
+    /// let b = world.spawn((Position(5.0), Target(a)));
+    /// let dead = world.spawn(());
+    /// world.despawn(dead).unwrap();
+    /// let c = world.spawn((Position(3.0), Target(dead)));
+    /// let mut distances = world
+    ///     .join::<Target, &Position, &Position, _>(|t| t.0)
+    ///     .iter()
+    ///     .map(|(e, pos, target)| (e, target.map(|t| pos.0 - t.0)))
+    ///     .collect::<Vec<_>>();
+    /// distances.sort_by_key(|x| x.0);
+    /// assert_eq!(distances, &[(b, Some(4.0)), (c, None)]);
+    /// ```
+    pub fn join<K, Q, R, F>(&self, key: F) -> JoinBorrow<'_, K, Q, R, F>
+    where
+        K: Component,
+        Q: Query,
+        R: Query,
+        F: Fn(&K) -> Entity,
+    {
+        JoinBorrow::new(
+            &self.entities.meta,
+            &self.archetypes,
+            &self.frame,
+            self.change_tick(),
+            key,
+        )
+    }
+
+    /// Prepare a query against a single entity
+    ///
+    /// Call `get` on the resulting `QueryOne` to actually execute the query. The `QueryOne` value
+    /// is responsible for releasing the dynamically-checked borrow made by `get`, so it can't be
+    /// dropped while references returned by `get` are live.
+    ///
+    /// Handy for accessing multiple components simultaneously.
     ///
     /// # Example
     /// ```
@@ -268,317 +1733,2117 @@ impl World {
     /// if *flag { *number *= 2; }
     /// assert_eq!(*number, 246);
     /// ```
-    pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'_, Q>, NoSuchEntity> {
-        let loc = self.entities.get(entity)?;
-        Ok(unsafe { QueryOne::new(&self.archetypes[loc.archetype as usize], loc.index) })
+    pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'_, Q>, NoSuchEntity> {
+        let loc = self.entities.get(entity)?;
+        Ok(unsafe {
+            QueryOne::new(
+                &self.archetypes[loc.archetype as usize],
+                loc.index,
+                self.change_tick(),
+            )
+        })
+    }
+
+    /// Borrow the `T` component of `entity`
+    ///
+    /// Panics if the component is already uniquely borrowed from another entity with the same
+    /// components.
+    pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        Ok(unsafe { Ref::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+    }
+
+    /// Uniquely borrow the `T` component of `entity`
+    ///
+    /// Panics if the component is already borrowed from another entity with the same components.
+    pub fn get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        Ok(unsafe {
+            RefMut::new(
+                &self.archetypes[loc.archetype as usize],
+                loc.index,
+                self.change_tick(),
+            )?
+        })
+    }
+
+    /// Like `get`, but returns an error rather than panicking if the component is already
+    /// uniquely borrowed
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let _x = world.get_mut::<i32>(a).unwrap();
+    /// assert!(matches!(world.try_get::<i32>(a), Err(WorldError::Borrow(_))));
+    /// ```
+    pub fn try_get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, WorldError> {
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes[loc.archetype as usize];
+        Ok(unsafe { Ref::try_new(archetype, loc.index)? })
+    }
+
+    /// Like `get_mut`, but returns an error rather than panicking if the component is already
+    /// borrowed or stored in read-only memory
+    pub fn try_get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, WorldError> {
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes[loc.archetype as usize];
+        Ok(unsafe { RefMut::try_new(archetype, loc.index, self.change_tick())? })
+    }
+
+    /// Uniquely access the `T` components of several distinct entities at once
+    ///
+    /// Fails if any entity doesn't exist or lacks a `T`. Panics if any two of `entities` are equal.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((10,));
+    /// let b = world.spawn((20, true));
+    /// let [x, y] = world.get_many_mut::<i32, 2>([a, b]).unwrap();
+    /// core::mem::swap(x, y);
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 20);
+    /// ```
+    pub fn get_many_mut<T: Component, const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[&mut T; N], ComponentError> {
+        assert_distinct(&entities);
+        self.flush_entities();
+        let tick = self.change_tick();
+        let mut targets = [NonNull::<T>::dangling(); N];
+        for (target, &entity) in targets.iter_mut().zip(&entities) {
+            let loc = self.entities.get(entity)?;
+            self.assert_writable(loc);
+            let archetype = &self.archetypes[loc.archetype as usize];
+            unsafe {
+                *target = NonNull::new_unchecked(
+                    archetype
+                        .get_at::<T>(loc.index)
+                        .ok_or_else(MissingComponent::new::<T>)?
+                        .as_ptr(),
+                );
+            }
+        }
+        for &entity in &entities {
+            let loc = self.entities.get(entity).unwrap();
+            let archetype = &self.archetypes[loc.archetype as usize];
+            archetype.touch(TypeId::of::<T>());
+            unsafe {
+                (*archetype
+                    .ticks::<T>()
+                    .unwrap()
+                    .as_ptr()
+                    .add(loc.index as usize))
+                .changed = tick;
+            }
+        }
+        // Distinct live entities never share a component
+        Ok(targets.map(|x| unsafe { &mut *x.as_ptr() }))
+    }
+
+    /// Execute the query `Q` on several distinct entities at once
+    ///
+    /// Yields `None` in place of any entity that doesn't exist or doesn't satisfy `Q`. Panics if
+    /// any two of `entities` are equal, or if `Q` borrows a component uniquely more than once.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((10, 1.0f32));
+    /// let b = world.spawn((20, 2.0f32));
+    /// let c = world.spawn((30,));
+    /// let [x, y, z] = world.query_many_mut::<(&mut i32, &f32), 3>([a, b, c]);
+    /// let ((health, armor), (damage, _)) = (x.unwrap(), y.unwrap());
+    /// *health -= (*damage as f32 / armor) as i32;
+    /// assert!(z.is_none());
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), -10);
+    /// ```
+    pub fn query_many_mut<Q: Query, const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> [Option<<Q::Fetch as Fetch<'_>>::Item>; N] {
+        assert_distinct(&entities);
+        self.flush_entities();
+        let ticks = QueryTicks::new(self.change_tick());
+        let locs = entities.map(|x| self.entities.get(x).ok());
+        for (i, loc) in locs.iter().enumerate() {
+            let loc = match *loc {
+                Some(x) => x,
+                None => continue,
+            };
+            let archetype = &self.archetypes[loc.archetype as usize];
+            if Q::Fetch::access(archetype).is_none()
+                || locs[..i]
+                    .iter()
+                    .any(|x| x.is_some_and(|x| x.archetype == loc.archetype))
+            {
+                continue;
+            }
+            if Q::Fetch::access(archetype) >= Some(Access::Write) {
+                self.assert_writable(loc);
+            }
+            // Rejects queries that alias themselves. Nothing else can hold borrows while the world
+            // is uniquely borrowed, so they can be released right away.
+            Q::Fetch::borrow(archetype);
+            Q::Fetch::release(archetype);
+        }
+        let archetypes = &self.archetypes;
+        locs.map(|loc| {
+            let loc = loc?;
+            unsafe {
+                let mut fetch = Q::Fetch::get(
+                    &archetypes[loc.archetype as usize],
+                    loc.index as usize,
+                    ticks,
+                )?;
+                Some(fetch.next())
+            }
+        })
+    }
+
+    /// Call `f` with a view of each non-empty archetype that `Q` matches, one after another
+    ///
+    /// Lets solvers, e.g. for physics, work on whole columns of components at a time with no
+    /// per-entity dispatch. Borrows can't conflict with anything else while the world is uniquely
+    /// borrowed, but panics if `Q` borrows a component uniquely more than once.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1.0f32, 2.0f64));
+    /// let b = world.spawn((3.0f32, 4.0f64, true));
+    /// world.spawn((5.0f32,));
+    /// let mut archetypes = 0;
+    /// world.for_each_archetype_mut::<(&mut f32, &f64)>(|mut rows| {
+    ///     archetypes += 1;
+    ///     let (positions, velocities) = rows.columns();
+    ///     for (p, v) in positions.iter_mut().zip(velocities) {
+    ///         *p += *v as f32;
+    ///     }
+    /// });
+    /// assert_eq!(archetypes, 2);
+    /// assert_eq!(*world.get::<f32>(a).unwrap(), 3.0);
+    /// assert_eq!(*world.get::<f32>(b).unwrap(), 7.0);
+    /// ```
+    pub fn for_each_archetype_mut<Q: Query>(
+        &mut self,
+        mut f: impl FnMut(TypedArchetypeView<'_, Q>),
+    ) {
+        self.flush_entities();
+        let world = &*self;
+        for archetype in &world.archetypes {
+            if archetype.is_empty() {
+                continue;
+            }
+            if let Some(view) = TypedArchetypeView::<Q>::new(world, archetype) {
+                f(view);
+            }
+        }
+    }
+
+    /// Look up the `T` components of many entities at once
+    ///
+    /// Replaces the contents of `out` with one element per element of `entities`, which is `None`
+    /// for entities that don't exist or lack a `T`. Components are read in storage order rather
+    /// than that of `entities`, making this much faster than calling `get` for each of thousands
+    /// of entities scattered across archetypes.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let c = world.spawn(("abc",));
+    /// let mut out = Vec::new();
+    /// world.get_batch::<i32>(&[b, c, a], &mut out);
+    /// assert_eq!(out, [Some(&2), None, Some(&1)]);
+    /// ```
+    pub fn get_batch<'a, T: Component>(
+        &'a mut self,
+        entities: &[Entity],
+        out: &mut Vec<Option<&'a T>>,
+    ) {
+        self.flush_entities();
+        out.clear();
+        out.resize(entities.len(), None);
+        let mut locs = entities
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &entity)| {
+                let loc = self.entities.get(entity).ok()?;
+                Some((loc.archetype, loc.index, i))
+            })
+            .collect::<Vec<_>>();
+        locs.sort_unstable();
+        for group in locs.chunk_by(|x, y| x.0 == y.0) {
+            let column = match self.archetypes[group[0].0 as usize].column::<T>() {
+                Some(x) => x,
+                None => continue,
+            };
+            for &(_, index, i) in group {
+                // The world is uniquely borrowed, so no conflicting borrows can exist
+                out[i] = Some(unsafe { &*column.add(index as usize).get() });
+            }
+        }
+    }
+
+    /// Obtain a long-lived reference to the `T` component of `entity`
+    ///
+    /// Suited to callbacks, such as those of audio or physics engines, that repeatedly access the
+    /// same components: `get_by_handle` skips looking the component up so long as it hasn't moved
+    /// in memory since the last access. Each handle occupies a small amount of memory until
+    /// released with `release_handle`.
+    ///
+    /// This is unrelated to `pin_component`, which prevents components from moving at all.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let handle = world.component_handle::<i32>(a).unwrap();
+    /// world.insert_one(a, "abc").unwrap();
+    /// *world.get_mut_by_handle(handle).unwrap() += 1;
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 124);
+    /// assert!(world.release_handle(handle));
+    /// assert!(world.get_by_handle(handle).is_err());
+    /// ```
+    pub fn component_handle<T: Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<ComponentHandle<T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if !self.archetypes[loc.archetype as usize].has::<T>() {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        Ok(self.handles.alloc(entity))
+    }
+
+    /// Borrow the component referred to by `handle`
+    ///
+    /// Fails if the entity has been despawned or has lost the component, or if the handle has been
+    /// released. Panics if the component is already uniquely borrowed from another entity with the
+    /// same components, or if `handle` was obtained from a different world.
+    pub fn get_by_handle<T: Component>(
+        &self,
+        handle: ComponentHandle<T>,
+    ) -> Result<Ref<'_, T>, ComponentError> {
+        let (archetype, _, target) =
+            self.handles
+                .resolve(handle, &self.entities, &self.archetypes)?;
+        Ok(unsafe { Ref::from_raw(archetype, target) })
+    }
+
+    /// Uniquely borrow the component referred to by `handle`
+    ///
+    /// Fails if the entity has been despawned or has lost the component, or if the handle has been
+    /// released. Panics if the component is already borrowed from another entity with the same
+    /// components, or if `handle` was obtained from a different world.
+    pub fn get_mut_by_handle<T: Component>(
+        &self,
+        handle: ComponentHandle<T>,
+    ) -> Result<RefMut<'_, T>, ComponentError> {
+        let (archetype, index, target) =
+            self.handles
+                .resolve(handle, &self.entities, &self.archetypes)?;
+        Ok(unsafe { RefMut::from_raw(archetype, index, target, self.change_tick()) })
+    }
+
+    /// Free the memory used by `handle`, returning whether it hadn't already been released
+    ///
+    /// Handles aren't released automatically, even when their entity is despawned. Panics if
+    /// `handle` was obtained from a different world.
+    pub fn release_handle<T: Component>(&mut self, handle: ComponentHandle<T>) -> bool {
+        self.handles.free(handle)
+    }
+
+    /// Access an entity regardless of its component types
+    ///
+    /// Does not immediately borrow any component.
+    pub fn entity(&self, entity: Entity) -> Result<EntityRef<'_>, NoSuchEntity> {
+        Ok(match self.entities.get(entity)? {
+            Location { archetype: 0, .. } => EntityRef::empty(),
+            loc => unsafe {
+                EntityRef::new(
+                    &self.archetypes[loc.archetype as usize],
+                    loc.index,
+                    self.change_tick(),
+                )
+            },
+        })
+    }
+
+    /// Iterate over all entities in the world
+    ///
+    /// Entities are yielded archetype by archetype, in the order of `World::archetypes`, making
+    /// this a canonical traversal for e.g. serialization or migrations that apply to every entity.
+    /// The order within an archetype is arbitrary and changes as entities are despawned. Prefer
+    /// `World::query` for better performance when components will be accessed in predictable
+    /// patterns.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// let b = world.spawn(());
+    /// assert_eq!(world.iter().len(), 2);
+    /// let ids = world.iter().map(|(id, _)| id).collect::<Vec<_>>();
+    /// assert!(ids.contains(&a));
+    /// assert!(ids.contains(&b));
+    /// ```
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.archetypes, &self.entities, self.change_tick())
+    }
+
+    /// Add `components` to `entity`
+    ///
+    /// Computational cost is proportional to the number of components `entity` has. If an entity
+    /// already has a component of a certain type, it is dropped and replaced. To get replaced
+    /// components back instead, use `insert_one`, or `exchange` for several at once.
+    ///
+    /// When inserting a single component, see `insert_one` for convenience.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// world.insert(e, (456, true));
+    /// assert_eq!(*world.get::<i32>(e).unwrap(), 456);
+    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// ```
+    pub fn insert(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), NoSuchEntity> {
+        self.insert_and_remove(entity, components, &[])
+    }
+
+    /// Like `insert`, but returns an error rather than panicking if `entity` is stored in
+    /// read-only memory
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// world.despawn(e).unwrap();
+    /// assert!(matches!(world.try_insert(e, (true,)), Err(WorldError::NoSuchEntity)));
+    /// ```
+    pub fn try_insert(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), WorldError> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        self.check_writable(loc)?;
+        self.insert(entity, components)?;
+        Ok(())
+    }
+
+    /// Add `components` to `entity` and drop its components of the types in `removed`, in a single
+    /// archetype move
+    ///
+    /// `removed` must not contain any types in `components`.
+    pub(crate) fn insert_and_remove(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+        removed: &[TypeId],
+    ) -> Result<(), NoSuchEntity> {
+        self.insert_and_take(entity, components, removed, &[], |_, _| {})
+    }
+
+    /// Like `insert_and_remove`, but rather than dropping the entity's components of the types in
+    /// `taken`, which it must have, let `take` move them out of the given archetype and index once
+    /// the move is known to succeed
+    ///
+    /// `taken` may contain types in `components`, which are then replaced.
+    fn insert_and_take(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+        removed: &[TypeId],
+        taken: &[TypeId],
+        take: impl FnOnce(&Archetype, u32),
+    ) -> Result<(), NoSuchEntity> {
+        use hashbrown::hash_map::Entry;
+
+        self.flush_entities();
+        self.assert_writable(self.entities.get(entity)?);
+        let loc = self.entities.meta[entity.id as usize].location;
+        unsafe {
+            // Assemble Vec<TypeInfo> for the final entity
+            let arch = &self.archetypes[loc.archetype as usize];
+            let (dropped, mut info) = arch
+                .types()
+                .iter()
+                .partition::<Vec<TypeInfo>, _>(|x| removed.contains(&x.id()));
+            let added = components.type_info();
+            for ty in &added {
+                if !arch.has_dynamic(ty.id()) {
+                    info.push(*ty);
+                }
+            }
+            info.sort();
+
+            // Find the archetype it'll live in
+            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+            let target = match self.index.entry(elements) {
+                Entry::Occupied(x) => *x.get(),
+                Entry::Vacant(x) => {
+                    let index = self.archetypes.len() as u32;
+                    self.archetypes.push(
+                        Archetype::new(info)
+                            .with_pins(&self.pinned)
+                            .with_column_order(self.column_order)
+                            .with_colocated(&self.colocated)
+                            .with_arenas(&self.arenas)
+                            .with_barriers(&self.write_barriers)
+                            .with_shadows(&self.double_buffered),
+                    );
+                    x.insert(index);
+                    self.archetype_generation += 1;
+                    index
+                }
+            };
+
+            let arch = &self.archetypes[loc.archetype as usize];
+            take(arch, loc.index);
+            if !self.indices.is_empty() {
+                for ty in &dropped {
+                    self.indices.removed(ty.id(), entity, arch, loc.index);
+                }
+            }
+            for ty in &added {
+                if taken.contains(&ty.id()) {
+                    continue;
+                }
+                if let Some(ptr) = arch.get_dynamic(ty.id(), ty.layout().size(), loc.index) {
+                    self.removal_sinks.discard(entity, ty, ptr.as_ptr());
+                }
+            }
+
+            if target == loc.archetype {
+                // Update components in the current archetype
+                let arch = &mut self.archetypes[loc.archetype as usize];
+                let now = self.change_tick.load(Ordering::Relaxed);
+                components.put(|ptr, ty, size| {
+                    let ticks = ComponentTicks {
+                        changed: now,
+                        ..arch.component_ticks(ty, loc.index).unwrap()
+                    };
+                    arch.put_dynamic(ptr, ty, size, loc.index, ticks);
+                    true
+                });
+                if !self.indices.is_empty() {
+                    for ty in &added {
+                        self.indices.changed(ty.id(), entity, arch, loc.index);
+                    }
+                }
+                if let Some(ref mut journal) = self.journal {
+                    let added = added.iter().map(|x| x.id()).collect::<Vec<_>>();
+                    journal.inserted(entity, arch, loc.index, &added);
+                }
+                return Ok(());
+            }
+
+            // Move into a new archetype
+            let (source_arch, target_arch) = index2(
+                &mut self.archetypes,
+                loc.archetype as usize,
+                target as usize,
+            );
+            let target_index = target_arch.allocate(entity.id);
+            let old_index = loc.index;
+            self.entities.meta[entity.id as usize].location = Location {
+                archetype: target,
+                index: target_index,
+            };
+            target_arch.inherit_shadows(source_arch, old_index);
+            let sinks = &mut self.removal_sinks;
+            if let Some(moved) =
+                source_arch.move_to(old_index, |ptr, ty, size, ticks| {
+                    match dropped.iter().find(|x| x.id() == ty) {
+                        Some(_) if taken.contains(&ty) => {}
+                        Some(info) => sinks.discard(entity, info, ptr),
+                        None => target_arch.put_dynamic(ptr, ty, size, target_index, ticks),
+                    }
+                })
+            {
+                self.entities.meta[moved as usize].location.index = old_index;
+            }
+            target_arch.adopt_pinned(source_arch, target_index);
+            let now = self.change_tick.load(Ordering::Relaxed);
+            components.put(|ptr, ty, size| {
+                let ticks = if source_arch.has_dynamic(ty) {
+                    ComponentTicks {
+                        changed: now,
+                        ..target_arch.component_ticks(ty, target_index).unwrap()
+                    }
+                } else {
+                    ComponentTicks::new(now)
+                };
+                target_arch.put_dynamic(ptr, ty, size, target_index, ticks);
+                true
+            });
+            if !self.indices.is_empty() {
+                for ty in &added {
+                    if source_arch.has_dynamic(ty.id()) {
+                        self.indices
+                            .changed(ty.id(), entity, target_arch, target_index);
+                    } else {
+                        self.indices
+                            .inserted(ty.id(), entity, target_arch, target_index);
+                    }
+                }
+            }
+            if let Some(ref mut journal) = self.journal {
+                if !dropped.is_empty() {
+                    journal.removed(entity, dropped.iter().map(|x| x.id()));
+                }
+                let added = added.iter().map(|x| x.id()).collect::<Vec<_>>();
+                journal.inserted(entity, target_arch, target_index, &added);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `component` to `entity`, returning the `T` it replaced, if any
+    ///
+    /// A replaced component is returned rather than passed to any `RemovalSink`. See `insert`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// assert_eq!(world.insert_one(e, 456), Ok(Some(123)));
+    /// assert_eq!(world.insert_one(e, "abc"), Ok(None));
+    /// assert_eq!(*world.get::<i32>(e).unwrap(), 456);
+    /// ```
+    pub fn insert_one<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        if !self.archetypes[loc.archetype as usize].has::<T>() {
+            self.insert(entity, (component,))?;
+            return Ok(None);
+        }
+        let mut old = None;
+        let taken = [TypeId::of::<T>()];
+        self.insert_and_take(entity, (component,), &[], &taken, |arch, index| unsafe {
+            old = Some(arch.get_at::<T>(index).unwrap().as_ptr().read());
+        })?;
+        Ok(old)
+    }
+
+    /// Add `component` to `entity` unless it already has an equal `T`, returning whether it was
+    /// inserted
+    ///
+    /// Redundant writes, e.g. from network updates, leave the existing component untouched and so
+    /// aren't seen by `Changed` queries, indices, or observers. Otherwise equivalent to
+    /// `insert_one`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123,));
+    /// let last_run = world.increment_change_tick();
+    /// assert!(!world.insert_if_changed(e, 123).unwrap());
+    /// world.increment_change_tick();
+    /// let mut query = world.query::<Changed<i32>>().since(last_run);
+    /// assert!(!query.iter().any(|(_, changed)| changed));
+    /// drop(query);
+    ///
+    /// assert!(world.insert_if_changed(e, 456).unwrap());
+    /// world.increment_change_tick();
+    /// let mut query = world.query::<Changed<i32>>().since(last_run);
+    /// assert!(query.iter().all(|(_, changed)| changed));
+    /// ```
+    pub fn insert_if_changed<T: Component + PartialEq>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<bool, NoSuchEntity> {
+        match self.get::<T>(entity) {
+            Ok(x) if *x == component => return Ok(false),
+            Err(ComponentError::NoSuchEntity) => return Err(NoSuchEntity),
+            _ => {}
+        }
+        self.insert(entity, (component,))?;
+        Ok(true)
+    }
+
+    /// Invoke `f` on every `T` component in the world
+    ///
+    /// The fastest way to update a single component type, since each archetype's `T`s are visited
+    /// as one contiguous slice without any per-entity query overhead. Every `T` is marked changed.
+    /// As with queries, indices aren't notified.
+    ///
+    /// Panics if any `T` is stored in read-only memory.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1.5f32,));
+    /// let b = world.spawn((2.5f32, true));
+    /// world.transform(|x: &mut f32| *x *= 2.0);
+    /// assert_eq!(*world.get::<f32>(a).unwrap(), 3.0);
+    /// assert_eq!(*world.get::<f32>(b).unwrap(), 5.0);
+    /// ```
+    pub fn transform<T: Component>(&mut self, mut f: impl FnMut(&mut T)) {
+        self.flush_entities();
+        let now = *self.change_tick.get_mut();
+        for archetype in &mut self.archetypes {
+            if archetype.is_empty() || !archetype.has::<T>() {
+                continue;
+            }
+            if archetype.is_read_only() {
+                panic!(
+                    "{} is stored in read-only memory",
+                    core::any::type_name::<T>()
+                );
+            }
+            archetype.touch(TypeId::of::<T>());
+            let len = archetype.len() as usize;
+            unsafe {
+                let ticks =
+                    core::slice::from_raw_parts_mut(archetype.ticks::<T>().unwrap().as_ptr(), len);
+                for x in ticks {
+                    x.changed = now;
+                }
+                let column = archetype.column::<T>().unwrap();
+                for index in 0..len {
+                    f(&mut *column.add(index).get());
+                }
+            }
+        }
+    }
+
+    /// Overwrite the `T` of each of `entities` with the corresponding element of `values`
+    ///
+    /// Much faster than calling `get_mut` for each entity, e.g. when applying the results of a
+    /// physics simulation. Consecutive entities which are adjacent in storage, such as those
+    /// visited consecutively by a query, are copied in a single operation. The written components
+    /// are marked changed, and indices are notified.
+    ///
+    /// If any entity doesn't exist or lacks a `T`, returns an error and writes nothing. Panics if
+    /// `entities` and `values` have different lengths.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(([0.0f32; 3], true));
+    /// let b = world.spawn(([0.0f32; 3],));
+    /// let c = world.spawn(([0.0f32; 3], true));
+    /// let values: [[f32; 3]; 3] = [[1.0; 3], [2.0; 3], [3.0; 3]];
+    /// world.write_column(&[a, c, b], &values).unwrap();
+    /// assert_eq!(*world.get::<[f32; 3]>(c).unwrap(), [2.0; 3]);
+    /// assert_eq!(*world.get::<[f32; 3]>(b).unwrap(), [3.0; 3]);
+    /// ```
+    pub fn write_column<T: Component + Copy>(
+        &mut self,
+        entities: &[Entity],
+        values: &[T],
+    ) -> Result<(), ComponentError> {
+        assert_eq!(
+            entities.len(),
+            values.len(),
+            "entities and values must have the same length"
+        );
+        self.flush_entities();
+        for &entity in entities {
+            let loc = self.entities.get(entity)?;
+            if !self.archetypes[loc.archetype as usize].has::<T>() {
+                return Err(MissingComponent::new::<T>().into());
+            }
+        }
+        let tick = self.change_tick();
+        let mut i = 0;
+        while i < entities.len() {
+            let start = self.entities.get(entities[i]).unwrap();
+            let archetype = &self.archetypes[start.archetype as usize];
+            if archetype.is_read_only() {
+                panic!("entity is stored in read-only memory");
+            }
+            // Extend the run for as long as entities are adjacent in storage
+            let mut len = 1;
+            while let Some(&next) = entities.get(i + len) {
+                let loc = self.entities.get(next).unwrap();
+                if loc.archetype != start.archetype || loc.index != start.index + len as u32 {
+                    break;
+                }
+                len += 1;
+            }
+            unsafe {
+                let column = archetype.column::<T>().unwrap();
+                match column {
+                    ColumnPtr::Direct(x) => core::ptr::copy_nonoverlapping(
+                        values[i..].as_ptr(),
+                        x.as_ptr().add(start.index as usize),
+                        len,
+                    ),
+                    ColumnPtr::Indirect(_) => {
+                        for (j, value) in values[i..i + len].iter().enumerate() {
+                            *column.add(start.index as usize + j).get() = *value;
+                        }
+                    }
+                }
+                let ticks = archetype.ticks::<T>().unwrap().as_ptr();
+                for j in 0..len {
+                    (*ticks.add(start.index as usize + j)).changed = tick;
+                }
+            }
+            archetype.touch(TypeId::of::<T>());
+            for (j, &entity) in entities[i..i + len].iter().enumerate() {
+                unsafe {
+                    self.indices.changed(
+                        TypeId::of::<T>(),
+                        entity,
+                        archetype,
+                        start.index + j as u32,
+                    );
+                }
+            }
+            i += len;
+        }
+        Ok(())
+    }
+
+    /// Prepare several insertions and removals to be applied to `entity` at once
+    ///
+    /// Committing the returned `Modification` moves the entity between archetypes at most once, no
+    /// matter how many components are inserted or removed, making it cheaper than the equivalent
+    /// sequence of `insert_one` and `remove_one` calls.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// let mut modification = world.modify(e);
+    /// modification.insert_one(true).insert_one(4.5f32).remove_one::<&str>();
+    /// modification.commit().unwrap();
+    /// assert_eq!(*world.get::<i32>(e).unwrap(), 123);
+    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// assert_eq!(*world.get::<f32>(e).unwrap(), 4.5);
+    /// assert!(world.get::<&str>(e).is_err());
+    /// ```
+    pub fn modify(&mut self, entity: Entity) -> Modification<'_> {
+        Modification::new(self, entity)
+    }
+
+    /// Remove components from `entity`
+    ///
+    /// Computational cost is proportional to the number of components `entity` has. The entity
+    /// itself is not removed, even if no components remain; use `despawn` for that. If any
+    /// component in `T` is not present in `entity`, no components are removed and an error is
+    /// returned.
+    ///
+    /// When removing a single component, see `remove_one` for convenience.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc", true));
+    /// assert_eq!(world.remove::<(i32, &str)>(e), Ok((123, "abc")));
+    /// assert!(world.get::<i32>(e).is_err());
+    /// assert!(world.get::<&str>(e).is_err());
+    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// ```
+    pub fn remove<T: Bundle>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        use hashbrown::hash_map::Entry;
+
+        self.flush_entities();
+        self.assert_writable(self.entities.get(entity)?);
+        let loc = self.entities.meta[entity.id as usize].location;
+        unsafe {
+            let removed = T::with_static_ids(|ids| ids.iter().copied().collect::<HashSet<_>>());
+            let info = self.archetypes[loc.archetype as usize]
+                .types()
+                .iter()
+                .cloned()
+                .filter(|x| !removed.contains(&x.id()))
+                .collect::<Vec<_>>();
+            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+            let target = match self.index.entry(elements) {
+                Entry::Occupied(x) => *x.get(),
+                Entry::Vacant(x) => {
+                    self.archetypes.push(
+                        Archetype::new(info)
+                            .with_pins(&self.pinned)
+                            .with_column_order(self.column_order)
+                            .with_colocated(&self.colocated)
+                            .with_arenas(&self.arenas)
+                            .with_barriers(&self.write_barriers)
+                            .with_shadows(&self.double_buffered),
+                    );
+                    let index = (self.archetypes.len() - 1) as u32;
+                    x.insert(index);
+                    self.archetype_generation += 1;
+                    index
+                }
+            };
+            let old_index = loc.index;
+            let source_arch = &self.archetypes[loc.archetype as usize];
+            if !self.indices.is_empty() && removed.iter().all(|&ty| source_arch.has_dynamic(ty)) {
+                for &ty in &removed {
+                    self.indices.removed(ty, entity, source_arch, old_index);
+                }
+            }
+            let bundle = T::get(|ty, size| source_arch.get_dynamic(ty, size, old_index))?;
+            let (source_arch, target_arch) = index2(
+                &mut self.archetypes,
+                loc.archetype as usize,
+                target as usize,
+            );
+            let target_index = target_arch.allocate(entity.id);
+            self.entities.meta[entity.id as usize].location = Location {
+                archetype: target,
+                index: target_index,
+            };
+            target_arch.inherit_shadows(source_arch, old_index);
+            if let Some(moved) = source_arch.move_to(old_index, |src, ty, size, ticks| {
+                // Only move the components present in the target archetype, i.e. the non-removed ones.
+                if target_arch.has_dynamic(ty) {
+                    target_arch.put_dynamic(src, ty, size, target_index, ticks);
+                }
+            }) {
+                self.entities.meta[moved as usize].location.index = old_index;
+            }
+            target_arch.adopt_pinned(source_arch, target_index);
+            if let Some(ref mut journal) = self.journal {
+                journal.removed(entity, removed);
+            }
+            Ok(bundle)
+        }
+    }
+
+    /// Remove the `T` component from `entity`
+    ///
+    /// Cheaper than the equivalent `remove`, since no bundle has to be assembled. See `remove`.
+    pub fn remove_one<T: Component>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        if !self.archetypes[loc.archetype as usize].has::<T>() {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        let mut component = None;
+        let ty = [TypeId::of::<T>()];
+        self.insert_and_take(entity, (), &ty, &ty, |arch, index| unsafe {
+            component = Some(arch.get_at::<T>(index).unwrap().as_ptr().read());
+        })?;
+        Ok(component.unwrap())
+    }
+
+    /// Remove the components in `T` from `entity` and add `components`, in a single archetype move
+    ///
+    /// Equivalent to `remove` followed by `insert`, but without moving the entity's other
+    /// components through an intermediate archetype. Components in `T` that are also in
+    /// `components` are replaced. If any component in `T` is not present in `entity`, the world
+    /// is left unchanged and an error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Walking(f32);
+    /// struct Falling { velocity: f32 }
+    /// let mut world = World::new();
+    /// let e = world.spawn((Walking(1.5), "player"));
+    /// let (Walking(speed),) = world.exchange::<(Walking,)>(e, (Falling { velocity: 0.0 },)).unwrap();
+    /// assert_eq!(speed, 1.5);
+    /// assert!(world.get::<Walking>(e).is_err());
+    /// assert_eq!(world.get::<Falling>(e).unwrap().velocity, 0.0);
+    /// ```
+    pub fn exchange<T: Bundle>(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<T, ComponentError> {
+        self.flush_entities();
+        let loc = self.entities.get(entity)?;
+        let arch = &self.archetypes[loc.archetype as usize];
+        unsafe {
+            if !T::with_static_ids(|ids| ids.iter().all(|&ty| arch.has_dynamic(ty))) {
+                // Reads nothing, since a component is missing
+                let missing = T::get(|ty, size| arch.get_dynamic(ty, size, loc.index));
+                return Err(missing.err().unwrap().into());
+            }
+        }
+        let added = components.type_info();
+        let (taken, removed) = T::with_static_ids(|ids| {
+            let removed = ids
+                .iter()
+                .copied()
+                .filter(|&ty| !added.iter().any(|x| x.id() == ty))
+                .collect::<Vec<_>>();
+            (ids.to_vec(), removed)
+        });
+        let mut bundle = None;
+        self.insert_and_take(entity, components, &removed, &taken, |arch, index| unsafe {
+            bundle = Some(T::get(|ty, size| arch.get_dynamic(ty, size, index)).unwrap());
+        })?;
+        Ok(bundle.unwrap())
+    }
+
+    /// Replace every `Old` component with the `New` component computed from it by `f`
+    ///
+    /// Useful for migrating live data when a component's definition changes, e.g. during hot
+    /// reloading. Each affected archetype is processed in a single pass, moving its entities into
+    /// the corresponding archetype with `New` in place of `Old`. Entities that already had a `New`
+    /// have it replaced. Indices are notified as if by `remove_one` followed by `insert_one`.
+    ///
+    /// If `f` panics, the entity whose component was being migrated is despawned.
+    ///
+    /// Panics if `Old` and `New` are the same type, or if any `Old` is stored in read-only memory.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct HealthV1(u8);
+    /// struct HealthV2 { current: f32, max: f32 }
+    /// let mut world = World::new();
+    /// let a = world.spawn((HealthV1(50), "abc"));
+    /// let b = world.spawn((HealthV1(100),));
+    /// world.migrate_component(|old: HealthV1| HealthV2 { current: old.0 as f32, max: 100.0 });
+    /// assert!(world.get::<HealthV1>(a).is_err());
+    /// assert_eq!(world.get::<HealthV2>(a).unwrap().current, 50.0);
+    /// assert_eq!(world.get::<HealthV2>(b).unwrap().current, 100.0);
+    /// assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+    /// ```
+    pub fn migrate_component<Old: Component, New: Component>(
+        &mut self,
+        mut f: impl FnMut(Old) -> New,
+    ) {
+        use hashbrown::hash_map::Entry;
+
+        assert_ne!(
+            TypeId::of::<Old>(),
+            TypeId::of::<New>(),
+            "migrated component types must differ"
+        );
+        self.flush_entities();
+        // Archetypes created below never contain `Old`
+        for source in 0..self.archetypes.len() {
+            let arch = &self.archetypes[source];
+            if !arch.has::<Old>() || arch.is_empty() {
+                continue;
+            }
+            if arch.is_read_only() {
+                panic!("entity is stored in read-only memory");
+            }
+            let mut info = arch
+                .types()
+                .iter()
+                .filter(|x| x.id() != TypeId::of::<Old>())
+                .copied()
+                .collect::<Vec<_>>();
+            if !arch.has::<New>() {
+                info.push(TypeInfo::of::<New>());
+                info.sort();
+            }
+            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+            let target = match self.index.entry(elements) {
+                Entry::Occupied(x) => *x.get(),
+                Entry::Vacant(x) => {
+                    let index = self.archetypes.len() as u32;
+                    self.archetypes.push(
+                        Archetype::new(info)
+                            .with_pins(&self.pinned)
+                            .with_column_order(self.column_order)
+                            .with_colocated(&self.colocated)
+                            .with_arenas(&self.arenas)
+                            .with_barriers(&self.write_barriers)
+                            .with_shadows(&self.double_buffered),
+                    );
+                    x.insert(index);
+                    self.archetype_generation += 1;
+                    index
+                }
+            };
+            while !self.archetypes[source].is_empty() {
+                unsafe {
+                    self.migrate_last(source, target as usize, &mut f);
+                }
+            }
+        }
+    }
+
+    /// Give every entity matching `F` that lacks a `T` component a `T::default()`, returning how
+    /// many there were
+    ///
+    /// A common step when introducing a new component to data saved by older versions. Entities
+    /// are moved an archetype at a time, and `F` is only used to select archetypes, like in
+    /// `despawn_all`, so its components are never borrowed. Panics if an affected entity is
+    /// stored in read-only memory or has a pinned component.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Default)]
+    /// struct Armor(u32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((100, "player"));
+    /// let b = world.spawn((50, Armor(10)));
+    /// let c = world.spawn(("prop",));
+    /// assert_eq!(world.backfill::<Armor, &i32>(), 1);
+    /// assert_eq!(world.get::<Armor>(a).unwrap().0, 0);
+    /// assert_eq!(world.get::<Armor>(b).unwrap().0, 10);
+    /// assert!(world.get::<Armor>(c).is_err());
+    /// ```
+    pub fn backfill<T: Component + Default, F: Query>(&mut self) -> u32 {
+        self.flush_entities();
+        let ticks = ComponentTicks::new(self.change_tick());
+        let mut count = 0;
+        // Archetypes created below always contain `T`
+        for source in 0..self.archetypes.len() {
+            let arch = &self.archetypes[source];
+            if arch.is_empty() || arch.has::<T>() || F::Fetch::access(arch).is_none() {
+                continue;
+            }
+            if arch.is_read_only() {
+                panic!("entity is stored in read-only memory");
+            }
+            if let Some(name) = arch.pinned() {
+                panic!("backfilling entities would move pinned component {}", name);
+            }
+            let mut info = arch.types().to_vec();
+            info.push(TypeInfo::of::<T>());
+            let len = arch.len();
+            // Construct every default up front, so a panic leaves the world untouched
+            let mut defaults = (0..len).map(|_| T::default()).collect::<Vec<_>>();
+            let target = self.archetype_for_types(&info);
+            let (source_arch, target_arch) = index2(&mut self.archetypes, source, target as usize);
+            target_arch.reserve(len);
+            for (index, value) in (0..len).zip(defaults.drain(..)) {
+                let id = source_arch.entity_id(index);
+                let entity = self.entities.meta[id as usize].entity(id);
+                unsafe {
+                    let row = target_arch.allocate(id);
+                    target_arch.inherit_shadows(source_arch, index);
+                    for ty in source_arch.types() {
+                        let size = ty.layout().size();
+                        let ptr = source_arch.get_dynamic(ty.id(), size, index).unwrap();
+                        let old_ticks = source_arch.component_ticks(ty.id(), index).unwrap();
+                        target_arch.put_dynamic(ptr.as_ptr(), ty.id(), size, row, old_ticks);
+                    }
+                    let mut value = mem::ManuallyDrop::new(value);
+                    target_arch.put_dynamic(
+                        (&mut *value as *mut T).cast::<u8>(),
+                        TypeId::of::<T>(),
+                        mem::size_of::<T>(),
+                        row,
+                        ticks,
+                    );
+                    self.entities.meta[id as usize].location = Location {
+                        archetype: target,
+                        index: row,
+                    };
+                    self.indices
+                        .inserted(TypeId::of::<T>(), entity, target_arch, row);
+                    if let Some(ref mut journal) = self.journal {
+                        journal.inserted(entity, target_arch, row, &[TypeId::of::<T>()]);
+                    }
+                }
+            }
+            // Every component has been moved out
+            source_arch.clear(|_| false, |_, _, _| {});
+            count += len;
+        }
+        count
+    }
+
+    /// Move the last entity in archetype `source` to `target`, replacing its `Old` with `f(Old)`
+    ///
+    /// # Safety
+    /// `target` must have the components of `source`, less `Old` and plus `New`.
+    unsafe fn migrate_last<Old: Component, New: Component>(
+        &mut self,
+        source: usize,
+        target: usize,
+        f: &mut impl FnMut(Old) -> New,
+    ) {
+        let (source_arch, target_arch) = index2(&mut self.archetypes, source, target);
+        let index = source_arch.len() - 1;
+        let id = source_arch.entity_id(index);
+        let entity = self.entities.meta[id as usize].entity(id);
+        let old = ptr::read(source_arch.get_at::<Old>(index).unwrap().as_ptr());
+        // Despawn the entity, sans the `Old` that's been moved out, if `f` panics
+        let guard = MigrationGuard {
+            archetype: &mut *source_arch,
+            index,
+            entity,
+            old: TypeId::of::<Old>(),
+            entities: &mut self.entities,
+            indices: &mut self.indices,
+            sinks: &mut self.removal_sinks,
+            tags: &mut self.tags,
+            names: &mut self.names,
+            journal: self.journal.as_deref_mut(),
+        };
+        let new = f(old);
+        mem::forget(guard);
+
+        self.indices
+            .removed(TypeId::of::<Old>(), entity, source_arch, index);
+        let had_new = source_arch.has::<New>();
+        let new_info = TypeInfo::of::<New>();
+        let mut ticks = ComponentTicks::new(*self.change_tick.get_mut());
+        let target_index = target_arch.allocate(id);
+        target_arch.inherit_shadows(source_arch, index);
+        let sinks = &mut self.removal_sinks;
+        let moved = source_arch.move_to(index, |ptr, ty, size, old_ticks| {
+            if ty == TypeId::of::<Old>() {
+                // Already moved out
+            } else if ty == TypeId::of::<New>() {
+                sinks.discard(entity, &new_info, ptr);
+                ticks.added = old_ticks.added;
+            } else {
+                target_arch.put_dynamic(ptr, ty, size, target_index, old_ticks);
+            }
+        });
+        debug_assert!(moved.is_none(), "migrated entity wasn't last");
+        target_arch.adopt_pinned(source_arch, target_index);
+        let mut new = mem::ManuallyDrop::new(new);
+        target_arch.put_dynamic(
+            (&mut *new as *mut New).cast::<u8>(),
+            TypeId::of::<New>(),
+            mem::size_of::<New>(),
+            target_index,
+            ticks,
+        );
+        self.entities.meta[id as usize].location = Location {
+            archetype: target as u32,
+            index: target_index,
+        };
+        if had_new {
+            self.indices
+                .changed(TypeId::of::<New>(), entity, target_arch, target_index);
+        } else {
+            self.indices
+                .inserted(TypeId::of::<New>(), entity, target_arch, target_index);
+        }
+        if let Some(ref mut journal) = self.journal {
+            journal.removed(entity, [TypeId::of::<Old>()]);
+            journal.inserted(entity, target_arch, target_index, &[TypeId::of::<New>()]);
+        }
+    }
+
+    /// Borrow the `T` component of `entity` without safety checks
+    ///
+    /// Should only be used as a building block for safe abstractions.
+    ///
+    /// # Safety
+    ///
+    /// `entity` must have been previously obtained from this `World`, and no unique borrow of the
+    /// same component of `entity` may be live simultaneous to the returned reference.
+    pub unsafe fn get_unchecked<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        Ok(&*self.archetypes[loc.archetype as usize]
+            .get_at::<T>(loc.index)
+            .ok_or_else(MissingComponent::new::<T>)?
+            .as_ptr())
+    }
+
+    /// Uniquely borrow the `T` component of `entity` without safety checks
+    ///
+    /// Should only be used as a building block for safe abstractions. Marks the component as
+    /// changed, like `get_mut`.
+    ///
+    /// # Safety
+    ///
+    /// `entity` must have been previously obtained from this `World`, and no borrow of the same
+    /// component of `entity` may be live simultaneous to the returned reference.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_unchecked_mut<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<&mut T, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        if loc.archetype == 0 {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        let archetype = &self.archetypes[loc.archetype as usize];
+        if archetype.is_read_only() {
+            panic!("entity is stored in read-only memory");
+        }
+        let target = archetype
+            .get_at::<T>(loc.index)
+            .ok_or_else(MissingComponent::new::<T>)?
+            .as_ptr();
+        archetype.touch(TypeId::of::<T>());
+        (*archetype
+            .ticks::<T>()
+            .unwrap()
+            .as_ptr()
+            .add(loc.index as usize))
+        .changed = self.change_tick();
+        Ok(&mut *target)
+    }
+
+    /// Convert all reserved entities into empty entities that can be iterated and accessed, then
+    /// run observers
+    ///
+    /// Reserved entities are also converted implicitly by `spawn`, `despawn`, `insert`, and
+    /// `remove`, but observers are only run here. See `observe`. Also frees everything allocated
+    /// from `frame_allocator`, drops the components of entities in the `graveyard`, records the
+    /// current values of components registered with `double_buffer` as their previous values, and
+    /// resets `access_stats`.
+    pub fn flush(&mut self) {
+        self.frame.reset();
+        #[cfg(feature = "stats")]
+        for archetype in &mut self.archetypes {
+            archetype.reset_access_counts();
+        }
+        if !self.double_buffered.is_empty() {
+            for archetype in &mut self.archetypes {
+                archetype.capture_shadows();
+            }
+        }
+        if let Some(ref mut graveyard) = self.graveyard {
+            graveyard.clear(&mut self.removal_sinks);
+        }
+        self.flush_entities();
+        self.record_journal_writes();
+        if self.observers.is_empty() {
+            return;
+        }
+        let next = self.increment_change_tick();
+        let mut observers = mem::take(&mut self.observers);
+        for observer in &mut observers {
+            observer.run(self, next);
+        }
+        self.observers = observers;
+    }
+
+    /// How often each component type was borrowed since the last `flush`, ordered by name
+    ///
+    /// Useful for finding large components that are rarely accessed, which might be better kept
+    /// elsewhere. See `AccessStats` for details.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// world.spawn((456,));
+    /// for (_, x) in world.query::<&mut i32>().iter() {
+    ///     *x += 1;
+    /// }
+    /// let stats = world.access_stats();
+    /// let ints = stats.iter().find(|x| x.name == "i32").unwrap();
+    /// assert_eq!((ints.entities, ints.reads, ints.writes), (2, 0, 2));
+    /// world.flush();
+    /// assert!(world.access_stats().iter().all(|x| x.writes == 0));
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn access_stats(&self) -> Vec<AccessStats> {
+        let mut stats = HashMap::<TypeId, AccessStats>::default();
+        for archetype in &self.archetypes {
+            for (ty, (reads, writes)) in archetype.access_counts() {
+                let x = stats.entry(ty.id()).or_insert(AccessStats {
+                    id: ty.id(),
+                    name: ty.name(),
+                    layout: ty.layout(),
+                    entities: 0,
+                    reads: 0,
+                    writes: 0,
+                });
+                x.entities += archetype.len();
+                x.reads += reads;
+                x.writes += writes;
+            }
+        }
+        let mut stats = stats.into_iter().map(|(_, x)| x).collect::<Vec<_>>();
+        stats.sort_unstable_by_key(|x| x.name);
+        stats
+    }
+
+    /// Register `f` to be called on each `flush` for every component change of kind `O`
+    ///
+    /// `O` is `Added<T>` or `Changed<T>` for some component type `T`. Every change made since the
+    /// previous `flush` (or since registration, on the first `flush`) is reported exactly once,
+    /// with the component's current value. Entities despawned or whose `T` was removed before the
+    /// `flush` are not reported.
+    ///
+    /// Each `flush` that runs observers advances the world's change tick.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::sync::{Arc, Mutex};
+    /// struct Collider(f32);
+    /// let mut world = World::new();
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let log2 = log.clone();
+    /// world.observe::<Added<Collider>, _>(move |entity, collider| {
+    ///     log2.lock().unwrap().push((entity, collider.0));
+    /// });
+    /// let a = world.spawn((Collider(1.0),));
+    /// let b = world.spawn((Collider(2.0),));
+    /// assert!(log.lock().unwrap().is_empty());
+    /// world.flush();
+    /// log.lock().unwrap().sort_by_key(|x| x.0);
+    /// assert_eq!(*log.lock().unwrap(), [(a, 1.0), (b, 2.0)]);
+    /// ```
+    pub fn observe<O, F>(&mut self, f: F)
+    where
+        O: Observe,
+        for<'a> O::Fetch: Fetch<'a, Item = bool>,
+        F: FnMut(Entity, &O::Target) + Send + Sync + 'static,
+    {
+        // Exclude changes that were already made at the current tick
+        let since = self.increment_change_tick();
+        self.observers.push(Observer::new::<O, F>(since, f));
+    }
+
+    /// Register `f` to be passed the ID of each entity whose `T` component is written through a
+    /// unique borrow, as the borrow is released
+    ///
+    /// Lets caches derived from components, such as text layouts or collision shapes, be
+    /// invalidated precisely and immediately, rather than by comparing every component each frame.
+    /// Writes by a query over `&mut T` are reported when the query's borrows are released, and
+    /// writes through `get_mut` when the `RefMut` is dropped. An entity may be reported more than
+    /// once for writes made at the same change tick. Writes through `&mut World`, such as by
+    /// `get_many_mut`, aren't reported; use `observe` for those. Replaces any write barrier already
+    /// registered for `T`.
+    ///
+    /// `f` runs while the components are still borrowed, so it mustn't borrow `T` from this world.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::sync::{Arc, Mutex};
+    /// struct Text(&'static str);
+    /// let mut world = World::new();
+    /// let stale = Arc::new(Mutex::new(Vec::new()));
+    /// let stale2 = stale.clone();
+    /// world.add_write_barrier::<Text>(move |id| stale2.lock().unwrap().push(id));
+    /// world.spawn((Text("hello"),));
+    /// let b = world.spawn((Text("world"),));
+    /// world.get_mut::<Text>(b).unwrap().0 = "there";
+    /// assert_eq!(*stale.lock().unwrap(), [b.id()]);
+    /// ```
+    pub fn add_write_barrier<T: Component>(&mut self, f: impl Fn(u32) + Send + Sync + 'static) {
+        self.write_barriers.insert(TypeId::of::<T>(), Arc::new(f));
+        for archetype in &mut self.archetypes {
+            archetype.set_barriers(&self.write_barriers);
+        }
     }
 
-    /// Borrow the `T` component of `entity`
+    /// Remove the write barrier registered for `T` with `add_write_barrier`, returning whether
+    /// there was one
+    pub fn remove_write_barrier<T: Component>(&mut self) -> bool {
+        if self.write_barriers.remove(&TypeId::of::<T>()).is_none() {
+            return false;
+        }
+        for archetype in &mut self.archetypes {
+            archetype.set_barriers(&self.write_barriers);
+        }
+        true
+    }
+
+    /// Remove all observers registered with `observe`
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    pub(crate) fn flush_entities(&mut self) {
+        self.clamp_change_ticks();
+        let arch = &mut self.archetypes[0];
+        for id in self.entities.flush() {
+            self.entities.meta[id as usize].location.index = unsafe { arch.allocate(id) };
+            if let Some(ref mut journal) = self.journal {
+                let meta = &self.entities.meta[id as usize];
+                let entity = meta.entity(id);
+                journal.spawned(entity, arch, meta.location.index);
+            }
+        }
+        for i in 0..self.entities.reserved_len() {
+            let id = self.entities.reserved(i);
+            self.entities.meta[id as usize].location.index = unsafe { arch.allocate(id) };
+            if let Some(ref mut journal) = self.journal {
+                let meta = &self.entities.meta[id as usize];
+                let entity = meta.entity(id);
+                journal.spawned(entity, arch, meta.location.index);
+            }
+        }
+        self.entities.clear_reserved();
+    }
+
+    /// Prevent change ticks from growing old enough to be confused with new ones after wrapping
     ///
-    /// Panics if the component is already uniquely borrowed from another entity with the same
-    /// components.
-    pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
-        let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
+    /// Cheap unless a substantial fraction of the tick space has been used since the last clamp.
+    fn clamp_change_ticks(&mut self) {
+        let now = *self.change_tick.get_mut();
+        if now.wrapping_sub(self.last_clamp) < CLAMP_INTERVAL {
+            return;
         }
-        Ok(unsafe { Ref::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+        for x in &mut self.archetypes {
+            x.clamp_ticks(now, MAX_CHANGE_AGE);
+        }
+        self.last_clamp = now;
     }
 
-    /// Uniquely borrow the `T` component of `entity`
+    /// The world's current change tick
     ///
-    /// Panics if the component is already borrowed from another entity with the same components.
-    pub fn get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError> {
-        let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
+    /// Components are stamped with the current tick when they're added or modified, allowing
+    /// changes to be detected with the `Added` and `Changed` queries.
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick.load(Ordering::Relaxed)
+    }
+
+    /// Advance the world's change tick, returning the new value
+    ///
+    /// A system interested in changes should call this each time it runs, and pass the value
+    /// returned on its previous run to `QueryBorrow::since`. This ensures every change is observed
+    /// exactly once, no matter how frequently different systems run.
+    ///
+    /// Ticks wrap around on overflow. Old ticks are clamped whenever the world is modified, so a
+    /// system may safely go up to `u32::MAX / 2` ticks between runs, provided that at least one
+    /// `spawn`, `despawn`, `insert`, `remove`, or `flush` call is made every `u32::MAX / 4` ticks.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((0,));
+    /// let mut last_run = 0;
+    /// for frame in 0..3 {
+    ///     if frame == 1 {
+    ///         *world.get_mut::<i32>(e).unwrap() += 1;
+    ///     }
+    ///     let since = std::mem::replace(&mut last_run, world.increment_change_tick());
+    ///     let changes = world.query::<Changed<i32>>()
+    ///         .since(since)
+    ///         .iter()
+    ///         .filter(|&(_, changed)| changed)
+    ///         .count();
+    ///     // The spawn is seen on the first frame, and the write on the second
+    ///     assert_eq!(changes, if frame == 2 { 0 } else { 1 });
+    /// }
+    /// ```
+    pub fn increment_change_tick(&self) -> u32 {
+        self.change_tick
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1)
+    }
+
+    pub(crate) fn archetypes_inner(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    pub(crate) fn archetypes_inner_mut(&mut self) -> &mut [Archetype] {
+        &mut self.archetypes
+    }
+
+    /// Move the components of the entity at `index` in `source` into a new entity in archetype
+    /// `target`
+    ///
+    /// # Safety
+    /// `target` must have exactly the component types of `source`, and the moved components must
+    /// not be used afterwards.
+    pub(crate) unsafe fn adopt(&mut self, target: u32, source: &Archetype, index: u32) -> Entity {
+        self.flush_entities();
+        let entity = self.entities.alloc();
+        let archetype = &mut self.archetypes[target as usize];
+        let row = archetype.allocate(entity.id);
+        archetype.inherit_shadows(source, index);
+        for ty in source.types() {
+            let size = ty.layout().size();
+            let ptr = source.get_dynamic(ty.id(), size, index).unwrap();
+            let ticks = source.component_ticks(ty.id(), index).unwrap();
+            archetype.put_dynamic(ptr.as_ptr(), ty.id(), size, row, ticks);
         }
-        Ok(unsafe { RefMut::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+        self.entities.meta[entity.id as usize].location = Location {
+            archetype: target,
+            index: row,
+        };
+        if let Some(ref mut journal) = self.journal {
+            journal.spawned(entity, archetype, row);
+        }
+        entity
     }
 
-    /// Access an entity regardless of its component types
+    pub(crate) fn entities_meta(&self) -> &[EntityMeta] {
+        &self.entities.meta
+    }
+
+    pub(crate) fn journal_inner(&self) -> Option<&Journal> {
+        self.journal.as_deref()
+    }
+
+    pub(crate) fn set_journal(&mut self, journal: Option<Box<Journal>>) -> Option<Box<Journal>> {
+        mem::replace(&mut self.journal, journal)
+    }
+
+    /// Log writes to components whose types are tracked by the journal, if any
+    pub(crate) fn record_journal_writes(&mut self) {
+        if self.journal.is_none() {
+            return;
+        }
+        let now = self.increment_change_tick();
+        let journal = self.journal.as_mut().unwrap();
+        journal.record_writes(&self.archetypes, &self.entities.meta, now);
+    }
+
+    pub(crate) fn entities_inner(&self) -> &Entities {
+        &self.entities
+    }
+
+    /// Despawn all entities, roll entity allocation back to `entities`, then call `fill` on each
+    /// archetype in turn
     ///
-    /// Does not immediately borrow any component.
-    pub fn entity(&self, entity: Entity) -> Result<EntityRef<'_>, NoSuchEntity> {
-        Ok(match self.entities.get(entity)? {
-            Location { archetype: 0, .. } => EntityRef::empty(),
-            loc => unsafe { EntityRef::new(&self.archetypes[loc.archetype as usize], loc.index) },
-        })
+    /// # Safety
+    /// `entities` must have been obtained from this world, and `fill` must allocate and initialize
+    /// exactly the entities that `entities` locates in each archetype, at the indices it records.
+    pub(crate) unsafe fn restore(
+        &mut self,
+        entities: &Entities,
+        mut fill: impl FnMut(usize, &mut Archetype),
+    ) {
+        self.flush_entities();
+        let entities = self.entities.rollback(entities);
+        // The rollback already returned discarded IDs to any custom allocator
+        self.entities.take_allocator();
+        self.clear();
+        self.entities = entities;
+        self.entities.set_world(self.uid.0);
+        for (i, archetype) in self.archetypes.iter_mut().enumerate() {
+            fill(i, archetype);
+            if self.indices.is_empty() && self.journal.is_none() {
+                continue;
+            }
+            for index in 0..archetype.len() {
+                let id = archetype.entity_id(index);
+                let entity = self.entities.meta[id as usize].entity(id);
+                self.indices.inserted_all(entity, archetype, index);
+                if let Some(ref mut journal) = self.journal {
+                    journal.spawned(entity, archetype, index);
+                }
+            }
+        }
     }
 
-    /// Iterate over all entities in the world
+    /// Create an independent copy of this world, cloning components with `registry`
+    ///
+    /// Every entity keeps its exact `Entity` handle, components, change ticks, tags, and name, and
+    /// entities subsequently spawned in either world receive the same handles. Entity and
+    /// archetype limits, pinned and double-buffered types, the column order, and the borrow policy
+    /// are copied too. Entities spawned with `spawn_external` are copied into ordinary storage.
+    /// Component indices, removal sinks, observers, component handles, and the graveyard are not
+    /// copied.
+    ///
+    /// Panics if the world contains a component type that isn't registered with `registry`.
+    pub fn clone_with(&mut self, registry: &CloneRegistry) -> World {
+        self.flush_entities();
+        let mut world = World::new();
+        world.pinned = self
+            .pinned
+            .iter()
+            .map(|(&ty, x)| (ty, Arc::new(x.fresh())))
+            .collect();
+        let pinned = &world.pinned;
+        world.archetypes = self
+            .archetypes
+            .iter_mut()
+            .map(|x| unsafe {
+                x.duplicate(pinned, |ty, source, target, len| {
+                    registry.clone(ty, source, target, len)
+                })
+            })
+            .collect();
+        world.entities = self.entities.clone();
+        world.entities.set_world(world.uid.0);
+        world.index = self.index.clone();
+        world.tags = self.tags.clone();
+        world.names = self.names.clone();
+        world.borrow_policy = self.borrow_policy;
+        world.column_order = self.column_order;
+        world.colocated = self.colocated.clone();
+        world.arenas = self.arenas.clone();
+        world.cold = self.cold.clone();
+        world.double_buffered = self.double_buffered.clone();
+        world.entity_limit = self.entity_limit;
+        world.archetype_limits = self.archetype_limits.clone();
+        *world.change_tick.get_mut() = self.change_tick();
+        world.last_clamp = self.last_clamp;
+        world
+    }
+
+    /// Move every entity from `other` into this world, returning a map from their handles in
+    /// `other` to their new handles
+    ///
+    /// Entities receive fresh handles, so handles stored in components, e.g. to represent
+    /// relationships, must be fixed up using the map. Components are moved rather than cloned, into
+    /// the archetypes this world already has for them where possible, and are treated as newly
+    /// added by change detection. Tags are carried over by name, as are entity names not already in
+    /// use in this world. Anything else registered with `other`, like indices and removal sinks,
+    /// is dropped along with it.
     ///
-    /// Entities are yielded in arbitrary order. Prefer `World::query` for better performance when
-    /// components will be accessed in predictable patterns.
+    /// Panics if a limit set by `set_entity_limit` or `set_archetype_limit` would be exceeded.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let a = world.spawn(());
-    /// let b = world.spawn(());
-    /// let ids = world.iter().map(|(id, _)| id).collect::<Vec<_>>();
-    /// assert_eq!(ids.len(), 2);
-    /// assert!(ids.contains(&a));
-    /// assert!(ids.contains(&b));
+    /// world.spawn((1,));
+    /// let mut level = World::new();
+    /// let a = level.spawn((2, true));
+    /// let map = world.merge(level);
+    /// assert_eq!(world.len(), 2);
+    /// assert_eq!(*world.get::<i32>(map.get(a).unwrap()).unwrap(), 2);
     /// ```
-    pub fn iter(&self) -> Iter<'_> {
-        Iter::new(&self.archetypes, &self.entities)
+    pub fn merge(&mut self, mut other: World) -> EntityMap {
+        self.flush_entities();
+        other.flush_entities();
+        let mut map = EntityMap {
+            map: HashMap::with_capacity(other.len() as usize),
+        };
+        let ticks = ComponentTicks::new(self.change_tick());
+        for source in &mut other.archetypes {
+            if source.is_empty() {
+                continue;
+            }
+            let len = source.len();
+            let target_id = self.archetype_for_types(source.types());
+            if let Err(e) = self.check_quota(target_id, len) {
+                panic!("{}", e);
+            }
+            self.entities.reserve(len);
+            let target = &mut self.archetypes[target_id as usize];
+            target.reserve(len);
+            for index in 0..len {
+                let id = source.entity_id(index);
+                let old = other.entities.meta[id as usize].entity(id);
+                let entity = self.entities.alloc();
+                unsafe {
+                    let row = target.allocate(entity.id);
+                    target.inherit_shadows(source, index);
+                    for ty in source.types() {
+                        let size = ty.layout().size();
+                        let ptr = source.get_dynamic(ty.id(), size, index).unwrap();
+                        target.put_dynamic(ptr.as_ptr(), ty.id(), size, row, ticks);
+                    }
+                    self.entities.meta[entity.id as usize].location = Location {
+                        archetype: target_id,
+                        index: row,
+                    };
+                    self.indices.inserted_all(entity, target, row);
+                    if let Some(ref mut journal) = self.journal {
+                        journal.spawned(entity, target, row);
+                    }
+                }
+                map.map.insert(old, entity);
+            }
+            // Every component has been moved out
+            source.clear(|_| false, |_, _, _| {});
+        }
+        for (name, members) in other.tags.iter() {
+            let tag = self.tags.get_or_insert(name);
+            let target = self.tags.members_mut(tag);
+            target.extend(members.iter().filter_map(|&x| map.get(x)));
+        }
+        for (name, entity) in other.names.iter() {
+            if let (None, Some(entity)) = (self.names.get(name), map.get(entity)) {
+                self.names.insert(entity, name);
+            }
+        }
+        map
     }
 
-    /// Add `components` to `entity`
+    /// Inspect the archetypes that entities are organized into
     ///
-    /// Computational cost is proportional to the number of components `entity` has. If an entity
-    /// already has a component of a certain type, it is dropped and replaced.
+    /// Useful for dynamically scheduling concurrent queries by checking borrows in advance. Does
+    /// not provide access to entities.
     ///
-    /// When inserting a single component, see `insert_one` for convenience.
+    /// Archetypes are yielded in the order they were created, and are never removed or reordered,
+    /// so worlds that underwent the same sequence of operations yield them in the same order,
+    /// regardless of hashing. Queries, `iter`, and snapshots traverse archetypes in this order too,
+    /// making it suitable for serialization and state hashing. The order of component types within
+    /// an archetype depends on `TypeId`s, however, which may differ between builds.
+    pub fn archetypes(&self) -> impl ExactSizeIterator<Item = &'_ Archetype> + '_ {
+        self.archetypes.iter()
+    }
+
+    /// Returns a distinct value after `archetypes` is changed
+    ///
+    /// Store the current value after deriving information from `archetypes`, then check whether the
+    /// value returned by this function differs before attempting an operation that relies on its
+    /// correctness. Useful for determining whether e.g. a concurrent query execution plan is still
+    /// correct.
+    ///
+    /// The generation may be, but is not necessarily, changed as a result of adding or removing any
+    /// entity or component.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let e = world.spawn((123, "abc"));
-    /// world.insert(e, (456, true));
-    /// assert_eq!(*world.get::<i32>(e).unwrap(), 456);
-    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// let initial_gen = world.archetypes_generation();
+    /// world.spawn((123, "abc"));
+    /// assert_ne!(initial_gen, world.archetypes_generation());
     /// ```
-    pub fn insert(
-        &mut self,
-        entity: Entity,
-        components: impl DynamicBundle,
-    ) -> Result<(), NoSuchEntity> {
-        use hashbrown::hash_map::Entry;
+    pub fn archetypes_generation(&self) -> ArchetypesGeneration {
+        ArchetypesGeneration(self.archetype_generation)
+    }
 
-        self.flush();
-        let loc = self.entities.get_mut(entity)?;
-        unsafe {
-            // Assemble Vec<TypeInfo> for the final entity
-            let arch = &mut self.archetypes[loc.archetype as usize];
-            let mut info = arch.types().to_vec();
-            for ty in components.type_info() {
-                if let Some(ptr) = arch.get_dynamic(ty.id(), ty.layout().size(), loc.index) {
-                    ty.drop(ptr.as_ptr());
-                } else {
-                    info.push(ty);
+    /// Whether `generation` was returned by this world's `archetypes_generation`, so that
+    /// information derived from `archetypes` at the time still describes the archetypes it covered
+    ///
+    /// Every world, including those created by `clone_with`, draws generations from a distinct
+    /// range, so a cache of archetype indices built for one world can reliably detect being used
+    /// with another. `PreparedQuery`, `DespawnAll`, `ComponentHandle`, and `Snapshot` all panic when
+    /// misused in this way.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let generation = world.archetypes_generation();
+    /// world.spawn((123,));
+    /// assert!(world.archetypes_extend(generation));
+    /// assert!(!World::new().archetypes_extend(generation));
+    /// ```
+    pub fn archetypes_extend(&self, generation: ArchetypesGeneration) -> bool {
+        generation.0 >> 32 == self.archetype_generation >> 32
+            && generation.0 <= self.archetype_generation
+    }
+
+    /// Identifies this world among all worlds created by the process
+    ///
+    /// Available for any world, unlike the `WorldId` assigned by a `WorldRegistry`. Like
+    /// `archetypes_generation`, it differs between a world and any created from it by
+    /// `clone_with`, whose archetypes and entities evolve independently. `WeakEntity` records the
+    /// identity of the world it was obtained from, and panics if upgraded in another, rather than
+    /// silently referring to an unrelated entity. With the `checked-entities` feature, so does
+    /// every `Entity`, though handles to entities that existed when a world was cloned remain
+    /// usable with the clone.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let weak = world.downgrade(a).unwrap();
+    /// assert_ne!(world.uid(), World::new().uid());
+    /// assert_eq!(weak.world(), world.uid());
+    /// ```
+    pub fn uid(&self) -> WorldUid {
+        self.uid
+    }
+
+    /// Keep `index` in sync with every `T` component in the world
+    ///
+    /// `index` is immediately informed of all existing `T` components, then of every subsequent
+    /// insertion, replacement, and removal. Replaces any index previously registered for `T`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(i32);
+    /// #[derive(Default)]
+    /// struct Cells(std::collections::HashMap<Entity, i32>);
+    /// impl ComponentIndex<Position> for Cells {
+    ///     fn insert(&mut self, entity: Entity, pos: &Position) { self.0.insert(entity, pos.0 / 10); }
+    ///     fn remove(&mut self, entity: Entity, _: &Position) { self.0.remove(&entity); }
+    ///     fn change(&mut self, entity: Entity, pos: &Position) { self.0.insert(entity, pos.0 / 10); }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn((Position(15),));
+    /// world.add_component_index::<Position, _>(Cells::default());
+    /// let b = world.spawn((Position(42),));
+    /// world.insert_one(a, Position(3)).unwrap();
+    /// let cells = world.component_index::<Position, Cells>().unwrap();
+    /// assert_eq!(cells.0[&a], 0);
+    /// assert_eq!(cells.0[&b], 4);
+    /// ```
+    pub fn add_component_index<T: Component, I: ComponentIndex<T>>(&mut self, index: I) {
+        self.flush_entities();
+        self.indices.add::<T, I>(index);
+        let ty = TypeId::of::<T>();
+        for archetype in self.archetypes.iter().filter(|x| x.has_dynamic(ty)) {
+            for index in 0..archetype.len() {
+                let id = archetype.entity_id(index);
+                let entity = self.entities.meta[id as usize].entity(id);
+                unsafe {
+                    self.indices.inserted(ty, entity, archetype, index);
                 }
             }
-            info.sort();
+        }
+    }
 
-            // Find the archetype it'll live in
-            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
-            let target = match self.index.entry(elements) {
-                Entry::Occupied(x) => *x.get(),
-                Entry::Vacant(x) => {
-                    let index = self.archetypes.len() as u32;
-                    self.archetypes.push(Archetype::new(info));
-                    x.insert(index);
-                    self.archetype_generation += 1;
-                    index
-                }
-            };
+    /// Access the index of type `I` registered for `T`, if any
+    pub fn component_index<T: Component, I: ComponentIndex<T>>(&self) -> Option<&I> {
+        self.indices.get::<T, I>()
+    }
 
-            if target == loc.archetype {
-                // Update components in the current archetype
-                let arch = &mut self.archetypes[loc.archetype as usize];
-                components.put(|ptr, ty, size| {
-                    arch.put_dynamic(ptr, ty, size, loc.index);
-                    true
-                });
-                return Ok(());
-            }
+    /// Uniquely access the index of type `I` registered for `T`, if any
+    pub fn component_index_mut<T: Component, I: ComponentIndex<T>>(&mut self) -> Option<&mut I> {
+        self.indices.get_mut::<T, I>()
+    }
 
-            // Move into a new archetype
-            let (source_arch, target_arch) = index2(
-                &mut self.archetypes,
-                loc.archetype as usize,
-                target as usize,
-            );
-            let target_index = target_arch.allocate(entity.id);
-            loc.archetype = target;
-            let old_index = mem::replace(&mut loc.index, target_index);
-            if let Some(moved) = source_arch.move_to(old_index, |ptr, ty, size| {
-                target_arch.put_dynamic(ptr, ty, size, target_index);
-            }) {
-                self.entities.meta[moved as usize].location.index = old_index;
-            }
-            components.put(|ptr, ty, size| {
-                target_arch.put_dynamic(ptr, ty, size, target_index);
-                true
-            });
+    /// Stop maintaining the index registered for `T`, returning whether one existed
+    pub fn remove_component_index<T: Component>(&mut self) -> bool {
+        self.indices.remove::<T>()
+    }
+
+    /// Inform the index registered for `T`, if any, that `entity`'s `T` was modified in place
+    ///
+    /// Needed after mutating a `T` through `get_mut` or a query, which the world cannot observe.
+    pub fn notify_changed<T: Component>(&mut self, entity: Entity) -> Result<(), ComponentError> {
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes[loc.archetype as usize];
+        if !archetype.has::<T>() {
+            return Err(MissingComponent::new::<T>().into());
+        }
+        unsafe {
+            self.indices
+                .changed(TypeId::of::<T>(), entity, archetype, loc.index);
         }
         Ok(())
     }
 
-    /// Add `component` to `entity`
+    /// Deliver `T` components that would otherwise be dropped to `sink`
+    ///
+    /// Replaces any sink previously registered for `T`. See `RemovalSink` for details.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct TextureHandle(u32);
+    /// let mut world = World::new();
+    /// world.add_removal_sink::<TextureHandle, _>(Vec::new());
+    /// let a = world.spawn((TextureHandle(7), true));
+    /// world.despawn(a).unwrap();
+    /// let removed = world
+    ///     .removal_sink_mut::<TextureHandle, Vec<(Entity, TextureHandle)>>()
+    ///     .unwrap();
+    /// let (entity, handle) = removed.pop().unwrap();
+    /// assert_eq!(entity, a);
+    /// assert_eq!(handle.0, 7);
+    /// ```
+    pub fn add_removal_sink<T: Component, S: RemovalSink<T>>(&mut self, sink: S) {
+        self.removal_sinks.add::<T, S>(sink);
+    }
+
+    /// Access the sink of type `S` registered for `T`, if any
+    pub fn removal_sink<T: Component, S: RemovalSink<T>>(&self) -> Option<&S> {
+        self.removal_sinks.get::<T, S>()
+    }
+
+    /// Uniquely access the sink of type `S` registered for `T`, if any
+    pub fn removal_sink_mut<T: Component, S: RemovalSink<T>>(&mut self) -> Option<&mut S> {
+        self.removal_sinks.get_mut::<T, S>()
+    }
+
+    /// Resume dropping discarded `T` components, returning whether a sink was registered
+    pub fn remove_removal_sink<T: Component>(&mut self) -> bool {
+        self.removal_sinks.remove::<T>()
+    }
+
+    /// Store `value` in the world, returning the first reference to it
+    ///
+    /// The world takes over the removal sink for `Shared<T>`, so one should not be registered by
+    /// other means.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Mesh(Vec<f32>);
+    /// let mut world = World::new();
+    /// let mesh = world.share(Mesh(vec![0.0; 1024]));
+    /// let other = world.clone_shared(&mesh).unwrap();
+    /// let a = world.spawn((mesh,));
+    /// let b = world.spawn((other,));
+    /// let mesh = world.get::<Shared<Mesh>>(a).unwrap();
+    /// assert_eq!(*mesh, *world.get::<Shared<Mesh>>(b).unwrap());
+    /// assert_eq!(world.get_shared(&mesh).unwrap().0.len(), 1024);
+    /// ```
+    pub fn share<T: Component>(&mut self, value: T) -> Shared<T> {
+        self.shared_values::<T>().share(value)
+    }
+
+    /// Like `share`, but returns a new reference to an existing value equal to `value` if one was
+    /// previously stored with `intern`
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.intern(String::from("soundbank"));
+    /// let b = world.intern(String::from("soundbank"));
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn intern<T: Component + Hash + Eq>(&mut self, value: T) -> Shared<T> {
+        self.shared_values::<T>().intern(value)
+    }
+
+    /// Obtain an additional reference to the value `shared` refers to
     ///
-    /// See `insert`.
-    pub fn insert_one(
-        &mut self,
-        entity: Entity,
-        component: impl Component,
-    ) -> Result<(), NoSuchEntity> {
-        self.insert(entity, (component,))
+    /// Returns `None` if `shared` was obtained from a different world.
+    pub fn clone_shared<T: Component>(&mut self, shared: &Shared<T>) -> Option<Shared<T>> {
+        self.shared_values::<T>().clone_shared(shared)
     }
 
-    /// Remove components from `entity`
+    /// Access the value `shared` refers to
     ///
-    /// Computational cost is proportional to the number of components `entity` has. The entity
-    /// itself is not removed, even if no components remain; use `despawn` for that. If any
-    /// component in `T` is not present in `entity`, no components are removed and an error is
-    /// returned.
+    /// Returns `None` if `shared` was obtained from a different world.
+    pub fn get_shared<T: Component>(&self, shared: &Shared<T>) -> Option<&T> {
+        self.removal_sinks
+            .get::<Shared<T>, SharedValues<T>>()?
+            .get(shared)
+    }
+
+    /// Discard a reference obtained from this world, dropping the value if it was the last
     ///
-    /// When removing a single component, see `remove_one` for convenience.
+    /// Returns `false`, doing nothing, if `shared` was obtained from a different world.
+    pub fn release_shared<T: Component>(&mut self, shared: Shared<T>) -> bool {
+        self.shared_values::<T>().release(shared)
+    }
+
+    /// Look up the tag named `name`, defining it if necessary
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let e = world.spawn((123, "abc", true));
-    /// assert_eq!(world.remove::<(i32, &str)>(e), Ok((123, "abc")));
-    /// assert!(world.get::<i32>(e).is_err());
-    /// assert!(world.get::<&str>(e).is_err());
-    /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
+    /// let selected = world.tag("selected");
+    /// let a = world.spawn((123,));
+    /// let b = world.spawn((456,));
+    /// assert!(world.add_tag(a, selected).unwrap());
+    /// assert!(world.has_tag(a, selected));
+    /// assert!(!world.has_tag(b, selected));
+    /// assert_eq!(world.tagged(selected).collect::<Vec<_>>(), [a]);
+    /// assert_eq!(world.find_tag("selected"), Some(selected));
+    /// assert_eq!(world.tag_name(selected), "selected");
     /// ```
-    pub fn remove<T: Bundle>(&mut self, entity: Entity) -> Result<T, ComponentError> {
-        use hashbrown::hash_map::Entry;
+    pub fn tag(&mut self, name: &str) -> Tag {
+        self.tags.get_or_insert(name)
+    }
 
-        self.flush();
-        let loc = self.entities.get_mut(entity)?;
-        unsafe {
-            let removed = T::with_static_ids(|ids| ids.iter().copied().collect::<HashSet<_>>());
-            let info = self.archetypes[loc.archetype as usize]
-                .types()
-                .iter()
-                .cloned()
-                .filter(|x| !removed.contains(&x.id()))
-                .collect::<Vec<_>>();
-            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
-            let target = match self.index.entry(elements) {
-                Entry::Occupied(x) => *x.get(),
-                Entry::Vacant(x) => {
-                    self.archetypes.push(Archetype::new(info));
-                    let index = (self.archetypes.len() - 1) as u32;
-                    x.insert(index);
-                    self.archetype_generation += 1;
-                    index
-                }
-            };
-            let old_index = loc.index;
-            let source_arch = &self.archetypes[loc.archetype as usize];
-            let bundle = T::get(|ty, size| source_arch.get_dynamic(ty, size, old_index))?;
-            let (source_arch, target_arch) = index2(
-                &mut self.archetypes,
-                loc.archetype as usize,
-                target as usize,
-            );
-            let target_index = target_arch.allocate(entity.id);
-            loc.archetype = target;
-            loc.index = target_index;
-            if let Some(moved) = source_arch.move_to(old_index, |src, ty, size| {
-                // Only move the components present in the target archetype, i.e. the non-removed ones.
-                if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
-                    ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
-                }
-            }) {
-                self.entities.meta[moved as usize].location.index = old_index;
-            }
-            Ok(bundle)
-        }
+    /// Look up the tag named `name`, if it's been defined
+    pub fn find_tag(&self, name: &str) -> Option<Tag> {
+        self.tags.get(name)
     }
 
-    /// Remove the `T` component from `entity`
-    ///
-    /// See `remove`.
-    pub fn remove_one<T: Component>(&mut self, entity: Entity) -> Result<T, ComponentError> {
-        self.remove::<(T,)>(entity).map(|(x,)| x)
+    /// The name `tag` was defined with
+    pub fn tag_name(&self, tag: Tag) -> &str {
+        self.tags.name(tag)
     }
 
-    /// Borrow the `T` component of `entity` without safety checks
-    ///
-    /// Should only be used as a building block for safe abstractions.
+    /// Add `tag` to `entity`, returning whether it was newly added
     ///
-    /// # Safety
-    ///
-    /// `entity` must have been previously obtained from this `World`, and no unique borrow of the
-    /// same component of `entity` may be live simultaneous to the returned reference.
-    pub unsafe fn get_unchecked<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
-        let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
-        }
-        Ok(&*self.archetypes[loc.archetype as usize]
-            .get::<T>()
-            .ok_or_else(MissingComponent::new::<T>)?
-            .as_ptr()
-            .add(loc.index as usize))
+    /// Cheap compared to inserting a component, since the entity doesn't move between archetypes.
+    pub fn add_tag(&mut self, entity: Entity, tag: Tag) -> Result<bool, NoSuchEntity> {
+        self.flush_entities();
+        self.entities.get(entity)?;
+        Ok(self.tags.members_mut(tag).insert(entity))
     }
 
-    /// Uniquely borrow the `T` component of `entity` without safety checks
-    ///
-    /// Should only be used as a building block for safe abstractions.
-    ///
-    /// # Safety
-    ///
-    /// `entity` must have been previously obtained from this `World`, and no borrow of the same
-    /// component of `entity` may be live simultaneous to the returned reference.
-    pub unsafe fn get_unchecked_mut<T: Component>(
-        &self,
-        entity: Entity,
-    ) -> Result<&mut T, ComponentError> {
-        let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
-        }
-        Ok(&mut *self.archetypes[loc.archetype as usize]
-            .get::<T>()
-            .ok_or_else(MissingComponent::new::<T>)?
-            .as_ptr()
-            .add(loc.index as usize))
+    /// Remove `tag` from `entity`, returning whether it was present
+    pub fn remove_tag(&mut self, entity: Entity, tag: Tag) -> Result<bool, NoSuchEntity> {
+        self.flush_entities();
+        self.entities.get(entity)?;
+        Ok(self.tags.members_mut(tag).remove(&entity))
     }
 
-    /// Convert all reserved entities into empty entities that can be iterated and accessed
-    ///
-    /// Invoked implicitly by `spawn`, `despawn`, `insert`, and `remove`.
-    pub fn flush(&mut self) {
-        let arch = &mut self.archetypes[0];
-        for id in self.entities.flush() {
-            self.entities.meta[id as usize].location.index = unsafe { arch.allocate(id) };
-        }
-        for i in 0..self.entities.reserved_len() {
-            let id = self.entities.reserved(i);
-            self.entities.meta[id as usize].location.index = unsafe { arch.allocate(id) };
-        }
-        self.entities.clear_reserved();
+    /// Whether `entity` exists and has `tag`
+    pub fn has_tag(&self, entity: Entity, tag: Tag) -> bool {
+        self.tags.members(tag).contains(&entity)
     }
 
-    /// Inspect the archetypes that entities are organized into
+    /// Iterate over all entities having `tag`, in arbitrary order
     ///
-    /// Useful for dynamically scheduling concurrent queries by checking borrows in advance. Does
-    /// not provide access to entities.
-    pub fn archetypes(&self) -> impl ExactSizeIterator<Item = &'_ Archetype> + '_ {
-        self.archetypes.iter()
+    /// To query the components of tagged entities, combine with `query_one`.
+    pub fn tagged(&self, tag: Tag) -> impl Iterator<Item = Entity> + '_ {
+        self.tags.members(tag).iter().copied()
     }
 
-    /// Returns a distinct value after `archetypes` is changed
-    ///
-    /// Store the current value after deriving information from `archetypes`, then check whether the
-    /// value returned by this function differs before attempting an operation that relies on its
-    /// correctness. Useful for determining whether e.g. a concurrent query execution plan is still
-    /// correct.
+    /// Give `entity` the unique `name`, returning the entity that had it before, if any
     ///
-    /// The generation may be, but is not necessarily, changed as a result of adding or removing any
-    /// entity or component.
+    /// An entity has at most one name, so any name `entity` already had is released. Names are
+    /// forgotten when their entity is despawned, so `lookup` never returns a stale handle.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let initial_gen = world.archetypes_generation();
-    /// world.spawn((123, "abc"));
-    /// assert_ne!(initial_gen, world.archetypes_generation());
+    /// let a = world.spawn((123,));
+    /// let b = world.spawn((456,));
+    /// assert_eq!(world.name(a, "player").unwrap(), None);
+    /// assert_eq!(world.lookup("player"), Some(a));
+    /// assert_eq!(world.name_of(a), Some("player"));
+    /// assert_eq!(world.name(b, "player").unwrap(), Some(a));
+    /// assert_eq!(world.name_of(a), None);
+    /// world.despawn(b).unwrap();
+    /// assert_eq!(world.lookup("player"), None);
     /// ```
-    pub fn archetypes_generation(&self) -> ArchetypesGeneration {
-        ArchetypesGeneration(self.archetype_generation)
+    pub fn name(&mut self, entity: Entity, name: &str) -> Result<Option<Entity>, NoSuchEntity> {
+        self.flush_entities();
+        self.entities.get(entity)?;
+        Ok(self.names.insert(entity, name))
+    }
+
+    /// Remove `entity`'s name, returning whether it had one
+    pub fn unname(&mut self, entity: Entity) -> bool {
+        self.names.despawned(entity)
+    }
+
+    /// The entity named `name`, if any
+    pub fn lookup(&self, name: &str) -> Option<Entity> {
+        self.names.get(name)
+    }
+
+    /// The name given to `entity`, if any
+    pub fn name_of(&self, entity: Entity) -> Option<&str> {
+        self.names.name(entity)
+    }
+
+    /// Iterate over every name and the entity having it, in arbitrary order
+    pub fn names(&self) -> impl Iterator<Item = (&str, Entity)> + '_ {
+        self.names.iter()
+    }
+
+    fn shared_values<T: Component>(&mut self) -> &mut SharedValues<T> {
+        if self
+            .removal_sinks
+            .get::<Shared<T>, SharedValues<T>>()
+            .is_none()
+        {
+            self.removal_sinks
+                .add::<Shared<T>, _>(SharedValues::<T>::new(self.uid.0));
+        }
+        self.removal_sinks
+            .get_mut::<Shared<T>, SharedValues<T>>()
+            .unwrap()
     }
 }
 
@@ -599,6 +3864,86 @@ impl<'a> IntoIterator for &'a World {
     }
 }
 
+/// Despawns an entity whose `old` component has been moved out if dropped
+struct MigrationGuard<'a> {
+    archetype: &'a mut Archetype,
+    index: u32,
+    entity: Entity,
+    old: TypeId,
+    entities: &'a mut Entities,
+    indices: &'a mut Indices,
+    sinks: &'a mut RemovalSinks,
+    tags: &'a mut Tags,
+    names: &'a mut Names,
+    journal: Option<&'a mut Journal>,
+}
+
+impl Drop for MigrationGuard<'_> {
+    fn drop(&mut self) {
+        let (entity, old) = (self.entity, self.old);
+        let sinks = &mut *self.sinks;
+        unsafe {
+            self.indices.removed_all(entity, self.archetype, self.index);
+            self.archetype.remove(self.index, |ty, ptr| {
+                if ty.id() != old {
+                    sinks.discard(entity, ty, ptr);
+                }
+            });
+        }
+        self.entities.free(entity).unwrap();
+        self.tags.despawned(entity);
+        self.names.despawned(entity);
+        if let Some(ref mut journal) = self.journal {
+            journal.despawned(entity);
+        }
+    }
+}
+
+/// Removes a freshly allocated row, dropping the components written so far, if dropped
+struct PartialRow<'a> {
+    archetype: &'a mut Archetype,
+    index: u32,
+    written: Vec<TypeId>,
+}
+
+impl PartialRow<'_> {
+    /// Keep the row, every component having been written
+    fn finish(mut self) -> u32 {
+        let index = self.index;
+        drop(mem::take(&mut self.written));
+        mem::forget(self);
+        index
+    }
+}
+
+impl Drop for PartialRow<'_> {
+    fn drop(&mut self) {
+        let written = &self.written;
+        unsafe {
+            self.archetype.remove(self.index, |ty, ptr| {
+                if written.contains(&ty.id()) {
+                    ty.drop(ptr);
+                }
+            });
+        }
+    }
+}
+
+/// Initial `archetype_generation` of a new world, leaving room for 2^32 archetypes to be created
+/// before it could coincide with another world's
+fn fresh_generation() -> u64 {
+    // Starts at 1 so that no world's `uid` is 0
+    static NEXT: AtomicU64 = AtomicU64::new(1 << 32);
+    NEXT.fetch_add(1 << 32, Ordering::Relaxed)
+}
+
+/// Panic if any two of `entities` are equal
+fn assert_distinct(entities: &[Entity]) {
+    for (i, x) in entities.iter().enumerate() {
+        assert!(!entities[..i].contains(x), "entities must be distinct");
+    }
+}
+
 fn index2<T>(x: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
     assert!(i != j);
     assert!(i < x.len());
@@ -641,6 +3986,120 @@ impl From<MissingComponent> for ComponentError {
     }
 }
 
+/// Errors that arise from the `try_` methods of `World`, in place of panics
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum WorldError {
+    /// The entity was already despawned
+    NoSuchEntity,
+    /// The entity did not have a requested component
+    MissingComponent(MissingComponent),
+    /// A component was already borrowed incompatibly
+    Borrow(BorrowError),
+    /// The entity is stored in read-only memory
+    ReadOnly,
+}
+
+#[cfg(feature = "std")]
+impl Error for WorldError {}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use WorldError::*;
+        match *self {
+            NoSuchEntity => f.write_str("no such entity"),
+            MissingComponent(ref x) => x.fmt(f),
+            Borrow(ref x) => x.fmt(f),
+            ReadOnly => f.write_str("entity is stored in read-only memory"),
+        }
+    }
+}
+
+impl From<NoSuchEntity> for WorldError {
+    fn from(NoSuchEntity: NoSuchEntity) -> Self {
+        WorldError::NoSuchEntity
+    }
+}
+
+impl From<MissingComponent> for WorldError {
+    fn from(x: MissingComponent) -> Self {
+        WorldError::MissingComponent(x)
+    }
+}
+
+impl From<BorrowError> for WorldError {
+    fn from(x: BorrowError) -> Self {
+        if x.is_read_only() {
+            return WorldError::ReadOnly;
+        }
+        WorldError::Borrow(x)
+    }
+}
+
+/// Errors that arise when spawning an entity with `World::spawn_at`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum SpawnAtError {
+    /// A different entity with the same ID is live
+    Occupied(Entity),
+    /// Spawning would exceed a limit
+    QuotaExceeded(QuotaExceeded),
+}
+
+#[cfg(feature = "std")]
+impl Error for SpawnAtError {}
+
+impl fmt::Display for SpawnAtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SpawnAtError::*;
+        match *self {
+            Occupied(x) => write!(f, "entity ID is occupied by {:?}", x),
+            QuotaExceeded(ref x) => x.fmt(f),
+        }
+    }
+}
+
+impl From<QuotaExceeded> for SpawnAtError {
+    fn from(x: QuotaExceeded) -> Self {
+        SpawnAtError::QuotaExceeded(x)
+    }
+}
+
+/// Error indicating that spawning an entity would exceed a limit set by `World::set_entity_limit`
+/// or `World::set_archetype_limit`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct QuotaExceeded {
+    limit: u32,
+    archetype: bool,
+}
+
+impl QuotaExceeded {
+    fn new(limit: u32, archetype: bool) -> Self {
+        Self { limit, archetype }
+    }
+
+    /// The limit that would have been exceeded
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Whether the limit applies to a single archetype, rather than the whole world
+    pub fn is_archetype_limit(&self) -> bool {
+        self.archetype
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for QuotaExceeded {}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.archetype {
+            write!(f, "archetype entity limit of {} reached", self.limit)
+        } else {
+            write!(f, "entity limit of {} reached", self.limit)
+        }
+    }
+}
+
 /// Types that can be components, implemented automatically for all `Send + Sync + 'static` types
 ///
 /// This is just a convenient shorthand for `Send + Sync + 'static`, and never needs to be
@@ -654,15 +4113,20 @@ pub struct Iter<'a> {
     entities: &'a Entities,
     current: Option<&'a Archetype>,
     index: u32,
+    tick: u32,
+    /// Number of entities yet to be yielded
+    remaining: u32,
 }
 
 impl<'a> Iter<'a> {
-    fn new(archetypes: &'a [Archetype], entities: &'a Entities) -> Self {
+    fn new(archetypes: &'a [Archetype], entities: &'a Entities, tick: u32) -> Self {
         Self {
             archetypes: archetypes.iter(),
             entities,
             current: None,
             index: 0,
+            tick,
+            remaining: archetypes.iter().map(|x| x.len()).sum(),
         }
     }
 }
@@ -680,27 +4144,30 @@ impl<'a> Iterator for Iter<'a> {
                     self.index = 0;
                 }
                 Some(current) => {
-                    if self.index == current.len() as u32 {
+                    if self.index == current.len() {
                         self.current = None;
                         continue;
                     }
                     let index = self.index;
                     self.index += 1;
+                    self.remaining -= 1;
                     let id = current.entity_id(index);
-                    return Some((
-                        Entity {
-                            id,
-                            generation: self.entities.meta[id as usize].generation,
-                        },
-                        unsafe { EntityRef::new(current, index) },
-                    ));
+                    return Some((self.entities.meta[id as usize].entity(id), unsafe {
+                        EntityRef::new(current, index, self.tick)
+                    }));
                 }
             }
         }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, Some(self.entities.meta.len()))
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {
+    fn len(&self) -> usize {
+        self.remaining as usize
     }
 }
 
@@ -723,10 +4190,45 @@ impl<A: DynamicBundle> core::iter::FromIterator<A> for World {
     }
 }
 
+/// How often, in ticks, to clamp old change ticks
+const CLAMP_INTERVAL: u32 = u32::MAX / 4;
+
+/// Maps the handles entities had in a world merged by `World::merge` to their new handles
+#[derive(Debug, Default, Clone)]
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    /// The new handle of the entity that was `old`, if it was merged
+    pub fn get(&self, old: Entity) -> Option<Entity> {
+        self.map.get(&old).copied()
+    }
+
+    /// Iterate over the old and new handles of every merged entity
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.map.iter().map(|(&old, &new)| (old, new))
+    }
+
+    /// Number of merged entities
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether no entities were merged
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
 /// Determines freshness of information derived from `World::archetypes`
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct ArchetypesGeneration(u64);
 
+/// Identifies a `World`, and any created from it by `World::clone_with`, within the process
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WorldUid(u32);
+
 /// Entity IDs created by `World::spawn_batch`
 pub struct SpawnBatchIter<'a, I>
 where
@@ -734,9 +4236,15 @@ where
     I::Item: Bundle,
 {
     inner: I,
+    /// Number of entities that can be spawned before `quota` is exceeded
+    room: u32,
+    quota: QuotaExceeded,
+    ticks: ComponentTicks,
     entities: &'a mut Entities,
     archetype_id: u32,
     archetype: &'a mut Archetype,
+    indices: &'a mut Indices,
+    journal: Option<&'a mut Journal>,
 }
 
 impl<I> Drop for SpawnBatchIter<'_, I>
@@ -758,17 +4266,27 @@ where
 
     fn next(&mut self) -> Option<Entity> {
         let components = self.inner.next()?;
+        if self.room == 0 {
+            self.inner.by_ref().for_each(drop);
+            panic!("{}", self.quota);
+        }
+        self.room -= 1;
         let entity = self.entities.alloc();
         unsafe {
             let index = self.archetype.allocate(entity.id);
+            let ticks = self.ticks;
             components.put(|ptr, ty, size| {
-                self.archetype.put_dynamic(ptr, ty, size, index);
+                self.archetype.put_dynamic(ptr, ty, size, index, ticks);
                 true
             });
             self.entities.meta[entity.id as usize].location = Location {
                 archetype: self.archetype_id,
                 index,
             };
+            self.indices.inserted_all(entity, self.archetype, index);
+            if let Some(ref mut journal) = self.journal {
+                journal.spawned(entity, self.archetype, index);
+            }
         }
         Some(entity)
     }
@@ -802,6 +4320,27 @@ mod tests {
         assert_ne!(a.generation, b.generation);
     }
 
+    #[test]
+    fn clamp_change_ticks() {
+        let mut world = World::new();
+        let e = world.spawn((42,));
+        *world.change_tick.get_mut() = CLAMP_INTERVAL;
+        world.flush();
+        assert_eq!(world.last_clamp, CLAMP_INTERVAL);
+        let archetype = &world.archetypes[world.entities.get(e).unwrap().archetype as usize];
+        assert_eq!(
+            archetype.component_ticks(TypeId::of::<i32>(), 0),
+            Some(ComponentTicks::new(0))
+        );
+        *world.change_tick.get_mut() = 3 * CLAMP_INTERVAL;
+        world.flush();
+        let archetype = &world.archetypes[world.entities.get(e).unwrap().archetype as usize];
+        assert_eq!(
+            archetype.component_ticks(TypeId::of::<i32>(), 0),
+            Some(ComponentTicks::new(3 * CLAMP_INTERVAL - MAX_CHANGE_AGE))
+        );
+    }
+
     #[test]
     fn reuse_populated() {
         let mut world = World::new();
@@ -113,6 +113,28 @@ fn query_optional_component() {
     assert!(ents.contains(&(f, Some(true), 456)));
 }
 
+#[test]
+fn query_iter_cloned() {
+    let mut world = World::new();
+    let e = world.spawn((String::from("abc"), 123));
+    let last_run = world.increment_change_tick();
+    let f = world.spawn((String::from("def"), 456, true));
+    world.increment_change_tick();
+    let mut query = world
+        .query::<With<i32, (&String, Option<&bool>, Changed<String>)>>()
+        .since(last_run);
+    let iter = query.iter_cloned();
+    assert_eq!(iter.len(), 2);
+    let ents = iter.collect::<Vec<_>>();
+    drop(query);
+    // Results are independent of the world
+    world.clear();
+    let ents = std::thread::spawn(move || ents).join().unwrap();
+    assert_eq!(ents.len(), 2);
+    assert!(ents.contains(&(e, (String::from("abc"), None, false))));
+    assert!(ents.contains(&(f, (String::from("def"), Some(true), true))));
+}
+
 #[test]
 fn build_entity() {
     let mut world = World::new();
@@ -259,6 +281,425 @@ fn clear() {
     assert_eq!(world.iter().count(), 0);
 }
 
+#[test]
+fn entity_bits() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    world.despawn(a).unwrap();
+    let b = world.spawn(());
+    assert_eq!(b.to_bits() as u32, b.id());
+    assert_ne!(b.to_bits() >> 32, a.to_bits() >> 32);
+    assert_eq!(Entity::from_bits(b.to_bits()), b);
+    assert_eq!(Entity::try_from_bits(b.to_bits()), Some(b));
+    assert_eq!(
+        Entity::try_from_bits(u64::from(u32::MAX) << 32 | u64::from(b.id())),
+        None
+    );
+    assert_eq!(Entity::from_wide_bits(b.to_wide_bits()), b);
+    assert_eq!(b.to_wide_bits(), u128::from(b.to_bits()));
+
+    // Restoring a world preserves the meaning of stored bits
+    let mut restored = World::new();
+    restored
+        .spawn_at(Entity::from_bits(b.to_bits()), (123,))
+        .unwrap();
+    assert_eq!(*restored.get::<i32>(b).unwrap(), 123);
+}
+
+#[test]
+fn entity_bits_format() {
+    // Generations start from 1, but are encoded from 0 as they were when they started from 0
+    let mut world = World::new();
+    let a = world.spawn(());
+    assert_eq!(a.generation(), 1);
+    assert_eq!(a.to_bits(), u64::from(a.id()));
+    assert_eq!(Entity::from_bits(0), a);
+    assert_eq!(Entity::try_from_bits(0), Some(a));
+    world.despawn(a).unwrap();
+    let b = world.spawn(());
+    assert_eq!(b.to_bits(), 1 << 32 | u64::from(b.id()));
+    assert_eq!(Entity::from_bits(1 << 32), b);
+
+    // Generation bits that `to_bits` never produces decode to an entity that's never live
+    let invalid = u64::from(u32::MAX) << 32 | u64::from(b.id());
+    assert_eq!(Entity::try_from_bits(invalid), None);
+    assert!(!world.contains(Entity::from_bits(invalid)));
+}
+
+#[test]
+fn entity_niche() {
+    assert_eq!(
+        std::mem::size_of::<Option<Entity>>(),
+        std::mem::size_of::<Entity>()
+    );
+}
+
+/// The generation of an ID's last entity before the ID is retired
+#[cfg(not(feature = "wide-entities"))]
+const LAST_GENERATION: Generation = u32::MAX as Generation - 1;
+#[cfg(feature = "wide-entities")]
+const LAST_GENERATION: Generation = Generation::MAX - 1;
+
+/// The entity with ID `id` and generation `generation`
+fn entity_at(id: u32, generation: Generation) -> Entity {
+    Entity::from_wide_bits(u128::from(generation - 1) << 32 | u128::from(id))
+}
+
+#[test]
+fn exhausted_generation() {
+    let mut world = World::new();
+    let last = entity_at(7, LAST_GENERATION);
+    world.spawn_at(last, (1,)).unwrap();
+    world.despawn(last).unwrap();
+    // The ID is retired rather than wrapping around to generation 1
+    let wrapped = Entity::from_bits(7);
+    for _ in 0..2000 {
+        let e = world.spawn(());
+        assert_ne!(e.id(), 7);
+    }
+    assert!(!world.contains(last));
+    assert!(!world.contains(wrapped));
+    world.clear();
+    assert!(world.iter().all(|(e, _)| e.id() != 7));
+    for _ in 0..2000 {
+        assert_ne!(world.spawn(()).id(), 7);
+    }
+
+    // Mirroring a handle from elsewhere can still claim it
+    world.spawn_at(wrapped, (2,)).unwrap();
+    assert_eq!(*world.get::<i32>(wrapped).unwrap(), 2);
+    world.despawn(wrapped).unwrap();
+}
+
+#[test]
+fn no_id_reuse() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    let b = world.spawn(());
+    world.despawn(a).unwrap();
+    world.set_id_reuse(false);
+    let ids = (0..2000)
+        .map(|_| {
+            let e = world.spawn(());
+            world.despawn(e).unwrap();
+            e.id()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(ids, (2..2002).collect::<Vec<_>>());
+    assert_eq!(world.reserve_entity().id(), 2002);
+    assert_eq!(
+        world
+            .reserve_entities(2)
+            .map(|x| x.id())
+            .collect::<Vec<_>>(),
+        [2003, 2004]
+    );
+    world.flush();
+    world.clear();
+    assert!(!world.contains(b));
+    assert_eq!(world.spawn(()).id(), 2005);
+
+    let mut snapshotter = Snapshotter::new();
+    let snapshot = snapshotter.take(&mut world);
+    let c = world.spawn(());
+    snapshotter.restore(&snapshot, &mut world);
+    assert!(world.spawn(()).id() > c.id());
+
+    // Reenabling reuse makes freed IDs available again
+    world.set_id_reuse(true);
+    let slots = world.entity_slots().len();
+    let free = world
+        .entity_slots()
+        .filter(|x| matches!(x, EntitySlot::Free(_)))
+        .count();
+    let ids = (0..free).map(|_| world.spawn(()).id()).collect::<Vec<_>>();
+    assert!(ids.contains(&a.id()));
+    assert_eq!(world.entity_slots().len(), slots);
+}
+
+#[test]
+fn entity_slots() {
+    let mut world = World::new();
+    assert_eq!(world.entity_slot(0), None);
+    let a = world.spawn(());
+    assert_eq!(a.generation(), 1);
+    let last = entity_at(2, LAST_GENERATION);
+    world.spawn_at(last, ()).unwrap();
+    world.despawn(last).unwrap();
+    let b = world.reserve_entity();
+    let slots = world.entity_slots().collect::<Vec<_>>();
+    assert_eq!(slots.len(), world.entity_slots().len());
+    assert!(slots.contains(&EntitySlot::Live(a)));
+    assert!(slots.contains(&EntitySlot::Live(b)));
+    assert_eq!(slots[2], EntitySlot::Retired(2));
+    assert_eq!(slots[2].id(), 2);
+    assert!(slots.iter().enumerate().all(|(i, x)| x.id() == i as u32));
+    assert_eq!(
+        slots
+            .iter()
+            .filter(|x| matches!(x, EntitySlot::Live(_)))
+            .count(),
+        2
+    );
+    assert_eq!(world.entity_slot(slots.len() as u32), None);
+
+    world.despawn(a).unwrap();
+    let next = Entity::from_bits(1 << 32 | u64::from(a.id()));
+    assert_eq!(world.entity_slot(a.id()), Some(EntitySlot::Free(next)));
+    assert_eq!(world.spawn(()), next);
+}
+
+#[test]
+fn generation() {
+    let mut world = World::new();
+    let a = world.spawn((123,));
+    assert_eq!(world.generation(a), Some(a.generation()));
+    world.despawn(a).unwrap();
+    // Stale, with the slot free
+    assert_eq!(world.generation(a), Some(a.generation() + 1));
+    let b = world.spawn((456,));
+    assert_eq!(b.id(), a.id());
+    // Stale, with the slot reoccupied
+    assert_eq!(world.generation(a), Some(b.generation()));
+    assert_eq!(world.generation(b), Some(b.generation()));
+    assert_ne!(world.generation(a), Some(a.generation()));
+
+    let last = entity_at(7, LAST_GENERATION);
+    world.spawn_at(last, ()).unwrap();
+    world.despawn(last).unwrap();
+    assert_eq!(world.generation(last), None);
+    assert_eq!(world.generation(Entity::from_bits(100_000)), None);
+}
+
+#[test]
+fn write_barrier() {
+    use std::sync::{Arc, Mutex};
+    let mut world = World::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let log2 = log.clone();
+    world.add_write_barrier::<i32>(move |id| log2.lock().unwrap().push(id));
+    let take = || {
+        let mut ids = std::mem::take(&mut *log.lock().unwrap());
+        ids.sort_unstable();
+        ids
+    };
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, "abc"));
+    assert_eq!(take(), []);
+
+    // Only entities a query actually yields are reported
+    for (_, x) in world.query::<&mut i32>().without::<&str>().iter() {
+        *x += 1;
+    }
+    let mut expected = vec![a.id(), b.id()];
+    expected.sort_unstable();
+    assert_eq!(take(), expected);
+
+    for (_, x) in world.query::<&i32>().iter() {
+        assert!(*x > 0);
+    }
+    assert_eq!(take(), []);
+
+    let x = world.get_mut::<i32>(c).unwrap();
+    let _x = RefMut::downgrade(x);
+    assert_eq!(take(), [c.id()]);
+    drop(_x);
+
+    // Archetypes created after registration report too
+    let d = world.spawn((4, 'x'));
+    *world.get_mut::<i32>(d).unwrap() = 5;
+    assert_eq!(take(), [d.id()]);
+    *world.get_mut::<bool>(a).unwrap() = false;
+    assert_eq!(take(), []);
+
+    assert!(world.remove_write_barrier::<i32>());
+    assert!(!world.remove_write_barrier::<i32>());
+    *world.get_mut::<i32>(a).unwrap() = 0;
+    assert_eq!(take(), []);
+}
+
+#[test]
+fn borrow_state() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    world.spawn((456, "abc"));
+    assert_eq!(world.borrow_state::<i32>(), BorrowState::default());
+    {
+        let x = world.get::<i32>(a).unwrap();
+        let y = world.get::<i32>(a).unwrap();
+        let state = world.borrow_state::<i32>();
+        assert_eq!(state.shared, 2);
+        assert!(state.can_borrow());
+        assert!(!state.can_borrow_mut());
+        drop((x, y));
+    }
+    {
+        let mut query = world.query::<&mut i32>().with::<bool>();
+        let _ = query.iter();
+        let state = world.borrow_state::<i32>();
+        assert_eq!(
+            state,
+            BorrowState {
+                shared: 0,
+                unique: 1
+            }
+        );
+        assert!(!world.borrow_state::<bool>().is_borrowed());
+    }
+    assert!(world.borrow_state::<i32>().can_borrow_mut());
+    assert!(!world.borrow_state::<char>().is_borrowed());
+}
+
+#[test]
+fn iter_archetype_order() {
+    let mut world = World::new();
+    for i in 0..4 {
+        world.spawn((i,));
+        world.spawn((i, true));
+        world.spawn(("abc",));
+    }
+    let mut types = world
+        .iter()
+        .map(|(_, e)| e.component_types().map(|x| x.id()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    // Entities of each archetype are contiguous
+    types.dedup();
+    assert_eq!(types.len(), 3);
+    let lens = world
+        .archetypes()
+        .map(|x| x.len())
+        .filter(|&x| x != 0)
+        .collect::<Vec<_>>();
+    assert_eq!(lens, [4, 4, 4]);
+    assert_eq!(
+        world.iter().map(|(e, _)| e).collect::<Vec<_>>(),
+        world
+            .query::<()>()
+            .iter()
+            .map(|(e, ())| e)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn archetype_creation_order() {
+    fn build() -> World {
+        let mut world = World::new();
+        let a = world.spawn((1, true));
+        world.spawn(("abc",));
+        world.spawn((2u8, 'x', 3.0f32));
+        world.insert_one(a, 4u64).unwrap();
+        world.remove_one::<bool>(a).unwrap();
+        world.spawn((5, true));
+        world
+    }
+    let lens = |world: &World| world.archetypes().map(|x| x.len()).collect::<Vec<_>>();
+    let world = build();
+    // The empty archetype, then each set of components in order of first use
+    assert_eq!(lens(&world), [0, 1, 1, 1, 0, 1]);
+    assert_eq!(lens(&build()), lens(&world));
+    assert_eq!(
+        world.iter().map(|(e, _)| e).collect::<Vec<_>>(),
+        build().iter().map(|(e, _)| e).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn garbage_stats() {
+    let mut world = World::new();
+    let entities = (0..100u32).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    for &e in &entities[4..] {
+        world.despawn(e).unwrap();
+    }
+    let stats = world
+        .archetypes()
+        .map(|x| x.garbage_stats())
+        .find(|x| x.peak != 0)
+        .unwrap();
+    assert_eq!(stats.peak, 100);
+    assert_eq!(stats.len, 4);
+    assert!(stats.capacity >= 100);
+    assert!(stats.reclaimable_bytes >= 96 * 4);
+
+    world.shrink_to_fit();
+    let stats = world
+        .archetypes()
+        .map(|x| x.garbage_stats())
+        .find(|x| x.len != 0)
+        .unwrap();
+    assert_eq!(stats.peak, 4);
+    assert_eq!(stats.capacity, 4);
+    assert_eq!(stats.reclaimable_bytes, 0);
+    for (i, &e) in entities[..4].iter().enumerate() {
+        assert_eq!(*world.get::<u32>(e).unwrap(), i as u32);
+    }
+    world.spawn((7u32,));
+}
+
+#[test]
+fn reserve_partially_filled() {
+    let mut world = World::new();
+    for i in 0..10 {
+        world.spawn((i,));
+    }
+    world.reserve::<(i32,)>(1000);
+    let capacity = |world: &World| {
+        world
+            .archetypes()
+            .map(|x| x.garbage_stats().capacity)
+            .max()
+            .unwrap()
+    };
+    let reserved = capacity(&world);
+    assert!(reserved >= 1010);
+    for i in 0..1000 {
+        world.spawn((i,));
+    }
+    assert_eq!(capacity(&world), reserved);
+}
+
+#[test]
+fn iter_len() {
+    let mut world = World::new();
+    world.spawn(("abc", 123));
+    let b = world.spawn(("def", 456, true));
+    world.spawn((789,));
+    world.despawn(b).unwrap();
+    let mut iter = world.iter();
+    assert_eq!(iter.len(), 2);
+    iter.next().unwrap();
+    assert_eq!(iter.size_hint(), (1, Some(1)));
+    iter.next().unwrap();
+    assert_eq!(iter.len(), 0);
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn clear_invalidates_handles() {
+    let mut world = World::new();
+    let a = world.spawn(("abc", 123));
+    let b = world.spawn(());
+    let c = world.reserve_entity();
+    world.clear();
+    for x in [a, b, c] {
+        assert!(!world.contains(x));
+        assert!(world.entity(x).is_err());
+    }
+    assert!(world.get::<i32>(a).is_err());
+    assert_eq!(world.despawn(a), Err(NoSuchEntity));
+    let d = world.spawn((456,));
+    assert!(!world.contains(a) && !world.contains(b));
+    assert_eq!(
+        world
+            .query::<&i32>()
+            .iter()
+            .map(|(e, &x)| (e, x))
+            .collect::<Vec<_>>(),
+        [(d, 456)]
+    );
+}
+
 #[test]
 #[should_panic(expected = "twice on the same borrow")]
 fn alias() {
@@ -352,3 +793,3463 @@ fn query_one() {
     world.despawn(a).unwrap();
     assert!(world.query_one::<&i32>(a).is_err());
 }
+
+#[test]
+fn compress_columns() {
+    let mut world = World::new();
+    let a = world.spawn((1u8, "abc"));
+    let b = world.spawn((2u8, 3u16));
+    let c = world.spawn((4u8,));
+    world.spawn(("unregistered",));
+    let mut compressors = ColumnCompressors::new();
+    compressors.register::<u8>(
+        7,
+        |column, out| out.extend(column.iter().map(|&x| x * 2)),
+        |data, out| {
+            out.extend(data.iter().map(|&x| x / 2));
+            Ok(())
+        },
+    );
+    assert!(compressors.contains::<u8>());
+    assert!(!compressors.contains::<u16>());
+    let mut out = Vec::new();
+    compressors.compress_world(&world, &mut out);
+
+    let mut replica = World::new();
+    compressors.decompress_world(&out, &mut replica).unwrap();
+    assert_eq!(replica.len(), 3);
+    assert_eq!(*replica.get::<u8>(a).unwrap(), 1);
+    assert_eq!(*replica.get::<u8>(b).unwrap(), 2);
+    assert_eq!(*replica.get::<u8>(c).unwrap(), 4);
+    assert!(replica.get::<u16>(b).is_err());
+
+    // Snapshots encode identically
+    let mut snapshotter = Snapshotter::new();
+    snapshotter
+        .register::<u8>()
+        .register::<u16>()
+        .register::<&'static str>();
+    let snapshot = snapshotter.take(&mut world);
+    *world.get_mut::<u8>(a).unwrap() = 5;
+    let mut from_snapshot = Vec::new();
+    compressors.compress_snapshot(&snapshot, &mut from_snapshot);
+    assert_eq!(from_snapshot, out);
+
+    // Malformed input is rejected without spawning anything
+    let mut replica = World::new();
+    assert_eq!(
+        compressors.decompress_world(&out[..out.len() - 1], &mut replica),
+        Err(DecompressError::Truncated)
+    );
+    let mut other = ColumnCompressors::new();
+    other.register::<u8>(8, |_, _| {}, |_, _| Ok(()));
+    assert_eq!(
+        other.decompress_world(&out, &mut replica),
+        Err(DecompressError::UnknownType(7))
+    );
+    assert!(replica.is_empty());
+}
+
+#[test]
+fn compress_panic_releases_borrow() {
+    let mut world = World::new();
+    let a = world.spawn((1u8,));
+    let mut compressors = ColumnCompressors::new();
+    compressors.register::<u8>(0, |_, _| panic!("oops"), |_, _| Ok(()));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        compressors.compress_world(&world, &mut Vec::new());
+    }));
+    assert!(result.is_err());
+    *world.get_mut::<u8>(a).unwrap() = 2;
+}
+
+#[test]
+#[should_panic(expected = "already in use")]
+fn compress_duplicate_id() {
+    let mut compressors = ColumnCompressors::new();
+    compressors.register::<u8>(0, |_, _| {}, |_, _| Ok(()));
+    compressors.register::<u16>(0, |_, _| {}, |_, _| Ok(()));
+}
+
+#[test]
+fn component_index() {
+    #[derive(Default)]
+    struct Log(Vec<(Entity, &'static str, i32)>);
+    impl ComponentIndex<i32> for Log {
+        fn insert(&mut self, entity: Entity, x: &i32) {
+            self.0.push((entity, "insert", *x));
+        }
+        fn remove(&mut self, entity: Entity, x: &i32) {
+            self.0.push((entity, "remove", *x));
+        }
+        fn change(&mut self, entity: Entity, x: &i32) {
+            self.0.push((entity, "change", *x));
+        }
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, "abc"));
+    world.add_component_index::<i32, _>(Log::default());
+    let b = world.spawn(("def",));
+    world.insert_one(b, 2).unwrap();
+    world.insert(a, (3, true)).unwrap();
+    *world.get_mut::<i32>(a).unwrap() = 4;
+    world.notify_changed::<i32>(a).unwrap();
+    assert!(world.notify_changed::<bool>(b).is_err());
+    world.remove_one::<i32>(b).unwrap();
+    world.despawn(a).unwrap();
+    let c = world.spawn((5,));
+    world.clear();
+    assert_eq!(
+        world.component_index::<i32, Log>().unwrap().0,
+        &[
+            (a, "insert", 1),
+            (b, "insert", 2),
+            (a, "change", 3),
+            (a, "change", 4),
+            (b, "remove", 2),
+            (a, "remove", 4),
+            (c, "insert", 5),
+            (c, "remove", 5),
+        ]
+    );
+    assert!(world.remove_component_index::<i32>());
+    assert!(world.component_index::<i32, Log>().is_none());
+}
+
+#[test]
+fn component_index_panic() {
+    struct Refuse;
+    impl ComponentIndex<i32> for Refuse {
+        fn insert(&mut self, _: Entity, _: &i32) {}
+        fn remove(&mut self, _: Entity, x: &i32) {
+            assert_ne!(*x, 13, "refused");
+        }
+        fn change(&mut self, _: Entity, _: &i32) {}
+    }
+
+    let mut world = World::new();
+    world.add_component_index::<i32, _>(Refuse);
+    let a = world.spawn((13, "abc"));
+    let b = world.spawn((14, "def"));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| world.despawn(a)));
+    assert!(result.is_err());
+    // The despawn was abandoned before anything changed
+    assert!(world.contains(a));
+    assert_eq!(*world.get::<i32>(a).unwrap(), 13);
+    assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+    assert_eq!(world.len(), 2);
+    world.despawn(b).unwrap();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 13);
+    assert!(
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| world.take::<(i32,)>(a))).is_err()
+    );
+    assert!(world.contains(a));
+    world.remove_component_index::<i32>();
+    world.despawn(a).unwrap();
+    assert!(world.is_empty());
+}
+
+#[test]
+fn mirror() {
+    let mut sim = World::new();
+    let mut render = World::new();
+    let mut mirror = WorldMirror::new();
+    mirror.register::<i32>().register::<bool>();
+
+    let a = sim.spawn((1, true, "abc"));
+    let b = sim.spawn((2,));
+    mirror.sync(&sim, &mut render);
+    let (ra, rb) = (mirror.get(a).unwrap(), mirror.get(b).unwrap());
+    assert_eq!(*render.get::<i32>(ra).unwrap(), 1);
+    assert!(*render.get::<bool>(ra).unwrap());
+    assert!(render.get::<&str>(ra).is_err());
+    assert_eq!(*render.get::<i32>(rb).unwrap(), 2);
+
+    *sim.get_mut::<i32>(a).unwrap() = 3;
+    sim.remove_one::<bool>(a).unwrap();
+    sim.despawn(b).unwrap();
+    render.insert_one(ra, 'x').unwrap();
+    mirror.sync(&sim, &mut render);
+    assert_eq!(mirror.get(a), Some(ra));
+    assert_eq!(*render.get::<i32>(ra).unwrap(), 3);
+    assert!(render.get::<bool>(ra).is_err());
+    assert_eq!(*render.get::<char>(ra).unwrap(), 'x');
+    assert!(mirror.get(b).is_none());
+    assert!(!render.contains(rb));
+}
+
+#[test]
+fn mirror_changes() {
+    let mut sim = World::new();
+    let mut render = World::new();
+    let mut mirror = WorldMirror::new();
+    mirror.register::<i32>();
+
+    let a = sim.spawn((1, 0u8));
+    let b = sim.spawn((2, 0u8));
+    mirror.sync(&sim, &mut render);
+    let (ra, rb) = (mirror.get(a).unwrap(), mirror.get(b).unwrap());
+
+    // Untouched source components aren't copied again
+    *render.get_mut::<i32>(ra).unwrap() = 10;
+    *render.get_mut::<i32>(rb).unwrap() = 20;
+    *sim.get_mut::<i32>(b).unwrap() = 3;
+    mirror.sync(&sim, &mut render);
+    assert_eq!(*render.get::<i32>(ra).unwrap(), 10);
+    assert_eq!(*render.get::<i32>(rb).unwrap(), 3);
+    mirror.sync(&sim, &mut render);
+    assert_eq!(*render.get::<i32>(rb).unwrap(), 3);
+
+    // Newly registered types reach existing counterparts
+    mirror.register::<u8>();
+    mirror.sync(&sim, &mut render);
+    assert_eq!(*render.get::<u8>(ra).unwrap(), 0);
+    assert_eq!(*render.get::<u8>(rb).unwrap(), 0);
+    assert_eq!(*render.get::<i32>(ra).unwrap(), 10);
+
+    // Counterparts despawned from the target are respawned once their source changes
+    render.despawn(ra).unwrap();
+    sim.insert_one(a, true).unwrap();
+    mirror.sync(&sim, &mut render);
+    let ra = mirror.get(a).unwrap();
+    assert_eq!(*render.get::<i32>(ra).unwrap(), 1);
+    assert_eq!(render.entity(ra).unwrap().component_types().len(), 2);
+}
+
+#[test]
+fn change_detection() {
+    fn changes<T: Component>(world: &World, since: u32) -> (Vec<Entity>, Vec<Entity>) {
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for (e, (a, c)) in world.query::<(Added<T>, Changed<T>)>().since(since).iter() {
+            if a {
+                added.push(e);
+            }
+            if c {
+                changed.push(e);
+            }
+        }
+        added.sort();
+        changed.sort();
+        (added, changed)
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, "abc"));
+    let b = world.spawn((2,));
+    let t1 = world.increment_change_tick();
+    assert_eq!(changes::<i32>(&world, 0), (vec![a, b], vec![a, b]));
+    assert_eq!(changes::<i32>(&world, t1), (vec![], vec![]));
+
+    // Moving between archetypes preserves ticks
+    world.insert_one(a, true).unwrap();
+    world.remove_one::<&str>(a).unwrap();
+    for (_, x) in world.query::<&mut i32>().with::<bool>().iter() {
+        *x += 1;
+    }
+    world.insert_one(b, 3).unwrap();
+    let c = world.spawn((4,));
+    // Changes made at the current tick aren't visible until it's incremented
+    assert_eq!(changes::<i32>(&world, t1), (vec![], vec![]));
+    let t2 = world.increment_change_tick();
+    assert_eq!(changes::<i32>(&world, t1), (vec![c], vec![a, b, c]));
+    assert_eq!(changes::<i32>(&world, t2), (vec![], vec![]));
+    assert_eq!(changes::<bool>(&world, t1), (vec![a], vec![a]));
+
+    world.entity(c).unwrap().get_mut::<i32>().unwrap();
+    world.increment_change_tick();
+    assert_eq!(changes::<i32>(&world, t2), (vec![], vec![c]));
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn change_detection_borrow() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.query::<(&mut i32, Changed<i32>)>().iter();
+}
+
+#[test]
+fn observers() {
+    use std::sync::{Arc, Mutex};
+
+    let mut world = World::new();
+    let existing = world.spawn((1,));
+    let added = Arc::new(Mutex::new(Vec::new()));
+    let changed = Arc::new(Mutex::new(Vec::new()));
+    {
+        let added = added.clone();
+        world.observe::<Added<i32>, _>(move |e, &x| added.lock().unwrap().push((e, x)));
+        let changed = changed.clone();
+        world.observe::<Changed<i32>, _>(move |e, &x| changed.lock().unwrap().push((e, x)));
+    }
+
+    let a = world.spawn((2,));
+    *world.get_mut::<i32>(existing).unwrap() = 3;
+    world.flush();
+    added.lock().unwrap().sort();
+    changed.lock().unwrap().sort();
+    assert_eq!(*added.lock().unwrap(), [(a, 2)]);
+    let mut expected = vec![(existing, 3), (a, 2)];
+    expected.sort();
+    assert_eq!(*changed.lock().unwrap(), expected);
+
+    added.lock().unwrap().clear();
+    changed.lock().unwrap().clear();
+    world.flush();
+    assert!(added.lock().unwrap().is_empty());
+    assert!(changed.lock().unwrap().is_empty());
+
+    world.clear_observers();
+    world.spawn((4,));
+    world.flush();
+    assert!(added.lock().unwrap().is_empty());
+}
+
+#[test]
+fn prepared_query() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static CLOCK: AtomicU64 = AtomicU64::new(0);
+    fn timer() -> u64 {
+        CLOCK.fetch_add(10, Ordering::Relaxed)
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let mut query = PreparedQuery::<&mut i32>::new();
+    query.set_timer(timer);
+    for (_, x) in query.query(&world).iter() {
+        *x += 1;
+    }
+    let b = world.spawn((2, "abc"));
+    world.spawn(("def",));
+    let mut entities = query
+        .query(&world)
+        .iter()
+        .map(|(e, &mut x)| (e, x))
+        .collect::<Vec<_>>();
+    entities.sort();
+    assert_eq!(entities, &[(a, 2), (b, 2)]);
+    assert_eq!(
+        *query.stats(),
+        QueryStats {
+            executions: 2,
+            archetypes: 3,
+            rows: 3,
+            elapsed: 20,
+        }
+    );
+    query.reset_stats();
+    assert_eq!(*query.stats(), QueryStats::default());
+}
+
+#[test]
+fn column_checksum() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    fn checksums(world: &World) -> Vec<u64> {
+        world
+            .archetypes()
+            .filter_map(|archetype| {
+                let mut hasher = DefaultHasher::new();
+                if archetype.column_checksum::<i32>(&mut hasher) {
+                    Some(hasher.finish())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    let mut a = World::new();
+    let mut b = World::new();
+    for world in [&mut a, &mut b].iter_mut() {
+        world.spawn((1, "abc"));
+        world.spawn((2, "def"));
+        world.spawn((3,));
+    }
+    assert_eq!(checksums(&a).len(), 2);
+    assert_eq!(checksums(&a), checksums(&b));
+    let e = b.query::<&i32>().iter().find(|&(_, &x)| x == 3).unwrap().0;
+    *b.get_mut::<i32>(e).unwrap() = 4;
+    let (ca, cb) = (checksums(&a), checksums(&b));
+    assert_eq!(ca[0], cb[0]);
+    assert_ne!(ca[1], cb[1]);
+}
+
+#[test]
+fn validate() {
+    let mut world = World::new();
+    let entities = (0..100).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    for &e in entities.iter().step_by(3) {
+        world.despawn(e).unwrap();
+    }
+    let reserved = world.reserve_entity();
+    let mut handles = entities.clone();
+    handles.push(reserved);
+    let live = world.validate(&handles);
+    assert_eq!(live.len(), 2);
+    for (i, &e) in handles.iter().enumerate() {
+        assert_eq!(live[i / 64] & (1 << (i % 64)) != 0, world.contains(e));
+    }
+    assert!(world.validate(&[]).is_empty());
+}
+
+#[test]
+fn modify() {
+    let mut world = World::new();
+    let a = world.spawn((123, true, "abc"));
+    let b = world.spawn((456, false));
+    let mut modification = world.modify(a);
+    modification
+        .insert_one(1.5f32)
+        .insert_one(7)
+        .remove_one::<bool>()
+        .remove_one::<char>()
+        .insert_one('x')
+        .remove_one::<char>();
+    modification.commit().unwrap();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 7);
+    assert_eq!(*world.get::<f32>(a).unwrap(), 1.5);
+    assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+    assert!(world.get::<bool>(a).is_err());
+    assert!(world.get::<char>(a).is_err());
+    assert_eq!(*world.get::<i32>(b).unwrap(), 456);
+    assert!(!*world.get::<bool>(b).unwrap());
+
+    world.despawn(b).unwrap();
+    assert!(world.modify(b).commit().is_err());
+}
+
+#[test]
+fn modify_repeatedly() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    let shared = std::sync::Arc::new(());
+    let mut modification = world.modify(a);
+    for i in 0..1000 {
+        modification
+            .insert_one(shared.clone())
+            .insert_one(i.to_string())
+            .remove_one::<u8>()
+            .insert_one(i as u8);
+        if i % 2 == 0 {
+            modification.remove_one::<String>();
+        }
+    }
+    assert_eq!(std::sync::Arc::strong_count(&shared), 2);
+    modification.commit().unwrap();
+    assert_eq!(std::sync::Arc::strong_count(&shared), 2);
+    assert_eq!(*world.get::<String>(a).unwrap(), "999");
+    assert_eq!(*world.get::<u8>(a).unwrap(), (999 % 256) as u8);
+    world.despawn(a).unwrap();
+    assert_eq!(std::sync::Arc::strong_count(&shared), 1);
+}
+
+#[test]
+fn snapshot() {
+    let mut world = World::new();
+    let mut snapshotter = Snapshotter::new();
+    snapshotter.register::<i32>().register::<String>();
+    let a = world.spawn((1, "a".to_string()));
+    let b = world.spawn((2,));
+    let first = snapshotter.take(&mut world);
+
+    *world.get_mut::<i32>(a).unwrap() = 10;
+    world.despawn(b).unwrap();
+    let c = world.spawn(("c".to_string(),));
+    let second = snapshotter.take(&mut world);
+    // Unchanged since `second`
+    let third = snapshotter.take(&mut world);
+    world
+        .query::<&mut String>()
+        .iter()
+        .for_each(|(_, s)| s.push('!'));
+
+    snapshotter.restore(&first, &mut world);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<String>(a).unwrap(), "a");
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    assert!(!world.contains(c));
+
+    snapshotter.restore(&third, &mut world);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+    assert!(!world.contains(b));
+    assert_eq!(*world.get::<String>(c).unwrap(), "c");
+    drop(second);
+
+    // Handles from before a restore aren't revived
+    let d = world.spawn((4,));
+    snapshotter.restore(&third, &mut world);
+    assert!(!world.contains(d));
+    let e = world.spawn((5,));
+    assert_ne!(e, d);
+    assert!(!world.contains(d));
+    assert_eq!(world.query::<()>().iter().count(), 3);
+}
+
+/// Allocates IDs upwards from 100, tracking which are in use
+#[derive(Default)]
+struct TestAllocator {
+    used: std::sync::Mutex<std::collections::BTreeSet<u32>>,
+}
+
+impl EntityAllocator for TestAllocator {
+    fn allocate(&self) -> u32 {
+        let mut used = self.used.lock().unwrap();
+        let id = (100..).find(|x| !used.contains(x)).unwrap();
+        used.insert(id);
+        id
+    }
+    fn free(&self, id: u32) {
+        assert!(self.used.lock().unwrap().remove(&id));
+    }
+    fn claim(&self, id: u32) {
+        assert!(self.used.lock().unwrap().insert(id));
+    }
+}
+
+#[test]
+fn entity_allocator() {
+    let allocator = std::sync::Arc::new(TestAllocator::default());
+    struct Shared(std::sync::Arc<TestAllocator>);
+    impl EntityAllocator for Shared {
+        fn allocate(&self) -> u32 {
+            self.0.allocate()
+        }
+        fn free(&self, id: u32) {
+            self.0.free(id)
+        }
+        fn claim(&self, id: u32) {
+            self.0.claim(id)
+        }
+    }
+    let used = || {
+        allocator
+            .used
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+    };
+
+    let mut world = World::with_allocator(Shared(allocator.clone()));
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    assert_eq!([a.id(), b.id()], [100, 101]);
+    world.despawn(a).unwrap();
+    assert_eq!(used(), [101]);
+    let c = world.spawn((3,));
+    assert_eq!(c.id(), 100);
+    assert!(!world.contains(a));
+
+    let mut snapshotter = Snapshotter::new();
+    snapshotter.register::<i32>();
+    let snapshot = snapshotter.take(&mut world);
+    world.despawn(b).unwrap();
+    let d = world.spawn((4,));
+    let e = world.spawn((5,));
+    assert_eq!(used(), [100, 101, 102]);
+    assert_eq!(d.id(), 101);
+    snapshotter.restore(&snapshot, &mut world);
+    assert!(!world.contains(d) && !world.contains(e));
+    assert_eq!(used(), [100, 101]);
+
+    world.spawn_at(Entity::from_bits(7), (6,)).unwrap();
+    assert_eq!(used(), [7, 100, 101]);
+    world.clear();
+    assert_eq!(used(), []);
+}
+
+#[test]
+#[should_panic(expected = "custom allocator")]
+fn entity_allocator_reserve() {
+    let world = World::with_allocator(TestAllocator::default());
+    world.reserve_entity();
+}
+
+#[test]
+#[should_panic(expected = "ALLOCATOR_ID_LIMIT")]
+fn entity_allocator_limit() {
+    struct Last;
+    impl EntityAllocator for Last {
+        fn allocate(&self) -> u32 {
+            u32::MAX
+        }
+        fn free(&self, _: u32) {}
+        fn claim(&self, _: u32) {}
+    }
+    World::with_allocator(Last).spawn(());
+}
+
+#[test]
+#[should_panic(expected = "unregistered component type")]
+fn snapshot_unregistered() {
+    let mut world = World::new();
+    world.spawn((true,));
+    Snapshotter::new().take(&mut world);
+}
+
+#[test]
+fn prefetch() {
+    let mut world = World::new();
+    world.spawn_batch((0..100).map(|i| (i, i as f32)));
+    world.spawn((1000, 0.0f32, true));
+    for distance in &[1, 4, 200] {
+        let mut sum = 0;
+        for (_, (&i, x, _)) in world
+            .query::<(&i32, &mut f32, Option<&bool>)>()
+            .prefetch(*distance)
+            .iter()
+        {
+            *x += 1.0;
+            sum += i;
+        }
+        assert_eq!(sum, 1000 + (0..100).sum::<i32>());
+    }
+    let mut query = PreparedQuery::<&i32>::new();
+    assert_eq!(query.query(&world).prefetch(8).iter().count(), 101);
+}
+
+#[test]
+fn removal_sink() {
+    type Sink = Vec<(Entity, String)>;
+    let mut world = World::new();
+    world.add_removal_sink::<String, Sink>(Vec::new());
+    let a = world.spawn(("a".to_string(), 1));
+    let b = world.spawn(("b".to_string(),));
+    let c = world.spawn(("c".to_string(), true));
+    let d = world.spawn(("d".to_string(),));
+
+    world.despawn(a).unwrap();
+    world.insert(b, ("b2".to_string(),)).unwrap();
+    let mut modification = world.modify(c);
+    modification.remove_one::<String>().insert_one(2);
+    modification.commit().unwrap();
+    // Returned to the caller rather than discarded
+    assert_eq!(world.remove_one::<String>(d).unwrap(), "d");
+    let removed = std::mem::take(world.removal_sink_mut::<String, Sink>().unwrap());
+    assert_eq!(
+        removed,
+        vec![
+            (a, "a".to_string()),
+            (b, "b".to_string()),
+            (c, "c".to_string())
+        ]
+    );
+
+    world.clear();
+    assert_eq!(
+        world.removal_sink::<String, Sink>().unwrap(),
+        &[(b, "b2".to_string())]
+    );
+    assert!(world.remove_removal_sink::<String>());
+    assert!(world.removal_sink::<String, Sink>().is_none());
+}
+
+#[test]
+fn join() {
+    struct Parent(Entity);
+    let mut world = World::new();
+    let root = world.spawn(("root", 0));
+    let a = world.spawn(("a", Parent(root)));
+    let b = world.spawn(("b", Parent(a), 2));
+    let mut names = world
+        .join::<Parent, (&&str, Option<&i32>), &&str, _>(|p| p.0)
+        .iter()
+        .map(|(e, (&name, _), parent)| (e, name, parent.copied()))
+        .collect::<Vec<_>>();
+    names.sort_by_key(|x| x.0);
+    assert_eq!(names, &[(a, "a", Some("root")), (b, "b", Some("a"))]);
+}
+
+#[test]
+#[should_panic(expected = "must not write")]
+fn join_write() {
+    struct Parent(Entity);
+    let mut world = World::new();
+    let root = world.spawn((0,));
+    world.spawn((Parent(root),));
+    world.join::<Parent, (), &mut i32, _>(|p| p.0).iter();
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn join_conflict() {
+    struct Parent(Entity);
+    let mut world = World::new();
+    let root = world.spawn((0,));
+    world.spawn((Parent(root), 1));
+    world.join::<Parent, &mut i32, &i32, _>(|p| p.0).iter();
+}
+
+#[test]
+fn grouped() {
+    let mut world = World::new();
+    for i in 0..10u32 {
+        world.spawn(((i % 3) as u8, i));
+        world.spawn(((i % 3) as u8, i, true));
+    }
+    world.spawn((100u8, 0u32));
+    world.spawn((7u32,));
+    let groups = world
+        .query::<&mut u32>()
+        .without::<bool>()
+        .iter_grouped_by::<u8>()
+        .map(|(key, group)| (key, group.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(groups, &[(0, 4), (1, 3), (2, 3), (100, 1)]);
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn grouped_by_written() {
+    let mut world = World::new();
+    world.spawn((0u8,));
+    world.query::<&mut u8>().iter_grouped_by::<u8>().count();
+}
+
+#[test]
+fn shared() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Payload(u32, Arc<AtomicUsize>);
+    impl PartialEq for Payload {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+    impl Eq for Payload {}
+    impl std::hash::Hash for Payload {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+    impl Drop for Payload {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    let x = world.intern(Payload(1, drops.clone()));
+    let y = world.intern(Payload(1, drops.clone()));
+    assert_eq!(x, y);
+    // The duplicate was dropped
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    let z = world.share(Payload(1, drops.clone()));
+    assert_ne!(x, z);
+
+    let a = world.spawn((x, true));
+    let b = world.spawn((y,));
+    world.release_shared(z);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+    world.despawn(a).unwrap();
+    let handle = world.get::<Shared<Payload>>(b).unwrap();
+    assert_eq!(world.get_shared(&handle).unwrap().0, 1);
+    drop(handle);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+    world.insert_one(b, false).unwrap();
+    let handle = world.remove_one::<Shared<Payload>>(b).unwrap();
+    world.release_shared(handle);
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+
+    // Freed slots are reused
+    let w = world.intern(Payload(2, drops.clone()));
+    assert_eq!(world.get_shared(&w).unwrap().0, 2);
+    world.spawn((w,));
+    world.clear();
+    assert_eq!(drops.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn shared_other_world() {
+    let mut a = World::new();
+    let mut b = World::new();
+    let x = a.share(String::from("a"));
+    let y = b.share(String::from("b"));
+    // Both are the first value stored in their worlds
+    assert_eq!(b.get_shared(&y).unwrap(), "b");
+    assert_ne!(x, y);
+    assert_eq!(b.get_shared(&x), None);
+    assert_eq!(b.clone_shared(&x), None);
+    let extra = a.clone_shared(&x).unwrap();
+    assert!(!b.release_shared(extra));
+    b.spawn((a.clone_shared(&x).unwrap(),));
+    b.clear();
+    assert_eq!(b.get_shared(&y).unwrap(), "b");
+    assert_eq!(a.get_shared(&x).unwrap(), "a");
+    assert!(a.release_shared(x));
+    assert_eq!(b.clone_shared(&y), Some(y));
+}
+
+#[test]
+fn spawn_mut() {
+    let mut world = World::new();
+    let a = world
+        .spawn_mut((1, "a"))
+        .insert(true)
+        .insert(2)
+        .remove::<&str>()
+        .remove::<char>()
+        .id();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 2);
+    assert!(*world.get::<bool>(a).unwrap());
+    assert!(world.get::<&str>(a).is_err());
+    {
+        let mut b = world.spawn_mut(());
+        for i in 0..3u8 {
+            b.insert(i);
+        }
+    }
+    assert_eq!(
+        world
+            .query::<&u8>()
+            .iter()
+            .map(|(_, &x)| x)
+            .collect::<Vec<_>>(),
+        &[2]
+    );
+}
+
+#[test]
+fn world_build_entity() {
+    let mut world = World::new();
+    let a = world
+        .build_entity()
+        .add(1)
+        .add("a")
+        .add(true)
+        .add(2)
+        .spawn();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 2);
+    assert_eq!(*world.get::<&str>(a).unwrap(), "a");
+    assert!(*world.get::<bool>(a).unwrap());
+    // Only the empty archetype and the final one exist
+    assert_eq!(world.archetypes().count(), 2);
+    world.build_entity().add(3u8);
+    assert_eq!(world.len(), 1);
+}
+
+#[test]
+fn despawn_all_incremental() {
+    let mut world = World::new();
+    let kept = world.spawn((true,));
+    world.spawn_batch((0..5).map(|i| (i,)));
+    world.spawn_batch((0..5).map(|i| (i, true)));
+    let mut op = DespawnAll::<Without<bool, &i32>>::new();
+    assert!(!op.run_for(&mut world, 0));
+    assert!(!op.run_for(&mut world, 4));
+    assert_eq!(world.query::<&i32>().iter().count(), 6);
+    assert!(op.run_for(&mut world, 1));
+    assert!(op.run_for(&mut world, 1));
+    assert_eq!(world.query::<&i32>().iter().count(), 5);
+
+    let mut clear = DespawnAll::<()>::default();
+    while !clear.run_for(&mut world, 2) {}
+    assert!(!world.contains(kept));
+    assert_eq!(world.iter().count(), 0);
+}
+
+#[test]
+fn spawn_external() {
+    static DATA: [u16; 4] = [1, 2, 3, 4];
+    let mut world = World::new();
+    let owned = world.spawn((5u16,));
+    let entities = world.spawn_external(&DATA);
+    assert_eq!(entities.len(), 4);
+    assert_eq!(
+        world.query::<&u16>().iter().map(|(_, &x)| x).sum::<u16>(),
+        15
+    );
+    assert_eq!(*world.get::<u16>(entities[3]).unwrap(), 4);
+    assert!(world.contains(entities[0]));
+    let mut op = DespawnAll::<()>::new();
+    assert!(op.run_for(&mut world, 10));
+    assert!(!world.contains(owned));
+    assert!(world.contains(entities[0]));
+    world.clear();
+    assert!(world.query::<&u16>().iter().next().is_none());
+}
+
+#[test]
+#[should_panic(expected = "read-only")]
+fn spawn_external_get_mut() {
+    static DATA: [u16; 1] = [1];
+    let mut world = World::new();
+    let entities = world.spawn_external(&DATA);
+    let _ = world.get_mut::<u16>(entities[0]);
+}
+
+#[test]
+#[should_panic(expected = "read-only")]
+fn spawn_external_despawn() {
+    static DATA: [u16; 1] = [1];
+    let mut world = World::new();
+    let entities = world.spawn_external(&DATA);
+    let _ = world.despawn(entities[0]);
+}
+
+#[test]
+fn try_operations() {
+    static DATA: [u16; 1] = [1];
+    let mut world = World::new();
+    let external = world.spawn_external(&DATA)[0];
+    assert_eq!(
+        world.try_get_mut::<u16>(external).err(),
+        Some(WorldError::ReadOnly)
+    );
+    assert_eq!(*world.try_get::<u16>(external).unwrap(), 1);
+    assert_eq!(
+        world.try_insert(external, (true,)),
+        Err(WorldError::ReadOnly)
+    );
+    assert_eq!(world.try_despawn(external), Err(WorldError::ReadOnly));
+
+    let a = world.spawn((123, true));
+    let b = world.spawn((456, true));
+    {
+        let _x = world.try_get_mut::<i32>(a).unwrap();
+        assert!(matches!(
+            world.try_get::<i32>(b),
+            Err(WorldError::Borrow(_))
+        ));
+        assert!(matches!(
+            world.try_get_mut::<i32>(b),
+            Err(WorldError::Borrow(_))
+        ));
+        assert!(world.try_query::<&i32>().is_err());
+        // A failed borrow doesn't leak
+        assert!(world.try_query::<(&bool, &i32)>().is_err());
+        let _q = world.try_query::<&mut bool>().unwrap();
+        assert!(world.try_get::<bool>(a).is_err());
+    }
+    assert!(matches!(
+        world.try_get::<&str>(a),
+        Err(WorldError::MissingComponent(_))
+    ));
+    let mut q = world.try_query::<&mut i32>().unwrap();
+    q.iter().for_each(|(_, x)| *x += 1);
+    drop(q);
+    assert_eq!(*world.try_get::<i32>(b).unwrap(), 457);
+
+    world.try_insert(a, ("abc",)).unwrap();
+    assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+    world.try_despawn(a).unwrap();
+    assert_eq!(world.try_despawn(a), Err(WorldError::NoSuchEntity));
+    assert_eq!(world.try_insert(a, (1,)), Err(WorldError::NoSuchEntity));
+    assert!(matches!(
+        world.try_get::<i32>(a),
+        Err(WorldError::NoSuchEntity)
+    ));
+}
+
+#[test]
+fn try_operations_unknown_entity() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    world.despawn(a).unwrap();
+    // Never allocated, and a free ID under the generation it would next be allocated with
+    let unknown = Entity::from_bits(1000);
+    let free = match world.entity_slot(a.id()) {
+        Some(EntitySlot::Free(x)) => x,
+        x => panic!("unexpected slot {:?}", x),
+    };
+    for entity in [unknown, free] {
+        assert!(matches!(
+            world.try_get::<i32>(entity),
+            Err(WorldError::NoSuchEntity)
+        ));
+        assert!(matches!(
+            world.try_get_mut::<i32>(entity),
+            Err(WorldError::NoSuchEntity)
+        ));
+        assert_eq!(
+            world.try_insert(entity, (1,)),
+            Err(WorldError::NoSuchEntity)
+        );
+        assert_eq!(world.try_despawn(entity), Err(WorldError::NoSuchEntity));
+        assert!(matches!(
+            world.remove_one::<i32>(entity),
+            Err(ComponentError::NoSuchEntity)
+        ));
+        assert!(world.get::<i32>(entity).is_err());
+        assert!(!world.contains(entity));
+    }
+    assert!(world.is_empty());
+}
+
+#[test]
+fn retain_pinned() {
+    struct Pinned(i32);
+    let mut world = World::new();
+    world.pin_component::<Pinned>();
+    let entities = (0..6)
+        .map(|i| world.spawn((Pinned(i),)))
+        .collect::<Vec<_>>();
+    let address = |world: &World| &*world.get::<Pinned>(entities[5]).unwrap() as *const Pinned;
+    let before = address(&world);
+    assert_eq!(
+        world.retain(|_, e| e.get::<Pinned>().unwrap().0 % 2 == 1),
+        3
+    );
+    assert_eq!(world.despawn_where::<Pinned>(|x| x.0 == 1), 1);
+    assert_eq!(world.len(), 2);
+    assert_eq!(address(&world), before);
+}
+
+#[test]
+fn access_conflict() {
+    assert!(access_conflicts::<&i32, &i32>().is_none());
+    assert!(access_conflicts::<&mut i32, &bool>().is_none());
+    assert!(access_conflicts::<With<i32, &bool>, &mut i32>().is_none());
+    let conflict = access_conflicts::<(&bool, Changed<i32>), &mut i32>().unwrap();
+    assert_eq!(conflict.type_id, std::any::TypeId::of::<i32>());
+    assert!(access_conflicts::<Without<bool, &mut i32>, Added<i32>>().is_some());
+
+    let mut system = QueryAccess::new();
+    system.extend(&QueryAccess::of::<&i32>());
+    assert!(system.conflicts(&QueryAccess::of::<&i32>()).is_none());
+    system.extend(&QueryAccess::of::<&mut i32>());
+    assert!(system.conflicts(&QueryAccess::of::<&i32>()).is_some());
+}
+
+#[test]
+fn insert_if_changed() {
+    let mut world = World::new();
+    let a = world.spawn((1, "abc"));
+    let t1 = world.increment_change_tick();
+    assert!(!world.insert_if_changed(a, 1).unwrap());
+    assert!(world.insert_if_changed(a, true).unwrap());
+    world.increment_change_tick();
+    let changed = |world: &World| {
+        world
+            .query::<Changed<i32>>()
+            .since(t1)
+            .iter()
+            .filter(|&(_, c)| c)
+            .count()
+    };
+    assert_eq!(changed(&world), 0);
+    assert!(world.insert_if_changed(a, 2).unwrap());
+    world.increment_change_tick();
+    assert_eq!(changed(&world), 1);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 2);
+    assert!(*world.get::<bool>(a).unwrap());
+
+    world.despawn(a).unwrap();
+    assert_eq!(world.insert_if_changed(a, 3), Err(NoSuchEntity));
+}
+
+#[test]
+fn tags() {
+    let mut world = World::new();
+    let red = world.tag("red");
+    let blue = world.tag("blue");
+    assert_ne!(red, blue);
+    assert_eq!(world.tag("red"), red);
+    assert_eq!(world.find_tag("green"), None);
+
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.reserve_entity();
+    assert!(world.add_tag(a, red).unwrap());
+    assert!(!world.add_tag(a, red).unwrap());
+    assert!(world.add_tag(b, red).unwrap());
+    assert!(world.add_tag(b, blue).unwrap());
+    assert!(world.add_tag(c, blue).unwrap());
+    let mut tagged = world.tagged(red).collect::<Vec<_>>();
+    tagged.sort();
+    assert_eq!(tagged, [a, b]);
+
+    // Tags survive archetype changes
+    world.insert_one(a, true).unwrap();
+    assert!(world.has_tag(a, red));
+
+    assert!(world.remove_tag(b, red).unwrap());
+    assert!(!world.remove_tag(b, red).unwrap());
+    assert!(world.has_tag(b, blue));
+    world.despawn(b).unwrap();
+    assert!(!world.has_tag(b, blue));
+    assert_eq!(world.add_tag(b, blue), Err(NoSuchEntity));
+    assert_eq!(world.tagged(blue).collect::<Vec<_>>(), [c]);
+
+    world.clear();
+    assert!(!world.has_tag(a, red));
+    assert_eq!(world.tagged(blue).count(), 0);
+    assert_eq!(world.tag_name(blue), "blue");
+}
+
+#[test]
+fn names() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    assert_eq!(world.name(a, "a").unwrap(), None);
+    assert_eq!(world.name(b, "b").unwrap(), None);
+    assert_eq!(world.name(a, "a").unwrap(), None);
+    assert_eq!(world.name(a, "c").unwrap(), None);
+    assert_eq!(world.lookup("a"), None);
+    assert_eq!(world.lookup("c"), Some(a));
+    assert_eq!(world.name(a, "b").unwrap(), Some(b));
+    assert_eq!(world.name_of(b), None);
+    assert_eq!(world.names().collect::<Vec<_>>(), [("b", a)]);
+
+    let a2 = world.invalidate_handles(a).unwrap();
+    assert_eq!(world.lookup("b"), Some(a2));
+    assert_eq!(world.name_of(a), None);
+    world.despawn(a2).unwrap();
+    assert_eq!(world.lookup("b"), None);
+    assert!(world.name(a2, "b").is_err());
+
+    assert!(!world.unname(b));
+    world.name(b, "b").unwrap();
+    assert!(world.unname(b));
+    assert_eq!(world.lookup("b"), None);
+
+    world.name(b, "b").unwrap();
+    world.despawn_all::<&i32>();
+    assert_eq!(world.names().count(), 0);
+
+    let mut other = World::new();
+    let c = other.spawn((3,));
+    let d = other.spawn((4,));
+    other.name(c, "c").unwrap();
+    other.name(d, "d").unwrap();
+    let e = world.spawn((5,));
+    world.name(e, "c").unwrap();
+    let map = world.merge(other);
+    assert_eq!(world.lookup("c"), Some(e));
+    assert_eq!(world.lookup("d"), map.get(d));
+    world.clear();
+    assert_eq!(world.lookup("c"), None);
+}
+
+#[test]
+fn write_column() {
+    let mut world = World::new();
+    let ents = (0..4).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    let other = world.spawn((10, true));
+    let t1 = world.increment_change_tick();
+    world
+        .write_column(&[ents[1], ents[2], other, ents[0]], &[21, 22, 23, 24])
+        .unwrap();
+    let mut values = world
+        .query::<&i32>()
+        .iter()
+        .map(|(e, &x)| (e, x))
+        .collect::<Vec<_>>();
+    values.sort();
+    assert_eq!(
+        values,
+        [
+            (ents[0], 24),
+            (ents[1], 21),
+            (ents[2], 22),
+            (ents[3], 3),
+            (other, 23)
+        ]
+    );
+    world.increment_change_tick();
+    let changed = world
+        .query::<Changed<i32>>()
+        .since(t1)
+        .iter()
+        .filter(|&(_, c)| c)
+        .count();
+    assert_eq!(changed, 4);
+
+    world.despawn(ents[3]).unwrap();
+    assert_eq!(
+        world.write_column(&[ents[0], ents[3]], &[0, 0]),
+        Err(ComponentError::NoSuchEntity)
+    );
+    assert!(world.write_column(&[ents[0]], &[true]).is_err());
+    assert_eq!(*world.get::<i32>(ents[0]).unwrap(), 24);
+}
+
+#[test]
+fn split_query() {
+    let mut world = World::new();
+    let a = world.spawn((1, 10u32, true));
+    let b = world.spawn((2, 20u32));
+    {
+        let (read, mut write) = world
+            .query::<(&i32, &mut u32, &bool)>()
+            .split::<(&i32, Option<&bool>), &mut u32>();
+        assert_eq!(read.iter().count(), 2);
+        assert_eq!(
+            read.get(a).map(|(&x, y)| (x, y.copied())),
+            Some((1, Some(true)))
+        );
+        for (e, x) in write.iter() {
+            let (&y, flag) = read.get(e).unwrap();
+            *x += y as u32 + flag.is_some() as u32;
+        }
+        // Shared reads of the read half's components remain possible
+        assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    }
+    assert_eq!(*world.get::<u32>(a).unwrap(), 12);
+    assert_eq!(*world.get::<u32>(b).unwrap(), 22);
+    // Borrows were released
+    world.query::<(&mut i32, &mut u32)>().iter();
+}
+
+#[test]
+#[should_panic(expected = "must not write")]
+fn split_query_read_half_writes() {
+    let world = World::new();
+    world.query::<&mut i32>().split::<&mut i32, ()>();
+}
+
+#[test]
+#[should_panic(expected = "incompatibly with the original")]
+fn split_query_excess() {
+    let world = World::new();
+    world.query::<(&i32, &u32)>().split::<&i32, &mut u32>();
+}
+
+#[test]
+#[should_panic(expected = "halves both borrow")]
+fn split_query_conflict() {
+    let world = World::new();
+    world.query::<&mut i32>().split::<&i32, &mut i32>();
+}
+
+#[test]
+fn borrow_policy() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world.spawn((2, "abc"));
+    world.spawn((3,));
+    world.set_borrow_policy(BorrowPolicy::Error);
+    let mut held = world.query::<&mut bool>();
+    let _held = held.iter();
+    {
+        let mut q = world.query::<(&i32, Option<&bool>)>();
+        let err = q.try_iter().err().unwrap();
+        assert_eq!(err.type_name(), std::any::type_name::<bool>());
+        assert!(!err.is_unique());
+        assert_eq!(err.to_string(), "bool already borrowed uniquely");
+    }
+    // Failed queries release everything they borrowed
+    assert_eq!(world.query::<&mut i32>().try_iter().unwrap().count(), 3);
+
+    let mut q = world
+        .query::<(&i32, &bool)>()
+        .borrow_policy(BorrowPolicy::Skip);
+    assert_eq!(q.iter().len(), 0);
+    assert_eq!(q.skipped().count(), 1);
+    drop(q);
+    let mut q = world
+        .query::<(&mut i32, Option<&bool>)>()
+        .borrow_policy(BorrowPolicy::Skip);
+    let mut values = q.iter().map(|(_, (&mut x, _))| x).collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, [2, 3]);
+    let mut q = world
+        .query::<(&i32, Option<&bool>)>()
+        .borrow_policy(BorrowPolicy::Skip);
+    assert_eq!(q.iter_batched(1).flatten().count() + q.skipped().len(), 3);
+}
+
+#[test]
+#[cfg(feature = "plugin")]
+fn plugin_vtable() {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    #[repr(C)]
+    struct Position([f32; 2]);
+
+    let mut host = PluginHost::new();
+    host.register::<Position>("Position")
+        .register::<u32>("Score");
+    let mut world = World::new();
+    let existing = world.spawn((Position([1.0, 2.0]), 5u32));
+    let other = world.spawn((Position([3.0, 4.0]),));
+
+    let mut vt = host.vtable(&mut world);
+    assert_eq!(vt.component("Missing"), None);
+    assert_eq!(vt.component_of::<u64>("Score"), None);
+    let pos = vt.component_of::<Position>("Position").unwrap();
+    let score = vt.component_of::<u32>("Score").unwrap();
+
+    let e = vt.spawn();
+    assert!(vt.contains(e));
+    unsafe {
+        assert!(vt.insert(e, pos, Position([5.0, 6.0])));
+        assert!(vt.insert(e, score, 7u32));
+        assert_eq!(
+            *vt.get(existing, pos).unwrap().cast::<Position>().as_ptr(),
+            Position([1.0, 2.0])
+        );
+        assert!(vt.get(other, score).is_none());
+        *vt.get_mut(other, pos).unwrap().cast::<Position>().as_ptr() = Position([0.0, 0.0]);
+    }
+
+    let mut total = 0;
+    assert!(vt.query(
+        &[QueryTerm::shared(pos), QueryTerm::unique(score)],
+        |_, components| unsafe {
+            let score = &mut *components[1].cast::<u32>();
+            *score += 1;
+            total += *score;
+        }
+    ));
+    assert_eq!(total, 14);
+    assert!(!vt.query(
+        &[QueryTerm::unique(score), QueryTerm::shared(score)],
+        |_, _| panic!()
+    ));
+
+    unsafe {
+        assert_eq!(vt.remove::<u32>(existing, score), Some(6));
+        assert_eq!(vt.remove::<u32>(existing, score), None);
+    }
+    assert!(vt.despawn(other));
+    assert!(!vt.despawn(other));
+
+    assert_eq!(*world.get::<u32>(e).unwrap(), 8);
+    assert!(world.get::<u32>(existing).is_err());
+    assert_eq!(
+        *world.get::<Position>(existing).unwrap(),
+        Position([1.0, 2.0])
+    );
+    assert!(!world.contains(other));
+    // Borrows were released
+    world.query::<(&mut Position, &mut u32)>().iter();
+}
+
+#[test]
+fn migrate_component() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::Arc;
+
+    let mut world = World::new();
+    let a = world.spawn((1u8, "a"));
+    let b = world.spawn((2u8, 20u32, "b"));
+    let c = world.spawn((3u8,));
+    let d = world.spawn(("d",));
+    world.add_removal_sink::<u32, _>(Vec::new());
+    world.migrate_component(|x: u8| u32::from(x) * 10);
+    assert_eq!(world.query::<&u8>().iter().count(), 0);
+    assert_eq!(*world.get::<u32>(a).unwrap(), 10);
+    assert_eq!(*world.get::<u32>(b).unwrap(), 20);
+    assert_eq!(*world.get::<u32>(c).unwrap(), 30);
+    assert!(world.get::<u32>(d).is_err());
+    assert_eq!(*world.get::<&str>(b).unwrap(), "b");
+    assert_eq!(
+        world.removal_sink::<u32, Vec<(Entity, u32)>>().unwrap(),
+        &[(b, 20)]
+    );
+
+    // Panics despawn the affected entity without leaking or double-dropping
+    let tracker = Arc::new(());
+    let e = world.spawn((Arc::clone(&tracker), 1i16));
+    world.spawn((Arc::clone(&tracker), 2i16));
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        world.migrate_component(|x: i16| {
+            if x == 2 {
+                panic!("migration failed");
+            }
+            i64::from(x)
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(world.query::<&Arc<()>>().iter().count(), 1);
+    assert_eq!(Arc::strong_count(&tracker), 2);
+    assert_eq!(*world.get::<i16>(e).unwrap(), 1);
+    world.migrate_component(|x: i16| i64::from(x));
+    assert_eq!(*world.get::<i64>(e).unwrap(), 1);
+    world.clear();
+    assert_eq!(Arc::strong_count(&tracker), 1);
+}
+
+#[test]
+fn transform() {
+    let mut world = World::new();
+    let ents = (0..10)
+        .map(|i| {
+            if i % 2 == 0 {
+                world.spawn((i,))
+            } else {
+                world.spawn((i, true))
+            }
+        })
+        .collect::<Vec<_>>();
+    world.spawn((false,));
+    let t1 = world.increment_change_tick();
+    let mut visited = 0;
+    world.transform(|x: &mut i32| {
+        *x += 100;
+        visited += 1;
+    });
+    assert_eq!(visited, 10);
+    for (i, &e) in ents.iter().enumerate() {
+        assert_eq!(*world.get::<i32>(e).unwrap(), i as i32 + 100);
+    }
+    world.increment_change_tick();
+    let changed = world
+        .query::<Changed<i32>>()
+        .since(t1)
+        .iter()
+        .filter(|&(_, c)| c)
+        .count();
+    assert_eq!(changed, 10);
+}
+
+#[test]
+fn prepared_query_cursor() {
+    let mut world = World::new();
+    let ents = (0..6).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    let mut query = PreparedQuery::<&i32>::new();
+
+    let mut borrow = query.query(&world);
+    let mut iter = borrow.iter_from(QueryCursor::default());
+    let first = iter.by_ref().take(2).map(|(_, &x)| x).collect::<Vec<_>>();
+    let cursor = iter.cursor();
+    drop(borrow);
+    assert_eq!(first, [0, 1]);
+
+    // Unchanged archetypes resume where they left off
+    let rest = query
+        .query(&world)
+        .iter_from(cursor)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(rest, [2, 3, 4, 5]);
+
+    // Modified archetypes are restarted
+    world.despawn(ents[0]).unwrap();
+    let mut restarted = query
+        .query(&world)
+        .iter_from(cursor)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    restarted.sort_unstable();
+    assert_eq!(restarted, [1, 2, 3, 4, 5]);
+
+    // A finished pass only visits new archetypes
+    let mut borrow = query.query(&world);
+    let mut iter = borrow.iter();
+    assert_eq!(iter.by_ref().count(), 5);
+    let end = iter.cursor();
+    drop(borrow);
+    world.spawn((7, true));
+    let new = query
+        .query(&world)
+        .iter_from(end)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(new, [7]);
+}
+
+#[test]
+fn ensure_archetype() {
+    let mut world = World::new();
+    world.ensure_archetype::<(i32, &str)>();
+    world.ensure_archetype_dynamic(&[
+        TypeInfo::of::<bool>(),
+        TypeInfo::of::<i32>(),
+        TypeInfo::of::<bool>(),
+    ]);
+    let generation = world.archetypes_generation();
+    world.ensure_archetype::<(&str, i32)>();
+    world.ensure_archetype::<(i32, bool)>();
+    assert_eq!(world.archetypes_generation(), generation);
+    let a = world.spawn(("abc", 1));
+    let b = world.spawn((true, 2));
+    assert_eq!(world.archetypes_generation(), generation);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert!(*world.get::<bool>(b).unwrap());
+    world.ensure_archetype::<(f32,)>();
+    assert_ne!(world.archetypes_generation(), generation);
+}
+
+#[test]
+fn pinned_component() {
+    struct Pinned(u64);
+    let mut world = World::new();
+    world.pin_component::<Pinned>();
+    let entities = (0..4)
+        .map(|i| world.spawn((Pinned(i), i as i32)))
+        .collect::<Vec<_>>();
+    let address = |world: &World, e| &*world.get::<Pinned>(e).unwrap() as *const Pinned;
+    let before = entities
+        .iter()
+        .map(|&e| address(&world, e))
+        .collect::<Vec<_>>();
+    world.despawn(entities[0]).unwrap();
+    world.insert_one(entities[1], true).unwrap();
+    world.remove_one::<i32>(entities[2]).unwrap();
+    world.exchange::<(i32,)>(entities[3], ("abc",)).unwrap();
+    for i in 1..4 {
+        assert_eq!(address(&world, entities[i]), before[i]);
+        assert_eq!(world.get::<Pinned>(entities[i]).unwrap().0, i as u64);
+    }
+    assert!(*world.get::<bool>(entities[1]).unwrap());
+    assert_eq!(*world.get::<&str>(entities[3]).unwrap(), "abc");
+
+    // Entities with the same components share an archetype, however many there are
+    let archetypes = world.archetypes().len();
+    let more = world
+        .spawn_batch((4..200).map(|i| (Pinned(i), i as i32)))
+        .collect::<Vec<_>>();
+    world.spawn((Pinned(200), 200));
+    assert_eq!(world.archetypes().len(), archetypes);
+    assert_eq!(world.query::<&Pinned>().iter().count(), 200);
+    for (_, (x, &i)) in world.query::<(&mut Pinned, &i32)>().iter() {
+        assert_eq!(x.0, i as u64);
+        x.0 += 1;
+    }
+    let before = address(&world, more[100]);
+    for &e in &more[..100] {
+        world.despawn(e).unwrap();
+    }
+    world.shrink_to_fit();
+    assert_eq!(address(&world, more[100]), before);
+    assert_eq!(world.get::<Pinned>(more[100]).unwrap().0, 105);
+
+    // Unpinned components are unaffected
+    let c = world.spawn((1,));
+    world.spawn((2,));
+    world.despawn(c).unwrap();
+}
+
+#[test]
+fn despawn_many() {
+    let mut world = World::new();
+    let ints = world.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    let pairs = world
+        .spawn_batch((0..10).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    let doomed = [
+        ints[3], pairs[9], ints[0], pairs[0], ints[9], ints[3], pairs[5],
+    ];
+    assert_eq!(world.despawn_many(&doomed), 6);
+    assert_eq!(world.despawn_many(&doomed), 0);
+    for &e in ints.iter().chain(&pairs) {
+        assert_eq!(world.contains(e), !doomed.contains(&e));
+        if world.contains(e) {
+            let i = world.get::<i32>(e).unwrap();
+            assert!(e == ints[*i as usize] || e == pairs[*i as usize]);
+        }
+    }
+    assert_eq!(world.query::<&i32>().iter().count(), 14);
+}
+
+#[test]
+fn query_page() {
+    let mut world = World::new();
+    world.spawn_batch((0..10).map(|i| (i,)));
+    world.spawn_batch((10..15).map(|i| (i, true)));
+    world.spawn(("abc",));
+    world.spawn_batch((15..30).map(|i| (i, 'x')));
+    let all = world
+        .query::<&i32>()
+        .iter()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    for offset in 0..32 {
+        for limit in 0..20 {
+            let mut query = world.query::<&i32>();
+            let page = query.iter_page(offset, limit);
+            assert_eq!(page.len(), all.len().saturating_sub(offset).min(limit));
+            let page = page.map(|(_, &x)| x).collect::<Vec<_>>();
+            let expected = all
+                .iter()
+                .copied()
+                .skip(offset)
+                .take(limit)
+                .collect::<Vec<_>>();
+            assert_eq!(page, expected);
+        }
+    }
+    let mut query = world.query::<&i32>();
+    let mut iter = query.iter();
+    assert_eq!(iter.nth(3).map(|(_, &x)| x), Some(all[3]));
+    assert_eq!(iter.nth(8).map(|(_, &x)| x), Some(all[12]));
+    assert_eq!(iter.len(), all.len() - 13);
+    assert!(iter.nth(all.len()).is_none());
+}
+
+#[test]
+fn frame_allocator() {
+    let mut world = World::new();
+    let frame = world.frame_allocator();
+    assert_eq!(frame.allocated(), 0);
+    let x = frame.alloc(42u64);
+    let xs = frame.alloc_from_iter((0..10).filter(|x| x % 2 == 0));
+    let ys = frame.alloc_from_iter((0u8..).take_while(|&x| x < 3));
+    assert_eq!(*x, 42);
+    assert_eq!(xs, [0, 2, 4, 6, 8]);
+    assert_eq!(ys, [0, 1, 2]);
+    assert!(frame.allocated() >= 8 + 10 * 4 + 3);
+    world.flush();
+    assert_eq!(world.frame_allocator().allocated(), 0);
+    let capacity = world.frame_allocator().capacity();
+    assert!(capacity > 0);
+
+    // Steady state fits without growing
+    for _ in 0..3 {
+        let frame = world.frame_allocator();
+        frame.alloc(42u64);
+        frame.alloc_from_iter((0..10).filter(|x| x % 2 == 0));
+        frame.alloc_from_iter((0u8..).take_while(|&x| x < 3));
+        world.flush();
+        assert_eq!(world.frame_allocator().capacity(), capacity);
+    }
+}
+
+#[test]
+fn grouped_by_drops_keys() {
+    use std::sync::Arc;
+
+    let key = Arc::new(0u32);
+    let mut world = World::new();
+    for i in 0..10 {
+        world.spawn((key.clone(), i));
+    }
+    world.spawn((Arc::new(1u32), 10));
+    let mut query = world.query::<&i32>();
+    let mut groups = query.iter_grouped_by::<Arc<u32>>();
+    let (first, group) = groups.next().unwrap();
+    assert_eq!(*first, 0);
+    assert_eq!(group.len(), 10);
+    drop(first);
+    drop(groups);
+    drop(query);
+    assert_eq!(Arc::strong_count(&key), 11);
+}
+
+#[test]
+fn snapshot_copy() {
+    let mut world = World::new();
+    let mut snapshotter = Snapshotter::new();
+    snapshotter
+        .register_copy::<i32>()
+        .register_copy::<bool>()
+        .register::<String>();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, false));
+    let c = world.spawn((3, "c".to_string()));
+    let snapshot = snapshotter.take(&mut world);
+
+    *world.get_mut::<i32>(a).unwrap() = 10;
+    *world.get_mut::<i32>(c).unwrap() = 30;
+    world.despawn(b).unwrap();
+    world.get_mut::<String>(c).unwrap().push('!');
+    world.increment_change_tick();
+
+    snapshotter.restore(&snapshot, &mut world);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert!(*world.get::<bool>(a).unwrap());
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    assert!(!*world.get::<bool>(b).unwrap());
+    assert_eq!(*world.get::<i32>(c).unwrap(), 3);
+    assert_eq!(*world.get::<String>(c).unwrap(), "c");
+}
+
+#[test]
+fn clear_copy_with_sink() {
+    type Sink = Vec<(Entity, i32)>;
+    let mut world = World::new();
+    world.add_removal_sink::<i32, Sink>(Vec::new());
+    let a = world.spawn((1, true));
+    world.clear();
+    assert_eq!(world.removal_sink::<i32, Sink>().unwrap(), &[(a, 1)]);
+}
+
+#[test]
+fn entity_size() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    let b = world.spawn((1u64, true, ()));
+    let a = world.entity(a).unwrap();
+    assert_eq!(a.component_count(), 0);
+    assert_eq!(a.bytes(), 0);
+    let b = world.entity(b).unwrap();
+    assert_eq!(b.component_count(), 3);
+    assert_eq!(b.bytes(), 9);
+}
+
+#[test]
+fn downgrade_ref_mut() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true));
+    let mut x = world.get_mut::<i32>(a).unwrap();
+    *x += 10;
+    let x = RefMut::downgrade(x);
+    let y = world.get::<i32>(b).unwrap();
+    assert_eq!((*x, *y), (11, 2));
+    drop(x);
+    drop(y);
+    *world.get_mut::<i32>(b).unwrap() = 3;
+    assert_eq!(*world.get::<i32>(b).unwrap(), 3);
+}
+
+#[test]
+fn graveyard() {
+    let mut world = World::new();
+    world.add_removal_sink::<u32, _>(Vec::<(Entity, u32)>::new());
+    world.keep_despawned(true);
+    let a = world.spawn((1u32, true));
+    let b = world.spawn((2u32,));
+    let c = world.spawn((3u32, true));
+    world.despawn(a).unwrap();
+    world.despawn(b).unwrap();
+    let removed = |world: &World| {
+        let mut x = world
+            .removal_sink::<u32, Vec<(Entity, u32)>>()
+            .unwrap()
+            .clone();
+        x.sort_unstable_by_key(|&(_, x)| x);
+        x
+    };
+    assert!(removed(&world).is_empty());
+
+    let graveyard = world.graveyard().unwrap();
+    assert_eq!(graveyard.len(), 2);
+    assert!(graveyard.contains(a));
+    assert!(!graveyard.contains(c));
+    assert_eq!(*graveyard.get::<u32>(b).unwrap(), 2);
+    assert!(graveyard.get::<bool>(b).is_none());
+    let mut dead = graveyard
+        .query::<&u32>()
+        .iter()
+        .map(|(e, &x)| (e, x))
+        .collect::<Vec<_>>();
+    dead.sort_unstable_by_key(|&(_, x)| x);
+    assert_eq!(dead, [(a, 1), (b, 2)]);
+    assert_eq!(*world.get::<u32>(c).unwrap(), 3);
+
+    world.flush();
+    assert!(world.graveyard().unwrap().is_empty());
+    assert_eq!(removed(&world), [(a, 1), (b, 2)]);
+
+    world.despawn(c).unwrap();
+    world.keep_despawned(false);
+    assert!(world.graveyard().is_none());
+    assert_eq!(removed(&world), [(a, 1), (b, 2), (c, 3)]);
+}
+
+#[test]
+fn component_handle() {
+    let mut world = World::new();
+    let a = world.spawn((1u32, true));
+    let b = world.spawn((2u32,));
+    assert!(world.component_handle::<i8>(a).is_err());
+    let ha = world.component_handle::<u32>(a).unwrap();
+    let hb = world.component_handle::<u32>(b).unwrap();
+    assert_eq!(ha.entity(), a);
+    assert_eq!(*world.get_by_handle(ha).unwrap(), 1);
+    assert_eq!(*world.get_by_handle(hb).unwrap(), 2);
+
+    // Moves between and within archetypes, and reallocations, are followed
+    world.remove_one::<bool>(a).unwrap();
+    assert_eq!(*world.get_by_handle(ha).unwrap(), 1);
+    world.spawn_batch((0..1000u32).map(|x| (x + 10,)));
+    world.despawn(b).unwrap();
+    *world.get_mut_by_handle(ha).unwrap() += 10;
+    assert_eq!(*world.get::<u32>(a).unwrap(), 11);
+    assert!(world.get_by_handle(hb).is_err());
+
+    world.remove_one::<u32>(a).unwrap();
+    assert!(world.get_by_handle(ha).is_err());
+    world.insert_one(a, 3u32).unwrap();
+    assert_eq!(*world.get_by_handle(ha).unwrap(), 3);
+
+    assert!(world.release_handle(ha));
+    assert!(!world.release_handle(ha));
+    let c = world.spawn((4u32,));
+    let hc = world.component_handle::<u32>(c).unwrap();
+    assert!(world.get_by_handle(ha).is_err());
+    assert_eq!(*world.get_by_handle(hc).unwrap(), 4);
+}
+
+#[test]
+fn query_columns() {
+    let mut world = World::new();
+    let a = world.spawn((1u32, true));
+    let b = world.spawn((2u32,));
+    let c = world.spawn((3u32, false));
+    world.spawn(("empty",));
+    let tick = world.increment_change_tick();
+    let mut seen = Vec::new();
+    for (entities, (values, flags)) in world.query::<(&mut u32, Option<&bool>)>().iter_columns() {
+        assert_eq!(entities.len(), values.len());
+        for (i, value) in values.iter_mut().enumerate() {
+            *value *= 10;
+            seen.push((entities[i], *value, flags.map(|x| x[i])));
+        }
+    }
+    seen.sort_unstable_by_key(|x| x.1);
+    assert_eq!(
+        seen,
+        [(a, 10, Some(true)), (b, 20, None), (c, 30, Some(false))]
+    );
+    world.increment_change_tick();
+
+    let changed = world
+        .query::<Changed<u32>>()
+        .since(tick)
+        .iter_columns()
+        .map(|(_, flags)| flags.iter().filter(|&x| x).count())
+        .sum::<usize>();
+    assert_eq!(changed, 3);
+    assert_eq!(
+        world
+            .query::<&&str>()
+            .iter_columns()
+            .map(|(e, _)| e.len())
+            .collect::<Vec<_>>(),
+        [1]
+    );
+}
+
+#[test]
+fn double_buffer() {
+    let mut world = World::new();
+    let existing = world.spawn(("x".to_string(),));
+    world.double_buffer::<String>();
+    world.double_buffer::<String>();
+    assert_eq!(world.previous::<String>(existing).unwrap(), "x");
+    let a = world.spawn(("a".to_string(), true));
+    let b = world.spawn(("b".to_string(),));
+    assert_eq!(world.previous::<String>(a).unwrap(), "a");
+    assert!(world.previous::<bool>(a).is_err());
+
+    for (_, (current, previous)) in world.query::<(&mut String, Previous<String>)>().iter() {
+        current.push_str(previous);
+    }
+    assert_eq!(*world.get::<String>(a).unwrap(), "aa");
+    assert_eq!(world.previous::<String>(a).unwrap(), "a");
+    world.flush();
+    assert_eq!(world.previous::<String>(a).unwrap(), "aa");
+    assert_eq!(world.previous::<String>(b).unwrap(), "bb");
+
+    // Previous values follow entities through structural changes
+    world.get_mut::<String>(a).unwrap().push('!');
+    world.remove_one::<bool>(a).unwrap();
+    world.insert_one(b, 1u32).unwrap();
+    world.despawn(existing).unwrap();
+    assert_eq!(world.previous::<String>(a).unwrap(), "aa");
+    assert_eq!(world.previous::<String>(b).unwrap(), "bb");
+    let mut previous = world
+        .query::<Previous<String>>()
+        .iter()
+        .map(|(_, x)| x.clone())
+        .collect::<Vec<_>>();
+    previous.sort();
+    assert_eq!(previous, ["aa", "bb"]);
+
+    // Replacing the component leaves the previous value alone, removing it discards it
+    world.insert_one(b, "c".to_string()).unwrap();
+    assert_eq!(world.previous::<String>(b).unwrap(), "bb");
+    world.remove_one::<String>(b).unwrap();
+    assert!(world.previous::<String>(b).is_err());
+    world.insert_one(b, "d".to_string()).unwrap();
+    assert_eq!(world.previous::<String>(b).unwrap(), "d");
+
+    let mut registry = CloneRegistry::new();
+    registry.register::<String>().register_copy::<u32>();
+    let clone = world.clone_with(&registry);
+    assert_eq!(clone.previous::<String>(a).unwrap(), "aa");
+    world.flush();
+    assert_eq!(world.previous::<String>(a).unwrap(), "aa!");
+}
+
+#[test]
+fn double_buffer_pinned() {
+    let mut world = World::new();
+    world.pin_component::<u32>();
+    world.double_buffer::<u32>();
+    let a = world.spawn((1u32,));
+    let b = world.spawn((2u32,));
+    let address = |world: &World| &*world.get::<u32>(a).unwrap() as *const u32;
+    let before = address(&world);
+    *world.get_mut::<u32>(a).unwrap() = 3;
+    world.flush();
+    assert_eq!(address(&world), before);
+    assert_eq!(*world.previous::<u32>(a).unwrap(), 3);
+    assert_eq!(*world.previous::<u32>(b).unwrap(), 2);
+}
+
+#[test]
+fn entity_limits() {
+    let mut world = World::new();
+    world.set_entity_limit(Some(3));
+    world.set_archetype_limit::<(u32,)>(Some(1));
+    world.spawn((1u32,));
+    let error = world.try_spawn((2u32,)).unwrap_err();
+    assert!(error.is_archetype_limit());
+    assert_eq!(error.limit(), 1);
+    let b = world.spawn((2u32, true));
+    world.spawn(());
+    let error = world.try_spawn(("abc",)).unwrap_err();
+    assert!(!error.is_archetype_limit());
+    assert_eq!(error.limit(), 3);
+
+    // Entering a limited archetype by other means isn't refused
+    world.remove_one::<bool>(b).unwrap();
+    assert_eq!(world.query::<&u32>().iter().count(), 2);
+
+    world.set_entity_limit(None);
+    world.set_archetype_limit::<(u32,)>(None);
+    world.spawn((3u32,));
+}
+
+#[test]
+#[should_panic(expected = "entity limit of 2 reached")]
+fn entity_limit_batch() {
+    let mut world = World::new();
+    world.set_entity_limit(Some(2));
+    world.spawn_batch((0..3).map(|i| (i,))).for_each(drop);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn testing_inspector() {
+    use hecs::testing::Inspector;
+
+    let (mut left, entities) = hecs::test_world![(1u32, true), (2u32,), ("abc",)];
+    let (right, _) = hecs::test_world![(1u32, false), (2u32, 'x')];
+    let mut inspector = Inspector::new();
+    inspector.register::<u32>().register::<bool>();
+    inspector.assert_components::<(bool, u32)>(&left, entities[0]);
+    assert_eq!(
+        inspector.describe(&left, entities[0]).unwrap(),
+        if std::any::TypeId::of::<u32>() < std::any::TypeId::of::<bool>() {
+            "{u32: 1, bool: true}"
+        } else {
+            "{bool: true, u32: 1}"
+        }
+    );
+
+    let diff = inspector
+        .diff(&left, &right)
+        .iter()
+        .map(|x| (x.entity(), x.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(diff.len(), 3);
+    assert_eq!(diff[0].0, entities[0]);
+    assert!(diff[0]
+        .1
+        .ends_with("bool is true on the left but false on the right"));
+    assert!(diff[1].1.contains("is missing on the left"));
+    assert!(diff[2].1.ends_with("is missing on the right"));
+    assert_eq!(diff[2].0, entities[2]);
+
+    left.despawn(entities[2]).unwrap();
+    left.insert_one(entities[0], false).unwrap();
+    inspector.register::<char>();
+    left.insert_one(entities[1], 'x').unwrap();
+    inspector.assert_eq(&left, &right);
+}
+
+#[test]
+fn export_columns() {
+    let mut world = World::new();
+    let a = world.spawn((1u32, [1.0f32, 2.0]));
+    world.spawn((2u32,));
+    let c = world.spawn((3u32, [3.0f32, 4.0], true));
+    let mut exporter = ColumnExporter::new();
+    exporter
+        .column("id", |&x: &u32| u64::from(x))
+        .column("pos", |&x: &[f32; 2]| x);
+    let columns = exporter.export(&world);
+    assert_eq!(columns.len(), 2);
+    assert_eq!(columns.names(), ["id", "pos"]);
+    let mut rows = columns
+        .entities()
+        .iter()
+        .zip(columns.get::<u64>("id").unwrap())
+        .zip(columns.get::<[f32; 2]>("pos").unwrap())
+        .map(|((&e, &id), &pos)| (e, id, pos))
+        .collect::<Vec<_>>();
+    rows.sort_unstable_by_key(|x| x.1);
+    assert_eq!(rows, [(a, 1, [1.0, 2.0]), (c, 3, [3.0, 4.0])]);
+    assert_eq!(columns.bytes("pos").unwrap().len(), 16);
+    assert!(columns.get::<u32>("id").is_none());
+    assert!(columns.bytes("missing").is_none());
+}
+
+#[test]
+fn access_conflicts_per_archetype() {
+    let mut world = World::new();
+    world.spawn((1i32, true));
+    world.spawn((2i32, 'x'));
+    assert!(access_conflicts_in::<With<bool, &mut i32>, With<char, &mut i32>>(&world).is_none());
+    assert!(access_conflicts_in::<Without<char, &mut i32>, &i32>(&world).is_some());
+    // Optional borrows only conflict where the component is present
+    assert!(access_conflicts_in::<(&bool, &mut i32), (&char, Option<&i32>)>(&world).is_none());
+
+    let mut system = QueryAccess::of::<With<bool, &mut i32>>();
+    system.extend(&QueryAccess::of::<&char>());
+    assert!(system
+        .conflicts_in(&QueryAccess::of::<With<char, &i32>>(), &world)
+        .is_none());
+    assert!(system
+        .conflicts_in(&QueryAccess::of::<&mut char>(), &world)
+        .is_some());
+
+    world.spawn((3i32, true, 'y'));
+    assert!(access_conflicts_in::<With<bool, &mut i32>, With<char, &mut i32>>(&world).is_some());
+}
+
+#[test]
+fn cached_query() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let mut sum = CachedQuery::<&u32, u32>::new(move |iter| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        iter.map(|(_, &x)| x).sum()
+    });
+    let mut world = World::new();
+    let a = world.spawn((1u32,));
+    world.spawn((2u32, true));
+    assert_eq!(*sum.get(&world), 3);
+    assert_eq!(*sum.get(&world), 3);
+    assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+    // Unrelated changes don't invalidate
+    world.spawn(("abc",));
+    for (_, x) in world.query::<&mut bool>().iter() {
+        *x = false;
+    }
+    assert_eq!(*sum.get(&world), 3);
+    assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+    for (_, x) in world.query::<&mut u32>().with::<bool>().iter() {
+        *x = 5;
+    }
+    assert_eq!(*sum.get(&world), 6);
+    world.spawn((10u32, 'x'));
+    assert_eq!(*sum.get(&world), 16);
+    world.despawn(a).unwrap();
+    assert_eq!(*sum.get(&world), 15);
+    assert_eq!(runs.load(Ordering::Relaxed), 4);
+    sum.invalidate();
+    assert_eq!(*sum.get(&world), 15);
+    assert_eq!(runs.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+#[should_panic(expected = "cached queries must not borrow components uniquely")]
+fn cached_query_mutable() {
+    CachedQuery::<&mut u32, ()>::new(|_| ());
+}
+
+#[test]
+fn reserve_concurrent() {
+    let mut world = World::new();
+    let a = world.spawn((1u32,));
+    world.despawn(a).unwrap();
+    let existing = (0..10u32).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    let reserved = {
+        let world = &world;
+        // Reserve while a query is partway through iterating
+        let mut borrow = world.query::<&mut u32>();
+        let mut iter = borrow.iter();
+        *iter.next().unwrap().1 += 100;
+        assert_eq!(world.borrow_state::<u32>().unique, 1);
+        std::thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|_| {
+                    scope
+                        .spawn(move || (0..100).map(|_| world.reserve_entity()).collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>();
+            for (_, x) in iter {
+                *x += 100;
+            }
+            threads
+                .into_iter()
+                .flat_map(|x| x.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+    let mut ids = reserved.iter().map(|x| x.id()).collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 400);
+    assert!(!reserved.contains(&a));
+    assert!(reserved.iter().all(|x| !existing.contains(x)));
+
+    world.flush();
+    assert_eq!(world.query::<()>().iter().count(), 410);
+    for &e in &reserved {
+        assert!(world.entity(e).unwrap().component_types().next().is_none());
+        world.insert_one(e, e.id()).unwrap();
+    }
+    for (i, &e) in existing.iter().enumerate() {
+        assert_eq!(*world.get::<u32>(e).unwrap(), i as u32 + 100);
+    }
+    assert!(reserved
+        .iter()
+        .all(|&e| *world.get::<u32>(e).unwrap() == e.id()));
+}
+
+#[test]
+fn reserve_entities() {
+    let mut world = World::new();
+    let batch = world.reserve_entities(3);
+    assert_eq!(batch.len(), 3);
+    let fresh = batch.collect::<Vec<_>>();
+    let single = world.reserve_entity();
+    assert_eq!(fresh.iter().map(|x| x.id()).collect::<Vec<_>>(), [0, 1, 2]);
+    assert_eq!(single.id(), 3);
+    assert!(fresh.iter().all(|&e| world.contains(e)));
+    world.flush();
+    assert_eq!(world.len(), 4);
+
+    world.despawn(fresh[1]).unwrap();
+    let reused = world.reserve_entities(2).collect::<Vec<_>>();
+    assert_eq!(reused[0].id(), 1);
+    assert_ne!(reused[0], fresh[1]);
+    assert_eq!(reused[1].id(), 4);
+    assert_eq!(world.reserve_entities(0).count(), 0);
+    world.flush();
+    assert_eq!(world.len(), 5);
+    assert!(reused.iter().all(|&e| world.contains(e)));
+
+    // Exhausting the freelist falls back to fresh IDs
+    let many = world.reserve_entities(5000).collect::<Vec<_>>();
+    let mut ids = many.iter().map(|x| x.id()).collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 5000);
+    assert!(many.iter().all(|&e| world.contains(e)));
+    world.flush();
+    assert_eq!(world.len(), 5005);
+    assert!(many.iter().all(|&e| world.contains(e)));
+}
+
+#[test]
+fn session() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, "a"));
+    let b = world.spawn((2i32, 1.0f32));
+    let spawned = world.session(|session| {
+        let mut ints = session.system::<&mut i32>();
+        let mut strs = session.system::<(&&str, &f32)>();
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for (_, x) in ints.query().iter() {
+                    *x *= 10;
+                }
+                ints.insert(b, (true,));
+                ints.despawn(a);
+            });
+            s.spawn(move || {
+                assert_eq!(strs.query().iter().count(), 0);
+                strs.spawn(("c",))
+            })
+            .join()
+            .unwrap()
+        })
+    });
+    assert!(!world.contains(a));
+    assert_eq!(*world.get::<i32>(b).unwrap(), 20);
+    assert!(*world.get::<bool>(b).unwrap());
+    assert_eq!(*world.get::<&str>(spawned).unwrap(), "c");
+}
+
+#[test]
+#[should_panic(expected = "conflicts")]
+fn session_conflict() {
+    let mut world = World::new();
+    world.spawn((1i32,));
+    world.session(|session| {
+        let _a = session.system::<&mut i32>();
+        let _b = session.system::<&i32>();
+    });
+}
+
+#[test]
+fn invalidate_handles() {
+    let mut world = World::new();
+    let a = world.spawn((123, "abc"));
+    let other = world.spawn((456,));
+    let tag = world.tag("owned");
+    world.add_tag(a, tag).unwrap();
+    let handle = world.component_handle::<i32>(a).unwrap();
+
+    let b = world.invalidate_handles(a).unwrap();
+    assert_eq!(a.id(), b.id());
+    assert_ne!(a, b);
+    assert!(!world.contains(a));
+    assert!(world.get::<i32>(a).is_err());
+    assert!(world.get_by_handle(handle).is_err());
+    assert_eq!(*world.get::<&str>(b).unwrap(), "abc");
+    assert!(world.has_tag(b, tag));
+    assert!(!world.has_tag(a, tag));
+    assert_eq!(world.invalidate_handles(a), Err(NoSuchEntity));
+    assert_eq!(world.iter().count(), 2);
+    assert_eq!(*world.get::<i32>(other).unwrap(), 456);
+
+    world.despawn(b).unwrap();
+    assert!(world.query::<&i32>().iter().all(|(e, _)| e == other));
+}
+
+#[test]
+fn spawn_at() {
+    let mut world = World::new();
+    let a = Entity::from_bits(5000 | (2 << 32));
+    world.spawn_at(a, (123, "abc")).unwrap();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 123);
+    assert_eq!(world.query::<&i32>().iter().count(), 1);
+
+    // Replacing the same entity's components
+    world.spawn_at(a, (true,)).unwrap();
+    assert!(world.get::<i32>(a).is_err());
+    assert!(*world.get::<bool>(a).unwrap());
+
+    let stale = Entity::from_bits(5000 | (1 << 32));
+    assert_eq!(
+        world.spawn_at(stale, (456,)),
+        Err(SpawnAtError::Occupied(a))
+    );
+
+    // Ordinary allocation never reuses the occupied ID
+    let others = (0..6000).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    assert!(others.iter().all(|x| x.id() != a.id()));
+    assert!(*world.get::<bool>(a).unwrap());
+
+    world.despawn(a).unwrap();
+    world.spawn_at(stale, (456,)).unwrap();
+    assert_eq!(*world.get::<i32>(stale).unwrap(), 456);
+    assert!(!world.contains(a));
+}
+
+#[test]
+fn column_order() {
+    fn addresses(world: &World, entity: Entity) -> (usize, usize) {
+        let a = &*world.get::<u8>(entity).unwrap() as *const u8 as usize;
+        let b = &*world.get::<u64>(entity).unwrap() as *const u64 as usize;
+        (a, b)
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1u8, 2u64));
+    let (x, y) = addresses(&world, a);
+    assert!(y < x);
+
+    // Smallest components first, applied when the existing archetype next grows
+    world.set_column_order(Some(|x, y| x.layout().size().cmp(&y.layout().size())));
+    let others = (0..2000u64)
+        .map(|i| world.spawn((i as u8, i)))
+        .collect::<Vec<_>>();
+    let (x, y) = addresses(&world, a);
+    assert!(x < y);
+    assert_eq!(*world.get::<u64>(a).unwrap(), 2);
+    for (i, &e) in others.iter().enumerate() {
+        assert_eq!(*world.get::<u8>(e).unwrap(), i as u8);
+        assert_eq!(*world.get::<u64>(e).unwrap(), i as u64);
+    }
+
+    let b = world.spawn((3u8, 4u64, true));
+    let (x, y) = addresses(&world, b);
+    assert!(x < y);
+}
+
+#[test]
+#[cfg(feature = "stats")]
+fn access_stats() {
+    let mut world = World::new();
+    let a = world.spawn((123, [0u8; 64]));
+    world.spawn((456, true));
+    world.query::<(&i32, &bool)>().iter().count();
+    *world.get_mut::<i32>(a).unwrap() += 1;
+    // Grows the archetype, which must not lose counts
+    world.spawn_batch((0..100).map(|i| (i, true))).count();
+
+    let stats = world.access_stats();
+    let names = stats.iter().map(|x| x.name).collect::<Vec<_>>();
+    assert_eq!(names, ["[u8; 64]", "bool", "i32"]);
+    let bytes = &stats[0];
+    assert_eq!((bytes.reads, bytes.writes, bytes.bytes()), (0, 0, 64));
+    let bools = &stats[1];
+    assert_eq!((bools.entities, bools.reads, bools.writes), (101, 1, 0));
+    let ints = &stats[2];
+    assert_eq!((ints.entities, ints.reads, ints.writes), (102, 1, 1));
+
+    world.flush();
+    assert!(world
+        .access_stats()
+        .iter()
+        .all(|x| x.reads == 0 && x.writes == 0));
+}
+
+#[test]
+fn take() {
+    use std::sync::Arc;
+
+    let mut world = World::new();
+    let shared = Arc::new(());
+    let a = world.spawn((123, "abc", shared.clone()));
+    let b = world.spawn((456, "def", shared.clone()));
+    assert!(world.take::<(i32, bool)>(a).is_err());
+    assert!(world.contains(a));
+
+    let (x, s) = world.take::<(i32, Arc<()>)>(a).unwrap();
+    assert_eq!(x, 123);
+    assert_eq!(Arc::strong_count(&shared), 3);
+    drop(s);
+    assert!(!world.contains(a));
+    assert_eq!(world.take::<(i32,)>(a), Err(ComponentError::NoSuchEntity));
+    assert_eq!(*world.get::<i32>(b).unwrap(), 456);
+
+    let mut builder = EntityBuilder::new();
+    world.take_dynamic(b, &mut builder).unwrap();
+    assert!(!world.contains(b));
+    assert_eq!(world.iter().count(), 0);
+    assert_eq!(Arc::strong_count(&shared), 2);
+    let mut other = World::new();
+    let c = other.spawn(builder.build());
+    assert_eq!(*other.get::<&str>(c).unwrap(), "def");
+    drop(other);
+    assert_eq!(Arc::strong_count(&shared), 1);
+}
+
+#[test]
+fn despawn_all() {
+    let mut world = World::new();
+    world.add_removal_sink::<u32, _>(Vec::<(Entity, u32)>::new());
+    let doomed = world
+        .spawn_batch((0..10u32).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    let a = world.spawn((10u32, "abc"));
+    let b = world.spawn((true,));
+    let tag = world.tag("t");
+    world.add_tag(doomed[3], tag).unwrap();
+    world.add_tag(b, tag).unwrap();
+
+    assert_eq!(world.despawn_all::<(&u32, &bool)>(), 10);
+    assert!(doomed.iter().all(|&x| !world.contains(x)));
+    assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+    assert!(world.contains(b));
+    assert_eq!(world.tagged(tag).collect::<Vec<_>>(), [b]);
+    let mut removed = world
+        .removal_sink::<u32, Vec<(Entity, u32)>>()
+        .unwrap()
+        .clone();
+    removed.sort_unstable_by_key(|x| x.1);
+    assert_eq!(
+        removed,
+        doomed
+            .iter()
+            .zip(0..)
+            .map(|(&e, i)| (e, i))
+            .collect::<Vec<_>>()
+    );
+
+    // Freed IDs are reused
+    let c = world.spawn((1u32, true));
+    assert!(doomed.iter().any(|x| x.id() == c.id()));
+
+    world.keep_despawned(true);
+    assert_eq!(world.despawn_all::<&u32>(), 2);
+    assert_eq!(*world.graveyard().unwrap().get::<&str>(a).unwrap(), "abc");
+    assert_eq!(world.despawn_all::<&u32>(), 0);
+    assert_eq!(world.iter().map(|(e, _)| e).collect::<Vec<_>>(), [b]);
+}
+
+#[test]
+fn world_registry() {
+    let mut worlds = WorldRegistry::new();
+    let a = worlds.insert("a", World::new());
+    let b = worlds.insert("b", World::new());
+    assert_eq!(worlds.id("b"), Some(b));
+    assert_eq!(worlds.name(a), Some("a"));
+    assert_eq!(worlds.len(), 2);
+
+    let x = worlds.spawn(a, (123, "abc"));
+    let y = worlds.spawn(b, (456,));
+    let moved = worlds.transfer(x, b).unwrap();
+    assert_eq!(moved.world, b);
+    assert!(!worlds.contains(x));
+    assert_eq!(*worlds.get::<&str>(moved).unwrap(), "abc");
+    assert_eq!(worlds.transfer(x, b), Err(NoSuchEntity));
+    assert_eq!(worlds.transfer(y, b), Ok(y));
+    *worlds.get_mut::<i32>(y).unwrap() += 1;
+
+    let (wa, wb) = worlds.world_pair_mut(b, a);
+    assert_eq!(wa.unwrap().query::<&i32>().iter().count(), 2);
+    assert_eq!(wb.unwrap().iter().count(), 0);
+    let names = worlds.iter().map(|(_, name, _)| name).collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b"]);
+
+    let removed = worlds.remove(b).unwrap();
+    assert_eq!(*removed.get::<i32>(y.entity).unwrap(), 457);
+    assert!(!worlds.contains(moved));
+    assert!(worlds.get::<i32>(y).is_err());
+    let c = worlds.insert("b", World::new());
+    assert_ne!(b, c);
+    let (wa, wb) = worlds.world_pair_mut(a, b);
+    assert!(wa.is_some() && wb.is_none());
+    for (_, _, world) in worlds.iter_mut() {
+        world.spawn((true,));
+    }
+    assert_eq!(worlds[c].iter().count(), 1);
+}
+
+#[test]
+fn prepared_query_matches() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let mut query = PreparedQuery::<&i32>::new();
+    query.query(&world);
+    assert!(query.newly_matched().is_empty());
+
+    query.track_matches(true);
+    query.query(&world);
+    let mut entered = query.newly_matched().to_vec();
+    entered.sort();
+    assert_eq!(entered, [a, b]);
+    assert!(query.no_longer_matched().is_empty());
+
+    query.query(&world);
+    assert!(query.newly_matched().is_empty());
+
+    // Moving between matched archetypes isn't reported
+    world.remove_one::<bool>(a).unwrap();
+    world.despawn(b).unwrap();
+    let c = world.spawn((3, "abc"));
+    let d = world.spawn(("def",));
+    query.query(&world);
+    assert_eq!(query.newly_matched(), [c]);
+    assert_eq!(query.no_longer_matched(), [b]);
+
+    world.insert_one(d, 4).unwrap();
+    world.remove_one::<i32>(c).unwrap();
+    query.query(&world);
+    assert_eq!(query.newly_matched(), [d]);
+    assert_eq!(query.no_longer_matched(), [c]);
+
+    query.track_matches(false);
+    world.despawn(d).unwrap();
+    query.query(&world);
+    assert!(query.no_longer_matched().is_empty());
+}
+
+#[test]
+fn world_len() {
+    let mut world = World::new();
+    assert!(world.is_empty());
+    let a = world.spawn((123, true));
+    world.spawn_batch((0..10).map(|i| (i,)));
+    assert_eq!(world.len(), 11);
+    assert!(!world.is_empty());
+    world.despawn(a).unwrap();
+    assert_eq!(world.len(), world.iter().count() as u32);
+    world.clear();
+    assert!(world.is_empty());
+}
+
+#[test]
+fn typed_archetype_view() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, false));
+    let c = world.spawn((3,));
+    let archetype = world
+        .archetypes()
+        .find(|x| x.access::<&bool>().is_some())
+        .unwrap();
+    assert!(TypedArchetypeView::<&f32>::new(&world, archetype).is_none());
+
+    let mut view = TypedArchetypeView::<(&mut i32, Option<&bool>)>::new(&world, archetype).unwrap();
+    assert_eq!(view.len(), 2);
+    assert_eq!(view.row(c), None);
+    let (ints, bools) = view.columns();
+    assert_eq!(bools.unwrap(), [true, false]);
+    for x in ints {
+        *x *= 10;
+    }
+    let row = view.row(b).unwrap();
+    assert_eq!(view.entity(row), Some(b));
+    assert_eq!(*view.get(row).unwrap().0, 20);
+    assert!(view.get(2).is_none());
+    let mut query = world.query::<&i32>().borrow_policy(BorrowPolicy::Error);
+    assert!(query.try_iter().is_err());
+    drop(query);
+    drop(view);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 10);
+
+    let other = World::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        TypedArchetypeView::<&i32>::new(&other, archetype);
+    }));
+    assert!(result.is_err());
+}
+
+#[test]
+fn contains_fabricated() {
+    let mut world = World::new();
+    let a = world.spawn((123,));
+    let b = world.spawn((456,));
+    // Handles that were never issued
+    assert!(!world.contains(Entity::from_bits(a.to_bits() + 2)));
+    assert!(!world.contains(Entity::from_bits(1 << 31)));
+
+    world.despawn(b).unwrap();
+    let reused = world.reserve_entity();
+    let pending = world.reserve_entity();
+    assert!(world.contains(reused));
+    assert!(world.contains(pending));
+    assert!(!world.contains(b));
+    assert!(!world.contains(Entity::from_bits(pending.to_bits() + 1)));
+    assert_eq!(world.validate(&[a, b, reused, pending]), [0b1101]);
+
+    world.flush();
+    assert!(world.contains(reused) && world.contains(pending));
+}
+
+#[test]
+fn try_spawn_with() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counted(Arc<AtomicUsize>);
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    let counted = || Ok(Counted(drops.clone()));
+
+    let a = world
+        .try_spawn_with((counted, || Ok::<_, &str>(42), || Ok(true)))
+        .unwrap();
+    assert_eq!(*world.get::<i32>(a).unwrap(), 42);
+    assert!(*world.get::<bool>(a).unwrap());
+
+    // Fails after the first component has been written
+    assert_eq!(
+        world.try_spawn_with((counted, || Ok(7), || Err::<bool, _>("missing texture"))),
+        Err("missing texture")
+    );
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    assert_eq!(world.len(), 1);
+    assert_eq!(world.query::<&i32>().iter().count(), 1);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = world.try_spawn_with((counted, || -> Result<i32, &str> { panic!() }));
+    }));
+    assert!(result.is_err());
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+    assert_eq!(world.len(), 1);
+
+    let b = world
+        .try_spawn_with((counted, || Ok(8), || Ok(false)))
+        .unwrap();
+    assert_eq!(*world.get::<i32>(b).unwrap(), 8);
+    assert!(world.contains(a) && world.contains(b));
+    assert_eq!(world.query::<&Counted>().iter().count(), 2);
+}
+
+#[test]
+fn retain() {
+    let mut world = World::new();
+    let entities = world
+        .spawn_batch((0..100).map(|i| (i, i as f32)))
+        .collect::<Vec<_>>();
+    let others = world
+        .spawn_batch((0..10).map(|i| (i, "abc")))
+        .collect::<Vec<_>>();
+    let empty = world.spawn(());
+    let mut visited = 0;
+    let removed = world.retain(|entity, e| {
+        visited += 1;
+        assert!(entity == empty || e.get::<i32>().is_some());
+        e.get::<f32>().is_none_or(|x| *x as i32 % 3 == 0)
+    });
+    assert_eq!(visited, 111);
+    assert_eq!(removed, 66);
+    assert_eq!(world.len(), 45);
+    for (i, &entity) in entities.iter().enumerate() {
+        assert_eq!(world.contains(entity), i % 3 == 0);
+        if i % 3 == 0 {
+            assert_eq!(*world.get::<i32>(entity).unwrap(), i as i32);
+            assert_eq!(*world.get::<f32>(entity).unwrap(), i as f32);
+        }
+    }
+    assert!(others.iter().all(|&x| world.contains(x)));
+    assert!(world.contains(empty));
+    assert_eq!(world.retain(|_, _| false), 45);
+    assert!(world.is_empty());
+}
+
+#[test]
+fn clone_with() {
+    let mut registry = CloneRegistry::new();
+    registry
+        .register::<String>()
+        .register_copy::<i32>()
+        .register_copy::<bool>();
+    assert!(registry.contains::<String>() && !registry.contains::<u8>());
+
+    let mut world = World::new();
+    let a = world.spawn((String::from("abc"), 123));
+    let b = world.spawn((456, true));
+    let c = world.spawn(());
+    world.despawn(c).unwrap();
+    let d = world.spawn((String::from("def"),));
+    let tag = world.tag("player");
+    world.add_tag(a, tag).unwrap();
+    let reserved = world.reserve_entity();
+
+    let mut copy = world.clone_with(&registry);
+    assert_eq!(copy.len(), world.len());
+    assert_eq!(*copy.get::<String>(a).unwrap(), "abc");
+    assert_eq!(*copy.get::<i32>(a).unwrap(), 123);
+    assert_eq!(*copy.get::<i32>(b).unwrap(), 456);
+    assert_eq!(*copy.get::<String>(d).unwrap(), "def");
+    assert!(copy.contains(reserved) && !copy.contains(c));
+    assert!(copy.has_tag(a, tag));
+
+    // The worlds are independent, but allocate identically
+    copy.get_mut::<String>(a).unwrap().push('!');
+    assert_eq!(*world.get::<String>(a).unwrap(), "abc");
+    copy.despawn(b).unwrap();
+    assert!(world.contains(b));
+    world.despawn(b).unwrap();
+    assert_eq!(world.spawn((1,)), copy.spawn((1,)));
+}
+
+#[test]
+#[should_panic(expected = "unregistered component type")]
+fn clone_with_unregistered() {
+    let mut world = World::new();
+    world.spawn((123, true));
+    world.clone_with(&CloneRegistry::new());
+}
+
+#[test]
+fn journal_replay() {
+    let mut journal = Journal::new();
+    journal
+        .register::<&'static str>()
+        .register::<bool>()
+        .track_writes::<i32>();
+    let mut world = World::new();
+    let existing = world.spawn(("abc",));
+    world.start_journal(journal);
+
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let batch = world
+        .spawn_batch((0..4).map(|i| (i, "batch")))
+        .collect::<Vec<_>>();
+    world.insert(b, (false, "b")).unwrap();
+    world.remove_one::<bool>(a).unwrap();
+    world.despawn(batch[1]).unwrap();
+    let reserved = world.reserve_entity();
+    world.insert_one(reserved, 7).unwrap();
+    world.retain(|_, e| e.get::<i32>().is_none_or(|x| *x != 3));
+    for (_, x) in world.query::<&mut i32>().iter() {
+        *x *= 10;
+    }
+    let b2 = world.invalidate_handles(b).unwrap();
+    assert!(!world.journal().unwrap().is_empty());
+    let journal = world.take_journal().unwrap();
+    assert!(world.journal().is_none());
+
+    let mut replica = World::new();
+    replica.replay(&journal);
+    assert_eq!(replica.len(), world.len());
+    for (entity, e) in world.iter() {
+        let r = replica.entity(entity).unwrap();
+        assert_eq!(e.component_count(), r.component_count());
+        assert_eq!(e.get::<i32>().map(|x| *x), r.get::<i32>().map(|x| *x));
+        assert_eq!(e.get::<bool>().map(|x| *x), r.get::<bool>().map(|x| *x));
+        assert_eq!(e.get::<&str>().map(|x| *x), r.get::<&str>().map(|x| *x));
+    }
+    assert_eq!(*replica.get::<&str>(existing).unwrap(), "abc");
+    assert_eq!(*replica.get::<i32>(b2).unwrap(), 20);
+    assert!(!replica.contains(b));
+    assert_eq!(*replica.get::<i32>(reserved).unwrap(), 70);
+    assert!(!replica.contains(batch[1]) && !replica.contains(batch[3]));
+
+    // Replaying is repeatable
+    let mut again = World::new();
+    again.replay(&journal);
+    assert_eq!(again.len(), replica.len());
+}
+
+#[test]
+fn merge() {
+    let mut world = World::new();
+    let existing = world.spawn((1, "world"));
+    let player = world.tag("player");
+
+    let mut level = World::new();
+    let a = level.spawn((2, "level"));
+    let b = level.spawn((String::from("abc"), true));
+    let c = level.spawn(());
+    level.despawn(c).unwrap();
+    let d = level.reserve_entity();
+    let level_player = level.tag("player");
+    let enemy = level.tag("enemy");
+    level.add_tag(a, level_player).unwrap();
+    level.add_tag(b, enemy).unwrap();
+    let generation = world.archetypes_generation();
+
+    let map = world.merge(level);
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get(c), None);
+    assert_eq!(world.len(), 4);
+    let a = map.get(a).unwrap();
+    let b = map.get(b).unwrap();
+    assert!(world.contains(existing) && world.contains(map.get(d).unwrap()));
+    assert_eq!(*world.get::<&str>(a).unwrap(), "level");
+    assert_eq!(*world.get::<String>(b).unwrap(), "abc");
+    assert!(world.has_tag(a, player));
+    assert!(world.has_tag(b, world.find_tag("enemy").unwrap()));
+    assert_ne!(world.archetypes_generation(), generation);
+    assert_eq!(world.query::<(&i32, &&str)>().iter().count(), 2);
+    assert_eq!(
+        map.iter()
+            .map(|(_, new)| new)
+            .collect::<std::collections::HashSet<_>>()
+            .len(),
+        3
+    );
+}
+
+#[test]
+fn column_arena() {
+    use std::sync::Arc;
+    let arena = Arc::new(ColumnArena::new());
+    let mut world = World::new();
+    let a = world.spawn((1u64, 'a'));
+    world.use_arena::<u64>(arena.clone());
+    assert_eq!(arena.allocated(), 0);
+    let entities = (0..100u64)
+        .map(|i| world.spawn((i, 'x', true)))
+        .collect::<Vec<_>>();
+    // Growing existing storage moves the column into the arena
+    for i in 0..100u64 {
+        world.spawn((i, 'y'));
+    }
+    assert!(arena.allocated() >= 200 * std::mem::size_of::<u64>());
+    assert_eq!(*world.get::<u64>(a).unwrap(), 1);
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<u64>(e).unwrap(), i as u64);
+        assert_eq!(*world.get::<char>(e).unwrap(), 'x');
+    }
+    world.despawn(entities[0]).unwrap();
+    assert_eq!(*world.get::<u64>(entities[99]).unwrap(), 99);
+    assert_eq!(
+        world.query::<&u64>().iter().map(|(_, &x)| x).sum::<u64>(),
+        1 + 2 * (0..100).sum::<u64>()
+    );
+    let copy = world.clone_with(
+        CloneRegistry::new()
+            .register_copy::<u64>()
+            .register_copy::<char>()
+            .register_copy::<bool>(),
+    );
+    drop(world);
+    assert_ne!(arena.allocated(), 0);
+    drop(copy);
+    assert_eq!(arena.allocated(), 0);
+}
+
+#[test]
+fn backfill() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, "abc"));
+    let c = world.spawn((3, 4.0f32));
+    let d = world.spawn((true,));
+    let reserved = world.reserve_entity();
+    assert_eq!(world.backfill::<f32, Without<&str, &i32>>(), 1);
+    assert_eq!(*world.get::<f32>(a).unwrap(), 0.0);
+    assert_eq!(*world.get::<f32>(c).unwrap(), 4.0);
+    assert!(world.get::<f32>(b).is_err() && world.get::<f32>(d).is_err());
+    assert!(*world.get::<bool>(a).unwrap());
+
+    // Reserved entities are flushed into the empty archetype, which `()` matches
+    assert_eq!(world.backfill::<u8, ()>(), 5);
+    assert_eq!(*world.get::<u8>(reserved).unwrap(), 0);
+    assert_eq!(world.query::<&u8>().iter().count(), 5);
+    assert_eq!(world.backfill::<u8, ()>(), 0);
+    assert_eq!(*world.get::<&str>(b).unwrap(), "abc");
+    assert_eq!(*world.get::<i32>(c).unwrap(), 3);
+}
+
+#[test]
+fn entity_ref_types() {
+    let mut world = World::new();
+    let a = world.spawn((123, "abc"));
+    let b = world.spawn(());
+    let entity = world.entity(a).unwrap();
+    let _borrow = entity.get_mut::<i32>().unwrap();
+    assert!(entity.has::<i32>() && entity.has::<&str>() && !entity.has::<bool>());
+    let mut types = entity.component_types().map(|x| x.id()).collect::<Vec<_>>();
+    types.sort();
+    let mut expected = vec![
+        std::any::TypeId::of::<i32>(),
+        std::any::TypeId::of::<&str>(),
+    ];
+    expected.sort();
+    assert_eq!(types, expected);
+    let empty = world.entity(b).unwrap();
+    assert!(!empty.has::<i32>());
+    assert_eq!(empty.component_types().len(), 0);
+}
+
+#[test]
+fn weak_entity() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+    let reserved = world.reserve_entity();
+    let mut weak = [a, b, c, reserved]
+        .iter()
+        .map(|&x| world.downgrade(x).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(weak[3].upgrade(&world), Some(reserved));
+    world.despawn(b).unwrap();
+    assert!(world.downgrade(b).is_none());
+    // The freed ID is reused, but the stale handle stays dead
+    let d = world.spawn((4,));
+    assert_eq!(d.id(), b.id());
+    assert_eq!(weak[1].upgrade(&world), None);
+    assert_eq!(weak[1].entity(), b);
+    let c = world.invalidate_handles(c).unwrap();
+    assert_eq!(
+        world.upgrade_all(&weak).collect::<Vec<_>>(),
+        [Some(a), None, None, Some(reserved)]
+    );
+    world.retain_live(&mut weak);
+    assert_eq!(
+        weak.iter().map(|x| x.entity()).collect::<Vec<_>>(),
+        [a, reserved]
+    );
+    world.clear();
+    assert!(weak.iter().all(|x| x.upgrade(&world).is_none()));
+    assert!(world.downgrade(c).is_none());
+}
+
+#[test]
+fn dense_map() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let mut map = DenseMap::new();
+    assert_eq!(map.insert(b, "b"), None);
+    assert_eq!(map.insert(a, "a"), None);
+    assert_eq!(map.insert(a, "a2"), Some("a"));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(a, &"a2"), (b, &"b")]);
+    for (_, x) in map.iter_mut() {
+        *x = "c";
+    }
+    assert_eq!(map.get(a), Some(&"c"));
+
+    world.despawn(b).unwrap();
+    let c = world.spawn((3,));
+    assert_eq!(c.id(), b.id());
+    // A new entity reusing the ID doesn't see the stale value
+    assert!(!map.contains(c));
+    assert_eq!(map.get_mut(c), None);
+    assert_eq!(map.remove(c), None);
+    let mut pruned = Vec::new();
+    map.prune(&world, |e, x| pruned.push((e, x)));
+    assert_eq!(pruned, [(b, "c")]);
+    assert_eq!(map.len(), 1);
+    map.prune(&world, |_, _| unreachable!());
+
+    // Stale values are replaced by new entities with the same ID
+    map.insert(b, "stale");
+    assert_eq!(map.insert(c, "c"), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get(b), None);
+    assert_eq!(map.remove(c), Some("c"));
+    assert_eq!(map.len(), 1);
+
+    world.clear();
+    map.prune(&world, |e, _| assert_eq!(e, a));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn get_many_mut() {
+    let mut world = World::new();
+    let a = world.spawn((1, "a"));
+    let b = world.spawn((2, "b"));
+    let c = world.spawn((3, true));
+    let d = world.spawn(("d",));
+    let tick = world.increment_change_tick();
+    {
+        let [x, y] = world.get_many_mut::<i32, 2>([c, a]).unwrap();
+        *x += *y + 2;
+    }
+    world.increment_change_tick();
+    assert_eq!(*world.get::<i32>(c).unwrap(), 6);
+    let mut changed = world
+        .query::<Changed<i32>>()
+        .since(tick)
+        .iter()
+        .filter(|&(_, x)| x)
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    changed.sort();
+    let mut expected = vec![a, c];
+    expected.sort();
+    assert_eq!(changed, expected);
+    assert!(world.get_many_mut::<i32, 2>([a, d]).is_err());
+    world.despawn(b).unwrap();
+    assert_eq!(
+        world.get_many_mut::<i32, 2>([a, b]).err(),
+        Some(ComponentError::NoSuchEntity)
+    );
+
+    let [x, y, z] = world.query_many_mut::<(&mut i32, Option<&bool>), 3>([a, b, c]);
+    assert!(y.is_none());
+    let ((x, none), (z, some)) = (x.unwrap(), z.unwrap());
+    std::mem::swap(x, z);
+    assert!(none.is_none() && *some.unwrap());
+    assert_eq!(*world.get::<i32>(a).unwrap(), 6);
+}
+
+#[test]
+#[should_panic(expected = "entities must be distinct")]
+fn get_many_mut_duplicate() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let _ = world.get_many_mut::<i32, 2>([a, a]);
+}
+
+#[test]
+#[should_panic]
+fn query_many_mut_aliasing() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let _ = world.query_many_mut::<(&mut i32, &i32), 1>([a]);
+}
+
+#[test]
+fn get_batch() {
+    let mut world = World::new();
+    let entities = (0..100)
+        .map(|i| match i % 3 {
+            0 => world.spawn((i,)),
+            1 => world.spawn((i, true)),
+            _ => world.spawn((true,)),
+        })
+        .collect::<Vec<_>>();
+    let dead = world.spawn((-1,));
+    world.despawn(dead).unwrap();
+    let mut query = entities.clone();
+    query.reverse();
+    query.push(dead);
+    query.push(entities[0]);
+
+    let mut out = vec![Some(&0)];
+    world.get_batch::<i32>(&query, &mut out);
+    assert_eq!(out.len(), query.len());
+    for (i, x) in out[..100].iter().enumerate() {
+        let expected = 99 - i as i32;
+        assert_eq!(*x, (expected % 3 != 2).then_some(&expected));
+    }
+    assert_eq!(out[100..], [None, Some(&0)]);
+}
+
+#[test]
+fn exchange() {
+    let mut world = World::new();
+    let a = world.spawn((1, "a", true));
+    let b = world.spawn((2, "b", true));
+    let generation = world.archetypes_generation();
+    assert_eq!(world.exchange::<(i32, bool)>(a, (1.5f32,)), Ok((1, true)));
+    assert_eq!(*world.get::<&str>(a).unwrap(), "a");
+    assert_eq!(*world.get::<f32>(a).unwrap(), 1.5);
+    assert!(world.get::<i32>(a).is_err() && world.get::<bool>(a).is_err());
+    // Only the final archetype was created
+    assert_eq!(world.archetypes().count(), 3);
+    assert_ne!(world.archetypes_generation(), generation);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+
+    // Replacing a component returns the old value
+    assert_eq!(world.exchange::<(&str,)>(b, ("c", 3u8)), Ok(("b",)));
+    assert_eq!(*world.get::<&str>(b).unwrap(), "c");
+    assert_eq!(*world.get::<u8>(b).unwrap(), 3);
+    assert_eq!(
+        world.exchange::<(f32,)>(b, ("d",)),
+        Err(ComponentError::MissingComponent(
+            MissingComponent::new::<f32>()
+        ))
+    );
+    assert_eq!(*world.get::<&str>(b).unwrap(), "c");
+
+    let value = std::sync::Arc::new(());
+    let c = world.spawn((value.clone(), 4));
+    let (taken,) = world
+        .exchange::<(std::sync::Arc<()>,)>(c, (value.clone(),))
+        .unwrap();
+    drop(taken);
+    assert_eq!(std::sync::Arc::strong_count(&value), 2);
+    world.remove::<(i32,)>(c).unwrap();
+    drop(world);
+    assert_eq!(std::sync::Arc::strong_count(&value), 1);
+}
+
+#[test]
+fn despawn_where() {
+    let mut world = World::new();
+    let entities = (0..20)
+        .map(|i| {
+            if i % 2 == 0 {
+                world.spawn((i,))
+            } else {
+                world.spawn((i, "odd"))
+            }
+        })
+        .collect::<Vec<_>>();
+    let other = world.spawn(("other",));
+    assert_eq!(world.despawn_where::<i32>(|&x| x % 3 == 0), 7);
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(world.contains(e), i % 3 != 0);
+        if i % 3 != 0 {
+            assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+        }
+    }
+    assert!(world.contains(other));
+    assert_eq!(world.despawn_where::<bool>(|_| true), 0);
+    assert_eq!(world.len(), 14);
+}
+
+#[test]
+fn colocate() {
+    struct A(u32);
+    struct B(u32);
+    struct C(u32);
+    struct D(u32);
+    fn address<T: Component>(world: &World, entity: Entity) -> usize {
+        &*world.get::<T>(entity).unwrap() as *const T as usize
+    }
+
+    let mut world = World::new();
+    let existing = world.spawn((A(0), B(0), C(0), D(0)));
+    world.colocate::<D, A>();
+    world.colocate::<A, C>();
+    // Existing storage is laid out anew once it grows
+    let entities = (1..100)
+        .map(|i| world.spawn((A(i), B(i), C(i), D(i))))
+        .collect::<Vec<_>>();
+    // Each column is the same size, so adjacent columns are evenly spaced
+    let (d, a, c) = (
+        address::<D>(&world, existing),
+        address::<A>(&world, existing),
+        address::<C>(&world, existing),
+    );
+    assert!(a > d);
+    assert_eq!(a - d, c - a);
+    assert!(a - d >= 4 * 100);
+    for (i, &e) in entities.iter().enumerate() {
+        let i = i as u32 + 1;
+        assert_eq!(world.get::<A>(e).unwrap().0, i);
+        assert_eq!(world.get::<B>(e).unwrap().0, i);
+        assert_eq!(world.get::<C>(e).unwrap().0, i);
+        assert_eq!(world.get::<D>(e).unwrap().0, i);
+    }
+}
+
+#[test]
+fn insert_one_returns_replaced() {
+    let mut world = World::new();
+    world.add_removal_sink::<String, Vec<(Entity, String)>>(Vec::new());
+    let a = world.spawn(("a".to_string(), 1));
+    let b = world.spawn((2,));
+    assert_eq!(world.insert_one(a, "b".to_string()), Ok(Some("a".into())));
+    assert_eq!(world.insert_one(b, "c".to_string()), Ok(None));
+    assert_eq!(*world.get::<String>(a).unwrap(), "b");
+    assert_eq!(*world.get::<String>(b).unwrap(), "c");
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    // Returned components bypass the sink, while `insert` still delivers them
+    assert!(world
+        .removal_sink::<String, Vec<(Entity, String)>>()
+        .unwrap()
+        .is_empty());
+    world.insert(a, ("d".to_string(),)).unwrap();
+    assert_eq!(
+        world
+            .removal_sink::<String, Vec<(Entity, String)>>()
+            .unwrap()[..],
+        [(a, "b".to_string())]
+    );
+    world.despawn(a).unwrap();
+    assert_eq!(world.insert_one(a, 3), Err(NoSuchEntity));
+}
+
+#[test]
+fn split_cold() {
+    let mut world = World::new();
+    let entities = (0..100)
+        .map(|i| world.spawn((i, [i as u8; 64], i % 2 == 0)))
+        .collect::<Vec<_>>();
+    let empty = world.spawn(([1u8; 64],));
+    world.despawn(empty).unwrap();
+    let tick = world.increment_change_tick();
+    world.split_cold::<[u8; 64]>();
+    assert!(world.is_cold::<[u8; 64]>() && !world.is_cold::<i32>());
+    // Reorganizing storage isn't a change
+    assert_eq!(
+        world
+            .query::<Changed<[u8; 64]>>()
+            .since(tick)
+            .iter()
+            .filter(|&(_, x)| x)
+            .count(),
+        0
+    );
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(world.get::<[u8; 64]>(e).unwrap()[63], i as u8);
+        assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+    }
+    // New entities are split as well
+    let a = world.spawn((1000, [9u8; 64], true));
+    for (_, (x, bytes)) in world.query::<(&i32, &mut [u8; 64])>().iter() {
+        bytes[0] = *x as u8;
+    }
+    world.despawn(entities[0]).unwrap();
+    world.merge_cold::<[u8; 64]>();
+    assert!(!world.is_cold::<[u8; 64]>());
+    assert_eq!(world.get::<[u8; 64]>(a).unwrap()[..2], [1000u32 as u8, 9]);
+    assert_eq!(world.get::<[u8; 64]>(entities[99]).unwrap()[0], 99);
+    assert_eq!(world.query::<&[u8; 64]>().iter().count(), 100);
+}
+
+#[test]
+#[cfg(feature = "stats")]
+fn split_cold_columns() {
+    let mut world = World::new();
+    let a = world.spawn((1, [0u8; 256]));
+    world.flush();
+    for (_, x) in world.query::<&mut i32>().iter() {
+        *x += 1;
+    }
+    let split = world.split_cold_columns(0);
+    assert_eq!(split.len(), 1);
+    assert_eq!(split[0].id, std::any::TypeId::of::<[u8; 256]>());
+    assert!(world.is_cold::<[u8; 256]>() && !world.is_cold::<i32>());
+    assert_eq!(*world.get::<i32>(a).unwrap(), 2);
+}
+
+#[test]
+fn remove_one_direct() {
+    let value = std::sync::Arc::new(());
+    let mut world = World::new();
+    world.add_removal_sink::<std::sync::Arc<()>, Vec<(Entity, std::sync::Arc<()>)>>(Vec::new());
+    let a = world.spawn((value.clone(), 1, "a"));
+    let b = world.spawn((value.clone(), 2, "b"));
+    let taken = world.remove_one::<std::sync::Arc<()>>(a).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&taken, &value));
+    assert_eq!(
+        world.remove_one::<std::sync::Arc<()>>(a).unwrap_err(),
+        ComponentError::MissingComponent(MissingComponent::new::<std::sync::Arc<()>>())
+    );
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<&str>(b).unwrap(), "b");
+    drop(taken);
+    assert_eq!(std::sync::Arc::strong_count(&value), 2);
+    assert!(world
+        .removal_sink::<std::sync::Arc<()>, Vec<(Entity, std::sync::Arc<()>)>>()
+        .unwrap()
+        .is_empty());
+    world.despawn(a).unwrap();
+    assert_eq!(
+        world.remove_one::<i32>(a),
+        Err(ComponentError::NoSuchEntity)
+    );
+}
+
+#[test]
+fn commands() {
+    let mut registry = CommandRegistry::new();
+    registry
+        .register::<i32>("health")
+        .register::<bool>("hostile")
+        .register::<String>("name");
+    let commands = hecs::commands![
+        spawn a { health: 10, name: "alice" };
+        spawn b { hostile: true };
+        insert a { hostile: false, health: 20 };
+        remove b [hostile, name];
+        spawn c {};
+        despawn c;
+    ];
+    let text = commands.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        text,
+        [
+            "spawn a health=10 name=alice",
+            "spawn b hostile=true",
+            "insert a hostile=false health=20",
+            "remove b hostile name",
+            "spawn c",
+            "despawn c",
+        ]
+    );
+    let parsed = text
+        .iter()
+        .map(|x| x.parse::<Command>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(parsed, commands);
+
+    let mut world = World::new();
+    let labels = registry.apply(&mut world, &commands).unwrap();
+    let (a, b, c) = (labels["a"], labels["b"], labels["c"]);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 20);
+    assert!(!*world.get::<bool>(a).unwrap());
+    assert_eq!(*world.get::<String>(a).unwrap(), "alice");
+    assert_eq!(world.entity(b).unwrap().component_count(), 0);
+    assert!(!world.contains(c));
+
+    let mut labels = labels;
+    assert_eq!(
+        registry.apply_with(
+            &mut world,
+            &hecs::commands![insert a { armor: 1 }],
+            &mut labels
+        ),
+        Err(CommandError::UnknownComponent("armor".into()))
+    );
+    assert_eq!(
+        registry.apply_with(
+            &mut world,
+            &hecs::commands![insert a { health: "x" }],
+            &mut labels
+        ),
+        Err(CommandError::InvalidValue {
+            component: "health".into(),
+            value: "x".into()
+        })
+    );
+    assert_eq!(
+        registry.apply_with(&mut world, &hecs::commands![despawn c], &mut labels),
+        Err(CommandError::NoSuchEntity("c".into()))
+    );
+    assert_eq!(
+        registry.apply_with(&mut world, &hecs::commands![spawn a {}], &mut labels),
+        Err(CommandError::DuplicateLabel("a".into()))
+    );
+    assert_eq!(
+        "despawn".parse::<Command>(),
+        Err(CommandError::Syntax("despawn".into()))
+    );
+    assert_eq!(*world.get::<i32>(a).unwrap(), 20);
+}
+
+#[test]
+fn for_each_archetype_mut() {
+    let mut world = World::new();
+    let a = world.spawn((1, 10u8));
+    let b = world.spawn((2, 20u8, "x"));
+    let c = world.spawn((3,));
+    world.spawn((30u8,));
+    let e = world.spawn((4, 40u8));
+    world.despawn(e).unwrap();
+    let mut seen = Vec::new();
+    world.for_each_archetype_mut::<(&mut i32, &u8)>(|mut rows| {
+        assert!(!rows.is_empty());
+        for row in 0..rows.len() {
+            seen.push(rows.entity(row).unwrap());
+        }
+        let (ints, bytes) = rows.columns();
+        for (x, &y) in ints.iter_mut().zip(bytes.iter()) {
+            *x += i32::from(y);
+        }
+    });
+    seen.sort();
+    assert_eq!(seen, [a, b]);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 11);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 22);
+    assert_eq!(*world.get::<i32>(c).unwrap(), 3);
+    // Views are released between calls
+    world.for_each_archetype_mut::<&mut i32>(|_| {});
+    assert_eq!(world.query::<&i32>().iter().count(), 3);
+}
+
+#[test]
+fn archetypes_extend() {
+    let mut world = World::new();
+    let generation = world.archetypes_generation();
+    world.spawn((1,));
+    world.spawn((2, true));
+    assert!(world.archetypes_extend(generation));
+    assert!(world.archetypes_extend(world.archetypes_generation()));
+    let mut other = World::new();
+    other.spawn((1,));
+    other.spawn((2, true));
+    assert!(!other.archetypes_extend(generation));
+    assert!(!other.archetypes_extend(world.archetypes_generation()));
+    let copy = world.clone_with(CloneRegistry::new().register::<i32>().register::<bool>());
+    assert!(!copy.archetypes_extend(world.archetypes_generation()));
+
+    let mut query = PreparedQuery::<&i32>::new();
+    assert_eq!(query.query(&world).iter().count(), 2);
+    world.spawn((3, "abc"));
+    assert_eq!(query.query(&world).iter().count(), 3);
+}
+
+#[test]
+#[should_panic(expected = "prepared query used with a different world")]
+fn prepared_query_different_world() {
+    let mut a = World::new();
+    a.spawn((1,));
+    let mut b = World::new();
+    b.spawn((true,));
+    b.spawn((2,));
+    let mut query = PreparedQuery::<&i32>::new();
+    query.query(&a);
+    query.query(&b);
+}
+
+#[test]
+#[should_panic(expected = "component handle used with a different world")]
+fn component_handle_different_world() {
+    let mut a = World::new();
+    let e = a.spawn((1,));
+    let handle = a.component_handle::<i32>(e).unwrap();
+    let mut b = World::new();
+    let f = b.spawn((2,));
+    b.component_handle::<i32>(f).unwrap();
+    let _ = b.get_by_handle(handle);
+}
+
+#[test]
+#[should_panic(expected = "incremental despawn resumed on a different world")]
+fn despawn_all_different_world() {
+    let mut a = World::new();
+    a.spawn_batch((0..4).map(|i| (i,)));
+    let mut b = World::new();
+    b.spawn((1,));
+    let mut op = DespawnAll::<&i32>::new();
+    assert!(!op.run_for(&mut a, 2));
+    op.run_for(&mut b, 2);
+}
+
+#[test]
+fn world_uid() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let weak = world.downgrade(a).unwrap();
+    assert_eq!(weak.world(), world.uid());
+    assert_ne!(World::new().uid(), world.uid());
+    let copy = world.clone_with(CloneRegistry::new().register::<i32>());
+    assert_ne!(copy.uid(), world.uid());
+    let mut weak = vec![weak];
+    world.retain_live(&mut weak);
+    assert_eq!(weak.len(), 1);
+}
+
+#[test]
+#[cfg(feature = "checked-entities")]
+#[should_panic(expected = "belongs to a different world")]
+fn entity_different_world() {
+    let mut a = World::new();
+    let mut b = World::new();
+    let e = a.spawn((1,));
+    b.spawn((2,));
+    let _ = b.get::<i32>(e);
+}
+
+#[test]
+#[cfg(feature = "checked-entities")]
+fn entity_cloned_world() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    world.despawn(b).unwrap();
+    let mut copy = world.clone_with(CloneRegistry::new().register::<i32>());
+    // Handles from before the clone refer to the same entities in both
+    assert_eq!(*copy.get::<i32>(a).unwrap(), 1);
+    assert!(!copy.contains(b));
+    // Handles from after don't
+    let c = world.spawn((3,));
+    copy.spawn((4,));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| copy.contains(c)));
+    assert!(result.is_err());
+    // Handles reconstructed from bits aren't checked
+    let c = Entity::from_bits(c.to_bits());
+    assert_eq!(*copy.get::<i32>(c).unwrap(), 4);
+}
+
+#[test]
+#[should_panic(expected = "belongs to a different world")]
+fn weak_entity_different_world() {
+    let mut a = World::new();
+    let e = a.spawn((1,));
+    let weak = a.downgrade(e).unwrap();
+    let mut b = World::new();
+    b.spawn((2,));
+    weak.upgrade(&b);
+}
+
+#[test]
+fn get_many_mut_snapshot() {
+    let mut world = World::new();
+    let mut snapshotter = Snapshotter::new();
+    snapshotter.register::<i32>();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let _first = snapshotter.take(&mut world);
+    let [x, y] = world.get_many_mut::<i32, 2>([a, b]).unwrap();
+    *x = 100;
+    *y = 200;
+    let second = snapshotter.take(&mut world);
+    *world.get_mut::<i32>(a).unwrap() = 0;
+    snapshotter.restore(&second, &mut world);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 100);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 200);
+}
+
+#[test]
+fn try_query_read_only() {
+    static DATA: [[f32; 2]; 2] = [[1.0, 2.0], [3.0, 4.0]];
+    let mut world = World::new();
+    world.spawn(([5.0f32, 6.0],));
+    world.spawn_external(&DATA);
+    assert!(matches!(
+        world.try_query::<&mut [f32; 2]>(),
+        Err(WorldError::ReadOnly)
+    ));
+    assert_eq!(world.try_query::<&[f32; 2]>().unwrap().iter().count(), 3);
+    // Nothing was left borrowed
+    world.try_query::<&mut [f32; 2]>().err().unwrap();
+    let mut q = world.try_query::<Option<&mut bool>>().unwrap();
+    assert_eq!(q.iter().count(), 3);
+}
+
+#[test]
+#[cfg(feature = "wide-entities")]
+fn wide_generations() {
+    let mut world = World::new();
+    let a = entity_at(5, u32::MAX.into());
+    world.spawn_at(a, (1,)).unwrap();
+    world.despawn(a).unwrap();
+    let next = entity_at(5, 1 << 32);
+    assert_eq!(world.entity_slot(5), Some(EntitySlot::Free(next)));
+    assert_eq!(Entity::from_wide_bits(next.to_wide_bits()), next);
+    // Too wide for `to_bits`, and for worlds without the feature
+    assert_eq!(next.to_wide_bits() >> 32, u128::from(u32::MAX));
+    assert!(std::panic::catch_unwind(|| next.to_bits()).is_err());
+}